@@ -1,5 +1,5 @@
-use glam::{Vec3, Mat4, vec3};
-use shipyard::{UniqueView, NonSendSync, EntitiesViewMut, ViewMut, UniqueViewMut, AllStoragesView, IntoIter};
+use glam::{Vec3, Mat4};
+use shipyard::{UniqueView, NonSendSync, EntitiesViewMut, View, ViewMut, UniqueViewMut, AllStoragesView, IntoIter};
 use uflow::{server::Event as ServerEvent, SendMode};
 use kubi_shared::{
   networking::{
@@ -13,13 +13,17 @@ use kubi_shared::{
     client::{Client, ClientId, Username},
     channels::Channel,
   }, 
-  player::{Player, PLAYER_HEALTH}, 
-  transform::Transform, entity::{Entity, Health}
+  block::SharedBlockRegistry,
+  falling_block::FallingBlock,
+  height::WorldHeight,
+  player::{GameMode, Inventory, Player, PLAYER_HEALTH},
+  transform::Transform, entity::{BreathState, Entity, FallDamageState, Health}
 };
 use crate::{
   config::ConfigTable, 
   server::{ServerEvents, UdpServer, IsMessageOfType}, 
-  client::{ClientAddress, ClientAddressMap}
+  client::{ClientAddress, ClientAddressMap},
+  world::{tasks::{ChunkTask, ChunkTaskManager}, MiningState},
 };
 pub use kubi_shared::networking::client::ClientIdMap;
 
@@ -31,6 +35,9 @@ pub fn authenticate_players(
   let server = storages.borrow::<NonSendSync<UniqueView<UdpServer>>>().unwrap();
   let events = storages.borrow::<UniqueView<ServerEvents>>().unwrap();
   let config = storages.borrow::<UniqueView<ConfigTable>>().unwrap();
+  let world_height = storages.borrow::<UniqueView<WorldHeight>>().unwrap();
+  let registry = storages.borrow::<UniqueView<SharedBlockRegistry>>().unwrap();
+  let task_manager = storages.borrow::<UniqueView<ChunkTaskManager>>().unwrap();
   
   for event in &events.0 {
     // NOT using `check_message_auth` here because the user is not authed yet!
@@ -94,6 +101,16 @@ pub fn authenticate_players(
       continue
     };
 
+    //Players keep their game mode between sessions
+    let gamemode = match task_manager.player_gamemode(&username) {
+      Some(gamemode) => gamemode,
+      None => {
+        let gamemode = config.world.default_gamemode;
+        task_manager.run(ChunkTask::SavePlayer { username: username.clone(), gamemode });
+        gamemode
+      }
+    };
+
     //Spawn the user
     let entity_id = {
       storages.borrow::<EntitiesViewMut>().unwrap().add_entity((
@@ -104,16 +121,30 @@ pub fn authenticate_players(
         &mut storages.borrow::<ViewMut<ClientAddress>>().unwrap(),
         &mut storages.borrow::<ViewMut<Transform>>().unwrap(),
         &mut storages.borrow::<ViewMut<Username>>().unwrap(),
+        &mut storages.borrow::<ViewMut<GameMode>>().unwrap(),
+        &mut storages.borrow::<ViewMut<Inventory>>().unwrap(),
+        &mut storages.borrow::<ViewMut<MiningState>>().unwrap(),
       ), (
         Entity,
         Player,
         Health::new(PLAYER_HEALTH),
         Client(client_id),
         ClientAddress(*client_addr),
-        Transform(Mat4::from_translation(vec3(0., 60., 0.))),
+        Transform(Mat4::from_translation(config.world.spawn_point)),
         Username(username.clone()),
+        gamemode,
+        Inventory::new(),
+        MiningState::default(),
       ))
     };
+    //damage is dealt by the server, based on its view of the player
+    storages.borrow::<EntitiesViewMut>().unwrap().add_component(entity_id, (
+      &mut storages.borrow::<ViewMut<FallDamageState>>().unwrap(),
+      &mut storages.borrow::<ViewMut<BreathState>>().unwrap(),
+    ), (
+      FallDamageState::default(),
+      BreathState::default(),
+    ));
 
     //Add the user to the ClientIdMap and ClientAddressMap
     client_entity_map.0.insert(client_id, entity_id);
@@ -123,11 +154,12 @@ pub fn authenticate_players(
     let init_data = {
       let mut user = None;
       let mut users = Vec::with_capacity(client_entity_map.0.len() - 1);
-      for (client, username, transform, &health) in (
+      for (client, username, transform, &health, &gamemode) in (
         &storages.borrow::<ViewMut<Client>>().unwrap(),
         &storages.borrow::<ViewMut<Username>>().unwrap(),
         &storages.borrow::<ViewMut<Transform>>().unwrap(),
         &storages.borrow::<ViewMut<Health>>().unwrap(),
+        &storages.borrow::<ViewMut<GameMode>>().unwrap(),
       ).iter() {
         let (_, direction, position) = transform.0.to_scale_rotation_translation();
         let idata = ClientInitData {
@@ -137,6 +169,7 @@ pub fn authenticate_players(
           velocity: Vec3::ZERO,
           direction,
           health,
+          gamemode,
         };
         if client_id == client.0 {
           user = Some(idata);
@@ -146,7 +179,11 @@ pub fn authenticate_players(
       }
       InitData {
         user: user.unwrap(),
-        users
+        users,
+        inventory: Inventory::new(),
+        block_registry: (*registry.0).clone(),
+        world_height: *world_height,
+        falling_blocks: storages.borrow::<View<FallingBlock>>().unwrap().iter().copied().collect(),
       }
     };
 
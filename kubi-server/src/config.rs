@@ -1,6 +1,13 @@
 use shipyard::{AllStoragesView, Unique};
 use serde::{Serialize, Deserialize};
 use std::{fs, net::SocketAddr, path::PathBuf};
+use anyhow::{bail, Result};
+use glam::Vec3;
+use kubi_shared::{
+  height::WorldHeight,
+  player::{GameMode, PLAYER_SPAWN_POINT},
+  worldgen::preset::{WorldGenPreset, WorldGenPresets, DEFAULT_PRESET},
+};
 
 #[derive(Serialize, Deserialize)]
 pub struct ConfigTableServer {
@@ -15,6 +22,70 @@ pub struct ConfigTableWorld {
   pub file: Option<PathBuf>,
   pub seed: u64,
   pub preheat_radius: u32,
+  /// Game mode of players joining the world for the first time\
+  /// (the game mode of each player is stored in the save file)
+  #[serde(default = "default_gamemode")]
+  pub default_gamemode: GameMode,
+  /// Position where players join and respawn
+  #[serde(default = "default_spawn_point")]
+  pub spawn_point: Vec3,
+  /// Block definition file, built-in block definitions are used if not specified
+  #[serde(default)]
+  pub blocks: Option<PathBuf>,
+  /// Directory with additional structure templates, only built-in templates are used if not specified
+  #[serde(default)]
+  pub structures: Option<PathBuf>,
+  /// World generator preset, only used when the world is created\
+  /// (existing worlds keep the preset stored in the save file)
+  #[serde(default)]
+  pub generator: ConfigGenerator,
+  /// Build height limits, only used when the world is created (like the generator)
+  #[serde(default)]
+  pub height: WorldHeight,
+}
+
+fn default_spawn_point() -> Vec3 {
+  PLAYER_SPAWN_POINT
+}
+
+//there are no items to build with in survival yet
+fn default_gamemode() -> GameMode {
+  GameMode::Creative
+}
+
+/// Either the name of a built-in preset, or a custom preset
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConfigGenerator {
+  Builtin(String),
+  Custom(WorldGenPreset),
+}
+
+impl Default for ConfigGenerator {
+  fn default() -> Self {
+    Self::Builtin(DEFAULT_PRESET.into())
+  }
+}
+
+impl ConfigGenerator {
+  /// Check that the built-in preset exists
+  pub fn validate(&self) -> Result<()> {
+    if let Self::Builtin(name) = self {
+      if WorldGenPreset::builtin(name).is_none() {
+        let names: Vec<String> = WorldGenPresets::builtin().presets.into_iter().map(|preset| preset.name).collect();
+        bail!("unknown world generator preset {name:?}, built-in presets are: {}", names.join(", "));
+      }
+    }
+    Ok(())
+  }
+
+  /// Panics if the preset doesn't exist, see [`Self::validate`]
+  pub fn preset(&self) -> WorldGenPreset {
+    match self {
+      Self::Builtin(name) => WorldGenPreset::builtin(name).expect("Unknown world generator preset"),
+      Self::Custom(preset) => preset.clone(),
+    }
+  }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -35,5 +106,8 @@ pub fn read_config(
   log::info!("Reading config...");
   let config_str = fs::read_to_string("Server.toml").expect("No config file found");
   let config: ConfigTable = toml::from_str(&config_str).expect("Invalid configuration file");
+  if let Err(error) = config.world.generator.validate() {
+    panic!("Invalid configuration file: {error}");
+  }
   storages.add_unique(config);
 }
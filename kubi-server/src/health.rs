@@ -0,0 +1,181 @@
+use glam::{Mat4, Vec3};
+use shipyard::{Get, IntoIter, IntoWorkload, NonSendSync, UniqueView, View, ViewMut, Workload};
+use uflow::SendMode;
+use kubi_shared::{
+  block::{Block, CollisionType, SharedBlockRegistry},
+  entity::{BreathState, DamageCause, FallDamageState, Health},
+  fixed_timestamp::FixedTimestamp,
+  networking::{
+    channels::Channel,
+    client::{Client, ClientId},
+    messages::{ClientToServerMessage, ClientToServerMessageType, ServerToClientMessage},
+  },
+  player::{GameMode, PLAYER_EYE_HEIGHT},
+  transform::Transform,
+};
+use crate::{
+  client::{ClientAddress, ClientAddressMap},
+  config::ConfigTable,
+  server::{ServerEvents, UdpServer},
+  util::{broadcast, check_message_auth},
+  world::ChunkManager,
+};
+
+/// Rate (in milliseconds) at which the breath of players is updated
+const BREATH_TICK_RATE_MILLIS: u16 = 100;
+
+/// Damage a player and let everyone know about their new health
+fn deal_damage(
+  server: &UdpServer,
+  addrs: &View<ClientAddress>,
+  client_id: ClientId,
+  health: &mut Health,
+  amount: u8,
+  cause: DamageCause,
+) {
+  health.damage(amount);
+  log::info!("Player {client_id} took {amount} damage ({cause:?}), health: {}", health.current);
+  if health.is_dead() {
+    log::info!("Player {client_id} died");
+  }
+  broadcast(server, addrs, &ServerToClientMessage::PlayerHealthChanged {
+    client_id,
+    health: *health,
+  }, Channel::SysEvt);
+}
+
+/// Get the block at `position`, unloaded chunks are treated as air
+fn block_at(chunks: &ChunkManager, position: Vec3) -> Block {
+  chunks.get_block(position.floor().as_ivec3()).unwrap_or(Block::Air)
+}
+
+/// Deal fall damage, based on the positions reported by clients and the server's copy of the world
+fn process_fall_damage(
+  server: NonSendSync<UniqueView<UdpServer>>,
+  events: UniqueView<ServerEvents>,
+  addr_map: UniqueView<ClientAddressMap>,
+  chunks: UniqueView<ChunkManager>,
+  registry: UniqueView<SharedBlockRegistry>,
+  clients: View<Client>,
+  addrs: View<ClientAddress>,
+  gamemodes: View<GameMode>,
+  mut healths: ViewMut<Health>,
+  mut falls: ViewMut<FallDamageState>,
+) {
+  for event in &events.0 {
+    let Some(message) = check_message_auth
+      ::<{ClientToServerMessageType::PositionChanged as u8}>
+      (&server, event, &clients, &addr_map) else { continue };
+
+    let ClientToServerMessage::PositionChanged { position, .. } = message.message else { unreachable!() };
+
+    let Ok((mut health, mut fall)) = (&mut healths, &mut falls).get(message.entity_id) else {
+      log::error!("Player has no health");
+      continue
+    };
+
+    //same checks as the client-side physics
+    let feet_position = position - Vec3::Y * PLAYER_EYE_HEIGHT;
+    let on_ground = [feet_position, feet_position - Vec3::Y * 0.01].into_iter().any(|position| {
+      registry.get(block_at(&chunks, position)).collision == CollisionType::Solid
+    });
+    //landing in water breaks the fall, only players in survival mode can take damage
+    let in_fluid = registry.get(block_at(&chunks, feet_position)).submerge.is_some();
+    let gamemode = gamemodes.get(message.entity_id).copied().unwrap_or_default();
+    let cancelled = in_fluid || health.is_dead() || !gamemode.has_survival_rules();
+
+    if let Some(amount) = fall.update(feet_position.y, on_ground, cancelled) {
+      deal_damage(&server, &addrs, message.client_id, &mut health, amount, DamageCause::Fall);
+    }
+  }
+}
+
+/// Deal drowning damage to players whose heads are submerged
+fn update_breath(
+  server: NonSendSync<UniqueView<UdpServer>>,
+  chunks: UniqueView<ChunkManager>,
+  registry: UniqueView<SharedBlockRegistry>,
+  clients: View<Client>,
+  addrs: View<ClientAddress>,
+  gamemodes: View<GameMode>,
+  transforms: View<Transform>,
+  mut healths: ViewMut<Health>,
+  mut breaths: ViewMut<BreathState>,
+) {
+  for (client, &gamemode, transform, mut health, mut breath) in (&clients, &gamemodes, &transforms, &mut healths, &mut breaths).iter() {
+    let head_position = transform.0.to_scale_rotation_translation().2;
+    let submerged = registry.get(block_at(&chunks, head_position)).submerge.is_some();
+    let submerged = submerged && !health.is_dead() && gamemode.has_survival_rules();
+    if let Some(amount) = breath.update(submerged, BREATH_TICK_RATE_MILLIS as f32 / 1000.) {
+      deal_damage(&server, &addrs, client.0, &mut health, amount, DamageCause::Drowning);
+    }
+  }
+}
+
+fn process_respawn_messages(
+  server: NonSendSync<UniqueView<UdpServer>>,
+  events: UniqueView<ServerEvents>,
+  addr_map: UniqueView<ClientAddressMap>,
+  config: UniqueView<ConfigTable>,
+  clients: View<Client>,
+  addrs: View<ClientAddress>,
+  mut healths: ViewMut<Health>,
+  mut transforms: ViewMut<Transform>,
+  mut falls: ViewMut<FallDamageState>,
+  mut breaths: ViewMut<BreathState>,
+) {
+  for event in &events.0 {
+    let Some(message) = check_message_auth
+      ::<{ClientToServerMessageType::Respawn as u8}>
+      (&server, event, &clients, &addr_map) else { continue };
+
+    let Ok(mut health) = (&mut healths).get(message.entity_id) else {
+      log::error!("Player has no health");
+      continue
+    };
+    //Ignore respawn requests from living players
+    if !health.is_dead() {
+      continue
+    }
+    health.reset();
+
+    let position = config.world.spawn_point;
+    let mut transform = (&mut transforms).get(message.entity_id).unwrap();
+    let (_, direction, _) = transform.0.to_scale_rotation_translation();
+    transform.0 = Mat4::from_rotation_translation(direction, position);
+    if let Ok((mut fall, mut breath)) = (&mut falls, &mut breaths).get(message.entity_id) {
+      *fall = FallDamageState::default();
+      *breath = BreathState::default();
+    }
+
+    log::info!("Player {} respawned", message.client_id);
+
+    //Move the respawned player...
+    message.client.borrow_mut().send(
+      postcard::to_allocvec(
+        &ServerToClientMessage::PlayerRespawn { position }
+      ).unwrap().into_boxed_slice(),
+      Channel::SysEvt as usize,
+      SendMode::Reliable,
+    );
+
+    //...and let everyone know about it
+    broadcast(&server, &addrs, &ServerToClientMessage::PlayerHealthChanged {
+      client_id: message.client_id,
+      health: *health,
+    }, Channel::SysEvt);
+    broadcast(&server, &addrs, &ServerToClientMessage::PlayerPositionChanged {
+      client_id: message.client_id,
+      position,
+      direction,
+    }, Channel::Move);
+  }
+}
+
+pub fn update_health() -> Workload {
+  (
+    process_fall_damage,
+    update_breath.into_workload().make_fixed(BREATH_TICK_RATE_MILLIS, 2),
+    process_respawn_messages,
+  ).into_sequential_workload()
+}
@@ -1,5 +1,9 @@
+#![allow(
+  clippy::too_many_arguments, // allowed because systems often need a lot of arguments
+)]
+
 use shipyard::{IntoWorkload, Workload, WorkloadModificator, World};
-use std::{thread, time::Duration};
+use std::{env, process, thread, time::Duration};
 use kubi_shared::fixed_timestamp::{FixedTimestamp, init_fixed_timestamp_storage};
 
 mod util;
@@ -8,11 +12,14 @@ mod server;
 mod client;
 mod world;
 mod auth;
+mod health;
+mod preview;
 
 use config::read_config;
 use server::{bind_server, update_server, log_server_errors};
 use client::{init_client_maps, on_client_disconnect, sync_client_positions};
 use auth::authenticate_players;
+use health::update_health;
 use world::{init_world, save::save_modified, update_world};
 
 fn initialize() -> Workload {
@@ -32,6 +39,7 @@ fn update() -> Workload {
       log_server_errors,
       authenticate_players,
       update_world,
+      update_health,
       sync_client_positions,
       on_client_disconnect,
     ).into_workload(),
@@ -43,6 +51,15 @@ fn update() -> Workload {
 
 fn main() {
   kubi_logging::init();
+  //`kubi-server preview ...` renders world generator previews instead of running the server
+  let args: Vec<String> = env::args().collect();
+  if args.get(1).map(String::as_str) == Some("preview") {
+    if let Err(error) = preview::run(&args[2..]) {
+      log::error!("Preview failed: {:#}", error);
+      process::exit(1);
+    }
+    return
+  }
   let world = World::new();
   world.add_workload(initialize);
   world.add_workload(update);
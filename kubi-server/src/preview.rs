@@ -0,0 +1,282 @@
+//! World generator preview
+//!
+//! `kubi-server preview [options]` generates a region of the world without starting the server,
+//! and renders it to PNG images:
+//! - `height.png` - top-down terrain height (water is blue)
+//! - `biome.png` - top-down biome map
+//! - `surface.png` - top-down color of the highest block
+//! - `slice_x.png`, `slice_z.png` - vertical slices through the center of the region
+//!
+//! Options:
+//! - `--preset <name>` - world generator preset (`default`)
+//! - `--presets <file>` - additional presets, in the same format as `assets/worldgen.toml`
+//! - `--structures <dir>` - additional structure templates
+//! - `--seed <seed>` - world seed, decimal or `0x` hex (`0`)
+//! - `--center <x>,<z>` - center of the region, in blocks (`0,0`)
+//! - `--radius <chunks>` - horizontal radius of the region, in chunks, at least 1 (`8`)
+//! - `--height <min>,<max>` - vertical range of the region, in chunks, inclusive (`-4,3`)
+//! - `--output <dir>` - directory the images are written to (`preview`)
+
+use std::{fs, hash::Hasher, path::{Path, PathBuf}};
+use anyhow::{Context, Result, anyhow, bail};
+use glam::{ivec2, ivec3, IVec2, IVec3};
+use hashbrown::HashMap;
+use image::{Rgb, RgbImage};
+use rayon::prelude::*;
+use kubi_shared::{
+  block::{Block, BlockRegistry},
+  chunk::{BlockData, CHUNK_SIZE},
+  height::WorldHeight,
+  worldgen::{
+    biome::{Biome, BiomeMap},
+    generate_world,
+    hash::StableHasher,
+    preset::{WorldGenPreset, WorldGenPresets, DEFAULT_PRESET},
+    structures::StructureTemplates,
+  },
+};
+
+const SKY_COLOR: Rgb<u8> = Rgb([170, 205, 255]);
+
+struct PreviewOptions {
+  preset: WorldGenPreset,
+  templates: StructureTemplates,
+  seed: u64,
+  center: IVec2,
+  radius: i32,
+  height: (i32, i32),
+  output: PathBuf,
+}
+
+fn parse_pair(value: &str) -> Result<(i32, i32)> {
+  let (a, b) = value.split_once(',').ok_or_else(|| anyhow!("expected two comma separated numbers, got {:?}", value))?;
+  Ok((a.trim().parse()?, b.trim().parse()?))
+}
+
+fn parse_seed(value: &str) -> Result<u64> {
+  Ok(match value.strip_prefix("0x") {
+    Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16)?,
+    None => value.parse()?,
+  })
+}
+
+fn parse_options(args: &[String]) -> Result<PreviewOptions> {
+  let mut preset_name = DEFAULT_PRESET.to_string();
+  let mut presets = WorldGenPresets::builtin();
+  let mut options = PreviewOptions {
+    preset: WorldGenPreset::default(),
+    templates: StructureTemplates::builtin(),
+    seed: 0,
+    center: IVec2::ZERO,
+    radius: 8,
+    height: (-4, 3),
+    output: PathBuf::from("preview"),
+  };
+
+  let mut args = args.iter();
+  while let Some(flag) = args.next() {
+    let value = args.next().ok_or_else(|| anyhow!("missing value for {}", flag))?;
+    match flag.as_str() {
+      "--preset" => preset_name = value.clone(),
+      "--presets" => {
+        let data = fs::read_to_string(value).with_context(|| format!("failed to read {:?}", value))?;
+        //presets from the file take precedence over built-in ones with the same name
+        presets.presets.splice(0..0, WorldGenPresets::parse(&data)?.presets);
+      },
+      "--structures" => options.templates = StructureTemplates::load_dir(Path::new(value))?,
+      "--seed" => options.seed = parse_seed(value)?,
+      "--center" => options.center = parse_pair(value)?.into(),
+      "--radius" => options.radius = value.parse()?,
+      "--height" => options.height = parse_pair(value)?,
+      "--output" => options.output = PathBuf::from(value),
+      _ => bail!("unknown option {}", flag),
+    }
+  }
+
+  options.preset = presets.presets.into_iter()
+    .find(|preset| preset.name == preset_name)
+    .ok_or_else(|| anyhow!("unknown world generator preset {:?}", preset_name))?;
+  if options.height.0 > options.height.1 {
+    bail!("invalid height range {:?}", options.height);
+  }
+  if options.radius < 1 {
+    bail!("radius must be at least 1, got {}", options.radius);
+  }
+  Ok(options)
+}
+
+/// Generated chunks of the previewed region
+struct Region {
+  /// Built-in block registry, used to generate and color the blocks
+  registry: BlockRegistry,
+  chunks: HashMap<IVec3, BlockData>,
+  biomes: HashMap<IVec2, BiomeMap>,
+  /// Min and max corner of the region in blocks (inclusive, exclusive)
+  min: IVec3,
+  max: IVec3,
+}
+
+impl Region {
+  fn generate(options: &PreviewOptions) -> Self {
+    let center_chunk = options.center.div_euclid(IVec2::splat(CHUNK_SIZE as i32));
+    let columns: Vec<IVec2> = (-options.radius..options.radius)
+      .flat_map(|x| (-options.radius..options.radius).map(move |z| center_chunk + ivec2(x, z)))
+      .collect();
+
+    let registry = BlockRegistry::builtin();
+    let generated: Vec<_> = columns.par_iter().map(|&column| {
+      let chunks: Vec<(IVec3, BlockData, BiomeMap)> = (options.height.0..=options.height.1).map(|y| {
+        let position = ivec3(column.x, y, column.y);
+        let (blocks, _, biomes) = generate_world(position, options.seed, &options.preset, &options.templates, WorldHeight::default(), &registry, None).unwrap();
+        (position, blocks, biomes)
+      }).collect();
+      (column, chunks)
+    }).collect();
+
+    let mut region = Region {
+      registry,
+      chunks: HashMap::new(),
+      biomes: HashMap::new(),
+      min: ivec3(center_chunk.x - options.radius, options.height.0, center_chunk.y - options.radius) * CHUNK_SIZE as i32,
+      max: ivec3(center_chunk.x + options.radius, options.height.1 + 1, center_chunk.y + options.radius) * CHUNK_SIZE as i32,
+    };
+    for (column, chunks) in generated {
+      for (position, blocks, biomes) in chunks {
+        region.chunks.insert(position, blocks);
+        //all chunks of the column have the same biome map
+        region.biomes.insert(column, biomes);
+      }
+    }
+    region
+  }
+
+  fn block(&self, position: IVec3) -> Block {
+    let chunk = position.div_euclid(IVec3::splat(CHUNK_SIZE as i32));
+    self.chunks.get(&chunk)
+      .map(|blocks| blocks.get(position.rem_euclid(IVec3::splat(CHUNK_SIZE as i32))))
+      .unwrap_or(Block::Air)
+  }
+
+  fn biome(&self, x: i32, z: i32) -> Biome {
+    let column = ivec2(x, z).div_euclid(IVec2::splat(CHUNK_SIZE as i32));
+    let local = ivec2(x, z).rem_euclid(IVec2::splat(CHUNK_SIZE as i32));
+    self.biomes.get(&column).map(|map| map.get(local.x as usize, local.y as usize)).unwrap_or_default()
+  }
+
+  /// Highest non-air block of the column and its height
+  fn top(&self, x: i32, z: i32) -> Option<(i32, Block)> {
+    (self.min.y..self.max.y).rev()
+      .map(|y| (y, self.block(ivec3(x, y, z))))
+      .find(|&(_, block)| block != Block::Air)
+  }
+
+  /// Render a top-down image, `pixel` gets global x and z of the column
+  fn render_map(&self, pixel: impl Fn(i32, i32) -> Rgb<u8>) -> RgbImage {
+    let size = self.max - self.min;
+    RgbImage::from_fn(size.x as u32, size.z as u32, |x, z| {
+      pixel(self.min.x + x as i32, self.min.z + z as i32)
+    })
+  }
+
+  /// Render a vertical slice along x (at the center z) or along z (at the center x)
+  fn render_slice(&self, along_x: bool) -> RgbImage {
+    let size = self.max - self.min;
+    let center = (self.min + self.max) / 2;
+    let width = if along_x { size.x } else { size.z };
+    RgbImage::from_fn(width as u32, size.y as u32, |i, y| {
+      let y = self.max.y - 1 - y as i32;
+      let position = match along_x {
+        true => ivec3(self.min.x + i as i32, y, center.z),
+        false => ivec3(center.x, y, self.min.z + i as i32),
+      };
+      match self.block(position) {
+        Block::Air => SKY_COLOR,
+        block => block_color(&self.registry, block),
+      }
+    })
+  }
+}
+
+fn block_color(registry: &BlockRegistry, block: Block) -> Rgb<u8> {
+  let name = registry.get(block).name.as_str();
+  Rgb(match name {
+    "stone" => [125, 125, 125],
+    "dirt" => [134, 96, 67],
+    "grass" => [95, 159, 53],
+    "snowy_grass" => [240, 242, 250],
+    "sand" => [219, 207, 163],
+    "cobblestone" => [100, 100, 100],
+    "tall_grass" => [110, 175, 60],
+    "planks" => [160, 130, 80],
+    "torch" => [255, 200, 60],
+    "wood" => [102, 81, 51],
+    "leaf" => [55, 115, 35],
+    "water" => [50, 80, 200],
+    "coal_ore" => [45, 45, 45],
+    "iron_ore" => [196, 150, 118],
+    "gold_ore" => [230, 190, 40],
+    "diamond_ore" => [80, 210, 220],
+    //other blocks get a random (but consistent) color
+    _ => {
+      let mut hasher = StableHasher::new(0);
+      hasher.write(name.as_bytes());
+      let hash = hasher.finish();
+      [hash as u8, (hash >> 8) as u8, (hash >> 16) as u8]
+    }
+  })
+}
+
+fn biome_color(biome: Biome) -> Rgb<u8> {
+  Rgb(match biome {
+    Biome::Plains => [140, 190, 80],
+    Biome::Forest => [40, 110, 40],
+    Biome::Desert => [225, 205, 130],
+    Biome::Snowy => [235, 240, 250],
+    Biome::Mountains => [120, 115, 110],
+  })
+}
+
+/// Gray shade of a height, from black at `min` to white at `max`
+fn height_color(height: i32, min: i32, max: i32) -> Rgb<u8> {
+  let value = ((height - min) as f32 / (max - min).max(1) as f32).clamp(0., 1.);
+  let shade = (value * 255.) as u8;
+  Rgb([shade, shade, shade])
+}
+
+pub fn run(args: &[String]) -> Result<()> {
+  let options = parse_options(args)?;
+  log::info!(
+    "Generating preview of {:?} (seed {:#x}) around {} with radius {} chunks",
+    options.preset.name, options.seed, options.center, options.radius
+  );
+  let region = Region::generate(&options);
+
+  fs::create_dir_all(&options.output)?;
+  let save = |name: &str, image: RgbImage| -> Result<()> {
+    let path = options.output.join(name);
+    image.save(&path).with_context(|| format!("failed to save {:?}", path))?;
+    log::info!("Saved {:?}", path);
+    Ok(())
+  };
+
+  save("height.png", region.render_map(|x, z| match region.top(x, z) {
+    //water is tinted blue, darker where it's deeper
+    Some((height, block)) if region.registry.get(block).fluid => {
+      let floor = (region.min.y..height).rev()
+        .find(|&y| !region.registry.get(region.block(ivec3(x, y, z))).fluid)
+        .unwrap_or(region.min.y);
+      let depth = ((height - floor) as f32 / 32.).clamp(0., 1.);
+      Rgb([20, (110. - depth * 70.) as u8, (230. - depth * 100.) as u8])
+    },
+    Some((height, _)) => height_color(height, region.min.y, region.max.y),
+    None => Rgb([0, 0, 0]),
+  }))?;
+  save("biome.png", region.render_map(|x, z| biome_color(region.biome(x, z))))?;
+  save("surface.png", region.render_map(|x, z| match region.top(x, z) {
+    Some((_, block)) => block_color(&region.registry, block),
+    None => SKY_COLOR,
+  }))?;
+  save("slice_x.png", region.render_slice(true))?;
+  save("slice_z.png", region.render_slice(false))?;
+  Ok(())
+}
@@ -1,13 +1,14 @@
 use std::{net::SocketAddr, rc::Rc, cell::RefCell};
-use shipyard::{View, Get, EntityId};
-use uflow::server::{Event as ServerEvent, RemoteClient};
+use shipyard::{View, Get, EntityId, IntoIter};
+use uflow::{server::{Event as ServerEvent, RemoteClient}, SendMode};
 use kubi_shared::networking::{
-  messages::ClientToServerMessage,
+  channels::Channel,
+  messages::{ClientToServerMessage, ServerToClientMessage},
   client::{Client, ClientId}
 };
 use crate::{
   server::{IsMessageOfType, UdpServer}, 
-  client::ClientAddressMap
+  client::{ClientAddress, ClientAddressMap}
 };
 
 #[derive(Clone)]
@@ -34,6 +35,23 @@ impl<'a> From<CtsMessageMetadata<'a>> for &'a Rc<RefCell<RemoteClient>> {
   fn from(value: CtsMessageMetadata<'a>) -> Self { value.client }
 }
 
+/// Send a message to all connected clients
+pub fn broadcast(
+  server: &UdpServer,
+  addrs: &View<ClientAddress>,
+  message: &ServerToClientMessage,
+  channel: Channel,
+) {
+  let data = postcard::to_allocvec(message).unwrap().into_boxed_slice();
+  for address in addrs.iter() {
+    let Some(client) = server.0.client(&address.0) else {
+      log::error!("Client with address not found");
+      continue
+    };
+    client.borrow_mut().send(data.clone(), channel as usize, SendMode::Reliable);
+  }
+}
+
 pub fn check_message_auth<'a, const C_MSG: u8>(
   server: &'a UdpServer,
   event: &ServerEvent, 
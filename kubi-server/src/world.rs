@@ -1,9 +1,15 @@
-use shipyard::{AllStoragesView, Get, IntoIter, IntoWorkload, NonSendSync, SystemModificator, Unique, UniqueView, UniqueViewMut, View, Workload};
+use shipyard::{AllStoragesView, Component, Get, IntoIter, IntoWorkload, NonSendSync, SystemModificator, Unique, UniqueView, UniqueViewMut, View, ViewMut, Workload};
 use glam::IVec3;
 use hashbrown::HashMap;
 use kubi_shared::{
+  block::{Block, BlockState, SharedBlockRegistry},
   chunk::CHUNK_SIZE,
+  height::WorldHeight,
+  player::{GameMode, Inventory},
   queue::QueuedBlock,
+  tick::{BlockAccess, RandomTicks, ScheduledTicks, BLOCK_TICK_RATE_MILLIS},
+  falling_block::FallingBlockIds,
+  fixed_timestamp::FixedTimestamp,
   networking::{
     channels::Channel,
     client::{Client, ClientId},
@@ -13,7 +19,7 @@ use kubi_shared::{
 use uflow::{server::RemoteClient, SendMode};
 use lz4_flex::compress_prepend_size as lz4_compress;
 use anyhow::Result;
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, rc::Rc, time::Instant};
 use kubi_shared::networking::client::ClientIdMap;
 use crate::{
   server::{UdpServer, ServerEvents}, 
@@ -25,12 +31,16 @@ use crate::{
 pub mod chunk;
 pub mod tasks;
 pub mod save;
+pub mod ticks;
+pub mod falling_blocks;
 
 use chunk::Chunk;
 
 use self::{
   tasks::{ChunkTaskManager, ChunkTask, ChunkTaskResponse, init_chunk_task_manager},
   chunk::ChunkState,
+  ticks::process_block_ticks,
+  falling_blocks::update_falling_blocks,
 };
 
 #[derive(Unique, Default)]
@@ -38,6 +48,14 @@ pub struct LocalBlockQueue {
   pub queue: Vec<QueuedBlock>,
 }
 
+/// Fraction of the mining time (block hardness) that has to pass on the server\
+/// before a block break is accepted, leaves some room for network jitter
+const MINING_TIME_TOLERANCE: f32 = 0.8;
+
+/// Block the player is currently mining and when they started mining it (survival mode)
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct MiningState(pub Option<(IVec3, Instant)>);
+
 #[derive(Unique, Default)]
 pub struct ChunkManager {
   pub chunks: HashMap<IVec3, Chunk>
@@ -51,6 +69,24 @@ impl ChunkManager {
   pub fn new() -> Self {
     Self::default()
   }
+  /// Get the block at `position` (in world coordinates)\
+  /// Returns `None` if the chunk is not loaded
+  pub fn get_block(&self, position: IVec3) -> Option<Block> {
+    Some(self.get_block_state(position)?.block)
+  }
+  /// Get the block state at `position` (in world coordinates)\
+  /// Returns `None` if the chunk is not loaded
+  pub fn get_block_state(&self, position: IVec3) -> Option<BlockState> {
+    let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE as i32));
+    let block_position = position.rem_euclid(IVec3::splat(CHUNK_SIZE as i32));
+    let blocks = self.chunks.get(&chunk_position)?.blocks.as_ref()?;
+    Some(blocks.get_state(block_position))
+  }
+}
+impl BlockAccess for ChunkManager {
+  fn get_block_state(&self, position: IVec3) -> Option<BlockState> {
+    Self::get_block_state(self, position)
+  }
 }
 
 ///Sends a compressed chunk packet
@@ -77,6 +113,7 @@ fn process_chunk_requests(
   mut chunk_manager: UniqueViewMut<ChunkManager>,
   task_manager: UniqueView<ChunkTaskManager>,
   config: UniqueView<ConfigTable>,
+  height: UniqueView<WorldHeight>,
   addr_map: UniqueView<ClientAddressMap>,
   clients: View<Client>
 ) {
@@ -89,16 +126,25 @@ fn process_chunk_requests(
       unreachable!()
     };
 
+    //chunks outside of the world height are always empty, clients don't need to request them
+    if !height.contains_chunk(chunk_position.y) {
+      log::warn!("Ignored request for chunk {chunk_position} outside of the world height");
+      continue
+    }
+
     if let Some(chunk) = chunk_manager.chunks.get_mut(&chunk_position) {
       chunk.subscriptions.insert(message.client_id);
       //TODO Start task here if status is "Nothing"
-      if let Some(blocks) = &chunk.blocks {
+      if let Some(blocks) = &mut chunk.blocks {
+        //get rid of block states that are no longer used after block changes
+        blocks.compact();
         send_chunk_compressed(
           message.client,
           &ServerToClientMessage::ChunkResponse {
             chunk: chunk_position,
             data: blocks.clone(),
-            queued: Vec::with_capacity(0)
+            queued: Vec::with_capacity(0),
+            biomes: chunk.biomes.clone().unwrap_or_default(),
           }
         ).unwrap();
       }
@@ -122,9 +168,10 @@ fn process_finished_tasks(
   id_map: UniqueView<ClientIdMap>,
   client_addr: View<ClientAddress>,
   mut local_queue: UniqueViewMut<LocalBlockQueue>,
+  mut ticks: UniqueViewMut<ScheduledTicks>,
 ) {
   'outer: while let Some(res) = task_manager.receive() {
-    let ChunkTaskResponse::ChunkLoaded { chunk_position, blocks, queue } = res;
+    let ChunkTaskResponse::ChunkLoaded { chunk_position, blocks, queue, ticks: pending_ticks, biomes } = res;
     let Some(chunk) = chunk_manager.chunks.get_mut(&chunk_position) else {
       log::warn!("Chunk discarded: Doesn't exist");
       continue
@@ -135,15 +182,18 @@ fn process_finished_tasks(
     }
     chunk.state = ChunkState::Loaded;
     chunk.blocks = Some(blocks.clone());
+    chunk.biomes = Some(biomes.clone());
 
     local_queue.queue.extend_from_slice(&queue);
+    ticks.restore(&pending_ticks);
 
     log::debug!("Chunk {chunk_position} loaded, {} subs", chunk.subscriptions.len());
 
     let chunk_packet = &ServerToClientMessage::ChunkResponse {
       chunk: chunk_position,
       data: blocks,
-      queued: queue //should this be here?
+      queued: queue, //should this be here?
+      biomes,
     };
 
     for &subscriber in &chunk.subscriptions {
@@ -195,13 +245,41 @@ fn process_chunk_unsubscribe_events(
   }
 }
 
+fn process_start_mining_messages(
+  server: NonSendSync<UniqueView<UdpServer>>,
+  events: UniqueView<ServerEvents>,
+  addr_map: UniqueView<ClientAddressMap>,
+  clients: View<Client>,
+  mut mining: ViewMut<MiningState>,
+) {
+  for event in &events.0 {
+    let Some(message) = check_message_auth
+      ::<{ClientToServerMessageType::StartMining as u8}>
+      (&server, event, &clients, &addr_map) else { continue };
+
+    let ClientToServerMessage::StartMining { position } = message.message else { unreachable!() };
+
+    let Ok(mut mining) = (&mut mining).get(message.entity_id) else {
+      log::error!("Player has no mining state");
+      continue
+    };
+    mining.0 = Some((position, Instant::now()));
+  }
+}
+
 fn process_block_queue_messages(
   server: NonSendSync<UniqueView<UdpServer>>,
   events: UniqueView<ServerEvents>,
   addr_map: UniqueView<ClientAddressMap>,
   clients: View<Client>,
   addrs: View<ClientAddress>,
+  gamemodes: View<GameMode>,
+  mut inventories: ViewMut<Inventory>,
+  mut mining: ViewMut<MiningState>,
+  chunk_manager: UniqueView<ChunkManager>,
   mut queue: UniqueViewMut<LocalBlockQueue>,
+  height: UniqueView<WorldHeight>,
+  registry: UniqueView<SharedBlockRegistry>,
 ) {
   for event in &events.0 {
     let Some(message) = check_message_auth
@@ -210,6 +288,73 @@ fn process_block_queue_messages(
 
     let ClientToServerMessage::QueueBlock { item } = message.message else { unreachable!() };
 
+    //Check if the player is allowed to make this change
+    //(blocks outside of the build height and in the bedrock floor can't be changed in any game mode)
+    let gamemode = gamemodes.get(message.entity_id).copied().unwrap_or_default();
+    let in_build_height = height.can_modify(item.position.y);
+    if !in_build_height || gamemode.has_survival_rules() || !gamemode.can_modify_world() {
+      let Ok(mut inventory) = (&mut inventories).get(message.entity_id) else {
+        log::error!("Player has no inventory");
+        continue
+      };
+      let Ok(mut mining) = (&mut mining).get(message.entity_id) else {
+        log::error!("Player has no mining state");
+        continue
+      };
+      let current_state = chunk_manager.get_block_state(item.position);
+      let current_block = current_state.map(|state| state.block);
+      let allowed = in_build_height && gamemode.can_modify_world() && match (current_block, item.block_type) {
+        //Breaking a block: the block must be breakable, and the player must have been mining it for long enough
+        (Some(current), Block::Air) => match registry.get(current).hardness {
+          Some(hardness) => {
+            let mined_for = mining.0
+              .filter(|&(position, _)| position == item.position)
+              .map(|(_, started)| started.elapsed().as_secs_f32());
+            let mined = mined_for.is_some_and(|time| time >= hardness * MINING_TIME_TOLERANCE);
+            if mined {
+              mining.0 = None;
+              if let Some(drop) = registry.get(current).drops {
+                inventory.add_single(drop, &registry);
+              }
+            }
+            mined
+          },
+          None => false,
+        },
+        //Placing a block: the player must have it in their inventory
+        (Some(current), block) if !registry.get(current).raycast_collision => {
+          inventory.take_block(block, &registry)
+        },
+        _ => false,
+      };
+      if !allowed {
+        log::warn!("Rejected block change {:?} at {} ({gamemode:?})", item.block_type, item.position);
+        //Revert the change on the client side
+        if let Some(state) = current_state {
+          message.client.borrow_mut().send(
+            postcard::to_allocvec(
+              &ServerToClientMessage::QueueBlock {
+                item: QueuedBlock { block_type: state.block, properties: state.properties, ..item }
+              }
+            ).unwrap().into_boxed_slice(),
+            Channel::Block as usize,
+            SendMode::Reliable,
+          );
+        }
+      }
+      //Sync the inventory, as the client might have predicted it wrong
+      message.client.borrow_mut().send(
+        postcard::to_allocvec(
+          &ServerToClientMessage::InventoryChanged { inventory: *inventory }
+        ).unwrap().into_boxed_slice(),
+        Channel::Block as usize,
+        SendMode::Reliable,
+      );
+      if !allowed {
+        continue
+      }
+    }
+
     //place in our local world
     queue.queue.push(item);
 
@@ -239,8 +384,11 @@ fn process_block_queue_messages(
 fn process_block_queue(
   mut chunk_manager: UniqueViewMut<ChunkManager>,
   mut queue: UniqueViewMut<LocalBlockQueue>,
+  mut ticks: UniqueViewMut<ScheduledTicks>,
+  registry: UniqueView<SharedBlockRegistry>,
 ) {
   let initial_len = queue.queue.len();
+  let mut changed = Vec::new();
   queue.queue.retain(|item| {
     let chunk_position = item.position.div_euclid(IVec3::splat(CHUNK_SIZE as i32));
     let block_position = item.position.rem_euclid(IVec3::splat(CHUNK_SIZE as i32));
@@ -250,13 +398,20 @@ fn process_block_queue(
     let Some(blocks) = &mut chunk.blocks else {
       return true
     };
-    let block = &mut blocks[block_position.x as usize][block_position.y as usize][block_position.z as usize];
-    if item.block_type != *block {
-      *block = item.block_type;
+    if item.soft && blocks.get_state(block_position).block != Block::Air {
+      return false
+    }
+    if item.state() != blocks.get_state(block_position) {
+      blocks.set_state(block_position, item.state());
       chunk.data_modified = true;
+      changed.push(item.position);
     }
     false
   });
+  //let nearby blocks react to the changes
+  for position in changed {
+    ticks.schedule_updates(&*chunk_manager, &registry, position);
+  }
   if initial_len != queue.queue.len() {
     log::debug!("queue processed {}/{} items", initial_len - queue.queue.len(), initial_len);
   }
@@ -268,16 +423,21 @@ fn init_chunk_manager_and_block_queue(
 ) {
   storages.add_unique(ChunkManager::new());
   storages.add_unique(LocalBlockQueue::default());
+  storages.add_unique(ScheduledTicks::new());
+  storages.add_unique(RandomTicks::new());
+  storages.add_unique(FallingBlockIds::new());
 }
 
 pub fn preheat_world(
   mut chunk_manager: UniqueViewMut<ChunkManager>,
   task_manager: UniqueView<ChunkTaskManager>,
   config: UniqueView<ConfigTable>,
+  height: UniqueView<WorldHeight>,
 ) {
   let r = config.world.preheat_radius as i32;
+  let chunk_range = height.chunk_range();
   for x in -r..=r {
-    for y in -r..=r {
+    for y in (-r).max(*chunk_range.start())..=r.min(*chunk_range.end()) {
       for z in -r..=r {
         let chunk_position = IVec3::new(x, y, z);
         let mut chunk = Chunk::new();
@@ -303,8 +463,13 @@ pub fn init_world() -> Workload {
 pub fn update_world() -> Workload {
   (
     process_finished_tasks,
+    process_start_mining_messages,
     process_block_queue_messages,
     process_block_queue,
+    (
+      process_block_ticks,
+      update_falling_blocks,
+    ).into_sequential_workload().make_fixed(BLOCK_TICK_RATE_MILLIS, 1),
     process_chunk_unsubscribe_events,
     process_chunk_requests,
   ).into_sequential_workload()
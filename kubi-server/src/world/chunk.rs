@@ -2,7 +2,8 @@ use hashbrown::HashSet;
 use nohash_hasher::BuildNoHashHasher;
 use kubi_shared::{
   chunk::BlockData, 
-  networking::client::ClientId
+  networking::client::ClientId,
+  worldgen::biome::BiomeMap,
 };
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -15,8 +16,12 @@ pub enum ChunkState {
 pub struct Chunk {
   pub state: ChunkState,
   pub blocks: Option<BlockData>,
+  /// Biome of each column, sent to clients together with the blocks
+  pub biomes: Option<BiomeMap>,
   pub subscriptions: HashSet<ClientId, BuildNoHashHasher<ClientId>>,
   pub data_modified: bool,
+  /// The last save included falling blocks, so the chunk has to be saved again once they leave it
+  pub saved_falling_blocks: bool,
 }
 
 impl Chunk {
@@ -24,8 +29,10 @@ impl Chunk {
     Self {
       state: ChunkState::Nothing,
       blocks: None,
+      biomes: None,
       subscriptions: HashSet::with_capacity_and_hasher(4, BuildNoHashHasher::default()),
       data_modified: false,
+      saved_falling_blocks: false,
     }
   }
 }
@@ -0,0 +1,41 @@
+use shipyard::{AllStoragesViewMut, IntoIter, IntoWithId, NonSendSync, UniqueView, UniqueViewMut, View, ViewMut};
+use kubi_shared::{
+  block::SharedBlockRegistry,
+  falling_block::{FallingBlock, FallingBlockUpdate},
+  networking::{channels::Channel, messages::ServerToClientMessage},
+  tick::BLOCK_TICK_RATE_MILLIS,
+};
+use crate::{client::ClientAddress, server::UdpServer, util::broadcast};
+use super::{ChunkManager, LocalBlockQueue};
+
+/// Simulate falling blocks, turning them back into blocks once they land\
+/// Runs at the block tick rate
+pub fn update_falling_blocks(
+  mut all_storages: AllStoragesViewMut,
+) {
+  let mut to_delete = Vec::new();
+  {
+    let server = all_storages.borrow::<NonSendSync<UniqueView<UdpServer>>>().unwrap();
+    let chunk_manager = all_storages.borrow::<UniqueView<ChunkManager>>().unwrap();
+    let registry = all_storages.borrow::<UniqueView<SharedBlockRegistry>>().unwrap();
+    let mut queue = all_storages.borrow::<UniqueViewMut<LocalBlockQueue>>().unwrap();
+    let mut falling_blocks = all_storages.borrow::<ViewMut<FallingBlock>>().unwrap();
+    let addrs = all_storages.borrow::<View<ClientAddress>>().unwrap();
+
+    let dt = BLOCK_TICK_RATE_MILLIS as f32 / 1000.;
+    for (entity_id, falling_block) in (&mut falling_blocks).iter().with_id() {
+      match falling_block.update(&*chunk_manager, &registry, dt) {
+        FallingBlockUpdate::Falling => continue,
+        FallingBlockUpdate::Landed(item) => {
+          queue.queue.push(item);
+          broadcast(&server, &addrs, &ServerToClientMessage::QueueBlock { item }, Channel::Block);
+        },
+      }
+      broadcast(&server, &addrs, &ServerToClientMessage::FallingBlockRemoved { id: falling_block.id }, Channel::Block);
+      to_delete.push(entity_id);
+    }
+  }
+  for entity_id in to_delete {
+    all_storages.delete_entity(entity_id);
+  }
+}
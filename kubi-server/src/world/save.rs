@@ -1,43 +1,123 @@
-use kubi_shared::data::{io_thread::IOThreadManager, open_local_save_file};
-use shipyard::{AllStoragesView, UniqueView, UniqueViewMut};
+use std::fs;
+use kubi_shared::{
+  block::{BlockDefinitions, BlockRegistry, SharedBlockRegistry},
+  chunk::CHUNK_SIZE,
+  data::{io_thread::IOThreadManager, open_local_save_file},
+  falling_block::FallingBlock,
+  height::WorldHeight,
+  queue::QueuedBlock,
+  tick::ScheduledTicks,
+  worldgen::{
+    preset::WorldGenPreset,
+    structures::StructureTemplates,
+  },
+};
+use glam::IVec3;
+use hashbrown::HashMap;
+use shipyard::{AllStoragesView, IntoIter, UniqueView, UniqueViewMut, View};
 use crate::config::ConfigTable;
 use super::{
   tasks::{ChunkTask, ChunkTaskManager},
-  ChunkManager,
+  ChunkManager, LocalBlockQueue,
 };
 
-pub fn init_save_file(storages: &AllStoragesView) -> Option<IOThreadManager> {
+fn load_block_definitions(config: &ConfigTable) -> BlockDefinitions {
+  let Some(path) = &config.world.blocks else {
+    return BlockDefinitions::builtin()
+  };
+  log::info!("Loading block definitions from {:?}", path);
+  let data = fs::read_to_string(path).expect("Failed to read block definitions");
+  BlockDefinitions::parse(&data).expect("Invalid block definitions")
+}
+
+fn load_structure_templates(config: &ConfigTable) -> StructureTemplates {
+  let Some(path) = &config.world.structures else {
+    return StructureTemplates::builtin()
+  };
+  log::info!("Loading structure templates from {:?}", path);
+  StructureTemplates::load_dir(path).expect("Failed to load structure templates")
+}
+
+/// Open the save file\
+/// Returns the block registry, world generator preset, structure templates and build height limits of the world
+/// (stored in the save file, if there is one)
+pub fn init_save_file(storages: &AllStoragesView) -> (Option<IOThreadManager>, SharedBlockRegistry, WorldGenPreset, StructureTemplates, WorldHeight) {
   let config = storages.borrow::<UniqueView<ConfigTable>>().unwrap();
+  let definitions = load_block_definitions(&config);
+  let templates = load_structure_templates(&config);
+  log::info!("using {} structure templates", templates.len());
+  let preset = config.world.generator.preset();
+  let height = config.world.height;
+  height.validate().expect("Invalid build height limits");
   if let Some(file_path) = &config.world.file {
     log::info!("Initializing save file from {:?}", file_path);
-    let save = open_local_save_file(file_path).unwrap();
-    Some(IOThreadManager::new(save))
+    let mut save = open_local_save_file(file_path).unwrap();
+    let registry = SharedBlockRegistry::new(save.create_block_registry(&definitions).expect("Failed to create block registry"));
+    let preset = save.world_generator(preset).expect("Failed to store world generator preset");
+    if !save.check_structure_templates(&templates).expect("Failed to store structure templates") {
+      log::warn!("structure templates changed since the world was created, structures in new chunks won't match the existing ones");
+    }
+    let height = save.world_height(height).expect("Failed to store build height limits");
+    (Some(IOThreadManager::new(save)), registry, preset, templates, height)
   } else {
     log::warn!("No save file specified, world will not be saved");
-    None
+    let registry = SharedBlockRegistry::new(BlockRegistry::new(&definitions, &[]).expect("Failed to create block registry"));
+    (None, registry, preset, templates, height)
   }
 }
 
 pub fn save_modified(
   mut chunks: UniqueViewMut<ChunkManager>,
   ctm: UniqueView<ChunkTaskManager>,
+  ticks: UniqueView<ScheduledTicks>,
+  mut queue: UniqueViewMut<LocalBlockQueue>,
+  falling_blocks: View<FallingBlock>,
+  registry: UniqueView<SharedBlockRegistry>,
 ) {
   log::info!("Saving...");
+
+  //falling blocks are saved as blocks at their current position
+  let mut falling_by_chunk: HashMap<IVec3, Vec<&FallingBlock>> = HashMap::new();
+  for falling_block in falling_blocks.iter() {
+    falling_by_chunk.entry(falling_block.chunk_position()).or_default().push(falling_block);
+  }
+
   let mut amount_saved = 0;
   for (position, chunk) in chunks.chunks.iter_mut() {
-    if chunk.data_modified {
-      let Some(data) = chunk.blocks.clone() else {
+    let falling = falling_by_chunk.get(position);
+    if chunk.data_modified || chunk.saved_falling_blocks || falling.is_some() {
+      let Some(mut data) = chunk.blocks.clone() else {
         continue
       };
+      let mut pending_ticks = ticks.pending_in_chunk(*position);
+      for falling_block in falling.into_iter().flatten() {
+        falling_block.persist(&registry, &mut data, &mut pending_ticks);
+      }
       ctm.run(ChunkTask::SaveChunk {
         position: *position,
         data,
+        ticks: pending_ticks,
       });
       chunk.data_modified = false;
+      chunk.saved_falling_blocks = falling.is_some();
       amount_saved += 1;
     }
   }
   if amount_saved > 0 {
     log::info!("Queued {} chunks for saving", amount_saved);
   }
+
+  //move blocks queued for chunks that aren't loaded (like leaves of trees on chunk borders) to the save file,
+  //they'll be sent back when the chunk is loaded
+  if ctm.has_save_file() {
+    let (unloaded, loaded): (Vec<QueuedBlock>, Vec<QueuedBlock>) = queue.queue.drain(..).partition(|item| {
+      let chunk_position = item.position.div_euclid(IVec3::splat(CHUNK_SIZE as i32));
+      !chunks.chunks.contains_key(&chunk_position)
+    });
+    queue.queue = loaded;
+    if !unloaded.is_empty() {
+      log::info!("Saving {} blocks queued for unloaded chunks", unloaded.len());
+      ctm.run(ChunkTask::QueueBlocks { blocks: unloaded });
+    }
+  }
 }
\ No newline at end of file
@@ -1,11 +1,13 @@
-use shipyard::{Unique, AllStoragesView};
+use std::sync::Arc;
+use shipyard::{Unique, AllStoragesView, UniqueView};
 use flume::{unbounded, Sender, Receiver};
 use glam::IVec3;
 use rayon::{ThreadPool, ThreadPoolBuilder};
 use anyhow::Result;
 use kubi_shared::{
-  chunk::BlockData, data::io_thread::{IOCommand, IOResponse, IOThreadManager}, queue::QueuedBlock, worldgen::generate_world
+  block::{BlockRegistry, SharedBlockRegistry}, chunk::BlockData, data::io_thread::{IOCommand, IOResponse, IOThreadManager}, height::WorldHeight, player::GameMode, queue::QueuedBlock, tick::PendingTick, worldgen::{biome::BiomeMap, generate_biome_map, generate_world, preset::WorldGenPreset, structures::StructureTemplates}
 };
+use crate::config::ConfigTable;
 use super::save::init_save_file;
 
 pub enum ChunkTask {
@@ -16,6 +18,16 @@ pub enum ChunkTask {
   SaveChunk {
     position: IVec3,
     data: BlockData,
+    ticks: Vec<PendingTick>,
+  },
+  /// Store blocks queued for chunks that aren't loaded in the save file
+  QueueBlocks {
+    blocks: Vec<QueuedBlock>,
+  },
+  /// Store the game mode of a player in the save file
+  SavePlayer {
+    username: String,
+    gamemode: GameMode,
   },
 }
 
@@ -23,7 +35,9 @@ pub enum ChunkTaskResponse {
   ChunkLoaded {
     chunk_position: IVec3,
     blocks: BlockData,
-    queue: Vec<QueuedBlock>
+    queue: Vec<QueuedBlock>,
+    ticks: Vec<PendingTick>,
+    biomes: BiomeMap,
   }
 }
 
@@ -32,44 +46,83 @@ pub struct ChunkTaskManager {
   channel: (Sender<ChunkTaskResponse>, Receiver<ChunkTaskResponse>),
   pool: ThreadPool,
   iota: Option<IOThreadManager>,
+  generator: Arc<WorldGenPreset>,
+  templates: Arc<StructureTemplates>,
+  registry: Arc<BlockRegistry>,
+  /// World seed, used to generate chunks that had blocks queued in the save file, but weren't saved themselves\
+  /// (and the biome maps of chunks loaded from the save file)
+  seed: u64,
+  height: WorldHeight,
 }
 
 impl ChunkTaskManager {
-  pub fn new(iota: Option<IOThreadManager>) -> Result<Self> {
+  pub fn new(
+    iota: Option<IOThreadManager>,
+    registry: Arc<BlockRegistry>,
+    generator: WorldGenPreset,
+    templates: StructureTemplates,
+    seed: u64,
+    height: WorldHeight,
+  ) -> Result<Self> {
     Ok(Self {
       channel: unbounded(),
       pool: ThreadPoolBuilder::new().build()?,
       iota,
+      generator: Arc::new(generator),
+      templates: Arc::new(templates),
+      registry,
+      seed,
+      height,
     })
   }
 
+  /// Generate the chunk on the thread pool\
+  /// `queued` blocks are applied after the blocks queued by the world generator
+  fn generate(&self, chunk_position: IVec3, seed: u64, queued: Vec<QueuedBlock>) {
+    let sender = self.channel.0.clone();
+    let generator = Arc::clone(&self.generator);
+    let templates = Arc::clone(&self.templates);
+    let registry = Arc::clone(&self.registry);
+    let height = self.height;
+    self.pool.spawn(move || {
+      sender.send({
+        //unwrap is fine because abort is not possible
+        let (blocks, mut queue, biomes) = generate_world(chunk_position, seed, &generator, &templates, height, &registry, None).unwrap();
+        queue.extend(queued);
+        ChunkTaskResponse::ChunkLoaded { chunk_position, blocks, queue, ticks: Vec::new(), biomes }
+      }).unwrap()
+    });
+  }
+
   pub fn run(&self, task: ChunkTask) {
     match task {
       ChunkTask::LoadChunk { position: chunk_position, seed } => {
-        // 1. Check if the chunk exists in the save file
-        if let ChunkTask::LoadChunk { position, .. } = &task {
-          if let Some(iota) = &self.iota {
-            if iota.chunk_exists(*position) {
-              iota.send(IOCommand::LoadChunk { position: *position });
-              return
-            }
+        // 1. Check if the chunk (or blocks queued for it) exists in the save file
+        if let Some(iota) = &self.iota {
+          if iota.chunk_exists(chunk_position) || iota.has_queued_blocks(chunk_position) {
+            iota.send(IOCommand::LoadChunk { position: chunk_position });
+            return
           }
         }
 
         // 2. Generate the chunk if it doesn't exist
-        let sender = self.channel.0.clone();
-        self.pool.spawn(move || {
-          sender.send({
-            //unwrap is fine because abort is not possible
-            let (blocks, queue) = generate_world(chunk_position, seed, None).unwrap();
-            ChunkTaskResponse::ChunkLoaded { chunk_position, blocks, queue }
-          }).unwrap()
-        });
+        self.generate(chunk_position, seed, Vec::new());
       },
-      ChunkTask::SaveChunk { position, data } => {
+      ChunkTask::QueueBlocks { blocks } => {
+        // Store the blocks until their chunks get loaded
+        if let Some(iota) = &self.iota {
+          iota.send(IOCommand::QueueBlocks { blocks });
+        }
+      },
+      ChunkTask::SaveChunk { position, data, ticks } => {
         // Save the chunk to the save file
         if let Some(iota) = &self.iota {
-          iota.send(IOCommand::SaveChunk { position, data });
+          iota.send(IOCommand::SaveChunk { position, data, ticks });
+        }
+      },
+      ChunkTask::SavePlayer { username, gamemode } => {
+        if let Some(iota) = &self.iota {
+          iota.send(IOCommand::SavePlayer { username, gamemode });
         }
       },
     }
@@ -78,18 +131,35 @@ impl ChunkTaskManager {
   pub fn receive(&self) -> Option<ChunkTaskResponse> {
     // Try to receive IO results first
     // If there are none, try to receive worldgen results
-    self.iota.as_ref().and_then(|iota| {
-      iota.poll_single().map(|response| match response {
-        IOResponse::ChunkLoaded { position, data } => ChunkTaskResponse::ChunkLoaded {
-          chunk_position: position,
-          blocks: data.expect("chunk data exists in the header, but was not loaded"),
-          queue: Vec::with_capacity(0)
-        },
-        _ => panic!("Unexpected response from IO thread"),
-      })
-    }).or_else(|| {
-      self.channel.1.try_recv().ok()
-    })
+    if let Some(iota) = &self.iota {
+      while let Some(response) = iota.poll_single() {
+        let IOResponse::ChunkLoaded { position, data, ticks, queued } = response else {
+          panic!("Unexpected response from IO thread")
+        };
+        match data {
+          Some(blocks) => return Some(ChunkTaskResponse::ChunkLoaded {
+            chunk_position: position,
+            blocks,
+            queue: queued,
+            ticks,
+            biomes: generate_biome_map(position, self.seed, &self.generator, &self.registry),
+          }),
+          // Only queued blocks were saved, the chunk itself still has to be generated
+          None => self.generate(position, self.seed, queued),
+        }
+      }
+    }
+    self.channel.1.try_recv().ok()
+  }
+
+  /// Whether chunks (and queued blocks) are persisted in a save file
+  pub fn has_save_file(&self) -> bool {
+    self.iota.is_some()
+  }
+
+  /// Game mode stored in the save file for the player `username` (if any)
+  pub fn player_gamemode(&self, username: &str) -> Option<GameMode> {
+    self.iota.as_ref()?.player_gamemode(username)
   }
 
   pub fn iota(self) -> Option<IOThreadManager> {
@@ -100,9 +170,13 @@ impl ChunkTaskManager {
 pub fn init_chunk_task_manager(
   storages: AllStoragesView
 ) {
-  let iota = init_save_file(&storages);
+  let (iota, registry, generator, templates, height) = init_save_file(&storages);
+  let seed = storages.borrow::<UniqueView<ConfigTable>>().unwrap().world.seed;
+  storages.add_unique(height);
+  let shared_registry = Arc::clone(&registry.0);
+  storages.add_unique(registry);
   storages.add_unique(
-    ChunkTaskManager::new(iota)
+    ChunkTaskManager::new(iota, shared_registry, generator, templates, seed, height)
       .expect("ChunkTaskManager Init failed")
   );
 }
@@ -0,0 +1,51 @@
+use shipyard::{EntitiesViewMut, NonSendSync, UniqueView, UniqueViewMut, View, ViewMut};
+use kubi_shared::{
+  block::{BlockState, SharedBlockRegistry},
+  falling_block::{should_start_falling, FallingBlock, FallingBlockIds},
+  queue::QueuedBlock,
+  tick::{scheduled_tick, RandomTicks, ScheduledTicks},
+  networking::{channels::Channel, messages::ServerToClientMessage},
+};
+use crate::{client::ClientAddress, server::UdpServer, util::broadcast};
+use super::{ChunkManager, LocalBlockQueue};
+
+/// Run block updates scheduled for the current tick and random ticks in all loaded chunks,
+/// and send the resulting changes to all clients
+pub fn process_block_ticks(
+  server: NonSendSync<UniqueView<UdpServer>>,
+  chunk_manager: UniqueView<ChunkManager>,
+  registry: UniqueView<SharedBlockRegistry>,
+  mut ticks: UniqueViewMut<ScheduledTicks>,
+  mut random_ticks: UniqueViewMut<RandomTicks>,
+  mut queue: UniqueViewMut<LocalBlockQueue>,
+  mut falling_block_ids: UniqueViewMut<FallingBlockIds>,
+  mut entities: EntitiesViewMut,
+  mut falling_blocks: ViewMut<FallingBlock>,
+  addrs: View<ClientAddress>,
+) {
+  let mut changes = Vec::new();
+  let mut spawned = Vec::new();
+  for position in ticks.advance() {
+    if let Some(state) = should_start_falling(&*chunk_manager, &registry, position) {
+      let falling_block = FallingBlock::new(falling_block_ids.next_id(), position, state);
+      entities.add_entity(&mut falling_blocks, falling_block);
+      changes.push(QueuedBlock::new(position, BlockState::AIR));
+      spawned.push(falling_block);
+      continue
+    }
+    changes.extend(scheduled_tick(&*chunk_manager, &registry, position));
+  }
+  let loaded_chunks = chunk_manager.chunks.iter()
+    .filter(|(_, chunk)| chunk.blocks.is_some())
+    .map(|(&position, _)| position);
+  changes.extend(random_ticks.run(&*chunk_manager, &registry, loaded_chunks));
+
+  for item in changes {
+    queue.queue.push(item);
+    broadcast(&server, &addrs, &ServerToClientMessage::QueueBlock { item }, Channel::Block);
+  }
+  //Sent after the block is removed (same channel, so the order is kept)
+  for block in spawned {
+    broadcast(&server, &addrs, &ServerToClientMessage::FallingBlockSpawned { block }, Channel::Block);
+  }
+}
@@ -1,204 +1,159 @@
-use glam::{vec4, Vec4};
+use glam::Vec4;
 use serde::{Serialize, Deserialize};
-use strum::EnumIter;
-use num_enum::TryFromPrimitive;
 use crate::item::Item;
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, EnumIter)]
-#[repr(u8)]
-pub enum BlockTexture {
-  Stone,
-  Dirt,
-  GrassTop,
-  GrassSide,
-  Sand,
-  Bedrock,
-  Wood,
-  WoodTop,
-  Leaf,
-  Torch,
-  TallGrass,
-  Snow,
-  GrassSideSnow,
-  Cobblestone,
-  Planks,
-  WaterSolid,
-  Water,
-}
+mod state;
+pub use state::{Axis, BlockProperties, BlockState, Facing};
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, EnumIter, TryFromPrimitive)]
-#[repr(u8)]
-pub enum Block {
-  #[default]
-  Air,
-  Marker,
-  Stone,
-  Dirt,
-  Grass,
-  Sand,
-  Cobblestone,
-  TallGrass,
-  Planks,
-  Torch,
-  Wood,
-  Leaf,
-  Water,
+mod registry;
+pub use registry::{
+  BlockDefinition,
+  BlockDefinitions,
+  BlockRegistry,
+  CubeTextureDefinition,
+  ItemDefinition,
+  RenderDefinition,
+  SharedBlockRegistry,
+};
+
+/// Index of a texture in the block texture array\
+/// Built-in textures have fixed indices, textures used by other blocks are appended after them
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct BlockTexture(pub u8);
+
+#[allow(non_upper_case_globals)]
+impl BlockTexture {
+  pub const Stone: Self = Self(0);
+  pub const Dirt: Self = Self(1);
+  pub const GrassTop: Self = Self(2);
+  pub const GrassSide: Self = Self(3);
+  pub const Sand: Self = Self(4);
+  pub const Bedrock: Self = Self(5);
+  pub const Wood: Self = Self(6);
+  pub const WoodTop: Self = Self(7);
+  pub const Leaf: Self = Self(8);
+  pub const Torch: Self = Self(9);
+  pub const TallGrass: Self = Self(10);
+  pub const Snow: Self = Self(11);
+  pub const GrassSideSnow: Self = Self(12);
+  pub const Cobblestone: Self = Self(13);
+  pub const Planks: Self = Self(14);
+  pub const WaterSolid: Self = Self(15);
+  pub const Water: Self = Self(16);
 }
 
+/// Names of built-in textures, in the same order as the `BlockTexture` constants
+pub(crate) const BUILTIN_TEXTURES: &[&str] = &[
+  "stone",
+  "dirt",
+  "grass_top",
+  "grass_side",
+  "sand",
+  "bedrock",
+  "wood",
+  "wood_top",
+  "leaf",
+  "torch",
+  "tall_grass",
+  "snow",
+  "grass_side_snow",
+  "cobblestone",
+  "planks",
+  "solid_water",
+  "water",
+];
+
+/// Numeric block id
+///
+/// Built-in blocks (the ones referenced by the engine itself, like world generation)
+/// always have the same ids, other blocks get their ids assigned per world,
+/// see [`BlockRegistry`] for details
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+#[repr(transparent)]
+pub struct Block(pub u8);
+
+#[allow(non_upper_case_globals)]
 impl Block {
-  #[inline]
-  pub const fn descriptor(self) -> BlockDescriptor {
-    match self {
-      Self::Air => BlockDescriptor {
-        name: "air",
-        render: RenderType::None,
-        collision: CollisionType::None,
-        raycast_collision: false,
-        drops: None,
-        submerge: None,
-      },
-      Self::Marker => BlockDescriptor {
-        name: "marker",
-        render: RenderType::None,
-        collision: CollisionType::None,
-        raycast_collision: false,
-        drops: None,
-        submerge: None,
-      },
-      Self::Stone => BlockDescriptor {
-        name: "stone",
-        render: RenderType::Cube(
-          Transparency::Solid,
-          CubeTexture::all(BlockTexture::Stone)
-        ),
-        collision: CollisionType::Solid,
-        raycast_collision: true,
-        drops: None,
-        submerge: None,
-      },
-      Self::Dirt => BlockDescriptor {
-        name: "dirt",
-        render: RenderType::Cube(
-          Transparency::Solid,
-          CubeTexture::all(BlockTexture::Dirt)
-        ),
-        collision: CollisionType::Solid,
-        raycast_collision: true,
-        drops: None,
-        submerge: None,
-      },
-      Self::Grass => BlockDescriptor {
-        name: "grass",
-        render: RenderType::Cube(
-          Transparency::Solid,
-          CubeTexture::top_sides_bottom(
-            BlockTexture::GrassTop,
-            BlockTexture::GrassSide,
-            BlockTexture::Dirt
-          )
-        ),
-        collision: CollisionType::Solid,
-        raycast_collision: true,
-        drops: None,
-        submerge: None,
-      },
-      Self::Sand => BlockDescriptor {
-        name: "sand",
-        render: RenderType::Cube(
-          Transparency::Solid,
-          CubeTexture::all(BlockTexture::Sand)
-        ),
-        collision: CollisionType::Solid,
-        raycast_collision: true,
-        drops: None,
-        submerge: None,
-      },
-      Self::Cobblestone => BlockDescriptor {
-        name: "cobblestone",
-        render: RenderType::Cube(
-          Transparency::Solid,
-          CubeTexture::all(BlockTexture::Cobblestone)
-        ),
-        collision: CollisionType::Solid,
-        raycast_collision: true,
-        drops: None,
-        submerge: None,
-      },
-      Self::TallGrass => BlockDescriptor {
-        name: "tall grass",
-        render: RenderType::Cross(CrossTexture::all(BlockTexture::TallGrass)),
-        collision: CollisionType::None,
-        raycast_collision: true,
-        drops: None,
-        submerge: None,
-      },
-      Self::Planks => BlockDescriptor {
-        name: "planks",
-        render: RenderType::Cube(
-          Transparency::Solid,
-          CubeTexture::all(BlockTexture::Planks)
-        ),
-        collision: CollisionType::Solid, 
-        raycast_collision: true, 
-        drops: None,
-        submerge: None,
-      },
-      Self::Torch => BlockDescriptor {
-        name: "torch",
-        render: RenderType::Cross(CrossTexture::all(BlockTexture::Torch)),
-        collision: CollisionType::None,
-        raycast_collision: true,
-        drops: None,
-        submerge: None,
-      },
-      Self::Wood => BlockDescriptor {
-        name: "leaf",
-        render: RenderType::Cube(
-          Transparency::Solid,
-          CubeTexture::horizontal_vertical(BlockTexture::Wood, BlockTexture::WoodTop)
-        ),
-        collision: CollisionType::Solid,
-        raycast_collision: true,
-        drops: None,
-        submerge: None,
-      },
-      Self::Leaf => BlockDescriptor {
-        name: "leaf",
-        render: RenderType::Cube(
-          Transparency::Binary,
-          CubeTexture::all(BlockTexture::Leaf)
-        ),
-        collision: CollisionType::Solid,
-        raycast_collision: true,
-        drops: None,
-        submerge: None,
-      },
-      Self::Water => BlockDescriptor {
-        name: "water",
-        render: RenderType::Cube(
-          Transparency::Trans,
-          CubeTexture::all(BlockTexture::Water)
-        ),
-        collision: CollisionType::None,
-        raycast_collision: true,
-        drops: None,
-        submerge: Some(vec4(0., 0., 0.25, 0.75)),
-      },
-    }
-  }
+  pub const Air: Self = Self(0);
+  pub const Marker: Self = Self(1);
+  pub const Stone: Self = Self(2);
+  pub const Dirt: Self = Self(3);
+  pub const Grass: Self = Self(4);
+  pub const Sand: Self = Self(5);
+  pub const Cobblestone: Self = Self(6);
+  pub const TallGrass: Self = Self(7);
+  pub const Planks: Self = Self(8);
+  pub const Torch: Self = Self(9);
+  pub const Wood: Self = Self(10);
+  pub const Leaf: Self = Self(11);
+  pub const Water: Self = Self(12);
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Names of built-in blocks, in the same order as the `Block` constants
+pub(crate) const BUILTIN_BLOCKS: &[&str] = &[
+  "air",
+  "marker",
+  "stone",
+  "dirt",
+  "grass",
+  "sand",
+  "cobblestone",
+  "tall_grass",
+  "planks",
+  "torch",
+  "wood",
+  "leaf",
+  "water",
+];
+
+/// Highest possible light level (of both sky and block light)
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct BlockDescriptor {
-  pub name: &'static str,
+  pub name: String,
   pub render: RenderType,
   pub collision: CollisionType,
   pub raycast_collision: bool,
   pub drops: Option<Item>,
+  /// Time (in seconds) it takes to mine the block by hand in survival mode\
+  /// `None` means the block can't be mined at all
+  pub hardness: Option<f32>,
   pub submerge: Option<Vec4>,
+  pub orientation: Orientation,
+  /// Flows and spreads, using the `level` property (see [`crate::fluid`])
+  pub fluid: bool,
+  /// Reaction to block ticks (see [`crate::tick`])
+  pub behavior: BlockBehavior,
+  /// Block light level emitted by the block (`0..=15`)
+  pub light_emission: u8,
+}
+
+impl BlockDescriptor {
+  /// Properties of the block when placed against the face of another block (`facing` is the normal of that face)
+  pub fn placement_properties(&self, facing: Facing) -> BlockProperties {
+    let properties = match self.orientation {
+      Orientation::None => BlockProperties::NONE,
+      Orientation::Axis => BlockProperties::NONE.with_facing(facing),
+    };
+    //leaves placed by players never decay
+    properties.with_persistent(self.behavior == BlockBehavior::Leaves)
+  }
+
+  /// Amount of light lost when passing through the block, on top of the usual falloff\
+  /// Opaque blocks return [`MAX_LIGHT_LEVEL`] and stop light completely
+  pub fn light_opacity(&self) -> u8 {
+    match self.render {
+      RenderType::Cube(Transparency::Solid, _) => MAX_LIGHT_LEVEL,
+      RenderType::Cube(Transparency::Trans, _) => 2,
+      RenderType::Cube(Transparency::Binary, _) => 1,
+      RenderType::None | RenderType::Cross(_) => 0,
+    }
+  }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CubeTexture {
   pub top: BlockTexture,
   pub bottom: BlockTexture,
@@ -226,7 +181,7 @@ impl CubeTexture {
   }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CrossTextureSides {
   pub front: BlockTexture,
   pub back: BlockTexture
@@ -240,31 +195,61 @@ impl CrossTextureSides {
   }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CrossTexture(pub CrossTextureSides, pub CrossTextureSides);
 impl CrossTexture {
   pub const fn all(texture: BlockTexture) -> Self {
     Self(
-      CrossTextureSides::all(texture), 
+      CrossTextureSides::all(texture),
       CrossTextureSides::all(texture)
     )
   }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
 pub enum CollisionType {
+  #[default]
   None,
   Solid,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
 pub enum Transparency {
+  #[default]
   Solid,
   Binary,
   Trans,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// How the block is oriented when placed
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+  #[default]
+  None,
+  /// Aligned with the axis of the face it was placed on (like logs)\
+  /// Uses the `facing` property, top and bottom textures are rotated to face along the axis
+  Axis,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockBehavior {
+  #[default]
+  None,
+  /// Spreads onto nearby dirt, turns back into dirt when covered
+  Grass,
+  /// Decays if there are no logs nearby (unless `persistent`)
+  Leaves,
+  /// Keeps nearby leaves from decaying
+  Log,
+  /// Falls down if there's nothing below
+  Gravity,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RenderType {
   None,
   Cube(Transparency, CubeTexture),
@@ -0,0 +1,311 @@
+use std::{num::NonZeroU8, ops::Deref, sync::Arc};
+use glam::Vec4;
+use hashbrown::HashMap;
+use serde::{Serialize, Deserialize};
+use shipyard::Unique;
+use anyhow::{Context, Result, bail, ensure};
+use crate::item::{Item, ItemDescriptor};
+use super::{
+  Block, BlockDescriptor, BlockTexture, CollisionType, CrossTexture,
+  BlockBehavior, CubeTexture, Orientation, RenderType, Transparency, BUILTIN_BLOCKS, BUILTIN_TEXTURES, MAX_LIGHT_LEVEL,
+};
+
+/// Block definitions shipped with the game, used if no other definitions are provided
+pub const DEFAULT_BLOCK_DEFINITIONS: &str = include_str!("../../../assets/blocks.toml");
+
+/// Textures of a cube block, referenced by name
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum CubeTextureDefinition {
+  All(String),
+  TopSidesBottom {
+    top: String,
+    sides: String,
+    bottom: String,
+  },
+  HorizontalVertical {
+    horizontal: String,
+    vertical: String,
+  },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RenderDefinition {
+  #[default]
+  None,
+  Cube {
+    #[serde(default)]
+    transparency: Transparency,
+    textures: CubeTextureDefinition,
+  },
+  Cross {
+    texture: String,
+  },
+}
+
+/// Block, as defined in a block definition file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlockDefinition {
+  pub name: String,
+  #[serde(default)]
+  pub render: RenderDefinition,
+  #[serde(default)]
+  pub collision: CollisionType,
+  #[serde(default)]
+  pub raycast_collision: bool,
+  /// Name of the item dropped when the block is mined
+  #[serde(default)]
+  pub drops: Option<String>,
+  #[serde(default)]
+  pub hardness: Option<f32>,
+  #[serde(default)]
+  pub submerge: Option<Vec4>,
+  #[serde(default)]
+  pub orientation: Orientation,
+  #[serde(default)]
+  pub fluid: bool,
+  #[serde(default)]
+  pub behavior: BlockBehavior,
+  #[serde(default)]
+  pub light_emission: u8,
+}
+
+fn default_stack_size() -> NonZeroU8 {
+  nz::u8!(64)
+}
+
+/// Item, as defined in a block definition file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ItemDefinition {
+  pub name: String,
+  /// Name of the block placed by the item
+  #[serde(default)]
+  pub places: Option<String>,
+  #[serde(default = "default_stack_size")]
+  pub stack_size: NonZeroU8,
+}
+
+/// Contents of a block definition file
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BlockDefinitions {
+  #[serde(rename = "block", default)]
+  pub blocks: Vec<BlockDefinition>,
+  #[serde(rename = "item", default)]
+  pub items: Vec<ItemDefinition>,
+}
+
+impl BlockDefinitions {
+  pub fn parse(data: &str) -> Result<Self> {
+    Ok(toml::from_str(data)?)
+  }
+
+  /// Block definitions shipped with the game
+  pub fn builtin() -> Self {
+    Self::parse(DEFAULT_BLOCK_DEFINITIONS).expect("built-in block definitions are invalid")
+  }
+}
+
+/// Maps numeric block and item ids to their descriptors
+///
+/// Ids are assigned per world: blocks listed in the world's palette keep their ids,
+/// and new blocks are given the next free id.\
+/// Built-in blocks always come first, so the `Block` constants are valid in every world\
+/// Items are never saved, so their ids simply follow the order of the definitions
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BlockRegistry {
+  blocks: Vec<BlockDescriptor>,
+  items: Vec<ItemDescriptor>,
+  textures: Vec<String>,
+}
+
+impl BlockRegistry {
+  /// Create a new registry from the block `definitions`
+  ///
+  /// `palette` is a list of block names indexed by their id, as stored in the save file\
+  /// (may be empty for new worlds)
+  pub fn new(definitions: &BlockDefinitions, palette: &[String]) -> Result<Self> {
+    let mut textures: Vec<String> = BUILTIN_TEXTURES.iter().map(|&x| x.into()).collect();
+    let mut texture = |name: &str| -> Result<BlockTexture> {
+      let index = match textures.iter().position(|x| x == name) {
+        Some(index) => index,
+        None => {
+          textures.push(name.into());
+          textures.len() - 1
+        }
+      };
+      ensure!(index <= u8::MAX as usize, "too many block textures");
+      Ok(BlockTexture(index as u8))
+    };
+
+    //Item ids are needed to resolve block drops
+    ensure!(definitions.items.len() <= u8::MAX as usize + 1, "too many items");
+    let mut item_ids = HashMap::with_capacity(definitions.items.len());
+    for (id, definition) in definitions.items.iter().enumerate() {
+      if item_ids.insert(definition.name.as_str(), Item(id as u8)).is_some() {
+        bail!("item {:?} is defined more than once", definition.name);
+      }
+    }
+
+    //Resolve block definitions into descriptors
+    let mut descriptors = HashMap::with_capacity(definitions.blocks.len());
+    for definition in &definitions.blocks {
+      let render = match &definition.render {
+        RenderDefinition::None => RenderType::None,
+        RenderDefinition::Cube { transparency, textures } => RenderType::Cube(*transparency, match textures {
+          CubeTextureDefinition::All(all) => CubeTexture::all(texture(all)?),
+          CubeTextureDefinition::TopSidesBottom { top, sides, bottom } => {
+            CubeTexture::top_sides_bottom(texture(top)?, texture(sides)?, texture(bottom)?)
+          },
+          CubeTextureDefinition::HorizontalVertical { horizontal, vertical } => {
+            CubeTexture::horizontal_vertical(texture(horizontal)?, texture(vertical)?)
+          },
+        }),
+        RenderDefinition::Cross { texture: name } => RenderType::Cross(CrossTexture::all(texture(name)?)),
+      };
+      let descriptor = BlockDescriptor {
+        name: definition.name.clone(),
+        render,
+        collision: definition.collision,
+        raycast_collision: definition.raycast_collision,
+        drops: match &definition.drops {
+          Some(name) => Some(*item_ids.get(name.as_str())
+            .with_context(|| format!("block {:?} drops undefined item {name:?}", definition.name))?),
+          None => None,
+        },
+        hardness: definition.hardness,
+        submerge: definition.submerge,
+        orientation: definition.orientation,
+        fluid: definition.fluid,
+        behavior: definition.behavior,
+        light_emission: definition.light_emission.min(MAX_LIGHT_LEVEL),
+      };
+      if descriptors.insert(definition.name.as_str(), descriptor).is_some() {
+        bail!("block {:?} is defined more than once", definition.name);
+      }
+    }
+
+    //Assign ids
+    let mut names: Vec<String> = match palette.is_empty() {
+      true => BUILTIN_BLOCKS.iter().map(|&x| x.into()).collect(),
+      false => palette.to_vec(),
+    };
+    ensure!(
+      names.iter().map(String::as_str).take(BUILTIN_BLOCKS.len()).eq(BUILTIN_BLOCKS.iter().copied()),
+      "block palette doesn't start with built-in blocks"
+    );
+    for definition in &definitions.blocks {
+      if !names.contains(&definition.name) {
+        names.push(definition.name.clone());
+      }
+    }
+    ensure!(names.len() <= u8::MAX as usize + 1, "too many blocks");
+
+    let blocks = names.into_iter().enumerate().map(|(id, name)| {
+      match descriptors.remove(name.as_str()) {
+        Some(descriptor) => Ok(descriptor),
+        None if id < BUILTIN_BLOCKS.len() => bail!("built-in block {name:?} is not defined"),
+        None => {
+          //Keep the id reserved, so that the block comes back if it gets defined again
+          log::warn!("block {name:?} is not defined anymore");
+          Ok(BlockDescriptor {
+            name,
+            render: RenderType::None,
+            collision: CollisionType::None,
+            raycast_collision: false,
+            drops: None,
+            hardness: None,
+            submerge: None,
+            orientation: Orientation::None,
+            fluid: false,
+            behavior: BlockBehavior::None,
+            light_emission: 0,
+          })
+        }
+      }
+    }).collect::<Result<Vec<_>>>()?;
+
+    let items = definitions.items.iter().map(|definition| Ok(ItemDescriptor {
+      name: definition.name.clone(),
+      places: match &definition.places {
+        Some(name) => Some(blocks.iter().position(|x| &x.name == name).map(|id| Block(id as u8))
+          .with_context(|| format!("item {:?} places undefined block {name:?}", definition.name))?),
+        None => None,
+      },
+      stack_size: definition.stack_size,
+    })).collect::<Result<Vec<_>>>()?;
+
+    Ok(Self { blocks, items, textures })
+  }
+
+  /// Get the descriptor of a block\
+  /// Unknown blocks are treated as air
+  pub fn get(&self, block: Block) -> &BlockDescriptor {
+    self.blocks.get(block.0 as usize).unwrap_or(&self.blocks[Block::Air.0 as usize])
+  }
+
+  pub fn by_name(&self, name: &str) -> Option<Block> {
+    self.blocks.iter().position(|x| x.name == name).map(|id| Block(id as u8))
+  }
+
+  /// Check if the block id is registered
+  pub fn contains(&self, block: Block) -> bool {
+    (block.0 as usize) < self.blocks.len()
+  }
+
+  pub fn len(&self) -> usize {
+    self.blocks.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.blocks.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (Block, &BlockDescriptor)> {
+    self.blocks.iter().enumerate().map(|(id, descriptor)| (Block(id as u8), descriptor))
+  }
+
+  /// Block names indexed by their id (to be stored in the save file)
+  pub fn palette(&self) -> Vec<String> {
+    self.blocks.iter().map(|x| x.name.clone()).collect()
+  }
+
+  /// Names of the textures, indexed by `BlockTexture`
+  pub fn textures(&self) -> &[String] {
+    &self.textures
+  }
+
+  /// Get the descriptor of an item\
+  /// Returns `None` for unknown items
+  pub fn item(&self, item: Item) -> Option<&ItemDescriptor> {
+    self.items.get(item.0 as usize)
+  }
+
+  pub fn item_by_name(&self, name: &str) -> Option<Item> {
+    self.items.iter().position(|x| x.name == name).map(|id| Item(id as u8))
+  }
+
+  /// Registry made from the built-in definitions
+  pub fn builtin() -> Self {
+    Self::new(&BlockDefinitions::builtin(), &[]).expect("built-in block definitions are invalid")
+  }
+}
+
+/// Block registry in use by the world\
+/// Replaced as a whole (before any chunks are loaded), so worker threads can keep a reference to it
+#[derive(Unique, Clone, Debug)]
+pub struct SharedBlockRegistry(pub Arc<BlockRegistry>);
+
+impl SharedBlockRegistry {
+  pub fn new(registry: BlockRegistry) -> Self {
+    log::info!("using block registry with {} blocks and {} textures", registry.len(), registry.textures().len());
+    Self(Arc::new(registry))
+  }
+}
+
+impl Deref for SharedBlockRegistry {
+  type Target = BlockRegistry;
+  fn deref(&self) -> &BlockRegistry {
+    &self.0
+  }
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+use glam::IVec3;
+use serde::{Serialize, Deserialize};
+use super::Block;
+
+/// Direction a block is facing
+#[repr(u8)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Facing {
+  #[default]
+  Up    = 0,
+  Down  = 1,
+  North = 2,
+  South = 3,
+  East  = 4,
+  West  = 5,
+}
+
+impl Facing {
+  pub const ALL: [Self; 6] = [Self::Up, Self::Down, Self::North, Self::South, Self::East, Self::West];
+
+  pub const fn normal(self) -> IVec3 {
+    match self {
+      Self::Up    => IVec3::Y,
+      Self::Down  => IVec3::NEG_Y,
+      Self::North => IVec3::NEG_Z,
+      Self::South => IVec3::Z,
+      Self::East  => IVec3::X,
+      Self::West  => IVec3::NEG_X,
+    }
+  }
+
+  pub fn from_normal(normal: IVec3) -> Option<Self> {
+    Self::ALL.into_iter().find(|facing| facing.normal() == normal)
+  }
+
+  pub const fn opposite(self) -> Self {
+    match self {
+      Self::Up    => Self::Down,
+      Self::Down  => Self::Up,
+      Self::North => Self::South,
+      Self::South => Self::North,
+      Self::East  => Self::West,
+      Self::West  => Self::East,
+    }
+  }
+
+  pub const fn axis(self) -> Axis {
+    match self {
+      Self::Up | Self::Down => Axis::Y,
+      Self::North | Self::South => Axis::Z,
+      Self::East | Self::West => Axis::X,
+    }
+  }
+
+  const fn from_bits(bits: u16) -> Self {
+    match bits {
+      1 => Self::Down,
+      2 => Self::North,
+      3 => Self::South,
+      4 => Self::East,
+      5 => Self::West,
+      _ => Self::Up,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Axis {
+  X,
+  #[default]
+  Y,
+  Z,
+}
+
+/// Per-block properties, packed into 16 bits
+///
+/// | bits    | property      |
+/// |---------|---------------|
+/// | `0..3`  | `facing`      |
+/// | `3`     | `waterlogged` |
+/// | `4..8`  | `level`       |
+/// | `8..12` | `age`         |
+/// | `12`    | `persistent`  |
+///
+/// Blocks only use the properties that make sense for them, the rest is left at zero
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct BlockProperties(pub u16);
+
+impl BlockProperties {
+  pub const NONE: Self = Self(0);
+  pub const MAX_LEVEL: u8 = 15;
+  pub const MAX_AGE: u8 = 15;
+
+  const FACING_MASK: u16 = 0b111;
+  const WATERLOGGED_BIT: u16 = 1 << 3;
+  const LEVEL_SHIFT: u16 = 4;
+  const AGE_SHIFT: u16 = 8;
+  const PERSISTENT_BIT: u16 = 1 << 12;
+
+  pub const fn facing(self) -> Facing {
+    Facing::from_bits(self.0 & Self::FACING_MASK)
+  }
+
+  pub const fn with_facing(self, facing: Facing) -> Self {
+    Self((self.0 & !Self::FACING_MASK) | facing as u16)
+  }
+
+  pub const fn waterlogged(self) -> bool {
+    self.0 & Self::WATERLOGGED_BIT != 0
+  }
+
+  pub const fn with_waterlogged(self, waterlogged: bool) -> Self {
+    match waterlogged {
+      true => Self(self.0 | Self::WATERLOGGED_BIT),
+      false => Self(self.0 & !Self::WATERLOGGED_BIT),
+    }
+  }
+
+  /// Fluid level, `0` being a full (source) block
+  pub const fn level(self) -> u8 {
+    ((self.0 >> Self::LEVEL_SHIFT) & 0xf) as u8
+  }
+
+  /// Values above [`Self::MAX_LEVEL`] are clamped
+  pub const fn with_level(self, level: u8) -> Self {
+    let level = if level > Self::MAX_LEVEL { Self::MAX_LEVEL } else { level };
+    Self((self.0 & !(0xf << Self::LEVEL_SHIFT)) | ((level as u16) << Self::LEVEL_SHIFT))
+  }
+
+  /// Growth stage (of crops, saplings, etc.)
+  pub const fn age(self) -> u8 {
+    ((self.0 >> Self::AGE_SHIFT) & 0xf) as u8
+  }
+
+  /// Values above [`Self::MAX_AGE`] are clamped
+  pub const fn with_age(self, age: u8) -> Self {
+    let age = if age > Self::MAX_AGE { Self::MAX_AGE } else { age };
+    Self((self.0 & !(0xf << Self::AGE_SHIFT)) | ((age as u16) << Self::AGE_SHIFT))
+  }
+
+  /// Placed by a player, and not subject to natural changes (like leaf decay)
+  pub const fn persistent(self) -> bool {
+    self.0 & Self::PERSISTENT_BIT != 0
+  }
+
+  pub const fn with_persistent(self, persistent: bool) -> Self {
+    match persistent {
+      true => Self(self.0 | Self::PERSISTENT_BIT),
+      false => Self(self.0 & !Self::PERSISTENT_BIT),
+    }
+  }
+}
+
+impl fmt::Debug for BlockProperties {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("BlockProperties")
+      .field("facing", &self.facing())
+      .field("waterlogged", &self.waterlogged())
+      .field("level", &self.level())
+      .field("age", &self.age())
+      .field("persistent", &self.persistent())
+      .finish()
+  }
+}
+
+/// Block together with its properties, this is what's actually stored in chunks
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlockState {
+  pub block: Block,
+  pub properties: BlockProperties,
+}
+
+impl BlockState {
+  pub const AIR: Self = Self::new(Block::Air);
+
+  pub const fn new(block: Block) -> Self {
+    Self { block, properties: BlockProperties::NONE }
+  }
+
+  pub const fn with_properties(block: Block, properties: BlockProperties) -> Self {
+    Self { block, properties }
+  }
+}
+
+impl From<Block> for BlockState {
+  fn from(block: Block) -> Self {
+    Self::new(block)
+  }
+}
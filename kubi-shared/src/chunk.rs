@@ -1,5 +1,208 @@
-use crate::block::Block;
+use glam::IVec3;
+use serde::{Serialize, Deserialize};
+use anyhow::{Result, ensure};
+use crate::block::{Block, BlockState};
 
 pub const CHUNK_SIZE: usize = 32;
-pub type BlockData = Box<[[[Block; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]>;
-pub type BlockDataRef = [[[Block; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// Block states of a single chunk
+///
+/// Blocks are stored as indices into a palette of distinct block states,
+/// packed into as few bits as the palette size allows (uniform chunks only store the palette)\
+/// Indices never cross `u64` word boundaries, so the bit width is always a power of two
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(try_from = "RawBlockData")]
+pub struct BlockData {
+  palette: Vec<BlockState>,
+  bits: u8,
+  data: Vec<u64>,
+}
+
+/// Unvalidated [`BlockData`], as received from the network or read from disk
+#[derive(Deserialize)]
+struct RawBlockData {
+  palette: Vec<BlockState>,
+  bits: u8,
+  data: Vec<u64>,
+}
+
+impl TryFrom<RawBlockData> for BlockData {
+  type Error = anyhow::Error;
+
+  fn try_from(raw: RawBlockData) -> Result<Self> {
+    let RawBlockData { palette, bits, data } = raw;
+    ensure!(!palette.is_empty(), "empty block palette");
+    ensure!(matches!(bits, 0 | 1 | 2 | 4 | 8 | 16), "invalid block index width");
+    ensure!(palette.len() <= 1 << bits, "block palette too large");
+    ensure!(data.len() == Self::word_count(bits), "invalid block data length");
+    let block_data = Self { palette, bits, data };
+    ensure!(
+      (0..CHUNK_VOLUME).all(|index| (block_data.palette_index(index) as usize) < block_data.palette.len()),
+      "block index out of palette range"
+    );
+    Ok(block_data)
+  }
+}
+
+impl Default for BlockData {
+  fn default() -> Self {
+    Self::filled(BlockState::AIR)
+  }
+}
+
+impl BlockData {
+  /// Create a chunk with all blocks set to air
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Create a chunk with all blocks set to `state`
+  pub fn filled(state: BlockState) -> Self {
+    Self {
+      palette: vec![state],
+      bits: 0,
+      data: Vec::new(),
+    }
+  }
+
+  const fn word_count(bits: u8) -> usize {
+    match bits {
+      0 => 0,
+      _ => CHUNK_VOLUME / (64 / bits as usize),
+    }
+  }
+
+  const fn bits_for_palette_len(len: usize) -> u8 {
+    match len {
+      0..=1 => 0,
+      2 => 1,
+      3..=4 => 2,
+      5..=16 => 4,
+      17..=256 => 8,
+      _ => 16,
+    }
+  }
+
+  #[inline]
+  fn linear_index(position: IVec3) -> usize {
+    debug_assert!(
+      position.cmpge(IVec3::ZERO).all() && position.cmplt(IVec3::splat(CHUNK_SIZE as i32)).all(),
+      "block position out of chunk bounds: {position}"
+    );
+    (position.x as usize * CHUNK_SIZE + position.y as usize) * CHUNK_SIZE + position.z as usize
+  }
+
+  #[inline]
+  fn palette_index(&self, index: usize) -> u16 {
+    if self.bits == 0 {
+      return 0
+    }
+    let per_word = 64 / self.bits as usize;
+    let word = self.data[index / per_word];
+    let shift = (index % per_word) * self.bits as usize;
+    ((word >> shift) & ((1 << self.bits) - 1)) as u16
+  }
+
+  #[inline]
+  fn set_palette_index(&mut self, index: usize, value: u16) {
+    debug_assert!(self.bits > 0);
+    let per_word = 64 / self.bits as usize;
+    let shift = (index % per_word) * self.bits as usize;
+    let mask = ((1u64 << self.bits) - 1) << shift;
+    let word = &mut self.data[index / per_word];
+    *word = (*word & !mask) | ((value as u64) << shift);
+  }
+
+  fn indices(&self) -> Vec<u16> {
+    (0..CHUNK_VOLUME).map(|index| self.palette_index(index)).collect()
+  }
+
+  /// Pack `indices` using the smallest index width that fits the current palette
+  fn pack(&mut self, indices: Vec<u16>) {
+    self.bits = Self::bits_for_palette_len(self.palette.len());
+    self.data = vec![0; Self::word_count(self.bits)];
+    if self.bits > 0 {
+      for (index, value) in indices.into_iter().enumerate() {
+        self.set_palette_index(index, value);
+      }
+    }
+  }
+
+  /// Get the block state at `position` (in chunk-local coordinates)
+  #[inline]
+  pub fn get_state(&self, position: IVec3) -> BlockState {
+    self.palette[self.palette_index(Self::linear_index(position)) as usize]
+  }
+
+  /// Get the block at `position` (in chunk-local coordinates)
+  #[inline]
+  pub fn get(&self, position: IVec3) -> Block {
+    self.get_state(position).block
+  }
+
+  /// Set the block state at `position` (in chunk-local coordinates)
+  pub fn set_state(&mut self, position: IVec3, state: BlockState) {
+    let index = Self::linear_index(position);
+    let palette_index = match self.palette.iter().position(|&x| x == state) {
+      Some(palette_index) => palette_index,
+      None => {
+        //Indices are 16 bits at most, get rid of unused states before running out of them
+        if self.palette.len() > u16::MAX as usize {
+          self.compact();
+        }
+        if Self::bits_for_palette_len(self.palette.len() + 1) != self.bits {
+          let indices = self.indices();
+          self.palette.push(state);
+          self.pack(indices);
+        } else {
+          self.palette.push(state);
+        }
+        self.palette.len() - 1
+      }
+    };
+    if self.bits > 0 {
+      self.set_palette_index(index, palette_index as u16);
+    }
+  }
+
+  /// Set the block at `position` (in chunk-local coordinates), resetting its properties
+  pub fn set(&mut self, position: IVec3, block: Block) {
+    self.set_state(position, BlockState::new(block));
+  }
+
+  /// Distinct block states that may be present in the chunk
+  pub fn palette(&self) -> &[BlockState] {
+    &self.palette
+  }
+
+  /// Check if the chunk only contains a single block state
+  pub fn is_uniform(&self) -> bool {
+    self.palette.len() == 1
+  }
+
+  /// Remove unused palette entries and shrink the index width if possible
+  ///
+  /// Palette entries are never removed by [`Self::set_state`], so this should be called
+  /// before the data gets stored or sent over the network
+  pub fn compact(&mut self) {
+    let mut used = vec![false; self.palette.len()];
+    for index in 0..CHUNK_VOLUME {
+      used[self.palette_index(index) as usize] = true;
+    }
+    if used.iter().all(|&x| x) {
+      return
+    }
+    let mut remap = vec![0u16; self.palette.len()];
+    let mut palette = Vec::with_capacity(self.palette.len());
+    for (old_index, &state) in self.palette.iter().enumerate() {
+      if used[old_index] {
+        remap[old_index] = palette.len() as u16;
+        palette.push(state);
+      }
+    }
+    let indices = self.indices().into_iter().map(|index| remap[index as usize]).collect();
+    self.palette = palette;
+    self.pack(indices);
+  }
+}
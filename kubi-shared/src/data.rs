@@ -6,28 +6,42 @@ use std::{
   borrow::Cow,
   sync::{Arc, RwLock}
 };
-use num_enum::TryFromPrimitive;
 use serde::{Serialize, Deserialize};
 use glam::IVec3;
-use hashbrown::HashMap;
-use anyhow::Result;
+use hashbrown::{HashMap, HashSet};
+use anyhow::{Result, ensure};
 use shipyard::Unique;
-use static_assertions::const_assert_eq;
 use crate::{
-  block::Block,
-  chunk::{CHUNK_SIZE, BlockDataRef, BlockData}
+  block::{Block, BlockDefinitions, BlockRegistry, BUILTIN_BLOCKS},
+  chunk::{CHUNK_SIZE, BlockData},
+  height::WorldHeight,
+  player::GameMode,
+  queue::QueuedBlock,
+  tick::PendingTick,
+  worldgen::{hash::HashVersion, preset::WorldGenPreset, structures::StructureTemplates},
 };
 
 pub mod io_thread;
 
-const SECTOR_SIZE: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * size_of::<Block>();
+//(sector size used to be the size of a whole chunk, when blocks were stored as raw bytes)
+const SECTOR_SIZE: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
 const RESERVED_SIZE: usize = 1048576; //~1mb (16 sectors assuming 32x32x32 world of 1byte blocks)
 const RESERVED_SECTOR_COUNT: usize = RESERVED_SIZE / SECTOR_SIZE;
 
+/// Upper limit for the size of encoded chunk data (to catch corrupted length prefixes)
+const MAX_CHUNK_DATA_SIZE: usize = 1 << 20;
+
+/// Bytes of the queued block map stored in each sector of its chain (after the index of the next sector)
+const CHAIN_PAYLOAD_SIZE: usize = SECTOR_SIZE - size_of::<u32>();
+
 //magic = "KUBI" + IDENTITY (4 bytes)
 const SUBHEADER_SIZE: usize = 8;
 const SUBHEADER_MAGIC: [u8; 4] = *b"KUBI";
-const SUBHEADER_IDENTITY: u32 = 1;
+//chunks are stored as a u32 length followed by bincode-encoded BlockData and pending scheduled ticks,
+//taking up as many sectors as needed (chunks saved before ticks were persisted only contain the BlockData)
+const SUBHEADER_IDENTITY: u32 = 2;
+//chunks are stored as raw block ids (one byte per block, one sector per chunk)
+const SUBHEADER_IDENTITY_LEGACY: u32 = 1;
 
 // #[repr(transparent)]
 // struct IVec3Hash(IVec3);
@@ -37,6 +51,41 @@ pub struct WorldSaveDataHeader {
   pub seed: u64,
   sector_count: u32,
   chunk_map: HashMap<IVec3, u32>,
+  /// Block names indexed by their numeric id (see `BlockRegistry`)\
+  /// Empty in older save files, which only contain built-in blocks
+  pub block_palette: Vec<String>,
+  /// World generator preset chosen when the world was created\
+  /// `None` in older save files
+  pub generator: Option<WorldGenPreset>,
+  /// Queued block map of older save files, which stored it in the header (limiting it to the reserved area)\
+  /// Moved to its own sector chain when loaded, always empty in newer save files (see `queued_map_chain`)
+  legacy_queued_map: HashMap<IVec3, u32>,
+  /// Build height limits chosen when the world was created\
+  /// `None` in older save files
+  pub height: Option<WorldHeight>,
+  /// Hashing used by the world generator\
+  /// `None` in save files created before the stable hash, which use [`HashVersion::Legacy`]
+  pub hash_version: Option<HashVersion>,
+  /// Game modes of players that joined the world, keyed by their username\
+  /// Empty in older save files
+  pub player_gamemodes: HashMap<String, GameMode>,
+  /// Runs of unused sectors (first sector and sector count), sorted and with adjacent runs merged\
+  /// Empty in older save files, which never freed any sectors
+  free_list: Vec<(u32, u32)>,
+  /// Content hash of the structure templates used by the world generator (see [`StructureTemplates::content_hash`]),
+  /// stored along with the `generator` preset\
+  /// `None` in older save files
+  pub structure_templates: Option<u64>,
+  /// First sector and encoded size of the queued block map\
+  /// The map is stored in a chain of sectors, each one starting with the index of the next one, so that it can grow as needed\
+  /// `None` if no blocks are queued
+  queued_map_chain: Option<(u32, u32)>,
+  /// Sectors of blocks queued for chunks that weren't loaded at the time (like leaves of trees near chunk borders),
+  /// keyed by the chunk they belong to\
+  /// They're stored like chunks, as a u32 length followed by a bincode-encoded `Vec<QueuedBlock>`\
+  /// The map itself is loaded from its sector chain (see `queued_map_chain`), it's not part of the header
+  #[serde(skip)]
+  pub queued_map: HashMap<IVec3, u32>,
 }
 
 impl Default for WorldSaveDataHeader {
@@ -45,8 +94,63 @@ impl Default for WorldSaveDataHeader {
       name: "World".into(),
       seed: 0,
       sector_count: RESERVED_SECTOR_COUNT as u32,
-      chunk_map: HashMap::new()
+      chunk_map: HashMap::new(),
+      block_palette: Vec::new(),
+      generator: None,
+      legacy_queued_map: HashMap::new(),
+      height: None,
+      hash_version: None,
+      player_gamemodes: HashMap::new(),
+      free_list: Vec::new(),
+      structure_templates: None,
+      queued_map_chain: None,
+      queued_map: HashMap::new(),
+    }
+  }
+}
+
+impl WorldSaveDataHeader {
+  /// Allocate `count` consecutive sectors, taking the first free run that's large enough,
+  /// or new sectors at the end of the file\
+  /// Returns the first allocated sector
+  fn allocate_sectors(&mut self, count: u32) -> u32 {
+    if let Some(index) = self.free_list.iter().position(|&(_, free)| free >= count) {
+      let (sector, free) = self.free_list[index];
+      if free == count {
+        self.free_list.remove(index);
+      } else {
+        self.free_list[index] = (sector + count, free - count);
+      }
+      return sector
+    }
+    let sector = self.sector_count;
+    self.sector_count += count;
+    sector
+  }
+
+  /// Put `count` sectors starting at `sector` on the free list
+  fn free_sectors(&mut self, sector: u32, count: u32) {
+    if count == 0 {
+      return
+    }
+    let mut index = self.free_list.partition_point(|&(start, _)| start < sector);
+    self.free_list.insert(index, (sector, count));
+    //merge with the following and the preceding run
+    if let Some(&(next, next_count)) = self.free_list.get(index + 1) {
+      if sector + count == next {
+        self.free_list[index].1 += next_count;
+        self.free_list.remove(index + 1);
+      }
+    }
+    if index > 0 {
+      let (previous, previous_count) = self.free_list[index - 1];
+      if previous + previous_count == sector {
+        self.free_list[index - 1].1 += self.free_list[index].1;
+        self.free_list.remove(index);
+        index -= 1;
+      }
     }
+    debug_assert!(self.free_list[index].0 + self.free_list[index].1 <= self.sector_count);
   }
 }
 
@@ -56,6 +160,16 @@ pub type SharedHeader = Arc<RwLock<WorldSaveDataHeader>>;
 pub struct WorldSaveFile {
   pub file: File,
   pub header: SharedHeader,
+  /// The save file was created in this session (as opposed to loaded)\
+  /// Used to tell new worlds apart from older save files, which lack some header fields
+  created: bool,
+  /// Size of the part of the reserved area that may contain a header (zeroes follow it)
+  header_size: usize,
+  /// Sectors of the queued block map chain, in order
+  queued_map_sectors: Vec<u32>,
+  /// Chunks whose queued blocks were loaded (and are being applied to them)\
+  /// Their queued blocks are removed once the chunk is written
+  loaded_queued_blocks: HashSet<IVec3>,
 }
 
 impl WorldSaveFile {
@@ -63,10 +177,16 @@ impl WorldSaveFile {
     WorldSaveFile {
       file,
       header: Arc::new(RwLock::new(WorldSaveDataHeader::default())),
+      created: false,
+      //the whole reserved area gets zeroed on the first write
+      header_size: RESERVED_SIZE - SUBHEADER_SIZE,
+      queued_map_sectors: Vec::new(),
+      loaded_queued_blocks: HashSet::new(),
     }
   }
 
-  fn read_header(&mut self) -> Result<()> {
+  /// Returns the identity (format version) of the save file
+  fn read_header(&mut self) -> Result<u32> {
     self.file.rewind()?;
 
     let mut subheader = [0u8; SUBHEADER_SIZE];
@@ -74,67 +194,346 @@ impl WorldSaveFile {
     if subheader[0..4] != SUBHEADER_MAGIC {
       return Err(anyhow::anyhow!("invalid file header"));
     }
-    if subheader[4..8] != SUBHEADER_IDENTITY.to_be_bytes() {
+    let identity = u32::from_be_bytes(subheader[4..8].try_into().unwrap());
+    if identity != SUBHEADER_IDENTITY && identity != SUBHEADER_IDENTITY_LEGACY {
       return Err(anyhow::anyhow!("this save file cannot be loaded by this version of the game"));
     }
 
-    let limit = (RESERVED_SIZE - SUBHEADER_SIZE) as u64;
-    *self.header.write().unwrap() = bincode::deserialize_from((&self.file).take(limit))?;
+    //The reserved area may not be fully written yet, treat the missing part as zeroes
+    //(this also allows reading headers from older versions, which may lack the trailing fields)
+    let limit = RESERVED_SIZE - SUBHEADER_SIZE;
+    let mut buffer = Vec::with_capacity(limit);
+    (&self.file).take(limit as u64).read_to_end(&mut buffer)?;
+    buffer.resize(limit, 0);
+    *self.header.write().unwrap() = bincode::deserialize(&buffer)?;
 
-    Ok(())
+    Ok(identity)
   }
 
   fn write_header(&mut self) -> Result<()> {
+    let mut header = bincode::serialize(&*self.header.read().unwrap())?;
+    ensure!(header.len() <= RESERVED_SIZE - SUBHEADER_SIZE, "header doesn't fit in the reserved area");
+    //zero the rest of the previous header, missing trailing fields are read as zeroes (see read_header),
+    //so leftover bytes of a longer header would be read as fields
+    let written_size = header.len();
+    header.resize(written_size.max(self.header_size), 0);
     self.file.rewind()?;
     self.file.write_all(&SUBHEADER_MAGIC)?;
     self.file.write_all(&SUBHEADER_IDENTITY.to_be_bytes())?;
-    //XXX: this can cause the header to destroy chunk data (if it's WAY too long)
-    //     read has checks against this, but write doesn't
-    //     1mb is pretty generous tho, so it's not a *big* deal
-    bincode::serialize_into(&self.file, &*self.header.read().unwrap())?;
+    self.file.write_all(&header)?;
+    self.header_size = written_size;
     Ok(())
   }
 
   pub fn initialize(&mut self) -> Result<()> {
+    self.created = true;
     self.write_header()?;
     Ok(())
   }
 
   pub fn load_data(&mut self) -> Result<()> {
-    self.read_header()?;
+    if self.read_header()? == SUBHEADER_IDENTITY_LEGACY {
+      self.upgrade_legacy_chunks()?;
+    }
+    self.load_queued_map()?;
     Ok(())
   }
 
-  // fn allocate_sector(&mut self) -> u32 {
-  //   let mut lock = self.header.write().unwrap();
-  //   let value = lock.sector_count + 1;
-  //   lock.sector_count += 1;
-  //   value
-  // }
+  /// Load the queued block map from its sector chain, or move it out of the header of older save files
+  fn load_queued_map(&mut self) -> Result<()> {
+    let chain = self.header.read().unwrap().queued_map_chain;
+    let Some(chain) = chain else {
+      let legacy = std::mem::take(&mut self.header.write().unwrap().legacy_queued_map);
+      if !legacy.is_empty() {
+        log::info!("upgrading save file, moving blocks queued for {} chunks out of the header", legacy.len());
+        self.header.write().unwrap().queued_map = legacy;
+        self.write_queued_map()?;
+        self.write_header()?;
+        self.file.sync_data()?;
+      }
+      return Ok(())
+    };
+    match self.read_queued_map(chain) {
+      Ok((queued_map, sectors)) => {
+        self.header.write().unwrap().queued_map = queued_map;
+        self.queued_map_sectors = sectors;
+      },
+      //XXX: the sectors of the chain and of the queued blocks are not freed, they can't be trusted
+      Err(error) => log::error!("failed to load blocks queued for unloaded chunks, dropping them: {error:?}"),
+    }
+    Ok(())
+  }
 
-  pub fn save_chunk(&mut self, position: IVec3, data: &BlockDataRef) -> Result<()> {
-    let mut header_lock = self.header.write().unwrap();
+  /// Read the queued block map from the sector chain starting at `sector`\
+  /// Returns the map and the sectors of the chain
+  fn read_queued_map(&mut self, (mut sector, size): (u32, u32)) -> Result<(HashMap<IVec3, u32>, Vec<u32>)> {
+    let sector_count = self.header.read().unwrap().sector_count;
+    let size = size as usize;
+    let mut sectors = Vec::new();
+    let mut encoded = Vec::with_capacity(size);
+    while encoded.len() < size {
+      ensure!((RESERVED_SECTOR_COUNT as u32..sector_count).contains(&sector), "invalid queued block map sector");
+      sectors.push(sector);
+      self.file.seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE as u64))?;
+      let mut next = [0u8; size_of::<u32>()];
+      self.file.read_exact(&mut next)?;
+      let start = encoded.len();
+      encoded.resize(start + (size - start).min(CHAIN_PAYLOAD_SIZE), 0);
+      self.file.read_exact(&mut encoded[start..])?;
+      sector = u32::from_le_bytes(next);
+    }
+    Ok((bincode::deserialize(&encoded)?, sectors))
+  }
 
-    let mut header_modified = false;
-    let sector = header_lock.chunk_map.get(&position).copied().unwrap_or_else(|| {
-      header_modified = true;
-      let sector = header_lock.sector_count;
-      header_lock.sector_count += 1;
-      header_lock.chunk_map.insert(position, sector);
-      sector
-      // self.allocate_sector()
-    });
+  /// Write the queued block map to its sector chain, without updating the header on disk\
+  /// The chain is extended with new sectors as needed, sectors it no longer needs are freed
+  fn write_queued_map(&mut self) -> Result<()> {
+    let (encoded, sector_count) = {
+      let header = self.header.read().unwrap();
+      let encoded = bincode::serialize(&header.queued_map)?;
+      let sector_count = match header.queued_map.is_empty() {
+        true => 0,
+        false => encoded.len().div_ceil(CHAIN_PAYLOAD_SIZE),
+      };
+      (encoded, sector_count)
+    };
+
+    let mut sectors = std::mem::take(&mut self.queued_map_sectors);
+    while sectors.len() < sector_count {
+      sectors.push(self.header.write().unwrap().allocate_sectors(1));
+    }
+    for (index, part) in encoded.chunks(CHAIN_PAYLOAD_SIZE).take(sector_count).enumerate() {
+      //sector 0 is part of the reserved area, so it marks the end of the chain
+      let next = match index + 1 < sector_count {
+        true => sectors[index + 1],
+        false => 0,
+      };
+      self.file.seek(SeekFrom::Start(sectors[index] as u64 * SECTOR_SIZE as u64))?;
+      self.file.write_all(&next.to_le_bytes())?;
+      self.file.write_all(part)?;
+    }
+
+    //the sectors are only freed after the chain is written
+    let mut header = self.header.write().unwrap();
+    for sector in sectors.drain(sector_count..) {
+      header.free_sectors(sector, 1);
+    }
+    header.queued_map_chain = sectors.first().map(|&sector| (sector, encoded.len() as u32));
+    drop(header);
+    self.queued_map_sectors = sectors;
+    Ok(())
+  }
+
+  /// Convert all chunks from the legacy one byte per block format
+  fn upgrade_legacy_chunks(&mut self) -> Result<()> {
+    let chunks: Vec<(IVec3, u32)> = self.header.read().unwrap().chunk_map.iter().map(|(&k, &v)| (k, v)).collect();
+    log::info!("upgrading save file, converting {} chunks", chunks.len());
+    let mut buffer = vec![0u8; SECTOR_SIZE];
+    for &(position, sector) in &chunks {
+      self.file.seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE as u64))?;
+      self.file.read_exact(&mut buffer)?;
+      let mut data = BlockData::new();
+      for (index, &byte) in buffer.iter().enumerate() {
+        let block_position = IVec3::new(
+          (index / (CHUNK_SIZE * CHUNK_SIZE)) as i32,
+          ((index / CHUNK_SIZE) % CHUNK_SIZE) as i32,
+          (index % CHUNK_SIZE) as i32,
+        );
+        data.set(block_position, Block(byte));
+      }
+      //encoded data may not fit in the old sector, always move it to new sectors
+      self.header.write().unwrap().chunk_map.remove(&position);
+      self.write_chunk(position, &data, &[])?;
+    }
+    //old sectors are only freed once all chunks are converted,
+    //otherwise converted chunks could overwrite ones that weren't read yet
+    {
+      let mut header = self.header.write().unwrap();
+      for &(_, sector) in &chunks {
+        header.free_sectors(sector, 1);
+      }
+    }
+    //Only mark the file as upgraded once all chunks are converted
+    self.write_header()?;
+    self.file.sync_data()?;
+    Ok(())
+  }
+
+  /// Create a block registry for this world, keeping block ids stored in the save file\
+  /// If any new blocks were added, the updated block palette is written to the header
+  pub fn create_block_registry(&mut self, definitions: &BlockDefinitions) -> Result<BlockRegistry> {
+    let palette = self.header.read().unwrap().block_palette.clone();
+    let registry = BlockRegistry::new(definitions, &palette)?;
+    let new_palette = registry.palette();
+    if new_palette != palette {
+      self.header.write().unwrap().block_palette = new_palette;
+      self.write_header()?;
+    }
+    Ok(registry)
+  }
+
+  /// Get the world generator preset of this world (with its [`HashVersion`])\
+  /// If the save file doesn't have one yet, `preset` is stored in it and used for new worlds,
+  /// older save files get the legacy preset (reproducing the generator they were created with)
+  pub fn world_generator(&mut self, preset: WorldGenPreset) -> Result<WorldGenPreset> {
+    {
+      let header = self.header.read().unwrap();
+      if let Some(stored) = &header.generator {
+        let mut stored = stored.clone();
+        stored.hash_version = header.hash_version.unwrap_or(HashVersion::Legacy);
+        return Ok(stored)
+      }
+    }
+    let preset = match self.created {
+      true => WorldGenPreset { hash_version: HashVersion::Stable, ..preset },
+      false => WorldGenPreset { hash_version: HashVersion::Legacy, ..WorldGenPreset::legacy() },
+    };
+    log::info!("using world generator preset {:?} ({:?} hash)", preset.name, preset.hash_version);
+    {
+      let mut header = self.header.write().unwrap();
+      header.generator = Some(preset.clone());
+      header.hash_version = Some(preset.hash_version);
+    }
+    self.write_header()?;
+    Ok(preset)
+  }
+
+  /// Get the build height limits of the world\
+  /// If the save file doesn't have them yet, `height` is stored and used for new worlds,
+  /// older save files get the default limits, extended to include all of their chunks
+  pub fn world_height(&mut self, height: WorldHeight) -> Result<WorldHeight> {
+    if let Some(stored) = self.header.read().unwrap().height {
+      return Ok(stored)
+    }
+    let height = match self.created {
+      true => height,
+      false => {
+        let header = self.header.read().unwrap();
+        let (min, max) = header.chunk_map.keys().chain(header.queued_map.keys())
+          .map(|position| (position.y.saturating_mul(CHUNK_SIZE as i32), (position.y + 1).saturating_mul(CHUNK_SIZE as i32)))
+          .fold((WorldHeight::default().min, WorldHeight::default().max), |(min, max), (bottom, top)| (min.min(bottom), max.max(top)));
+        log::warn!("save file has no build height limits, using {min}..{max} (blocks outside of them can't be accessed)");
+        WorldHeight { min, max, ..WorldHeight::default() }
+      }
+    };
+    log::info!("using build height limits {}..{}", height.min, height.max);
+    self.header.write().unwrap().height = Some(height);
+    self.write_header()?;
+    Ok(height)
+  }
+
+  /// Check that the structure templates are the ones the world was created with\
+  /// If the save file doesn't have their hash yet, the hash of `templates` is stored\
+  /// Returns `false` if they changed (structures in new chunks won't match the ones in existing chunks)
+  pub fn check_structure_templates(&mut self, templates: &StructureTemplates) -> Result<bool> {
+    let hash = templates.content_hash();
+    if let Some(stored) = self.header.read().unwrap().structure_templates {
+      return Ok(stored == hash)
+    }
+    self.header.write().unwrap().structure_templates = Some(hash);
+    self.write_header()?;
+    Ok(true)
+  }
+
+  /// Get the game mode stored for the player `username`, if they joined the world before
+  pub fn player_gamemode(&self, username: &str) -> Option<GameMode> {
+    self.header.read().unwrap().player_gamemodes.get(username).copied()
+  }
 
-    drop(header_lock);
+  /// Store the game mode of the player `username`
+  pub fn save_player_gamemode(&mut self, username: &str, gamemode: GameMode) -> Result<()> {
+    let previous = self.header.write().unwrap().player_gamemodes.insert(username.into(), gamemode);
+    if previous != Some(gamemode) {
+      self.write_header()?;
+      self.file.sync_data()?;
+    }
+    Ok(())
+  }
+
+  /// Number of sectors taken up by the chunk stored at `sector`
+  fn chunk_sector_count(&mut self, sector: u32) -> Result<u32> {
+    let mut length = [0u8; size_of::<u32>()];
+    self.file.seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE as u64))?;
+    self.file.read_exact(&mut length)?;
+    Ok((size_of::<u32>() + u32::from_le_bytes(length) as usize).div_ceil(SECTOR_SIZE) as u32)
+  }
+
+  /// Write length-prefixed data, reusing the `current` allocation if the data still fits in it
+  /// (freeing the sectors it no longer needs), otherwise allocating new sectors and freeing the old ones\
+  /// Returns the first sector of the data, and whether the in-memory header was modified
+  fn write_sectors(&mut self, current: Option<u32>, encoded: &[u8]) -> Result<(u32, bool)> {
+    ensure!(encoded.len() <= MAX_CHUNK_DATA_SIZE, "chunk data too large");
+    let sectors_needed = (size_of::<u32>() + encoded.len()).div_ceil(SECTOR_SIZE) as u32;
+
+    let current = match current {
+      Some(sector) => Some((sector, self.chunk_sector_count(sector)?)),
+      None => None,
+    };
+    let sector = match current {
+      Some((sector, count)) if count >= sectors_needed => sector,
+      _ => self.header.write().unwrap().allocate_sectors(sectors_needed),
+    };
 
     let offset = sector as u64 * SECTOR_SIZE as u64;
+    self.file.seek(SeekFrom::Start(offset))?;
+    self.file.write_all(&(encoded.len() as u32).to_le_bytes())?;
+    self.file.write_all(encoded)?;
+
+    //the old sectors are only freed after the data is written to the new ones
+    let header_modified = match current {
+      Some((current_sector, count)) if current_sector == sector => {
+        self.header.write().unwrap().free_sectors(sector + sectors_needed, count - sectors_needed);
+        count > sectors_needed
+      },
+      Some((current_sector, count)) => {
+        self.header.write().unwrap().free_sectors(current_sector, count);
+        true
+      },
+      None => true,
+    };
 
-    const_assert_eq!(size_of::<Block>(), 1);
-    let data: &[u8; SECTOR_SIZE] = unsafe { std::mem::transmute(data) };
+    Ok((sector, header_modified))
+  }
 
+  /// Read length-prefixed data written by [`Self::write_sectors`]
+  fn read_sectors(&mut self, sector: u32) -> Result<Vec<u8>> {
+    let offset = sector as u64 * SECTOR_SIZE as u64;
     self.file.seek(SeekFrom::Start(offset))?;
-    self.file.write_all(data)?;
 
+    let mut length = [0u8; size_of::<u32>()];
+    self.file.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length) as usize;
+    ensure!(length <= MAX_CHUNK_DATA_SIZE, "invalid chunk data length");
+
+    let mut buffer = vec![0u8; length];
+    self.file.read_exact(&mut buffer)?;
+    Ok(buffer)
+  }
+
+  /// Write the chunk data without updating the header on disk\
+  /// Returns `true` if the in-memory header was modified
+  fn write_chunk(&mut self, position: IVec3, data: &BlockData, ticks: &[PendingTick]) -> Result<bool> {
+    let mut data = data.clone();
+    data.compact();
+    let mut encoded = bincode::serialize(&data)?;
+    encoded.extend(bincode::serialize(ticks)?);
+
+    let current_sector = self.header.read().unwrap().chunk_map.get(&position).copied();
+    let (sector, header_modified) = self.write_sectors(current_sector, &encoded)?;
+    if current_sector != Some(sector) {
+      self.header.write().unwrap().chunk_map.insert(position, sector);
+    }
+    Ok(header_modified)
+  }
+
+  /// Write the chunk data\
+  /// Blocks queued for the chunk are removed if they were loaded before (they're applied to `data` by then)
+  pub fn save_chunk(&mut self, position: IVec3, data: &BlockData, ticks: &[PendingTick]) -> Result<()> {
+    let mut header_modified = self.write_chunk(position, data, ticks)?;
+    if self.loaded_queued_blocks.remove(&position) {
+      self.remove_queued_blocks(position)?;
+      header_modified = true;
+    }
     if header_modified {
       self.write_header()?;
     }
@@ -151,32 +550,117 @@ impl WorldSaveFile {
     self.header.read().unwrap().chunk_map.contains_key(&position)
   }
 
-  pub fn load_chunk(&mut self, position: IVec3) -> Result<Option<BlockData>> {
+  /// Load the chunk at `position`, together with the ticks that were scheduled in it
+  pub fn load_chunk(&mut self, position: IVec3) -> Result<Option<(BlockData, Vec<PendingTick>)>> {
     let Some(&sector) = self.header.read().unwrap().chunk_map.get(&position) else {
       return Ok(None);
     };
 
-    let mut buffer = Box::new([0u8; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * size_of::<Block>()]);
-    let offset = sector as u64 * SECTOR_SIZE as u64;
+    let buffer = self.read_sectors(sector)?;
+    let mut reader = buffer.as_slice();
+    let data: BlockData = bincode::deserialize_from(&mut reader)?;
+    let ticks: Vec<PendingTick> = match reader.is_empty() {
+      true => Vec::new(),
+      false => bincode::deserialize_from(&mut reader)?,
+    };
 
-    self.file.seek(SeekFrom::Start(offset))?;
-    self.file.read_exact(&mut buffer[..])?;
-
-    //should be safe under these conditions:
-    //Block is a single byte
-    //All block data bytes are in valid range
-    const_assert_eq!(size_of::<Block>(), 1);
-    for &byte in &buffer[..] {
-      let block = Block::try_from_primitive(byte);
-      match block {
-        //Sanity check, not actually required: (should NEVER happen)
-        Ok(block) => debug_assert_eq!(byte, block as u8),
-        Err(_) => anyhow::bail!("invalid block data"),
+    //block ids not present in the palette indicate corrupted data
+    ensure!(data.palette().iter().all(|state| self.is_known_block(state.block)), "invalid block data");
+
+    Ok(Some((data, ticks)))
+  }
+
+  pub fn has_queued_blocks(&self, position: IVec3) -> bool {
+    self.header.read().unwrap().queued_map.contains_key(&position)
+  }
+
+  /// Check if the block id is present in the block palette of the world\
+  /// (worlds that didn't store a palette yet only use built-in blocks)
+  fn is_known_block(&self, block: Block) -> bool {
+    let palette_len = self.header.read().unwrap().block_palette.len();
+    (block.0 as usize) < palette_len.max(BUILTIN_BLOCKS.len())
+  }
+
+  fn read_queued_blocks(&mut self, sector: u32) -> Result<Vec<QueuedBlock>> {
+    let blocks: Vec<QueuedBlock> = bincode::deserialize(&self.read_sectors(sector)?)?;
+    ensure!(blocks.iter().all(|block| self.is_known_block(block.block_type)), "invalid queued block data");
+    Ok(blocks)
+  }
+
+  /// Store blocks queued for chunks that aren't loaded, to be applied when they are (see [`Self::load_queued_blocks`])\
+  /// A block replaces any block queued earlier at the same position
+  pub fn queue_blocks(&mut self, blocks: &[QueuedBlock]) -> Result<()> {
+    let mut by_chunk: HashMap<IVec3, Vec<QueuedBlock>> = HashMap::new();
+    for &block in blocks {
+      let chunk_position = block.position.div_euclid(IVec3::splat(CHUNK_SIZE as i32));
+      by_chunk.entry(chunk_position).or_default().push(block);
+    }
+
+    let mut header_modified = false;
+    let mut map_modified = false;
+    for (chunk_position, new_blocks) in by_chunk {
+      //the chunk was unloaded before it got written, the blocks loaded earlier may not be part of it,
+      //so they're kept together with the new ones
+      self.loaded_queued_blocks.remove(&chunk_position);
+      let current_sector = self.header.read().unwrap().queued_map.get(&chunk_position).copied();
+      let mut chunk_blocks = match current_sector {
+        Some(sector) => self.read_queued_blocks(sector)?,
+        None => Vec::new(),
+      };
+      for block in new_blocks {
+        merge_queued_block(&mut chunk_blocks, block);
       }
+      let (sector, modified) = self.write_sectors(current_sector, &bincode::serialize(&chunk_blocks)?)?;
+      if current_sector != Some(sector) {
+        self.header.write().unwrap().queued_map.insert(chunk_position, sector);
+        map_modified = true;
+      }
+      header_modified |= modified;
+    }
+
+    if map_modified {
+      self.write_queued_map()?;
+      header_modified = true;
+    }
+    if header_modified {
+      self.write_header()?;
+    }
+    self.file.sync_data()?;
+    Ok(())
+  }
+
+  /// Get the blocks queued for the chunk at `position`\
+  /// They're kept until the chunk is written (see [`Self::save_chunk`]), so that they aren't lost if it's unloaded
+  /// before they're applied, unless they can't be read (so that invalid data doesn't fail every load of the chunk)
+  pub fn load_queued_blocks(&mut self, position: IVec3) -> Result<Vec<QueuedBlock>> {
+    let Some(sector) = self.header.read().unwrap().queued_map.get(&position).copied() else {
+      return Ok(Vec::new())
+    };
+    match self.read_queued_blocks(sector) {
+      Ok(blocks) => {
+        self.loaded_queued_blocks.insert(position);
+        Ok(blocks)
+      },
+      Err(error) => {
+        //XXX: the sectors are not freed, their length prefix can't be trusted
+        self.header.write().unwrap().queued_map.remove(&position);
+        self.write_queued_map()?;
+        self.write_header()?;
+        self.file.sync_data()?;
+        Err(error)
+      },
     }
-    let data: BlockData = unsafe { std::mem::transmute(buffer) };
+  }
 
-    Ok(Some(data))
+  /// Remove the blocks queued for the chunk at `position` and free their sectors, without updating the header on disk
+  fn remove_queued_blocks(&mut self, position: IVec3) -> Result<()> {
+    let Some(sector) = self.header.write().unwrap().queued_map.remove(&position) else {
+      return Ok(())
+    };
+    self.write_queued_map()?;
+    let count = self.chunk_sector_count(sector)?;
+    self.header.write().unwrap().free_sectors(sector, count);
+    Ok(())
   }
 
   pub fn get_shared_header(&self) -> SharedHeader {
@@ -184,6 +668,14 @@ impl WorldSaveFile {
   }
 }
 
+/// Add `block` to the list, replacing the block queued at the same position (if any)
+fn merge_queued_block(blocks: &mut Vec<QueuedBlock>, block: QueuedBlock) {
+  match blocks.iter_mut().find(|queued| queued.position == block.position) {
+    Some(queued) => *queued = block,
+    None => blocks.push(block),
+  }
+}
+
 /// Utility function to open a local save file, creating it if it doesn't exist
 pub fn open_local_save_file(path: &Path) -> Result<WorldSaveFile> {
   let mut save_file = WorldSaveFile::new({
@@ -202,3 +694,422 @@ pub fn open_local_save_file(path: &Path) -> Result<WorldSaveFile> {
   Ok(save_file)
 }
 
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+  use glam::{ivec3, IVec3};
+  use crate::{
+    block::{Block, BlockProperties, BlockState, Facing},
+    chunk::{BlockData, CHUNK_SIZE},
+    height::WorldHeight,
+    player::GameMode,
+    queue::QueuedBlock,
+    worldgen::{
+      hash::HashVersion,
+      preset::{WorldGenPreset, DEFAULT_PRESET, LEGACY_PRESET},
+      structures::{StructureTemplate, StructureTemplates},
+    },
+  };
+  use super::{
+    merge_queued_block, open_local_save_file, WorldSaveDataHeader,
+    CHAIN_PAYLOAD_SIZE, RESERVED_SECTOR_COUNT, RESERVED_SIZE, SUBHEADER_SIZE,
+  };
+
+  /// Path of a new save file in the temp directory
+  fn temp_save_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("kubi_test_{}_{}.kubi", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+  }
+
+  fn queued(position: IVec3, block: Block) -> QueuedBlock {
+    QueuedBlock::new(position, BlockState::new(block))
+  }
+
+  fn blocks_at(blocks: &[QueuedBlock]) -> Vec<(IVec3, Block)> {
+    blocks.iter().map(|block| (block.position, block.block_type)).collect()
+  }
+
+  /// All block positions of a chunk, in chunk-local coordinates
+  fn chunk_positions() -> impl Iterator<Item = IVec3> {
+    let size = CHUNK_SIZE as i32;
+    (0..size).flat_map(move |x| (0..size).flat_map(move |y| (0..size).map(move |z| ivec3(x, y, z))))
+  }
+
+  /// Chunk with `count` distinct block states (stone with different properties)
+  fn chunk_with_states(count: u16) -> BlockData {
+    let mut data = BlockData::new();
+    for (index, position) in chunk_positions().enumerate() {
+      let properties = BlockProperties(index as u16 % count);
+      data.set_state(position, BlockState::with_properties(Block::Stone, properties));
+    }
+    data.compact();
+    data
+  }
+
+  fn encoded_size(data: &BlockData) -> u64 {
+    bincode::serialized_size(data).unwrap()
+  }
+
+  #[test]
+  fn palette_promotion_and_demotion() {
+    let mut data = BlockData::new();
+    let uniform_size = encoded_size(&data);
+    assert!(data.is_uniform());
+
+    //every new state that doesn't fit the index width widens it, without changing the other blocks
+    let states: Vec<BlockState> = (0..20)
+      .map(|age| BlockState::with_properties(Block::Leaf, BlockProperties::NONE.with_age(age % 16).with_persistent(age >= 16)))
+      .collect();
+    let mut sizes = Vec::new();
+    for (index, &state) in states.iter().enumerate() {
+      data.set_state(ivec3(index as i32, 0, 0), state);
+      sizes.push(encoded_size(&data));
+      for (other_index, &other_state) in states[..=index].iter().enumerate() {
+        assert_eq!(data.get_state(ivec3(other_index as i32, 0, 0)), other_state);
+      }
+      assert_eq!(data.get_state(ivec3(0, 1, 0)), BlockState::AIR);
+    }
+    assert_eq!(data.palette().len(), states.len() + 1);
+    assert!(sizes.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert!(sizes[0] > uniform_size);
+
+    //states that are no longer used are removed by compact, shrinking the index width back
+    for index in 2..states.len() {
+      data.set_state(ivec3(index as i32, 0, 0), BlockState::AIR);
+    }
+    data.compact();
+    assert_eq!(data.palette().len(), 3);
+    assert_eq!(encoded_size(&data), sizes[1]);
+    assert_eq!(data.get_state(ivec3(1, 0, 0)), states[1]);
+
+    for index in 0..2 {
+      data.set_state(ivec3(index, 0, 0), BlockState::AIR);
+    }
+    data.compact();
+    assert!(data.is_uniform());
+    assert_eq!(data, BlockData::new());
+    assert_eq!(encoded_size(&data), uniform_size);
+  }
+
+  #[test]
+  fn block_data_round_trip() {
+    let properties = BlockProperties::NONE
+      .with_facing(Facing::West)
+      .with_waterlogged(true)
+      .with_level(7)
+      .with_age(3)
+      .with_persistent(true);
+    let decoded: BlockProperties = bincode::deserialize(&bincode::serialize(&properties).unwrap()).unwrap();
+    assert_eq!(decoded, properties);
+    assert_eq!(decoded.facing(), Facing::West);
+    assert!(decoded.waterlogged() && decoded.persistent());
+    assert_eq!((decoded.level(), decoded.age()), (7, 3));
+
+    //every index width
+    for count in [1, 2, 3, 16, 200, 300] {
+      let mut data = chunk_with_states(count);
+      data.set_state(ivec3(5, 6, 7), BlockState::with_properties(Block::Water, properties));
+      let decoded: BlockData = bincode::deserialize(&bincode::serialize(&data).unwrap()).unwrap();
+      assert_eq!(decoded, data);
+      assert_eq!(decoded.get_state(ivec3(5, 6, 7)).properties, properties);
+    }
+
+    //indices pointing past the end of the palette are rejected
+    let mut encoded = bincode::serialize(&chunk_with_states(3)).unwrap();
+    let last = encoded.len() - 1;
+    encoded[last] = 0xff;
+    assert!(bincode::deserialize::<BlockData>(&encoded).is_err());
+  }
+
+  #[test]
+  fn free_list_merges_runs() {
+    let mut header = WorldSaveDataHeader::default();
+    let first = header.allocate_sectors(2);
+    let second = header.allocate_sectors(3);
+    let third = header.allocate_sectors(1);
+    assert_eq!((first, second, third), (RESERVED_SECTOR_COUNT as u32, first + 2, first + 5));
+
+    header.free_sectors(first, 2);
+    header.free_sectors(third, 1);
+    assert_eq!(header.free_list, [(first, 2), (third, 1)]);
+    //freeing the run in between merges all three
+    header.free_sectors(second, 3);
+    assert_eq!(header.free_list, [(first, 6)]);
+
+    //first fit, the rest of the run stays free
+    assert_eq!(header.allocate_sectors(4), first);
+    assert_eq!(header.free_list, [(first + 4, 2)]);
+    assert_eq!(header.allocate_sectors(3), first + 6);
+    assert_eq!(header.allocate_sectors(2), first + 4);
+    assert!(header.free_list.is_empty());
+  }
+
+  #[test]
+  fn chunk_sectors_are_reused() {
+    let path = temp_save_path("sectors");
+    let (a, b, c) = (ivec3(0, 0, 0), ivec3(1, 0, 0), ivec3(2, 0, 0));
+    //16 bit indices take up three sectors, 8 bit ones two, uniform chunks one
+    let large = chunk_with_states(300);
+    let medium = chunk_with_states(20);
+    let small = BlockData::filled(BlockState::new(Block::Dirt));
+    {
+      let mut save = open_local_save_file(&path).unwrap();
+      let sector = |save: &super::WorldSaveFile, position| save.header.read().unwrap().chunk_map[&position];
+
+      save.save_chunk(a, &large, &[]).unwrap();
+      save.save_chunk(b, &small, &[]).unwrap();
+      let first = sector(&save, a);
+      assert_eq!(sector(&save, b), first + 3);
+
+      //smaller data stays in place, freeing the sectors it doesn't need
+      save.save_chunk(a, &small, &[]).unwrap();
+      assert_eq!(sector(&save, a), first);
+      save.save_chunk(c, &medium, &[]).unwrap();
+      assert_eq!(sector(&save, c), first + 1);
+
+      //larger data is moved, freeing the old sector
+      save.save_chunk(b, &large, &[]).unwrap();
+      assert_eq!(sector(&save, b), first + 4);
+      save.save_chunk(a, &large, &[]).unwrap();
+      assert_eq!(sector(&save, a), first + 7);
+      assert_eq!(save.header.read().unwrap().free_list, [(first, 1), (first + 3, 1)]);
+    }
+
+    let mut save = open_local_save_file(&path).unwrap();
+    assert_eq!(save.header.read().unwrap().free_list.len(), 2);
+    assert_eq!(save.load_chunk(a).unwrap().unwrap().0, large);
+    assert_eq!(save.load_chunk(b).unwrap().unwrap().0, large);
+    assert_eq!(save.load_chunk(c).unwrap().unwrap().0, medium);
+
+    drop(save);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn merge_replaces_blocks_at_the_same_position() {
+    let mut blocks = Vec::new();
+    merge_queued_block(&mut blocks, queued(ivec3(1, 2, 3), Block::Stone));
+    merge_queued_block(&mut blocks, queued(ivec3(3, 2, 1), Block::Dirt));
+    merge_queued_block(&mut blocks, queued(ivec3(1, 2, 3), Block::Leaf));
+    assert_eq!(blocks_at(&blocks), [(ivec3(1, 2, 3), Block::Leaf), (ivec3(3, 2, 1), Block::Dirt)]);
+  }
+
+  #[test]
+  fn queued_blocks_round_trip() {
+    let path = temp_save_path("queued_blocks");
+    {
+      let mut save = open_local_save_file(&path).unwrap();
+      //blocks of two chunks, the second batch replaces one of the blocks of the first one
+      save.queue_blocks(&[queued(ivec3(1, 2, 3), Block::Stone), queued(ivec3(-1, 0, 40), Block::Wood)]).unwrap();
+      save.queue_blocks(&[queued(ivec3(1, 2, 3), Block::Leaf), queued(ivec3(4, 5, 6), Block::Dirt)]).unwrap();
+    }
+
+    //reopen the file, to make sure that the blocks were persisted
+    let mut save = open_local_save_file(&path).unwrap();
+    assert!(save.has_queued_blocks(IVec3::ZERO));
+    assert!(save.has_queued_blocks(ivec3(-1, 0, 1)));
+    assert_eq!(
+      blocks_at(&save.load_queued_blocks(IVec3::ZERO).unwrap()),
+      [(ivec3(1, 2, 3), Block::Leaf), (ivec3(4, 5, 6), Block::Dirt)]
+    );
+    //the blocks are kept until the chunk is written
+    assert!(save.has_queued_blocks(IVec3::ZERO));
+    save.save_chunk(IVec3::ZERO, &BlockData::new(), &[]).unwrap();
+    assert!(!save.has_queued_blocks(IVec3::ZERO));
+    assert!(save.load_queued_blocks(IVec3::ZERO).unwrap().is_empty());
+
+    //blocks queued after the chunk was loaded are kept when it's written
+    let other = ivec3(-1, 0, 1);
+    assert_eq!(blocks_at(&save.load_queued_blocks(other).unwrap()), [(ivec3(-1, 0, 40), Block::Wood)]);
+    save.queue_blocks(&[queued(ivec3(-2, 0, 40), Block::Dirt)]).unwrap();
+    save.save_chunk(other, &BlockData::new(), &[]).unwrap();
+    assert_eq!(
+      blocks_at(&save.load_queued_blocks(other).unwrap()),
+      [(ivec3(-1, 0, 40), Block::Wood), (ivec3(-2, 0, 40), Block::Dirt)]
+    );
+    save.save_chunk(other, &BlockData::new(), &[]).unwrap();
+    assert!(!save.has_queued_blocks(other));
+
+    //only the sectors of the two chunks are still used
+    let header = save.header.read().unwrap();
+    let free: u32 = header.free_list.iter().map(|&(_, count)| count).sum();
+    assert_eq!(header.sector_count - RESERVED_SECTOR_COUNT as u32 - free, 2);
+    assert_eq!(header.queued_map_chain, None);
+    drop(header);
+
+    drop(save);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn queued_map_chain_grows_and_shrinks() {
+    let path = temp_save_path("queued_map");
+    //enough entries (pointing to made up sectors) for the map to be larger than the reserved area
+    let positions: Vec<IVec3> = (0..100_000).map(|index| ivec3(index, -index, index % 7)).collect();
+    {
+      let mut save = open_local_save_file(&path).unwrap();
+      save.header.write().unwrap().queued_map = positions.iter().map(|&position| (position, 1234)).collect();
+      save.write_queued_map().unwrap();
+      save.write_header().unwrap();
+      assert!(save.queued_map_sectors.len() * CHAIN_PAYLOAD_SIZE > RESERVED_SIZE);
+    }
+
+    let mut save = open_local_save_file(&path).unwrap();
+    assert_eq!(save.header.read().unwrap().queued_map.len(), positions.len());
+    assert!(positions.iter().all(|&position| save.has_queued_blocks(position)));
+
+    //the sectors the chain no longer needs are freed
+    let chain = save.queued_map_sectors.clone();
+    save.header.write().unwrap().queued_map.retain(|position, _| position.x < 10);
+    save.write_queued_map().unwrap();
+    assert_eq!(save.queued_map_sectors, chain[..1]);
+    assert_eq!(save.header.read().unwrap().free_list, [(chain[1], chain.len() as u32 - 1)]);
+    save.write_header().unwrap();
+    drop(save);
+    let mut save = open_local_save_file(&path).unwrap();
+    assert_eq!(save.header.read().unwrap().queued_map.len(), 10);
+
+    save.header.write().unwrap().queued_map.clear();
+    save.write_queued_map().unwrap();
+    assert!(save.queued_map_sectors.is_empty());
+    assert_eq!(save.header.read().unwrap().free_list, [(chain[0], chain.len() as u32)]);
+
+    drop(save);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn queued_map_is_moved_out_of_legacy_headers() {
+    let path = temp_save_path("legacy_queued_map");
+    {
+      let mut save = open_local_save_file(&path).unwrap();
+      save.queue_blocks(&[queued(ivec3(1, 2, 3), Block::Stone)]).unwrap();
+      //pretend that the save file is from before the map got its own sectors
+      let mut header = save.header.write().unwrap();
+      header.legacy_queued_map = header.queued_map.clone();
+      header.queued_map_chain = None;
+      drop(header);
+      save.write_header().unwrap();
+    }
+
+    let mut save = open_local_save_file(&path).unwrap();
+    assert_eq!(blocks_at(&save.load_queued_blocks(IVec3::ZERO).unwrap()), [(ivec3(1, 2, 3), Block::Stone)]);
+    let header = save.header.read().unwrap();
+    assert!(header.legacy_queued_map.is_empty());
+    assert!(header.queued_map_chain.is_some());
+    drop(header);
+
+    drop(save);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn header_is_zero_filled() {
+    let path = temp_save_path("header");
+    let mut save = open_local_save_file(&path).unwrap();
+    //a shorter header must not leave bytes of the longer one behind
+    save.header.write().unwrap().name = "x".repeat(1000).into();
+    save.write_header().unwrap();
+    save.header.write().unwrap().name = "World".into();
+    save.write_header().unwrap();
+
+    let data = fs::read(&path).unwrap();
+    assert_eq!(data.len(), RESERVED_SIZE);
+    let header_size = bincode::serialized_size(&*save.header.read().unwrap()).unwrap() as usize;
+    assert!(data[SUBHEADER_SIZE + header_size..].iter().all(|&byte| byte == 0));
+
+    drop(save);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn player_gamemodes_are_persisted() {
+    let path = temp_save_path("players");
+    {
+      let mut save = open_local_save_file(&path).unwrap();
+      assert_eq!(save.player_gamemode("player"), None);
+      save.save_player_gamemode("player", GameMode::Spectator).unwrap();
+      save.save_player_gamemode("other", GameMode::Creative).unwrap();
+    }
+    let save = open_local_save_file(&path).unwrap();
+    assert_eq!(save.player_gamemode("player"), Some(GameMode::Spectator));
+    assert_eq!(save.player_gamemode("other"), Some(GameMode::Creative));
+
+    drop(save);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn legacy_saves_use_the_legacy_preset() {
+    let path = temp_save_path("preset");
+    {
+      let mut save = open_local_save_file(&path).unwrap();
+      let preset = save.world_generator(WorldGenPreset::default()).unwrap();
+      assert_eq!((preset.name.as_str(), preset.hash_version), (DEFAULT_PRESET, HashVersion::Stable));
+      //pretend that the save file is from before the presets were added
+      let mut header = save.header.write().unwrap();
+      header.generator = None;
+      header.hash_version = None;
+      drop(header);
+      save.write_header().unwrap();
+    }
+
+    let mut save = open_local_save_file(&path).unwrap();
+    let preset = save.world_generator(WorldGenPreset::default()).unwrap();
+    assert_eq!(preset.steps, WorldGenPreset::legacy().steps);
+    assert_eq!((preset.name.as_str(), preset.hash_version), (LEGACY_PRESET, HashVersion::Legacy));
+    //the preset is stored
+    drop(save);
+    assert_eq!(open_local_save_file(&path).unwrap().world_generator(WorldGenPreset::default()).unwrap(), preset);
+
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn structure_template_changes_are_detected() {
+    let path = temp_save_path("templates");
+    let templates = StructureTemplates::builtin();
+    {
+      let mut save = open_local_save_file(&path).unwrap();
+      assert!(save.check_structure_templates(&templates).unwrap());
+    }
+
+    let mut save = open_local_save_file(&path).unwrap();
+    assert!(save.check_structure_templates(&templates).unwrap());
+    let mut changed = templates.clone();
+    changed.add(StructureTemplate::parse("name = \"hut\"\nkind = \"village_house\"\npalette = { p = \"planks\" }\nlayers = [[\"p\"]]").unwrap());
+    assert!(!save.check_structure_templates(&changed).unwrap());
+    //the original hash is kept
+    assert!(save.check_structure_templates(&templates).unwrap());
+
+    drop(save);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn legacy_height_includes_saved_chunks() {
+    let height = WorldHeight { min: -64, max: 64, bedrock: 1 };
+    let path = temp_save_path("height");
+    {
+      //new worlds use the requested limits
+      let mut save = open_local_save_file(&path).unwrap();
+      save.queue_blocks(&[queued(ivec3(0, 1000, 0), Block::Stone), queued(ivec3(0, -700, 0), Block::Stone)]).unwrap();
+      assert_eq!(save.world_height(height).unwrap(), height);
+      //pretend that the save file is from before the limits were added
+      save.header.write().unwrap().height = None;
+      save.write_header().unwrap();
+    }
+
+    let mut save = open_local_save_file(&path).unwrap();
+    let legacy = save.world_height(height).unwrap();
+    assert!(legacy.contains(1000) && legacy.contains(-700));
+    assert!(legacy.min <= WorldHeight::default().min && legacy.max >= WorldHeight::default().max);
+    //the limits are stored
+    drop(save);
+    assert_eq!(open_local_save_file(&path).unwrap().world_height(height).unwrap(), legacy);
+
+    fs::remove_file(&path).unwrap();
+  }
+}
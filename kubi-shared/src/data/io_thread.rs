@@ -1,7 +1,7 @@
 use glam::IVec3;
 use flume::{Receiver, Sender, TryIter};
 use shipyard::Unique;
-use crate::chunk::BlockData;
+use crate::{chunk::BlockData, player::GameMode, queue::QueuedBlock, tick::PendingTick};
 use super::{SharedHeader, WorldSaveFile};
 
 // Maximum amount of chunks to save in a single batch before checking if there are any pending read requests
@@ -23,6 +23,7 @@ pub enum IOCommand {
   SaveChunk {
     position: IVec3,
     data: BlockData,
+    ticks: Vec<PendingTick>,
   },
 
   /// Load a chunk from the disk and send it to the main thread
@@ -30,6 +31,18 @@ pub enum IOCommand {
     position: IVec3,
   },
 
+  /// Store blocks queued for chunks that aren't currently loaded\
+  /// They will be sent back with the chunk once it gets loaded
+  QueueBlocks {
+    blocks: Vec<QueuedBlock>,
+  },
+
+  /// Store the game mode of a player (by their username)
+  SavePlayer {
+    username: String,
+    gamemode: GameMode,
+  },
+
   /// Process all pending write commands and make the thread end itself
   /// LoadChunk commands will be ignored after this command is received
   Kys,
@@ -43,6 +56,11 @@ pub enum IOResponse {
   ChunkLoaded {
     position: IVec3,
     data: Option<BlockData>,
+    /// Scheduled ticks that were pending in the chunk when it was saved
+    ticks: Vec<PendingTick>,
+    /// Blocks queued for the chunk while it wasn't loaded\
+    /// (should be applied after the chunk is generated if data is None)
+    queued: Vec<QueuedBlock>,
   },
 
   /// In-progress shutdown info
@@ -56,7 +74,7 @@ struct IOThreadContext {
   tx: Sender<IOResponse>,
   rx: Receiver<IOCommand>,
   save: WorldSaveFile,
-  save_queue: Vec<(IVec3, BlockData)>,
+  save_queue: Vec<(IVec3, BlockData, Vec<PendingTick>)>,
 }
 
 //TODO: Implement proper error handling (I/O errors are rlly common)
@@ -76,6 +94,14 @@ impl IOThreadContext {
     Self { tx, rx, save, save_queue }
   }
 
+  /// Load the blocks queued for the chunk, dropping them if they can't be read
+  fn load_queued_blocks(&mut self, position: IVec3) -> Vec<QueuedBlock> {
+    self.save.load_queued_blocks(position).unwrap_or_else(|error| {
+      log::error!("failed to load blocks queued for chunk {position}, dropping them: {error:?}");
+      Vec::new()
+    })
+  }
+
   pub fn run(mut self) {
     loop {
       // because were waiting for the next command, we can't process the save_queue
@@ -89,16 +115,17 @@ impl IOThreadContext {
         }
       } {
         match command {
-          IOCommand::SaveChunk { position, data } => {
+          IOCommand::SaveChunk { position, data, ticks } => {
             // if chunk already has a save request, overwrite it
-            for (pos, old_data) in self.save_queue.iter_mut() {
+            for (pos, old_data, old_ticks) in self.save_queue.iter_mut() {
               if *pos == position {
                 *old_data = data;
+                *old_ticks = ticks;
                 continue 'rx;
               }
             }
             // if not, save to the queue
-            self.save_queue.push((position, data));
+            self.save_queue.push((position, data, ticks));
             //log::trace!("amt of unsaved chunks: {}", self.save_queue.len());
           }
           IOCommand::LoadChunk { position } => {
@@ -106,14 +133,31 @@ impl IOThreadContext {
             // first check if the chunk is already in the save queue
             // if it is, send it and continue
             // (NOT doing this WILL result in data loss if the user returns to the chunk too quickly)
-            for (pos, data) in self.save_queue.iter() {
-              if *pos == position {
-                self.tx.send(IOResponse::ChunkLoaded { position, data: Some(data.clone()) }).unwrap();
-                continue 'rx;
-              }
+            // it's written right away, as writing the chunk removes the blocks queued for it once they're loaded
+            if let Some(index) = self.save_queue.iter().position(|(pos, _, _)| *pos == position) {
+              let (_, data, ticks) = self.save_queue.remove(index);
+              self.save.save_chunk(position, &data, &ticks).unwrap();
+              let queued = self.load_queued_blocks(position);
+              self.tx.send(IOResponse::ChunkLoaded {
+                position,
+                data: Some(data),
+                ticks,
+                queued,
+              }).unwrap();
+              continue 'rx;
             }
-            let data = self.save.load_chunk(position).unwrap();
-            self.tx.send(IOResponse::ChunkLoaded { position, data }).unwrap();
+            let queued = self.load_queued_blocks(position);
+            let (data, ticks) = match self.save.load_chunk(position).unwrap() {
+              Some((data, ticks)) => (Some(data), ticks),
+              None => (None, Vec::new()),
+            };
+            self.tx.send(IOResponse::ChunkLoaded { position, data, ticks, queued }).unwrap();
+          }
+          IOCommand::QueueBlocks { blocks } => {
+            self.save.queue_blocks(&blocks).unwrap();
+          }
+          IOCommand::SavePlayer { username, gamemode } => {
+            self.save.save_player_gamemode(&username, gamemode).unwrap();
           }
           IOCommand::Kys => {
             self.tx.send(IOResponse::KysProgressInformational(
@@ -125,8 +169,8 @@ impl IOThreadContext {
 
             log::info!("info: queue has {} chunks", save_queue_len);
             let mut saved_amount = 0;
-            for (pos, data) in self.save_queue.drain(..) {
-              self.save.save_chunk(pos, &data).unwrap();
+            for (pos, data, ticks) in self.save_queue.drain(..) {
+              self.save.save_chunk(pos, &data, &ticks).unwrap();
               saved_amount += 1;
 
               // Send kys preflight info
@@ -145,11 +189,19 @@ impl IOThreadContext {
             )).unwrap();
 
             for cmd in self.rx.try_iter() {
-              let IOCommand::SaveChunk { position, data } = cmd else {
-                continue;
-              };
-              self.save.save_chunk(position, &data).unwrap();
-              saved_amount += 1;
+              match cmd {
+                IOCommand::SaveChunk { position, data, ticks } => {
+                  self.save.save_chunk(position, &data, &ticks).unwrap();
+                  saved_amount += 1;
+                }
+                IOCommand::QueueBlocks { blocks } => {
+                  self.save.queue_blocks(&blocks).unwrap();
+                }
+                IOCommand::SavePlayer { username, gamemode } => {
+                  self.save.save_player_gamemode(&username, gamemode).unwrap();
+                }
+                _ => (),
+              }
             }
             log::info!("saved {} chunks on exit", saved_amount);
 
@@ -166,8 +218,8 @@ impl IOThreadContext {
       if !self.save_queue.is_empty() {
         let will_drain = MAX_SAVE_BATCH_SIZE.min(self.save_queue.len());
         log::info!("saving {}/{} chunks with batch size {}...", will_drain, self.save_queue.len(), MAX_SAVE_BATCH_SIZE);
-        for (pos, data) in self.save_queue.drain(..will_drain) {
-          self.save.save_chunk(pos, &data).unwrap();
+        for (pos, data, ticks) in self.save_queue.drain(..will_drain) {
+          self.save.save_chunk(pos, &data, &ticks).unwrap();
         }
       }
     }
@@ -256,6 +308,14 @@ impl IOSingleThread {
   pub fn chunk_exists(&self, position: IVec3) -> bool {
     self.header.read().unwrap().chunk_map.contains_key(&position)
   }
+
+  pub fn has_queued_blocks(&self, position: IVec3) -> bool {
+    self.header.read().unwrap().queued_map.contains_key(&position)
+  }
+
+  pub fn player_gamemode(&self, username: &str) -> Option<GameMode> {
+    self.header.read().unwrap().player_gamemodes.get(username).copied()
+  }
 }
 
 impl Drop for IOSingleThread {
@@ -298,6 +358,14 @@ impl IOThreadManager {
     self.thread.chunk_exists(position)
   }
 
+  pub fn has_queued_blocks(&self, position: IVec3) -> bool {
+    self.thread.has_queued_blocks(position)
+  }
+
+  pub fn player_gamemode(&self, username: &str) -> Option<GameMode> {
+    self.thread.player_gamemode(username)
+  }
+
   #[allow(deprecated)]
   #[deprecated(note = "Use stop_async and block_on_termination instead")]
   pub fn deprecated_stop_sync(&mut self) {
@@ -1,6 +1,19 @@
 use shipyard::Component;
 use serde::{Serialize, Deserialize};
 
+/// Damage dealt by each drowning damage tick
+pub const DROWNING_DAMAGE: u8 = 2;
+/// Time (in seconds) an entity can stay submerged before drowning
+pub const MAX_BREATH: f32 = 10.;
+/// Time (in seconds) between drowning damage ticks
+pub const DROWNING_DAMAGE_INTERVAL: f32 = 1.;
+/// Landing slower than this (in blocks per second) doesn't cause any damage
+pub const FALL_DAMAGE_MIN_VELOCITY: f32 = 10.;
+/// Damage per each block per second above `FALL_DAMAGE_MIN_VELOCITY`
+pub const FALL_DAMAGE_PER_VELOCITY: f32 = 1.;
+/// Downward acceleration of falling entities, in blocks per second squared
+pub const GRAVITY: f32 = 9.8;
+
 #[derive(Component)]
 pub struct Entity;
 
@@ -16,6 +29,108 @@ impl Health {
       max: health
     }
   }
+
+  pub fn is_dead(&self) -> bool {
+    self.current == 0
+  }
+
+  /// Subtract `amount` from current health, stopping at zero
+  pub fn damage(&mut self, amount: u8) {
+    self.current = self.current.saturating_sub(amount);
+  }
+
+  /// Restore health to the maximum value
+  pub fn reset(&mut self) {
+    self.current = self.max;
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageCause {
+  /// Hitting the ground at high velocity
+  Fall,
+  /// Staying submerged for too long
+  Drowning,
+}
+
+/// Damage dealt by falling from `height` (in blocks)\
+/// Based on the velocity of a free fall from that height
+pub fn fall_damage(height: f32) -> u8 {
+  let velocity = (2. * GRAVITY * height.max(0.)).sqrt();
+  if velocity <= FALL_DAMAGE_MIN_VELOCITY {
+    return 0
+  }
+  ((velocity - FALL_DAMAGE_MIN_VELOCITY) * FALL_DAMAGE_PER_VELOCITY).ceil().min(u8::MAX as f32) as u8
+}
+
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct FallDamageState {
+  /// Highest point (feet height) since the entity left the ground
+  pub fall_start: Option<f32>,
+  /// Fall damage is only enabled after touching the ground for the first time,\
+  /// so that (re)spawning mid-air doesn't kill the player
+  pub armed: bool,
+}
+
+impl FallDamageState {
+  /// Track the fall of an entity with its feet at height `y`\
+  /// `cancelled` ends the current fall without damage (landing in water, not being in survival mode, ...)
+  ///
+  /// Returns the fall damage once the entity lands
+  pub fn update(&mut self, y: f32, on_ground: bool, cancelled: bool) -> Option<u8> {
+    if cancelled {
+      self.fall_start = None;
+      return None
+    }
+    if !on_ground {
+      self.fall_start = Some(self.fall_start.map_or(y, |start| start.max(y)));
+      return None
+    }
+    let fall_start = self.fall_start.take();
+    if !std::mem::replace(&mut self.armed, true) {
+      return None
+    }
+    Some(fall_damage(fall_start? - y)).filter(|&amount| amount > 0)
+  }
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BreathState {
+  /// Remaining breath, in seconds
+  pub remaining: f32,
+  pub damage_timer: f32,
+}
+
+impl Default for BreathState {
+  fn default() -> Self {
+    Self {
+      remaining: MAX_BREATH,
+      damage_timer: 0.,
+    }
+  }
+}
+
+impl BreathState {
+  /// Advance the breath of an entity by `dt` seconds, breath is restored as soon as it's not `submerged` anymore
+  ///
+  /// Returns the drowning damage dealt in this step (if any)
+  pub fn update(&mut self, submerged: bool, dt: f32) -> Option<u8> {
+    if !submerged {
+      *self = Self::default();
+      return None
+    }
+    self.remaining -= dt;
+    if self.remaining > 0. {
+      return None
+    }
+    self.remaining = 0.;
+    self.damage_timer += dt;
+    if self.damage_timer < DROWNING_DAMAGE_INTERVAL {
+      return None
+    }
+    self.damage_timer -= DROWNING_DAMAGE_INTERVAL;
+    Some(DROWNING_DAMAGE)
+  }
 }
 
 // impl PartialEq for Health {
@@ -34,3 +149,65 @@ impl Health {
 //     self.current.cmp(&other.current)
 //   }
 // }
+
+#[cfg(test)]
+mod tests {
+  use super::{fall_damage, BreathState, FallDamageState, DROWNING_DAMAGE, MAX_BREATH};
+
+  /// Fall from `from` to `to`, returns the damage dealt on landing
+  fn fall(state: &mut FallDamageState, from: f32, to: f32, cancelled: bool) -> Option<u8> {
+    assert_eq!(state.update(from, false, cancelled), None);
+    assert_eq!(state.update((from + to) / 2., false, cancelled), None);
+    state.update(to, true, cancelled)
+  }
+
+  #[test]
+  fn fall_damage_depends_on_height() {
+    assert_eq!(fall_damage(-10.), 0);
+    assert_eq!(fall_damage(5.), 0);
+    //landing at ~19.8 blocks per second
+    assert_eq!(fall_damage(20.), 10);
+    assert_eq!(fall_damage(1e9), u8::MAX);
+  }
+
+  #[test]
+  fn first_landing_is_free() {
+    let mut state = FallDamageState::default();
+    assert_eq!(fall(&mut state, 100., 0., false), None);
+    assert_eq!(fall(&mut state, 20., 0., false), Some(10));
+  }
+
+  #[test]
+  fn falls_start_at_the_highest_point() {
+    let mut state = FallDamageState { armed: true, ..Default::default() };
+    //jumping up before falling down
+    assert_eq!(state.update(0., false, false), None);
+    assert_eq!(state.update(20., false, false), None);
+    assert_eq!(state.update(0., true, false), Some(10));
+    //short falls don't hurt
+    assert_eq!(fall(&mut state, 1., 0., false), None);
+  }
+
+  #[test]
+  fn cancelled_falls_deal_no_damage() {
+    let mut state = FallDamageState { armed: true, ..Default::default() };
+    assert_eq!(state.update(50., false, false), None);
+    //landing in water
+    assert_eq!(state.update(10., false, true), None);
+    assert_eq!(state.update(10., true, false), None);
+  }
+
+  #[test]
+  fn drowning_starts_after_running_out_of_breath() {
+    let mut state = BreathState::default();
+    let mut damage = Vec::new();
+    for _ in 0..((MAX_BREATH + 3.) * 4.) as usize {
+      damage.extend(state.update(true, 0.25));
+    }
+    assert_eq!(damage, [DROWNING_DAMAGE; 3]);
+
+    //surfacing restores the breath
+    assert_eq!(state.update(false, 0.25), None);
+    assert_eq!(state.remaining, MAX_BREATH);
+  }
+}
@@ -0,0 +1,164 @@
+//! Falling blocks
+//!
+//! Blocks with the `gravity` behavior turn into falling block entities once the block below them
+//! is no longer solid, and turn back into blocks when they land\
+//! If the block they land on is a non-solid block that can't be replaced, they're placed in the nearest free cell above it\
+//! Falling block entities aren't saved, they're stored as blocks at their current position instead
+//! (see [`FallingBlock::persist`])
+
+use glam::{IVec3, Vec3};
+use serde::{Serialize, Deserialize};
+use shipyard::{Component, Unique};
+use crate::{
+  block::{Block, BlockBehavior, BlockRegistry, BlockState, CollisionType},
+  chunk::{BlockData, CHUNK_SIZE},
+  queue::QueuedBlock,
+  tick::{BlockAccess, PendingTick},
+};
+
+/// Downwards acceleration of falling blocks (in blocks per second squared)
+pub const FALLING_BLOCK_GRAVITY: f32 = 20.;
+
+/// Terminal velocity of falling blocks (in blocks per second)
+pub const FALLING_BLOCK_MAX_VELOCITY: f32 = 40.;
+
+/// Check if the block at `position` should start falling\
+/// Returns the block state to turn into a falling block
+pub fn should_start_falling(world: &impl BlockAccess, registry: &BlockRegistry, position: IVec3) -> Option<BlockState> {
+  let state = world.get_block_state(position)?;
+  if registry.get(state.block).behavior != BlockBehavior::Gravity {
+    return None
+  }
+  let below = world.get_block_state(position - IVec3::Y)?;
+  (registry.get(below.block).collision != CollisionType::Solid).then_some(state)
+}
+
+/// Check if a landing block can replace `target`
+fn can_replace(registry: &BlockRegistry, target: BlockState) -> bool {
+  let descriptor = registry.get(target.block);
+  target.block == Block::Air || descriptor.fluid || (
+    descriptor.collision == CollisionType::None &&
+    descriptor.hardness.is_some()
+  )
+}
+
+/// Find the lowest cell at or above `position` that a landing block can replace\
+/// Returns `None` if the search reaches a chunk that isn't loaded
+fn free_cell_above(world: &impl BlockAccess, registry: &BlockRegistry, mut position: IVec3) -> Option<IVec3> {
+  loop {
+    if can_replace(registry, world.get_block_state(position)?) {
+      return Some(position)
+    }
+    position += IVec3::Y;
+  }
+}
+
+/// Result of a [`FallingBlock::update`]
+#[derive(Clone, Copy, Debug)]
+pub enum FallingBlockUpdate {
+  /// Still falling (or waiting for the chunk below to load)
+  Falling,
+  /// Landed, and should be turned back into a block
+  Landed(QueuedBlock),
+}
+
+/// Block falling down, simulated by the server (or the client in singleplayer)\
+/// Clients in multiplayer only simulate the fall for display purposes, landing is handled by the server
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct FallingBlock {
+  /// Used to refer to the falling block over the network
+  pub id: u64,
+  pub state: BlockState,
+  /// Position of the bottom corner of the block (x and z are always whole numbers)
+  pub position: Vec3,
+  /// Vertical velocity (negative when falling down)
+  pub velocity: f32,
+}
+
+impl FallingBlock {
+  pub fn new(id: u64, position: IVec3, state: BlockState) -> Self {
+    Self {
+      id,
+      state,
+      position: position.as_vec3(),
+      velocity: 0.,
+    }
+  }
+
+  /// Position of the block cell the falling block is currently in
+  pub fn cell(&self) -> IVec3 {
+    self.position.floor().as_ivec3()
+  }
+
+  /// Position of the chunk the falling block is currently in
+  pub fn chunk_position(&self) -> IVec3 {
+    self.cell().div_euclid(IVec3::splat(CHUNK_SIZE as i32))
+  }
+
+  /// Write the falling block into the data of the chunk it's in (which is about to be saved) as a regular block\
+  /// A tick is scheduled at its position, so that it continues falling once the chunk is loaded again\
+  /// If its cell is taken by a block that can't be replaced, the nearest free cell above it (in the same chunk) is used
+  ///
+  /// Returns `false` if there's no such cell (the falling block is lost)
+  pub fn persist(&self, registry: &BlockRegistry, blocks: &mut BlockData, ticks: &mut Vec<PendingTick>) -> bool {
+    let cell = self.cell();
+    let local_position = cell.rem_euclid(IVec3::splat(CHUNK_SIZE as i32));
+    let Some(local_y) = (local_position.y..CHUNK_SIZE as i32).find(|&y| {
+      can_replace(registry, blocks.get_state(local_position.with_y(y)))
+    }) else {
+      return false
+    };
+    blocks.set_state(local_position.with_y(local_y), self.state);
+    ticks.push(PendingTick { position: cell.with_y(cell.y - local_position.y + local_y), delay: 1 });
+    true
+  }
+
+  /// Move the block down, checking for collisions with solid blocks on the way
+  pub fn update(&mut self, world: &impl BlockAccess, registry: &BlockRegistry, dt: f32) -> FallingBlockUpdate {
+    let velocity = (self.velocity - FALLING_BLOCK_GRAVITY * dt).max(-FALLING_BLOCK_MAX_VELOCITY);
+    let new_y = self.position.y + velocity * dt;
+    let (x, z) = (self.position.x as i32, self.position.z as i32);
+
+    //Check all blocks entered by the bottom face, so fast blocks can't skip through the floor
+    let entered_from = self.position.y.floor() as i32 - 1;
+    let entered_to = new_y.floor() as i32;
+    for y in (entered_to..=entered_from).rev() {
+      let Some(state) = world.get_block_state(IVec3::new(x, y, z)) else {
+        //Chunk not loaded, wait for it
+        self.velocity = 0.;
+        return FallingBlockUpdate::Falling
+      };
+      if registry.get(state.block).collision != CollisionType::Solid {
+        continue
+      }
+      self.velocity = 0.;
+      let Some(landed_at) = free_cell_above(world, registry, IVec3::new(x, y + 1, z)) else {
+        //Chunk above not loaded, wait for it
+        return FallingBlockUpdate::Falling
+      };
+      self.position.y = landed_at.y as f32;
+      return FallingBlockUpdate::Landed(QueuedBlock::new(landed_at, self.state))
+    }
+
+    self.position.y = new_y;
+    self.velocity = velocity;
+    FallingBlockUpdate::Falling
+  }
+}
+
+/// Source of unique [`FallingBlock`] ids
+#[derive(Unique, Default)]
+pub struct FallingBlockIds {
+  next: u64,
+}
+
+impl FallingBlockIds {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn next_id(&mut self) -> u64 {
+    self.next += 1;
+    self.next
+  }
+}
@@ -0,0 +1,128 @@
+//! Fluid simulation
+//!
+//! Fluids use the `level` block property:
+//! - `0` is a source block, which never disappears on its own
+//! - `1..=MAX_FLOW_DISTANCE` is flowing fluid, spreading sideways from a source (higher = further away)
+//! - `FALLING_LEVEL` is fluid falling down from the block above
+//!
+//! Flowing fluid that is no longer connected to a source dries out
+
+use glam::IVec3;
+use crate::{
+  block::{Block, BlockProperties, BlockRegistry, BlockState, CollisionType},
+  queue::QueuedBlock,
+  tick::BlockAccess,
+};
+
+/// How far fluids can flow sideways from their source
+pub const MAX_FLOW_DISTANCE: u8 = 7;
+
+/// Level of fluid falling down
+pub const FALLING_LEVEL: u8 = 8;
+
+/// Delay between fluid updates (in block ticks)
+pub const FLUID_TICK_DELAY: u32 = 5;
+
+const HORIZONTAL: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// Height of the fluid surface, in range `0..=1`
+pub fn fluid_surface_height(level: u8) -> f32 {
+  match level {
+    FALLING_LEVEL => 1.,
+    level => (MAX_FLOW_DISTANCE + 1 - level.min(MAX_FLOW_DISTANCE)) as f32 / (MAX_FLOW_DISTANCE + 2) as f32,
+  }
+}
+
+/// Level of fluid flowing sideways out of a block with `level`
+const fn flow_level(level: u8) -> u8 {
+  match level {
+    0 | FALLING_LEVEL => 1,
+    level => level + 1,
+  }
+}
+
+fn fluid_state(block: Block, level: u8) -> BlockState {
+  BlockState::with_properties(block, BlockProperties::NONE.with_level(level))
+}
+
+/// Check if `fluid` with `level` can flow into a block with state `target`
+fn can_flow_into(registry: &BlockRegistry, fluid: Block, level: u8, target: BlockState) -> bool {
+  if target.block == fluid {
+    let target_level = target.properties.level();
+    return match (target_level, level) {
+      //sources are never replaced
+      (0, _) => false,
+      //falling fluid takes priority over flowing fluid
+      (FALLING_LEVEL, _) => false,
+      (_, FALLING_LEVEL) => true,
+      (target_level, level) => target_level > level,
+    }
+  }
+  //fluids wash away non-solid, breakable blocks (like tall grass or torches)
+  let descriptor = registry.get(target.block);
+  target.block == Block::Air || (
+    descriptor.collision == CollisionType::None &&
+    descriptor.hardness.is_some() &&
+    !descriptor.fluid
+  )
+}
+
+/// Run a fluid update at `position`, returning the resulting block changes
+///
+/// Once applied, changed blocks and their neighbors should be updated again (see [`crate::tick::ScheduledTicks::schedule_updates`])
+pub fn fluid_tick(world: &impl BlockAccess, registry: &BlockRegistry, position: IVec3) -> Vec<QueuedBlock> {
+  let Some(state) = world.get_block_state(position) else { return Vec::new() };
+  let fluid = state.block;
+  if !registry.get(fluid).fluid {
+    return Vec::new()
+  }
+  let level = state.properties.level();
+
+  //Check if flowing fluid is still fed by a neighboring block
+  if level != 0 {
+    let fed_from_above = world.get_block_state(position + IVec3::Y).is_some_and(|above| above.block == fluid);
+    let expected_level = match fed_from_above {
+      true => Some(FALLING_LEVEL),
+      false => HORIZONTAL.iter()
+        .filter_map(|&offset| world.get_block_state(position + offset))
+        .filter(|neighbor| neighbor.block == fluid)
+        .map(|neighbor| flow_level(neighbor.properties.level()))
+        .filter(|&level| level <= MAX_FLOW_DISTANCE)
+        .min(),
+    };
+    match expected_level {
+      None => return vec![QueuedBlock::new(position, BlockState::AIR)],
+      Some(expected_level) if expected_level != level => {
+        return vec![QueuedBlock::new(position, fluid_state(fluid, expected_level))]
+      },
+      _ => (),
+    }
+  }
+
+  //Flow down if possible
+  let below = position - IVec3::Y;
+  match world.get_block_state(below) {
+    None => return Vec::new(),
+    Some(target) if can_flow_into(registry, fluid, FALLING_LEVEL, target) => {
+      return vec![QueuedBlock::new(below, fluid_state(fluid, FALLING_LEVEL))]
+    },
+    Some(target) if target.block == fluid && level != 0 => {
+      //Already flowing into fluid below, don't spread sideways
+      return Vec::new()
+    },
+    _ => (),
+  }
+
+  //Spread sideways
+  let next_level = flow_level(level);
+  if next_level > MAX_FLOW_DISTANCE {
+    return Vec::new()
+  }
+  HORIZONTAL.iter()
+    .map(|&offset| position + offset)
+    .filter(|&neighbor| {
+      world.get_block_state(neighbor).is_some_and(|target| can_flow_into(registry, fluid, next_level, target))
+    })
+    .map(|neighbor| QueuedBlock::new(neighbor, fluid_state(fluid, next_level)))
+    .collect()
+}
@@ -0,0 +1,66 @@
+//! Vertical limits of the world
+//!
+//! The chunk grid is unbounded, but blocks only exist between the minimum and the maximum build height.\
+//! Chunks outside of these limits are always empty, they're never generated, loaded or saved
+
+use std::ops::RangeInclusive;
+use serde::{Serialize, Deserialize};
+use shipyard::Unique;
+use anyhow::{Result, ensure};
+use crate::chunk::CHUNK_SIZE;
+
+#[derive(Unique, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct WorldHeight {
+  /// Lowest y coordinate of blocks (inclusive), the bedrock floor starts here
+  pub min: i32,
+  /// Highest y coordinate of blocks (exclusive)
+  pub max: i32,
+  /// Thickness of the bedrock floor, `0` disables it\
+  /// Blocks in the floor can't be changed by players
+  pub bedrock: u32,
+}
+
+impl Default for WorldHeight {
+  fn default() -> Self {
+    Self {
+      min: -512,
+      max: 256,
+      bedrock: 1,
+    }
+  }
+}
+
+impl WorldHeight {
+  pub fn validate(&self) -> Result<()> {
+    ensure!(self.min < self.max, "minimum build height must be below the maximum build height");
+    ensure!(self.bedrock as i64 <= self.max as i64 - self.min as i64, "bedrock floor is thicker than the world");
+    Ok(())
+  }
+
+  /// Top of the bedrock floor (exclusive)
+  pub fn bedrock_top(&self) -> i32 {
+    self.min + self.bedrock as i32
+  }
+
+  /// Can blocks exist at this height?
+  pub fn contains(&self, y: i32) -> bool {
+    (self.min..self.max).contains(&y)
+  }
+
+  /// Can players place or break blocks at this height?\
+  /// (inside of the limits and above the bedrock floor)
+  pub fn can_modify(&self, y: i32) -> bool {
+    (self.bedrock_top()..self.max).contains(&y)
+  }
+
+  /// Y coordinates of chunks that can contain blocks
+  pub fn chunk_range(&self) -> RangeInclusive<i32> {
+    self.min.div_euclid(CHUNK_SIZE as i32)..=(self.max - 1).div_euclid(CHUNK_SIZE as i32)
+  }
+
+  /// Can the chunk at this y coordinate contain blocks?
+  pub fn contains_chunk(&self, chunk_y: i32) -> bool {
+    self.chunk_range().contains(&chunk_y)
+  }
+}
@@ -1,40 +1,36 @@
 use std::num::NonZeroU8;
-use num_enum::TryFromPrimitive;
 use serde::{Serialize, Deserialize};
-use strum::EnumIter;
-use crate::block::Block;
-
-#[derive(Clone, Copy)]
-pub enum ItemUsage {
-  AsBlock(Block)
-}
+use crate::block::{Block, BlockRegistry};
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ItemDescriptor {
-  pub name: &'static str,
-  pub usage: Option<ItemUsage>,
+  pub name: String,
+  /// Block placed when the item is used
+  pub places: Option<Block>,
   pub stack_size: NonZeroU8,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, EnumIter, TryFromPrimitive)]
-#[repr(u8)]
-pub enum Item {
-  TestItem,
-}
+/// Numeric item id
+///
+/// Items are defined next to the blocks (see [`BlockRegistry`]), and have no fixed ids
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[repr(transparent)]
+pub struct Item(pub u8);
 
 impl Item {
-  #[inline]
-  pub const fn descriptor(self) -> ItemDescriptor {
-    match self {
-      Self::TestItem => ItemDescriptor {
-        name: "Test Item",
-        usage: None,
-        stack_size: nz::u8!(32),
-      },
-    }
+  /// Get the block this item places, if any
+  pub fn as_block(self, registry: &BlockRegistry) -> Option<Block> {
+    registry.item(self)?.places
+  }
+
+  /// Maximum amount of the item in a single slot\
+  /// Unknown items don't stack
+  pub fn stack_size(self, registry: &BlockRegistry) -> NonZeroU8 {
+    registry.item(self).map(|descriptor| descriptor.stack_size).unwrap_or(nz::u8!(1))
   }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct ItemCollection(Option<(Item, NonZeroU8)>);
 
 impl ItemCollection {
@@ -94,10 +90,10 @@ impl ItemCollection {
   }
 
   /// Check if the slot is full (contains the maximum stack size)
-  pub const fn is_full(&self) -> bool {
+  pub fn is_full(&self, registry: &BlockRegistry) -> bool {
     match self.0 {
       Some((item, amount)) => {
-        amount.get() >= item.descriptor().stack_size.get()
+        amount >= item.stack_size(registry)
       },
       None => false,
     }
@@ -135,12 +131,12 @@ impl ItemCollection {
 
   /// Add items from another slot, copying them\
   /// Returns the leftover items (items that could not be added)
-  pub fn add(&mut self, from: &Self) -> Self {
+  pub fn add(&mut self, from: &Self, registry: &BlockRegistry) -> Self {
     // If there are no items to add, return
     let Some((add_item, add_count)) = from.0 else {
       return Self::new_empty()
     };
-    let item_stack_size = add_item.descriptor().stack_size;
+    let item_stack_size = add_item.stack_size(registry);
 
     // Add items to the slot
     let (this_slot, leftovers) = match self.0 {
@@ -188,15 +184,15 @@ impl ItemCollection {
   /// Move as much as possible items from another slot, removing them
   ///
   /// This may not be possible if the slot is full or contains a different item
-  pub fn move_all(&mut self, to: &mut Self) {
-    let leftovers = to.add(self);
+  pub fn move_all(&mut self, to: &mut Self, registry: &BlockRegistry) {
+    let leftovers = to.add(self, registry);
     *self = leftovers;
   }
 
   /// Move up to `amount` items from another slot, removing them
   ///
   /// If `amount` is 0, nothing will be moved
-  pub fn move_up_to(&mut self, to: &mut Self, limit: u8) {
+  pub fn move_up_to(&mut self, to: &mut Self, limit: u8, registry: &BlockRegistry) {
 
     if self.is_empty() { return }
     // SAFETY: slot is guaranteed to be non-empty
@@ -209,7 +205,7 @@ impl ItemCollection {
     let amount_with_limit = amount.min(limit);
     let self_with_limit = self.with_amount_nonzero(amount_with_limit);
 
-    let mut leftovers = to.add(&self_with_limit);
+    let mut leftovers = to.add(&self_with_limit, registry);
 
     // Compensate for the amount of items that were not moved
     let amount_difference = amount.get() - amount_with_limit.get();
@@ -225,7 +221,7 @@ impl ItemCollection {
   /// Try to move a single item from another slot, removing it
   ///
   /// This may not be possible if the slot is full or contains a different item
-  pub fn move_single(&mut self, to: &mut Self) {
-    self.move_up_to(to, 1);
+  pub fn move_single(&mut self, to: &mut Self, registry: &BlockRegistry) {
+    self.move_up_to(to, 1, registry);
   }
 }
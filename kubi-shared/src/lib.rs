@@ -3,9 +3,13 @@ pub mod item;
 pub mod networking;
 pub mod worldgen;
 pub mod chunk;
+pub mod height;
 pub mod transform;
 pub mod entity;
 pub mod player;
 pub mod queue;
 pub mod data;
 pub mod fixed_timestamp;
+pub mod tick;
+pub mod fluid;
+pub mod falling_block;
@@ -1,6 +1,15 @@
 use glam::{Vec3, IVec3, Quat};
 use serde::{Serialize, Deserialize};
-use crate::{chunk::{BlockData, CHUNK_SIZE}, queue::QueuedBlock, entity::Health};
+use crate::{
+  block::BlockRegistry,
+  chunk::BlockData,
+  height::WorldHeight,
+  queue::QueuedBlock,
+  entity::Health,
+  falling_block::FallingBlock,
+  player::{GameMode, Inventory},
+  worldgen::biome::BiomeMap,
+};
 use super::client::ClientId;
 
 pub const PROTOCOL_ID: u16 = 0;
@@ -17,6 +26,9 @@ pub enum ClientToServerMessageType {
   ChunkSubRequest = 2,
   ChunkUnsubscribe = 3,
   QueueBlock = 4,
+  //5 used to be TakeDamage (damage is now dealt by the server)
+  Respawn = 6,
+  StartMining = 7,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -41,6 +53,13 @@ pub enum ClientToServerMessage {
   QueueBlock {
     item: QueuedBlock
   } = ClientToServerMessageType::QueueBlock as u8,
+  /// Dead player wants to respawn
+  Respawn = ClientToServerMessageType::Respawn as u8,
+  /// Player started mining a block (survival mode)\
+  /// Used by the server to check how long it took to break it
+  StartMining {
+    position: IVec3,
+  } = ClientToServerMessageType::StartMining as u8,
 }
 
 impl ToMessageType<ClientToServerMessageType> for ClientToServerMessage {
@@ -51,6 +70,8 @@ impl ToMessageType<ClientToServerMessageType> for ClientToServerMessage {
       ClientToServerMessage::ChunkSubRequest { .. } => ClientToServerMessageType::ChunkSubRequest,
       ClientToServerMessage::ChunkUnsubscribe { .. } => ClientToServerMessageType::ChunkUnsubscribe,
       ClientToServerMessage::QueueBlock { .. } => ClientToServerMessageType::QueueBlock,
+      ClientToServerMessage::Respawn => ClientToServerMessageType::Respawn,
+      ClientToServerMessage::StartMining { .. } => ClientToServerMessageType::StartMining,
     }
   }
 }
@@ -65,9 +86,13 @@ pub enum ServerToClientMessageType {
   QueueBlock = 4,
   PlayerConnected = 5,
   PlayerDisconnected = 6,
+  InventoryChanged = 7,
+  PlayerHealthChanged = 8,
+  PlayerRespawn = 9,
+  FallingBlockSpawned = 10,
+  FallingBlockRemoved = 11,
 }
 
-#[serde_with::serde_as]
 #[derive(Serialize, Deserialize, Clone)]
 #[repr(u8)]
 #[non_exhaustive]
@@ -92,9 +117,9 @@ pub enum ServerToClientMessage {
   ///TO REDUCE NETWORK USAGE
   ChunkResponse {
     chunk: IVec3,
-    #[serde_as(as = "Box<[[[_; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]>")]
     data: BlockData,
     queued: Vec<QueuedBlock>,
+    biomes: BiomeMap,
   } = ServerToClientMessageType::ChunkResponse as u8,
 
   QueueBlock {
@@ -108,6 +133,33 @@ pub enum ServerToClientMessage {
   PlayerDisconnected {
     id: ClientId
   } = ServerToClientMessageType::PlayerDisconnected as u8,
+
+  /// Server-side copy of the player's inventory has changed\
+  /// (sent only to the owner of the inventory)
+  InventoryChanged {
+    inventory: Inventory,
+  } = ServerToClientMessageType::InventoryChanged as u8,
+
+  PlayerHealthChanged {
+    client_id: ClientId,
+    health: Health,
+  } = ServerToClientMessageType::PlayerHealthChanged as u8,
+
+  /// Player has been respawned at `position`\
+  /// (sent only to the respawned player)
+  PlayerRespawn {
+    position: Vec3,
+  } = ServerToClientMessageType::PlayerRespawn as u8,
+
+  /// Block started falling (the block itself is removed using a separate `QueueBlock` message)
+  FallingBlockSpawned {
+    block: FallingBlock,
+  } = ServerToClientMessageType::FallingBlockSpawned as u8,
+
+  /// Falling block landed or broke (the resulting block is placed using a separate `QueueBlock` message)
+  FallingBlockRemoved {
+    id: u64,
+  } = ServerToClientMessageType::FallingBlockRemoved as u8,
 }
 
 impl ToMessageType<ServerToClientMessageType> for ServerToClientMessage {
@@ -120,6 +172,11 @@ impl ToMessageType<ServerToClientMessageType> for ServerToClientMessage {
       ServerToClientMessage::QueueBlock { .. } => ServerToClientMessageType::QueueBlock,
       ServerToClientMessage::PlayerConnected { .. } => ServerToClientMessageType::PlayerConnected,
       ServerToClientMessage::PlayerDisconnected { .. } => ServerToClientMessageType::PlayerDisconnected,
+      ServerToClientMessage::InventoryChanged { .. } => ServerToClientMessageType::InventoryChanged,
+      ServerToClientMessage::PlayerHealthChanged { .. } => ServerToClientMessageType::PlayerHealthChanged,
+      ServerToClientMessage::PlayerRespawn { .. } => ServerToClientMessageType::PlayerRespawn,
+      ServerToClientMessage::FallingBlockSpawned { .. } => ServerToClientMessageType::FallingBlockSpawned,
+      ServerToClientMessage::FallingBlockRemoved { .. } => ServerToClientMessageType::FallingBlockRemoved,
     }
   }
 }
@@ -134,10 +191,18 @@ pub struct ClientInitData {
   pub velocity: Vec3,
   pub direction: Quat,
   pub health: Health,
+  pub gamemode: GameMode,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct InitData {
   pub user: ClientInitData,
-  pub users: Vec<ClientInitData>
+  pub users: Vec<ClientInitData>,
+  pub inventory: Inventory,
+  /// Block registry used by the server, block ids in chunk data refer to it
+  pub block_registry: BlockRegistry,
+  /// Build height limits of the world
+  pub world_height: WorldHeight,
+  /// Blocks that are currently falling
+  pub falling_blocks: Vec<FallingBlock>,
 }
@@ -1,7 +1,14 @@
+use glam::{vec3, Vec3};
 use shipyard::Component;
-use crate::block::Block;
+use serde::{Serialize, Deserialize, Deserializer};
+use crate::{block::{Block, BlockRegistry}, item::{Item, ItemCollection}};
 
 pub const PLAYER_HEALTH: u8 = 20;
+/// Default world spawn point, players appear here when joining or respawning
+pub const PLAYER_SPAWN_POINT: Vec3 = vec3(0., 60., 0.);
+pub const PLAYER_INVENTORY_SIZE: usize = 9;
+/// Height of the player's eyes (their position) above their feet
+pub const PLAYER_EYE_HEIGHT: f32 = 1.5;
 
 #[derive(Component)]
 pub struct Player;
@@ -9,3 +16,122 @@ pub struct Player;
 #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct PlayerHolding(pub Option<Block>);
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum GameMode {
+  /// Instant block breaking, infinite blocks, flying, no damage
+  Creative,
+  /// Blocks take time to mine, placing blocks consumes items, player takes damage
+  #[default]
+  Survival,
+  /// Free camera, can't interact with the world
+  Spectator,
+}
+
+impl GameMode {
+  /// Can the player place and break blocks?
+  pub const fn can_modify_world(self) -> bool {
+    !matches!(self, Self::Spectator)
+  }
+
+  /// Does breaking/placing blocks use up time and items?
+  pub const fn has_survival_rules(self) -> bool {
+    matches!(self, Self::Survival)
+  }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Inventory {
+  pub slots: [ItemCollection; PLAYER_INVENTORY_SIZE],
+  /// Index of the selected slot\
+  /// Always in range `0..PLAYER_INVENTORY_SIZE`, use [`Inventory::select`] to change it
+  #[serde(deserialize_with = "deserialize_slot_index")]
+  selected: usize,
+}
+
+/// Clamp slot indices received from the network to a valid slot
+fn deserialize_slot_index<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+  Ok(usize::deserialize(deserializer)?.min(PLAYER_INVENTORY_SIZE - 1))
+}
+
+impl Default for Inventory {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Inventory {
+  pub const fn new() -> Self {
+    Self {
+      slots: [ItemCollection::new_empty(); PLAYER_INVENTORY_SIZE],
+      selected: 0,
+    }
+  }
+
+  /// Get the index of the currently selected slot
+  pub const fn selected(&self) -> usize {
+    self.selected
+  }
+
+  /// Select a slot, out of range indices are clamped to the last slot
+  pub fn select(&mut self, index: usize) {
+    self.selected = index.min(PLAYER_INVENTORY_SIZE - 1);
+  }
+
+  /// Get the currently selected slot
+  pub fn selected_slot(&self) -> &ItemCollection {
+    &self.slots[self.selected]
+  }
+
+  /// Get the block placed by the item in the selected slot (if any)
+  pub fn selected_block(&self, registry: &BlockRegistry) -> Option<Block> {
+    self.selected_slot().item()?.as_block(registry)
+  }
+
+  /// Try to add items to the inventory\
+  /// Items are stacked onto existing slots first, then placed into empty ones
+  ///
+  /// Returns the leftover items (items that did not fit)
+  pub fn add(&mut self, items: ItemCollection, registry: &BlockRegistry) -> ItemCollection {
+    let mut leftovers = items;
+    let Some(item) = leftovers.item() else { return leftovers };
+    for slot in self.slots.iter_mut().filter(|slot| slot.item() == Some(item)) {
+      leftovers = slot.add(&leftovers, registry);
+      if leftovers.is_empty() { return leftovers }
+    }
+    for slot in self.slots.iter_mut().filter(|slot| slot.is_empty()) {
+      leftovers = slot.add(&leftovers, registry);
+      if leftovers.is_empty() { return leftovers }
+    }
+    leftovers
+  }
+
+  /// Add a single item to the inventory
+  ///
+  /// Returns `false` if the inventory is full
+  pub fn add_single(&mut self, item: Item, registry: &BlockRegistry) -> bool {
+    self.add(ItemCollection::new_single(item), registry).is_empty()
+  }
+
+  /// Remove a single item that places `block` from the inventory\
+  /// The selected slot is checked first
+  ///
+  /// Returns `false` if there are no such items
+  pub fn take_block(&mut self, block: Block, registry: &BlockRegistry) -> bool {
+    let places_block = |slot: &ItemCollection| {
+      slot.item().and_then(|item| item.as_block(registry)) == Some(block)
+    };
+    let index = match places_block(self.selected_slot()) {
+      true => self.selected,
+      false => match self.slots.iter().position(places_block) {
+        Some(index) => index,
+        None => return false,
+      },
+    };
+    let slot = &mut self.slots[index];
+    *slot = slot.with_amount(slot.amount() - 1);
+    true
+  }
+}
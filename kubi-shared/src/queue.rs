@@ -1,11 +1,27 @@
 use glam::IVec3;
 use serde::{Serialize, Deserialize};
-use crate::block::Block;
+use crate::block::{Block, BlockProperties, BlockState};
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct QueuedBlock {
   pub position: IVec3,
   pub block_type: Block,
+  pub properties: BlockProperties,
   /// Only replace air blocks
   pub soft: bool,
 }
+
+impl QueuedBlock {
+  pub fn new(position: IVec3, state: BlockState) -> Self {
+    Self {
+      position,
+      block_type: state.block,
+      properties: state.properties,
+      soft: false,
+    }
+  }
+
+  pub fn state(&self) -> BlockState {
+    BlockState::with_properties(self.block_type, self.properties)
+  }
+}
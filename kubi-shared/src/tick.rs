@@ -0,0 +1,213 @@
+//! Block ticks
+//!
+//! There are two kinds of block ticks:
+//! - scheduled ticks, which happen after a delay, in reaction to nearby block changes
+//!   (used by fluids, and blocks that may start falling, see [`crate::falling_block`])
+//! - random ticks, which happen at random positions in all loaded chunks
+//!   (used by slow natural changes, like grass spreading or leaves decaying)
+
+use std::collections::BTreeMap;
+use glam::IVec3;
+use hashbrown::HashMap;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+use serde::{Serialize, Deserialize};
+use shipyard::Unique;
+use crate::{
+  block::{BlockBehavior, BlockRegistry, BlockState},
+  chunk::CHUNK_SIZE,
+  fluid::{fluid_tick, FLUID_TICK_DELAY},
+  queue::QueuedBlock,
+};
+
+mod behavior;
+#[cfg(test)]
+mod tests;
+pub use behavior::{GRAVITY_TICK_DELAY, LEAF_DECAY_DISTANCE};
+
+/// Duration of a single block tick (in milliseconds)
+pub const BLOCK_TICK_RATE_MILLIS: u16 = 50;
+
+/// Number of random ticks per loaded chunk, every block tick\
+/// (same rate per block as 3 ticks per 16x16x16 section)
+pub const RANDOM_TICKS_PER_CHUNK: usize = 24;
+
+/// Read-only access to the blocks of a world\
+/// Used by the block tick logic, which is shared between the client and the server
+pub trait BlockAccess {
+  /// Get the block state at `position` (in world coordinates)\
+  /// Returns `None` if the chunk is not loaded
+  fn get_block_state(&self, position: IVec3) -> Option<BlockState>;
+}
+
+/// Delay of the scheduled tick a block needs after a nearby change\
+/// `None` if the block doesn't react to scheduled ticks
+pub fn scheduled_tick_delay(registry: &BlockRegistry, state: BlockState) -> Option<u32> {
+  let descriptor = registry.get(state.block);
+  if descriptor.fluid {
+    return Some(FLUID_TICK_DELAY)
+  }
+  match descriptor.behavior {
+    BlockBehavior::Gravity => Some(GRAVITY_TICK_DELAY),
+    _ => None,
+  }
+}
+
+/// Run a scheduled tick at `position`, returning the resulting block changes
+///
+/// Blocks with the `gravity` behavior are not handled here, as they turn into entities instead
+/// (see [`crate::falling_block::should_start_falling`])
+pub fn scheduled_tick(world: &impl BlockAccess, registry: &BlockRegistry, position: IVec3) -> Vec<QueuedBlock> {
+  let Some(state) = world.get_block_state(position) else { return Vec::new() };
+  match registry.get(state.block).fluid {
+    true => fluid_tick(world, registry, position),
+    false => Vec::new(),
+  }
+}
+
+/// Run a random tick at `position`, returning the resulting block changes
+pub fn random_tick(world: &impl BlockAccess, registry: &BlockRegistry, position: IVec3, rng: &mut impl Rng) -> Vec<QueuedBlock> {
+  let Some(state) = world.get_block_state(position) else { return Vec::new() };
+  match registry.get(state.block).behavior {
+    BlockBehavior::Grass => behavior::grass_tick(world, registry, position, state, rng),
+    BlockBehavior::Leaves => behavior::leaves_tick(world, registry, position, state),
+    _ => Vec::new(),
+  }
+}
+
+/// Scheduled tick that has not happened yet, as stored in save files
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingTick {
+  pub position: IVec3,
+  /// Remaining delay (in block ticks)
+  pub delay: u32,
+}
+
+/// Block updates scheduled to happen after a given number of block ticks
+#[derive(Unique, Default)]
+pub struct ScheduledTicks {
+  current_tick: u64,
+  queue: BTreeMap<u64, Vec<IVec3>>,
+  scheduled: HashMap<IVec3, u64>,
+}
+
+impl ScheduledTicks {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Schedule a tick at `position`, `delay` ticks from now\
+  /// If there's already an earlier tick scheduled at that position, it's kept instead
+  pub fn schedule(&mut self, position: IVec3, delay: u32) {
+    let tick = self.current_tick + delay.max(1) as u64;
+    if self.scheduled.get(&position).is_some_and(|&existing| existing <= tick) {
+      return
+    }
+    //The old entry (if any) stays in the queue, but gets ignored as it no longer matches `scheduled`
+    self.scheduled.insert(position, tick);
+    self.queue.entry(tick).or_default().push(position);
+  }
+
+  /// Let the block at `position` and its direct neighbors react to a change at `position`\
+  /// Should be called after the change has been applied to the world
+  pub fn schedule_updates(&mut self, world: &impl BlockAccess, registry: &BlockRegistry, position: IVec3) {
+    const OFFSETS: [IVec3; 7] = [
+      IVec3::ZERO,
+      IVec3::X, IVec3::NEG_X,
+      IVec3::Y, IVec3::NEG_Y,
+      IVec3::Z, IVec3::NEG_Z,
+    ];
+    for offset in OFFSETS {
+      let position = position + offset;
+      let Some(state) = world.get_block_state(position) else { continue };
+      if let Some(delay) = scheduled_tick_delay(registry, state) {
+        self.schedule(position, delay);
+      }
+    }
+  }
+
+  /// Advance to the next tick, returning all positions scheduled for it
+  pub fn advance(&mut self) -> Vec<IVec3> {
+    self.current_tick += 1;
+    let tick = self.current_tick;
+    let Some(positions) = self.queue.remove(&tick) else {
+      return Vec::new()
+    };
+    positions.into_iter().filter(|position| {
+      if self.scheduled.get(position) == Some(&tick) {
+        self.scheduled.remove(position);
+        return true
+      }
+      false
+    }).collect()
+  }
+
+  /// Ticks scheduled within the chunk at `chunk_position`, with delays relative to the current tick
+  pub fn pending_in_chunk(&self, chunk_position: IVec3) -> Vec<PendingTick> {
+    self.scheduled.iter()
+      .filter(|(position, _)| position.div_euclid(IVec3::splat(CHUNK_SIZE as i32)) == chunk_position)
+      .map(|(&position, &tick)| PendingTick {
+        position,
+        delay: (tick - self.current_tick) as u32,
+      })
+      .collect()
+  }
+
+  /// Forget all ticks scheduled within the chunk at `chunk_position` (e.g. when it gets unloaded)
+  pub fn remove_chunk(&mut self, chunk_position: IVec3) {
+    self.scheduled.retain(|position, _| position.div_euclid(IVec3::splat(CHUNK_SIZE as i32)) != chunk_position);
+  }
+
+  /// Schedule ticks loaded from a save file
+  pub fn restore(&mut self, ticks: &[PendingTick]) {
+    for tick in ticks {
+      self.schedule(tick.position, tick.delay);
+    }
+  }
+
+  /// Number of scheduled ticks
+  pub fn len(&self) -> usize {
+    self.scheduled.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.scheduled.is_empty()
+  }
+}
+
+/// Source of random block ticks
+#[derive(Unique)]
+pub struct RandomTicks {
+  rng: Xoshiro256StarStar,
+}
+
+impl Default for RandomTicks {
+  fn default() -> Self {
+    Self {
+      rng: Xoshiro256StarStar::from_entropy(),
+    }
+  }
+}
+
+impl RandomTicks {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Run [`RANDOM_TICKS_PER_CHUNK`] random ticks in each of the chunks, returning the resulting block changes
+  pub fn run(&mut self, world: &impl BlockAccess, registry: &BlockRegistry, chunks: impl IntoIterator<Item = IVec3>) -> Vec<QueuedBlock> {
+    let mut changes = Vec::new();
+    for chunk_position in chunks {
+      for _ in 0..RANDOM_TICKS_PER_CHUNK {
+        let offset = IVec3::new(
+          self.rng.gen_range(0..CHUNK_SIZE as i32),
+          self.rng.gen_range(0..CHUNK_SIZE as i32),
+          self.rng.gen_range(0..CHUNK_SIZE as i32),
+        );
+        let position = chunk_position * CHUNK_SIZE as i32 + offset;
+        changes.extend(random_tick(world, registry, position, &mut self.rng));
+      }
+    }
+    changes
+  }
+}
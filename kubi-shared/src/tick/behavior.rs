@@ -0,0 +1,59 @@
+use glam::IVec3;
+use rand::Rng;
+use crate::{
+  block::{Block, BlockBehavior, BlockRegistry, BlockState, RenderType, Transparency},
+  queue::QueuedBlock,
+};
+use super::BlockAccess;
+
+/// Delay before blocks start falling (in block ticks)
+pub const GRAVITY_TICK_DELAY: u32 = 2;
+
+/// Leaves decay if there are no logs within this distance
+pub const LEAF_DECAY_DISTANCE: i32 = 4;
+
+/// Check if the block blocks the sky for the block below it (for grass)
+fn is_covering(registry: &BlockRegistry, state: BlockState) -> bool {
+  let descriptor = registry.get(state.block);
+  descriptor.fluid || matches!(descriptor.render, RenderType::Cube(Transparency::Solid, _))
+}
+
+/// Turn into dirt if covered, otherwise try to spread onto a random nearby dirt block
+pub fn grass_tick(world: &impl BlockAccess, registry: &BlockRegistry, position: IVec3, state: BlockState, rng: &mut impl Rng) -> Vec<QueuedBlock> {
+  if world.get_block_state(position + IVec3::Y).is_some_and(|above| is_covering(registry, above)) {
+    return vec![QueuedBlock::new(position, BlockState::new(Block::Dirt))]
+  }
+  let target = position + IVec3::new(
+    rng.gen_range(-1..=1),
+    rng.gen_range(-1..=1),
+    rng.gen_range(-1..=1),
+  );
+  let can_spread =
+    world.get_block_state(target).is_some_and(|target| target.block == Block::Dirt) &&
+    world.get_block_state(target + IVec3::Y).is_some_and(|above| !is_covering(registry, above));
+  match can_spread {
+    true => vec![QueuedBlock::new(target, BlockState::new(state.block))],
+    false => Vec::new(),
+  }
+}
+
+/// Decay if there are no logs within [`LEAF_DECAY_DISTANCE`]
+pub fn leaves_tick(world: &impl BlockAccess, registry: &BlockRegistry, position: IVec3, state: BlockState) -> Vec<QueuedBlock> {
+  if state.properties.persistent() {
+    return Vec::new()
+  }
+  let range = -LEAF_DECAY_DISTANCE..=LEAF_DECAY_DISTANCE;
+  for x in range.clone() {
+    for y in range.clone() {
+      for z in range.clone() {
+        match world.get_block_state(position + IVec3::new(x, y, z)) {
+          //The log might be in a chunk that's not loaded, don't risk it
+          None => return Vec::new(),
+          Some(neighbor) if registry.get(neighbor.block).behavior == BlockBehavior::Log => return Vec::new(),
+          _ => (),
+        }
+      }
+    }
+  }
+  vec![QueuedBlock::new(position, BlockState::AIR)]
+}
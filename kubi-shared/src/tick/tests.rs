@@ -0,0 +1,245 @@
+use glam::{ivec3, IVec3};
+use hashbrown::{HashMap, HashSet};
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256StarStar;
+use crate::{
+  block::{Block, BlockProperties, BlockRegistry, BlockState},
+  falling_block::{FallingBlock, FallingBlockUpdate},
+  fluid::{FLUID_TICK_DELAY, MAX_FLOW_DISTANCE},
+  queue::QueuedBlock,
+};
+use super::{random_tick, scheduled_tick, BlockAccess, ScheduledTicks, LEAF_DECAY_DISTANCE};
+
+/// Small in-memory world, positions that were never set are treated as unloaded
+#[derive(Default)]
+pub struct TestWorld {
+  pub blocks: HashMap<IVec3, BlockState>,
+}
+
+impl TestWorld {
+  pub fn set(&mut self, position: IVec3, block: Block) {
+    self.blocks.insert(position, BlockState::new(block));
+  }
+
+  pub fn block(&self, position: IVec3) -> Block {
+    self.blocks[&position].block
+  }
+
+  /// Fill the box between `min` and `max` (inclusive)
+  pub fn fill(&mut self, min: IVec3, max: IVec3, block: Block) {
+    for x in min.x..=max.x {
+      for y in min.y..=max.y {
+        for z in min.z..=max.z {
+          self.set(ivec3(x, y, z), block);
+        }
+      }
+    }
+  }
+
+  fn apply(&mut self, changes: &[QueuedBlock]) {
+    for change in changes {
+      self.blocks.insert(change.position, change.state());
+    }
+  }
+}
+
+impl BlockAccess for TestWorld {
+  fn get_block_state(&self, position: IVec3) -> Option<BlockState> {
+    self.blocks.get(&position).copied()
+  }
+}
+
+fn states_at(changes: &[QueuedBlock]) -> Vec<(IVec3, BlockState)> {
+  changes.iter().map(|change| (change.position, change.state())).collect()
+}
+
+/// Apply a block change and let the nearby blocks react to it, like the server does
+fn change(world: &mut TestWorld, ticks: &mut ScheduledTicks, registry: &BlockRegistry, position: IVec3, block: Block) {
+  world.set(position, block);
+  ticks.schedule_updates(world, registry, position);
+}
+
+/// Run `count` block ticks, applying the changes of each tick before the next one
+fn run_ticks(world: &mut TestWorld, ticks: &mut ScheduledTicks, registry: &BlockRegistry, count: usize) {
+  for _ in 0..count {
+    let changes: Vec<QueuedBlock> = ticks.advance().into_iter()
+      .flat_map(|position| scheduled_tick(world, registry, position))
+      .collect();
+    world.apply(&changes);
+    for change in changes {
+      ticks.schedule_updates(world, registry, change.position);
+    }
+  }
+}
+
+/// Stone floor at y = -1 with air above it, spanning `-radius..=radius` on x and z
+fn pool_world(radius: i32) -> TestWorld {
+  let mut world = TestWorld::default();
+  world.fill(ivec3(-radius, -1, -radius), ivec3(radius, -1, radius), Block::Stone);
+  world.fill(ivec3(-radius, 0, -radius), ivec3(radius, 1, radius), Block::Air);
+  world
+}
+
+/// Fluid level at `position`, `None` if it's not water
+fn water_level(world: &TestWorld, position: IVec3) -> Option<u8> {
+  let state = world.get_block_state(position)?;
+  (state.block == Block::Water).then_some(state.properties.level())
+}
+
+/// Enough ticks for the water to spread as far as it can
+const SETTLE_TICKS: usize = (MAX_FLOW_DISTANCE as usize + 4) * FLUID_TICK_DELAY as usize;
+
+#[test]
+fn fluid_level_decreases_with_distance() {
+  let registry = BlockRegistry::builtin();
+  let mut world = pool_world(10);
+  let mut ticks = ScheduledTicks::new();
+  change(&mut world, &mut ticks, &registry, IVec3::ZERO, Block::Water);
+  run_ticks(&mut world, &mut ticks, &registry, SETTLE_TICKS);
+
+  for x in -10i32..=10 {
+    for z in -10i32..=10 {
+      let distance = (x.abs() + z.abs()) as u8;
+      let expected = (distance <= MAX_FLOW_DISTANCE).then_some(distance);
+      assert_eq!(water_level(&world, ivec3(x, 0, z)), expected, "at {x}, {z}");
+      assert_eq!(world.block(ivec3(x, 1, z)), Block::Air);
+    }
+  }
+  //nothing changes once the water has settled
+  run_ticks(&mut world, &mut ticks, &registry, SETTLE_TICKS);
+  assert!(ticks.is_empty());
+}
+
+#[test]
+fn fluid_source_refills_neighbors() {
+  let registry = BlockRegistry::builtin();
+  let mut world = pool_world(10);
+  let mut ticks = ScheduledTicks::new();
+  change(&mut world, &mut ticks, &registry, IVec3::ZERO, Block::Water);
+  run_ticks(&mut world, &mut ticks, &registry, SETTLE_TICKS);
+  let settled = world.blocks.clone();
+
+  //removed flowing water is refilled by the source, and spreads again from there
+  change(&mut world, &mut ticks, &registry, IVec3::X, Block::Stone);
+  run_ticks(&mut world, &mut ticks, &registry, SETTLE_TICKS);
+  assert_eq!(water_level(&world, ivec3(2, 0, 0)), Some(4), "flows around the obstacle");
+  change(&mut world, &mut ticks, &registry, IVec3::X, Block::Air);
+  run_ticks(&mut world, &mut ticks, &registry, SETTLE_TICKS);
+  assert_eq!(water_level(&world, IVec3::X), Some(1));
+  assert!(world.blocks == settled);
+
+  //without the source, all of the flowing water dries out
+  change(&mut world, &mut ticks, &registry, IVec3::ZERO, Block::Stone);
+  run_ticks(&mut world, &mut ticks, &registry, SETTLE_TICKS * 2);
+  assert!(world.blocks.values().all(|state| state.block != Block::Water));
+}
+
+#[test]
+fn schedule_keeps_the_earliest_tick() {
+  let position = ivec3(1, 2, 3);
+  let mut ticks = ScheduledTicks::new();
+  ticks.schedule(position, 10);
+  ticks.schedule(position, 3);
+  //a later tick doesn't replace the earlier one
+  ticks.schedule(position, 5);
+  assert_eq!(ticks.len(), 1);
+
+  let fired: Vec<usize> = (1..=12).filter(|_| !ticks.advance().is_empty()).collect();
+  assert_eq!(fired, [3]);
+  assert!(ticks.is_empty());
+
+  //zero delay still waits for the next tick
+  ticks.schedule(position, 0);
+  assert_eq!(ticks.advance(), [position]);
+}
+
+#[test]
+fn covered_grass_turns_into_dirt() {
+  let registry = BlockRegistry::builtin();
+  let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+  let mut world = TestWorld::default();
+  world.set(IVec3::ZERO, Block::Grass);
+  world.set(IVec3::Y, Block::Stone);
+  let changes = random_tick(&world, &registry, IVec3::ZERO, &mut rng);
+  assert_eq!(states_at(&changes), [(IVec3::ZERO, BlockState::new(Block::Dirt))]);
+
+  //non-solid blocks (like tall grass) don't cover it
+  world.set(IVec3::Y, Block::TallGrass);
+  let changes = random_tick(&world, &registry, IVec3::ZERO, &mut rng);
+  assert!(changes.iter().all(|change| change.block_type == Block::Grass));
+}
+
+#[test]
+fn grass_spreads_onto_uncovered_dirt() {
+  let registry = BlockRegistry::builtin();
+  let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+  //3x3 dirt layer around the grass block, on top of more dirt, one dirt block covered by stone
+  let mut world = TestWorld::default();
+  world.fill(ivec3(-2, -2, -2), ivec3(2, 0, 2), Block::Dirt);
+  world.fill(ivec3(-2, 1, -2), ivec3(2, 2, 2), Block::Air);
+  world.set(IVec3::ZERO, Block::Grass);
+  world.set(ivec3(1, 1, 0), Block::Stone);
+
+  let mut spread_to = HashSet::new();
+  for _ in 0..200 {
+    for change in random_tick(&world, &registry, IVec3::ZERO, &mut rng) {
+      assert_eq!(change.block_type, Block::Grass);
+      spread_to.insert(change.position);
+    }
+  }
+  let expected: HashSet<IVec3> = (-1..=1)
+    .flat_map(|x| (-1..=1).map(move |z| ivec3(x, 0, z)))
+    .filter(|&position| position != IVec3::ZERO && position != IVec3::X)
+    .collect();
+  assert_eq!(spread_to, expected);
+}
+
+/// Air around a leaf block at the origin, covering the whole decay range
+fn leaf_world(leaf: BlockState) -> TestWorld {
+  let mut world = TestWorld::default();
+  world.fill(IVec3::splat(-LEAF_DECAY_DISTANCE), IVec3::splat(LEAF_DECAY_DISTANCE), Block::Air);
+  world.blocks.insert(IVec3::ZERO, leaf);
+  world
+}
+
+#[test]
+fn leaves_decay_without_logs() {
+  let registry = BlockRegistry::builtin();
+  let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+  let leaf = BlockState::new(Block::Leaf);
+
+  let world = leaf_world(leaf);
+  assert_eq!(states_at(&random_tick(&world, &registry, IVec3::ZERO, &mut rng)), [(IVec3::ZERO, BlockState::AIR)]);
+
+  //a log anywhere in range keeps the leaves
+  let mut world = leaf_world(leaf);
+  world.set(IVec3::splat(LEAF_DECAY_DISTANCE), Block::Wood);
+  assert!(random_tick(&world, &registry, IVec3::ZERO, &mut rng).is_empty());
+
+  //player placed leaves never decay
+  let world = leaf_world(BlockState::with_properties(Block::Leaf, BlockProperties::NONE.with_persistent(true)));
+  assert!(random_tick(&world, &registry, IVec3::ZERO, &mut rng).is_empty());
+
+  //neither do leaves near unloaded chunks, which might contain a log
+  let mut world = leaf_world(leaf);
+  world.blocks.remove(&ivec3(0, -LEAF_DECAY_DISTANCE, 0));
+  assert!(random_tick(&world, &registry, IVec3::ZERO, &mut rng).is_empty());
+}
+
+#[test]
+fn falling_block_lands_above_unreplaceable_blocks() {
+  let registry = BlockRegistry::builtin();
+  let mut world = TestWorld::default();
+  world.set(IVec3::ZERO, Block::Stone);
+  world.fill(IVec3::Y, ivec3(0, 10, 0), Block::Air);
+  //markers aren't solid, but can't be broken either
+  world.set(ivec3(0, 1, 0), Block::Marker);
+  world.set(ivec3(0, 2, 0), Block::Marker);
+
+  let mut falling_block = FallingBlock::new(1, ivec3(0, 8, 0), BlockState::new(Block::Sand));
+  let landed = (0..100).find_map(|_| match falling_block.update(&world, &registry, 0.05) {
+    FallingBlockUpdate::Falling => None,
+    FallingBlockUpdate::Landed(item) => Some(item),
+  });
+  assert_eq!(landed.map(|item| (item.position, item.state())), Some((ivec3(0, 3, 0), BlockState::new(Block::Sand))));
+}
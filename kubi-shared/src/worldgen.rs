@@ -1,17 +1,30 @@
-use std::sync::Arc;
+use std::{hash::Hasher, rc::Rc, sync::Arc};
 use atomic::Atomic;
 use bytemuck::{CheckedBitPattern, NoUninit};
-use glam::IVec3;
+use glam::{ivec3, IVec3};
 use static_assertions::const_assert;
 use crate::{
-  block::Block,
+  block::{Block, BlockRegistry},
   chunk::{BlockData, CHUNK_SIZE},
+  height::WorldHeight,
   queue::QueuedBlock,
 };
 
+pub mod biome;
+pub mod hash;
+pub mod preset;
 pub mod steps;
 pub mod structures;
 
+use biome::{Biome, BiomeMap, BiomeWeights};
+use preset::{StepConfig, WorldGenPreset};
+use steps::_00_biomes::BiomeSampler;
+use structures::StructureTemplates;
+use hash::{legacy_hash, stable_hash, HashVersion, SplitMix64, StableHasher};
+
+#[cfg(test)]
+mod tests;
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, Default, NoUninit, CheckedBitPattern)]
 pub enum AbortState {
@@ -22,81 +35,98 @@ pub enum AbortState {
 }
 const_assert!(Atomic::<AbortState>::is_lock_free());
 
+/// Derives the seeds of the world generation steps from the world seed
 pub struct SeedThingy {
-  pseed: u64,
-  iseed: i32,
+  seed: u64,
+  version: HashVersion,
+  rng: SplitMix64,
+  /// Amount of seeds taken, only used by [`HashVersion::Legacy`]
   iter: u8,
 }
 
 impl SeedThingy {
   pub fn new(seed: u64) -> Self {
+    Self::with_version(seed, HashVersion::Stable)
+  }
+
+  pub fn with_version(seed: u64, version: HashVersion) -> Self {
     Self {
-      pseed: seed,
-      iseed: (seed & 0x7fffffffu64) as i32,
+      seed,
+      version,
+      rng: SplitMix64::new(seed),
       iter: 0,
     }
   }
 
+  /// Next seed, always positive (as noise seeds are `i32`)
   pub fn next_seed(&mut self) -> i32 {
-    self.iter += 1;
-    self.iseed = (
-      self.pseed
-        .rotate_left((3 * self.iter) as _)
-      & 0x7fffffff
-    ) as i32;
-    self.iseed
+    match self.version {
+      HashVersion::Stable => (self.rng.next_u64() & 0x7fffffff) as i32,
+      HashVersion::Legacy => {
+        self.iter = self.iter.wrapping_add(1);
+        (self.seed.rotate_left(self.iter.wrapping_mul(3) as u32) & 0x7fffffff) as i32
+      }
+    }
+  }
+
+  /// Seed derived only from the world seed and the `name`, always positive\
+  /// Unlike [`Self::next_seed`], it doesn't depend on (or change) the seeds of the other steps
+  pub fn named_seed(&self, name: &str) -> i32 {
+    let mut hasher = StableHasher::new(self.seed);
+    hasher.write(name.as_bytes());
+    (hasher.finish() & 0x7fffffff) as i32
   }
 }
 trait WorldGenStep {
-  fn initialize(generator: &WorldGenerator, seeder: &mut SeedThingy) -> Self;
+  /// Parameters of the step, as specified in the preset
+  type Config;
+  fn initialize(generator: &WorldGenerator, seeder: &mut SeedThingy, config: &Self::Config) -> Self;
   fn generate(&mut self, generator: &mut WorldGenerator);
 }
 
-macro_rules! run_steps {
-  ($gen: expr, $abort: expr, [$($step:ty),* $(,)?]) => {
-    (||{
-      let _abort: ::std::sync::Arc<::atomic::Atomic<$crate::worldgen::AbortState>> =
-        $abort.unwrap_or_else(|| ::std::sync::Arc::new(::atomic::Atomic::new($crate::worldgen::AbortState::Continue)));
-
-      let _chkabt = || _abort.compare_exchange(
-          $crate::worldgen::AbortState::Abort,
-          $crate::worldgen::AbortState::Aborted,
-          ::atomic::Ordering::Relaxed,
-          ::atomic::Ordering::Relaxed
-        ).is_ok();
-
-      if _chkabt() { return false }
-
-      let mut _seeder = $crate::worldgen::SeedThingy::new($gen.seed);
-      $({
-        let _ensure_ref: &mut $crate::worldgen::WorldGenerator = $gen;
-        struct _Ensure0<T: $crate::worldgen::WorldGenStep>(T);
-        type _Ensure1 = _Ensure0<$step>;
-        let mut step: _Ensure1 = _Ensure0(<$step>::initialize(&*_ensure_ref, &mut _seeder));
-        if _chkabt() { return false }
-        step.0.generate(_ensure_ref);
-        if _chkabt() { return false }
-      })*
-
-      true
-    })()
-  };
-}
-
 #[derive(Default)]
 pub struct WorldGeneratorData {
   pub master_height_map: Option<Vec<Vec<i32>>>,
+  /// Biome of each column
+  pub biome_map: Option<BiomeMap>,
+  /// Influence of each biome on each column, used to blend biome parameters
+  pub biome_weights: Option<Vec<Vec<BiomeWeights>>>,
+  /// Biomes of columns outside of the chunk
+  pub biome_sampler: Option<Rc<BiomeSampler>>,
+  /// Terrain height of any column (global x, z)\
+  /// Structures use it to find the surface outside of the chunk
+  pub height_sampler: Option<Rc<dyn Fn(i32, i32) -> i32>>,
 }
 
-pub struct WorldGenerator {
+impl WorldGeneratorData {
+  /// Biome of the column\
+  /// (if the preset doesn't have a biome step, the whole world is the default biome)
+  pub fn biome(&self, x: usize, z: usize) -> Biome {
+    self.biome_map.as_ref().map(|map| map.get(x, z)).unwrap_or_default()
+  }
+
+  /// Biome weights of the column, see [`Self::biome`]
+  pub fn biome_weights(&self, x: usize, z: usize) -> BiomeWeights {
+    self.biome_weights.as_ref().map(|weights| weights[x][z]).unwrap_or_default()
+  }
+}
+
+pub struct WorldGenerator<'a> {
   seed: u64,
+  /// Used to look up blocks that aren't built-in by name
+  registry: &'a BlockRegistry,
+  /// Templates of the buildings placed by structures
+  templates: &'a StructureTemplates,
+  /// Set from the preset when the generation starts
+  hash_version: HashVersion,
+  height: WorldHeight,
   chunk_position: IVec3,
   blocks: BlockData,
   queue: Vec<QueuedBlock>,
   pub data: WorldGeneratorData,
 }
 
-impl WorldGenerator {
+impl<'a> WorldGenerator<'a> {
   fn offset(&self) -> IVec3 {
     self.chunk_position * CHUNK_SIZE as i32
   }
@@ -107,9 +137,9 @@ impl WorldGenerator {
     // if let Some(block) = self.queue.iter().find(|block| block.position == event_pos) {
     //   block.block_type
     // } else {
-    //   self.blocks[position.x as usize][position.y as usize][position.z as usize]
+    //   self.blocks.get(position)
     // }
-    self.blocks[position.x as usize][position.y as usize][position.z as usize]
+    self.blocks.get(position)
   }
 
   fn place(&mut self, position: IVec3, block: Block) {
@@ -118,29 +148,48 @@ impl WorldGenerator {
     // self.queue.retain(|block: &QueuedBlock| {
     //   block.position != event_pos
     // });
-    self.blocks[position.x as usize][position.y as usize][position.z as usize] = block;
+    self.blocks.set(position, block);
   }
 
-  fn place_if_empty(&mut self, position: IVec3, block: Block) {
-    if self.query(position) == Block::Air {
-      self.place(position, block);
+  /// Place a block at a global position, if it's inside of the chunk\
+  /// Structures are placed this way, piece by piece, in each chunk they intersect
+  fn place_global(&mut self, position: IVec3, block: Block) {
+    let local = position - self.offset();
+    if local.cmpge(IVec3::ZERO).all() && local.cmplt(IVec3::splat(CHUNK_SIZE as i32)).all() {
+      self.blocks.set(local, block);
     }
   }
 
-  fn place_or_queue(&mut self, position: IVec3, block: Block) {
-    let offset = self.offset();
-    if position.to_array().iter().any(|&x| !(0..CHUNK_SIZE).contains(&(x as usize))) {
-      let event_pos = offset + position;
-      self.queue.retain(|block: &QueuedBlock| {
-        block.position != event_pos
-      });
-      self.queue.push(QueuedBlock {
-        position: event_pos,
-        block_type: block,
-        soft: true
-      });
-    } else {
-      self.blocks[position.x as usize][position.y as usize][position.z as usize] = block;
+  /// Block at a global position, `None` if it's outside of the chunk
+  fn query_global(&self, position: IVec3) -> Option<Block> {
+    let local = position - self.offset();
+    (local.cmpge(IVec3::ZERO).all() && local.cmplt(IVec3::splat(CHUNK_SIZE as i32)).all())
+      .then(|| self.blocks.get(local))
+  }
+
+  /// Terrain height of any column, `None` if the preset has no terrain
+  fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+    self.data.height_sampler.as_ref().map(|sampler| sampler(x, z))
+  }
+
+  /// Biome of any column
+  fn biome_at(&self, x: i32, z: i32) -> Biome {
+    self.biome_weights_at(x, z).dominant()
+  }
+
+  /// Biome weights of any column, see [`Self::biome_at`]
+  fn biome_weights_at(&self, x: i32, z: i32) -> BiomeWeights {
+    self.data.biome_sampler.as_ref().map(|sampler| sampler.weights(x, z)).unwrap_or_default()
+  }
+
+  /// Look up a block by its name in the block registry of the world
+  fn block_by_name(&self, name: &str) -> Option<Block> {
+    self.registry.by_name(name)
+  }
+
+  fn place_if_empty(&mut self, position: IVec3, block: Block) {
+    if self.query(position) == Block::Air {
+      self.place(position, block);
     }
   }
 
@@ -159,42 +208,111 @@ impl WorldGenerator {
     (0..CHUNK_SIZE as i32).contains(&position).then_some(position)
   }
 
-  /// crude hash of self.seed and x
+  /// Stable hash of self.seed and x (see [`hash::StableHasher`] for what can be hashed)\
+  /// (or the legacy hash, for worlds created before it)
   fn seeded_hash(&self, x: impl std::hash::Hash) -> u64 {
-    //use std::hash to hash the seed and x
-    use std::hash::{Hash, Hasher};
-    let mut hasher = std::collections::hash_map::DefaultHasher::new();
-    x.hash(&mut hasher);
-    self.seed.hash(&mut hasher);
-    hasher.finish()
+    match self.hash_version {
+      HashVersion::Stable => stable_hash(self.seed, x),
+      HashVersion::Legacy => legacy_hash(self.seed, x),
+    }
   }
 
-  pub fn new(chunk_position: IVec3, seed: u64) -> Self {
+  pub fn new(chunk_position: IVec3, seed: u64, height: WorldHeight, registry: &'a BlockRegistry, templates: &'a StructureTemplates) -> Self {
     Self {
       seed,
+      registry,
+      templates,
+      hash_version: HashVersion::default(),
+      height,
       chunk_position,
-      blocks: Box::new([[[Block::Air; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]),
+      blocks: BlockData::new(),
       queue: Vec::with_capacity(0),
       data: Default::default(),
     }
   }
 
-  /// Generate the chunk.
+  /// Run the steps in order, returns `false` if the generation was aborted
+  fn run_steps(&mut self, steps: &[StepConfig], abort: Option<&Atomic<AbortState>>) -> bool {
+    let check_abort = || abort.is_some_and(|abort| abort.compare_exchange(
+      AbortState::Abort,
+      AbortState::Aborted,
+      atomic::Ordering::Relaxed,
+      atomic::Ordering::Relaxed
+    ).is_ok());
+
+    if check_abort() { return false }
+
+    let mut seeder = SeedThingy::with_version(self.seed, self.hash_version);
+    for step in steps {
+      step.run(self, &mut seeder);
+      if check_abort() { return false }
+    }
+
+    true
+  }
+
+  /// Remove blocks outside of the world height (including queued ones) and place the bedrock floor
+  fn apply_height_limits(&mut self) {
+    let height = self.height;
+    //custom block definitions may not have bedrock
+    let bedrock = self.block_by_name("bedrock").unwrap_or(Block::Stone);
+    for y in 0..CHUNK_SIZE as i32 {
+      let block = match self.offset().y + y {
+        global_y if !height.contains(global_y) => Block::Air,
+        global_y if global_y < height.bedrock_top() => bedrock,
+        _ => continue,
+      };
+      for x in 0..CHUNK_SIZE as i32 {
+        for z in 0..CHUNK_SIZE as i32 {
+          self.place(ivec3(x, y, z), block);
+        }
+      }
+    }
+    self.queue.retain(|block| height.contains(block.position.y));
+  }
+
+  /// Generate the chunk using the steps of the `preset`.
   ///
   /// Will return `None` only if the generation was aborted.
-  pub fn generate(mut self, abort: Option<Arc<Atomic<AbortState>>>) -> Option<(BlockData, Vec<QueuedBlock>)> {
-    run_steps!(&mut self, abort, [
-      steps::_01_terrain::TerrainStep,
-      steps::_02_water::WaterStep,
-      steps::_03_caves::CaveStep,
-      steps::_04_layers::LayersStep,
-      steps::_05_decorate::DecorateStep,
-      steps::_06_trees::TreesStep,
-    ]).then_some((self.blocks, self.queue))
+  pub fn generate(mut self, preset: &WorldGenPreset, abort: Option<Arc<Atomic<AbortState>>>) -> Option<(BlockData, Vec<QueuedBlock>, BiomeMap)> {
+    //chunks outside of the world height are always empty, no need to run any steps
+    if !self.height.contains_chunk(self.chunk_position.y) {
+      return Some((self.blocks, self.queue, BiomeMap::default()))
+    }
+    self.hash_version = preset.hash_version;
+    self.run_steps(&preset.steps, abort.as_deref()).then(|| {
+      self.apply_height_limits();
+      self.blocks.compact();
+      (self.blocks, self.queue, self.data.biome_map.unwrap_or_default())
+    })
+  }
+
+  /// Compute the biome map of the chunk, without generating any terrain
+  pub fn generate_biome_map(mut self, preset: &WorldGenPreset) -> BiomeMap {
+    self.hash_version = preset.hash_version;
+    //biome seeds don't depend on the other steps, so the biome step can run on its own
+    if let Some(step) = preset.steps.iter().find(|step| matches!(step, StepConfig::Biomes(_))) {
+      self.run_steps(std::slice::from_ref(step), None);
+    }
+    self.data.biome_map.unwrap_or_default()
   }
 }
 
-pub fn generate_world(chunk_position: IVec3, seed: u64, abort: Option<Arc<Atomic<AbortState>>>) -> Option<(BlockData, Vec<QueuedBlock>)> {
+pub fn generate_world(
+  chunk_position: IVec3,
+  seed: u64,
+  preset: &WorldGenPreset,
+  templates: &StructureTemplates,
+  height: WorldHeight,
+  registry: &BlockRegistry,
+  abort: Option<Arc<Atomic<AbortState>>>,
+) -> Option<(BlockData, Vec<QueuedBlock>, BiomeMap)> {
   //TODO: pass through None for abort
-  WorldGenerator::new(chunk_position, seed).generate(abort)
+  WorldGenerator::new(chunk_position, seed, height, registry, templates).generate(preset, abort)
+}
+
+/// Biome map of a chunk, for chunks that weren't generated in this session (e.g. loaded from the save file)
+pub fn generate_biome_map(chunk_position: IVec3, seed: u64, preset: &WorldGenPreset, registry: &BlockRegistry) -> BiomeMap {
+  //biomes don't depend on the world height or structures
+  WorldGenerator::new(chunk_position, seed, WorldHeight::default(), registry, &StructureTemplates::default()).generate_biome_map(preset)
 }
@@ -0,0 +1,220 @@
+//! Biomes, chosen per column from temperature and humidity noise
+//!
+//! Each biome has a point in the (temperature, humidity) climate space,
+//! columns belong to the biome closest to their climate.\
+//! Numeric parameters (terrain height, tree density...) are blended between nearby biomes,
+//! so that there are no cliffs at biome borders
+
+use serde::{Serialize, Deserialize};
+use crate::{block::{Block, BlockRegistry}, chunk::CHUNK_SIZE};
+
+/// Controls how wide biome borders are (lower = wider)
+const BLEND_SHARPNESS: f32 = 40.;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Biome {
+  #[default]
+  Plains,
+  Forest,
+  Desert,
+  Snowy,
+  Mountains,
+}
+
+impl Biome {
+  pub const COUNT: usize = 5;
+  pub const ALL: [Self; Self::COUNT] = [
+    Self::Plains,
+    Self::Forest,
+    Self::Desert,
+    Self::Snowy,
+    Self::Mountains,
+  ];
+
+  pub fn params(self) -> &'static BiomeParams {
+    match self {
+      Self::Plains => &PLAINS,
+      Self::Forest => &FOREST,
+      Self::Desert => &DESERT,
+      Self::Snowy => &SNOWY,
+      Self::Mountains => &MOUNTAINS,
+    }
+  }
+}
+
+const PLAINS: BiomeParams = BiomeParams {
+  climate: (0.2, -0.1),
+  height: 2.,
+  height_variation: 10.,
+  roughness: 1.,
+  surface: NamedBlock::new("grass", Block::Grass),
+  subsurface: NamedBlock::new("dirt", Block::Dirt),
+  tree_density: 0.5,
+  grass_chance: 0.12,
+};
+
+const FOREST: BiomeParams = BiomeParams {
+  climate: (0.1, 0.6),
+  height: 4.,
+  height_variation: 16.,
+  roughness: 2.,
+  surface: NamedBlock::new("grass", Block::Grass),
+  subsurface: NamedBlock::new("dirt", Block::Dirt),
+  tree_density: 3.,
+  grass_chance: 0.06,
+};
+
+const DESERT: BiomeParams = BiomeParams {
+  climate: (0.7, -0.6),
+  height: 2.,
+  height_variation: 8.,
+  roughness: 1.,
+  surface: NamedBlock::new("sand", Block::Sand),
+  subsurface: NamedBlock::new("sand", Block::Sand),
+  tree_density: 0.,
+  grass_chance: 0.,
+};
+
+const SNOWY: BiomeParams = BiomeParams {
+  climate: (-0.7, 0.2),
+  height: 6.,
+  height_variation: 20.,
+  roughness: 2.,
+  surface: NamedBlock::new("snowy_grass", Block::Grass),
+  subsurface: NamedBlock::new("dirt", Block::Dirt),
+  tree_density: 0.7,
+  grass_chance: 0.,
+};
+
+const MOUNTAINS: BiomeParams = BiomeParams {
+  climate: (-0.3, -0.6),
+  height: 20.,
+  height_variation: 44.,
+  roughness: 6.,
+  surface: NamedBlock::new("stone", Block::Stone),
+  subsurface: NamedBlock::new("stone", Block::Stone),
+  tree_density: 0.3,
+  grass_chance: 0.,
+};
+
+/// Block referenced by name, as blocks that aren't built-in have no fixed id\
+/// Falls back to a built-in block if the block registry doesn't define it
+#[derive(Clone, Copy, Debug)]
+pub struct NamedBlock {
+  pub name: &'static str,
+  pub fallback: Block,
+}
+
+impl NamedBlock {
+  pub const fn new(name: &'static str, fallback: Block) -> Self {
+    Self { name, fallback }
+  }
+
+  pub fn resolve(self, registry: &BlockRegistry) -> Block {
+    registry.by_name(self.name).unwrap_or(self.fallback)
+  }
+}
+
+pub struct BiomeParams {
+  /// Point in the climate space (temperature, humidity), both in `-1..=1`
+  pub climate: (f32, f32),
+  /// Average terrain height
+  pub height: f32,
+  /// Large scale terrain height variation
+  pub height_variation: f32,
+  /// Small scale terrain height variation
+  pub roughness: f32,
+  /// Top block of the terrain (above water level)
+  pub surface: NamedBlock,
+  /// Blocks below the surface (and at the bottom of lakes)
+  pub subsurface: NamedBlock,
+  /// Multiplier of the tree density
+  pub tree_density: f32,
+  /// Chance of tall grass on each surface block
+  pub grass_chance: f32,
+}
+
+/// Influence of each biome on a column (sums up to 1), indexed by `Biome as usize`
+#[derive(Clone, Copy, Debug)]
+pub struct BiomeWeights(pub [f32; Biome::COUNT]);
+
+impl BiomeWeights {
+  /// Weights of a column fully inside of the `biome`
+  pub fn single(biome: Biome) -> Self {
+    let mut weights = [0.; Biome::COUNT];
+    weights[biome as usize] = 1.;
+    Self(weights)
+  }
+
+  /// Compute biome weights for the given temperature and humidity
+  pub fn from_climate(temperature: f32, humidity: f32) -> Self {
+    let mut weights = Biome::ALL.map(|biome| {
+      let (t, h) = biome.params().climate;
+      let distance_sq = (temperature - t).powi(2) + (humidity - h).powi(2);
+      (-distance_sq * BLEND_SHARPNESS).exp()
+    });
+    //`exp` may underflow far away from all biomes, use the closest one then
+    let total: f32 = weights.iter().sum();
+    if total > f32::EPSILON {
+      weights.iter_mut().for_each(|weight| *weight /= total);
+    } else {
+      return Self::single(Self::closest(temperature, humidity))
+    }
+    Self(weights)
+  }
+
+  fn closest(temperature: f32, humidity: f32) -> Biome {
+    Biome::ALL.into_iter().min_by(|a, b| {
+      let distance_sq = |biome: &Biome| {
+        let (t, h) = biome.params().climate;
+        (temperature - t).powi(2) + (humidity - h).powi(2)
+      };
+      distance_sq(a).total_cmp(&distance_sq(b))
+    }).unwrap()
+  }
+
+  /// Biome with the highest weight
+  pub fn dominant(&self) -> Biome {
+    Biome::ALL.into_iter()
+      .max_by(|&a, &b| self.0[a as usize].total_cmp(&self.0[b as usize]))
+      .unwrap()
+  }
+
+  /// Weighted average of a biome parameter
+  pub fn blend(&self, param: impl Fn(&BiomeParams) -> f32) -> f32 {
+    Biome::ALL.into_iter()
+      .map(|biome| self.0[biome as usize] * param(biome.params()))
+      .sum()
+  }
+}
+
+impl Default for BiomeWeights {
+  fn default() -> Self {
+    Self::single(Biome::default())
+  }
+}
+
+/// Biome of each column of a chunk
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BiomeMap(Box<[[Biome; CHUNK_SIZE]; CHUNK_SIZE]>);
+
+impl BiomeMap {
+  pub fn new() -> Self {
+    Self(Box::new([[Biome::default(); CHUNK_SIZE]; CHUNK_SIZE]))
+  }
+
+  pub fn get(&self, x: usize, z: usize) -> Biome {
+    self.0[x][z]
+  }
+
+  pub fn set(&mut self, x: usize, z: usize, biome: Biome) {
+    self.0[x][z] = biome;
+  }
+}
+
+impl Default for BiomeMap {
+  fn default() -> Self {
+    Self::new()
+  }
+}
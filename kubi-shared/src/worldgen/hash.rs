@@ -0,0 +1,192 @@
+//! Stable hashing for world generation
+//!
+//! `std`'s `DefaultHasher` may change between Rust versions (and integers are hashed in native byte order),
+//! which would silently change the worlds generated from existing saves.\
+//! Everything here is fully specified (SplitMix64), and must never change:
+//! any change to the output changes every generated world\
+//! Worlds created before the stable hash keep using the old one, see [`HashVersion`]
+
+use std::hash::{Hash, Hasher};
+use serde::{Serialize, Deserialize};
+
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// SplitMix64 finalizer
+pub const fn mix64(mut z: u64) -> u64 {
+  z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  z ^ (z >> 31)
+}
+
+/// Hasher with an output that doesn't depend on the Rust version or the platform
+///
+/// Integers are hashed as 64-bit values (signed ones sign-extended), so `1u8` and `1u64` hash the same.\
+/// Only hash integers (and tuples of them) with it, other `Hash` implementations are not guaranteed to be stable\
+/// (arrays and slices of integers are hashed as raw bytes in native byte order)
+#[derive(Clone, Copy, Debug)]
+pub struct StableHasher(u64);
+
+impl StableHasher {
+  pub const fn new(seed: u64) -> Self {
+    Self(seed)
+  }
+}
+
+impl Hasher for StableHasher {
+  fn finish(&self) -> u64 {
+    mix64(self.0)
+  }
+
+  fn write(&mut self, bytes: &[u8]) {
+    for chunk in bytes.chunks(8) {
+      let mut buffer = [0; 8];
+      buffer[..chunk.len()].copy_from_slice(chunk);
+      self.write_u64(u64::from_le_bytes(buffer));
+    }
+  }
+
+  fn write_u64(&mut self, i: u64) {
+    self.0 = mix64((self.0 ^ i).wrapping_add(GOLDEN_GAMMA));
+  }
+
+  fn write_u8(&mut self, i: u8) { self.write_u64(i as u64) }
+  fn write_u16(&mut self, i: u16) { self.write_u64(i as u64) }
+  fn write_u32(&mut self, i: u32) { self.write_u64(i as u64) }
+  fn write_u128(&mut self, i: u128) {
+    self.write_u64(i as u64);
+    self.write_u64((i >> 64) as u64);
+  }
+  fn write_usize(&mut self, i: usize) { self.write_u64(i as u64) }
+  fn write_i8(&mut self, i: i8) { self.write_u64(i as u64) }
+  fn write_i16(&mut self, i: i16) { self.write_u64(i as u64) }
+  fn write_i32(&mut self, i: i32) { self.write_u64(i as u64) }
+  fn write_i64(&mut self, i: i64) { self.write_u64(i as u64) }
+  fn write_i128(&mut self, i: i128) { self.write_u128(i as u128) }
+  fn write_isize(&mut self, i: isize) { self.write_u64(i as u64) }
+}
+
+/// Hash `value` together with the `seed`
+pub fn stable_hash(seed: u64, value: impl Hash) -> u64 {
+  let mut hasher = StableHasher::new(seed);
+  value.hash(&mut hasher);
+  hasher.finish()
+}
+
+/// SipHash-1-3 with zero keys, the algorithm of `std`'s `DefaultHasher::new()` when legacy worlds were created
+///
+/// Implemented here, so that legacy worlds don't depend on the Rust version.\
+/// Integers are hashed in little endian byte order (`DefaultHasher` uses the native one, legacy worlds were created on little endian platforms)
+#[derive(Clone, Copy, Debug)]
+pub struct LegacyHasher {
+  v: [u64; 4],
+  /// Bytes that don't fill a whole 8 byte word yet
+  tail: u64,
+  tail_length: usize,
+  length: usize,
+}
+
+impl LegacyHasher {
+  pub const fn new() -> Self {
+    Self {
+      v: [0x736f_6d65_7073_6575, 0x646f_7261_6e64_6f6d, 0x6c79_6765_6e65_7261, 0x7465_6462_7974_6573],
+      tail: 0,
+      tail_length: 0,
+      length: 0,
+    }
+  }
+
+  fn round(&mut self) {
+    let [v0, v1, v2, v3] = &mut self.v;
+    *v0 = v0.wrapping_add(*v1); *v1 = v1.rotate_left(13); *v1 ^= *v0; *v0 = v0.rotate_left(32);
+    *v2 = v2.wrapping_add(*v3); *v3 = v3.rotate_left(16); *v3 ^= *v2;
+    *v0 = v0.wrapping_add(*v3); *v3 = v3.rotate_left(21); *v3 ^= *v0;
+    *v2 = v2.wrapping_add(*v1); *v1 = v1.rotate_left(17); *v1 ^= *v2; *v2 = v2.rotate_left(32);
+  }
+
+  fn compress(&mut self, word: u64) {
+    self.v[3] ^= word;
+    self.round();
+    self.v[0] ^= word;
+  }
+}
+
+impl Default for LegacyHasher {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Hasher for LegacyHasher {
+  fn finish(&self) -> u64 {
+    let mut state = *self;
+    let last = ((self.length as u64 & 0xff) << 56) | self.tail;
+    state.compress(last);
+    state.v[2] ^= 0xff;
+    for _ in 0..3 {
+      state.round();
+    }
+    let [v0, v1, v2, v3] = state.v;
+    v0 ^ v1 ^ v2 ^ v3
+  }
+
+  fn write(&mut self, bytes: &[u8]) {
+    self.length += bytes.len();
+    for &byte in bytes {
+      self.tail |= (byte as u64) << (8 * self.tail_length);
+      self.tail_length += 1;
+      if self.tail_length == 8 {
+        self.compress(self.tail);
+        self.tail = 0;
+        self.tail_length = 0;
+      }
+    }
+  }
+
+  fn write_u8(&mut self, i: u8) { self.write(&[i]) }
+  fn write_u16(&mut self, i: u16) { self.write(&i.to_le_bytes()) }
+  fn write_u32(&mut self, i: u32) { self.write(&i.to_le_bytes()) }
+  fn write_u64(&mut self, i: u64) { self.write(&i.to_le_bytes()) }
+  fn write_u128(&mut self, i: u128) { self.write(&i.to_le_bytes()) }
+  fn write_usize(&mut self, i: usize) { self.write(&(i as u64).to_le_bytes()) }
+  fn write_i8(&mut self, i: i8) { self.write_u8(i as u8) }
+  fn write_i16(&mut self, i: i16) { self.write_u16(i as u16) }
+  fn write_i32(&mut self, i: i32) { self.write_u32(i as u32) }
+  fn write_i64(&mut self, i: i64) { self.write_u64(i as u64) }
+  fn write_i128(&mut self, i: i128) { self.write_u128(i as u128) }
+  fn write_isize(&mut self, i: isize) { self.write_usize(i as usize) }
+}
+
+/// Hash used by worlds created before [`stable_hash`], see [`HashVersion::Legacy`]
+pub fn legacy_hash(seed: u64, value: impl Hash) -> u64 {
+  let mut hasher = LegacyHasher::new();
+  value.hash(&mut hasher);
+  seed.hash(&mut hasher);
+  hasher.finish()
+}
+
+/// Hashing used by the world generator, stored in the save file when the world is created
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashVersion {
+  /// [`legacy_hash`] and rotated step seeds\
+  /// Used by worlds created before the stable hash, always together with the legacy preset
+  /// (see [`LEGACY_PRESET`](super::preset::LEGACY_PRESET)), so that new chunks match the existing ones
+  Legacy,
+  /// [`stable_hash`] and [`SplitMix64`] step seeds
+  #[default]
+  Stable,
+}
+
+/// SplitMix64 generator, used to derive seeds of the world generation steps
+#[derive(Clone, Copy, Debug)]
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+  pub const fn new(seed: u64) -> Self {
+    Self(seed)
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(GOLDEN_GAMMA);
+    mix64(self.0)
+  }
+}
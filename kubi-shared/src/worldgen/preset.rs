@@ -0,0 +1,125 @@
+//! World generator presets
+//!
+//! A preset is a named list of world generation steps and their parameters.\
+//! Built-in presets are defined in `assets/worldgen.toml`, custom ones can be specified in the server config.\
+//! The preset is chosen when the world is created and stored in the save file,
+//! so that changes to the built-in presets don't affect existing worlds
+
+use serde::{Serialize, Deserialize};
+use anyhow::Result;
+use super::{
+  steps::{
+    _00_biomes::{BiomeStep, BiomesConfig},
+    _01_terrain::{TerrainStep, TerrainConfig, LegacyTerrainStep, LegacyTerrainConfig},
+    _01_superflat::{SuperflatStep, SuperflatConfig},
+    _02_water::WaterStep,
+    _03_caves::{CaveStep, CavesConfig},
+    _04_ores::{OresStep, OresConfig},
+    _05_layers::LayersStep,
+    _06_decorate::{DecorateStep, LegacyDecorateStep},
+    _07_trees::{TreesStep, TreesConfig},
+    _08_structures::{StructuresStep, StructuresConfig},
+  },
+  hash::HashVersion,
+  SeedThingy, WorldGenStep, WorldGenerator,
+};
+
+/// Presets shipped with the game
+pub const DEFAULT_PRESETS: &str = include_str!("../../../assets/worldgen.toml");
+
+/// Name of the preset used if none was chosen
+pub const DEFAULT_PRESET: &str = "default";
+
+/// Name of the preset reproducing the world generator of save files created before presets
+pub const LEGACY_PRESET: &str = "legacy";
+
+/// World generation step, with its parameters\
+/// Presets are stored in save files, which refer to the steps by their index, so new steps must be added at the end
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepConfig {
+  Biomes(BiomesConfig),
+  Terrain(TerrainConfig),
+  Superflat(SuperflatConfig),
+  Water,
+  Caves(CavesConfig),
+  Ores(OresConfig),
+  Layers,
+  Decorate,
+  Trees(TreesConfig),
+  Structures(StructuresConfig),
+  LegacyTerrain(LegacyTerrainConfig),
+  LegacyDecorate,
+}
+
+fn run_step<T: WorldGenStep>(generator: &mut WorldGenerator, seeder: &mut SeedThingy, config: &T::Config) {
+  let mut step = T::initialize(generator, seeder, config);
+  step.generate(generator);
+}
+
+impl StepConfig {
+  pub(super) fn run(&self, generator: &mut WorldGenerator, seeder: &mut SeedThingy) {
+    match self {
+      Self::Biomes(config) => run_step::<BiomeStep>(generator, seeder, config),
+      Self::Terrain(config) => run_step::<TerrainStep>(generator, seeder, config),
+      Self::Superflat(config) => run_step::<SuperflatStep>(generator, seeder, config),
+      Self::Water => run_step::<WaterStep>(generator, seeder, &()),
+      Self::Caves(config) => run_step::<CaveStep>(generator, seeder, config),
+      Self::Ores(config) => run_step::<OresStep>(generator, seeder, config),
+      Self::Layers => run_step::<LayersStep>(generator, seeder, &()),
+      Self::Decorate => run_step::<DecorateStep>(generator, seeder, &()),
+      Self::Trees(config) => run_step::<TreesStep>(generator, seeder, config),
+      Self::Structures(config) => run_step::<StructuresStep>(generator, seeder, config),
+      Self::LegacyTerrain(config) => run_step::<LegacyTerrainStep>(generator, seeder, config),
+      Self::LegacyDecorate => run_step::<LegacyDecorateStep>(generator, seeder, &()),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WorldGenPreset {
+  pub name: String,
+  /// Steps are run in this order
+  #[serde(default)]
+  pub steps: Vec<StepConfig>,
+  /// Hashing used by the world, not a part of the preset itself\
+  /// (stored separately in the save file, see [`WorldSaveFile::world_generator`](crate::data::WorldSaveFile::world_generator))
+  #[serde(skip)]
+  pub hash_version: HashVersion,
+}
+
+impl WorldGenPreset {
+  /// Get a built-in preset by its name
+  pub fn builtin(name: &str) -> Option<Self> {
+    WorldGenPresets::builtin().presets.into_iter().find(|preset| preset.name == name)
+  }
+
+  /// Preset of save files created before presets (see [`LEGACY_PRESET`])
+  pub fn legacy() -> Self {
+    Self::builtin(LEGACY_PRESET).expect("built-in presets don't contain the legacy preset")
+  }
+}
+
+impl Default for WorldGenPreset {
+  fn default() -> Self {
+    Self::builtin(DEFAULT_PRESET).expect("built-in presets don't contain the default preset")
+  }
+}
+
+/// Contents of a preset file
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WorldGenPresets {
+  #[serde(rename = "preset", default)]
+  pub presets: Vec<WorldGenPreset>,
+}
+
+impl WorldGenPresets {
+  pub fn parse(data: &str) -> Result<Self> {
+    Ok(toml::from_str(data)?)
+  }
+
+  /// Presets shipped with the game
+  pub fn builtin() -> Self {
+    Self::parse(DEFAULT_PRESETS).expect("built-in world generator presets are invalid")
+  }
+}
@@ -1,6 +1,10 @@
+pub mod _00_biomes;
 pub mod _01_terrain;
+pub mod _01_superflat;
 pub mod _02_water;
 pub mod _03_caves;
-pub mod _04_layers;
-pub mod _05_decorate;
-pub mod _06_trees;
+pub mod _04_ores;
+pub mod _05_layers;
+pub mod _06_decorate;
+pub mod _07_trees;
+pub mod _08_structures;
@@ -0,0 +1,87 @@
+use std::rc::Rc;
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use glam::ivec3;
+use serde::{Serialize, Deserialize};
+use crate::chunk::CHUNK_SIZE;
+use super::super::{
+  biome::{BiomeMap, BiomeWeights},
+  SeedThingy, WorldGenStep, WorldGenerator,
+};
+
+/// Climate noise is multiplied by this, as fractal noise rarely gets close to -1 or 1
+const CLIMATE_SCALE: f32 = 1.5;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct BiomesConfig {
+  /// Frequency of the climate noise (higher = smaller biomes)
+  pub frequency: f32,
+}
+
+impl Default for BiomesConfig {
+  fn default() -> Self {
+    Self { frequency: 0.0012 }
+  }
+}
+
+/// Computes the biome weights of any column
+pub struct BiomeSampler {
+  temperature: FastNoiseLite,
+  humidity: FastNoiseLite,
+}
+
+impl BiomeSampler {
+  pub fn weights(&self, x: i32, z: i32) -> BiomeWeights {
+    let (x, z) = (x as f64, z as f64);
+    let temperature = (self.temperature.get_noise_2d(x, z) * CLIMATE_SCALE).clamp(-1., 1.);
+    let humidity = (self.humidity.get_noise_2d(x, z) * CLIMATE_SCALE).clamp(-1., 1.);
+    BiomeWeights::from_climate(temperature, humidity)
+  }
+}
+
+pub struct BiomeStep {
+  sampler: Rc<BiomeSampler>,
+}
+
+fn climate_noise(seed: i32, frequency: f32) -> FastNoiseLite {
+  let mut noise = FastNoiseLite::with_seed(seed);
+  noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+  noise.set_fractal_type(Some(FractalType::FBm));
+  noise.set_fractal_octaves(Some(3));
+  noise.set_frequency(Some(frequency));
+  noise
+}
+
+impl WorldGenStep for BiomeStep {
+  type Config = BiomesConfig;
+
+  fn initialize(_: &WorldGenerator, seeder: &mut SeedThingy, config: &BiomesConfig) -> Self {
+    Self {
+      sampler: Rc::new(BiomeSampler {
+        //independent of the other steps, see WorldGenerator::generate_biome_map
+        temperature: climate_noise(seeder.named_seed("biomes/temperature"), config.frequency),
+        humidity: climate_noise(seeder.named_seed("biomes/humidity"), config.frequency),
+      }),
+    }
+  }
+
+  fn generate(&mut self, gen: &mut WorldGenerator) {
+    //biomes are computed for every chunk (even the ones with no terrain)
+    //so that the biome map is always available
+    let biome_weights: Vec<Vec<BiomeWeights>> = (0..CHUNK_SIZE as i32).map(|x| {
+      (0..CHUNK_SIZE as i32).map(|z| {
+        let global_xz = gen.global_position(ivec3(x, 0, z));
+        self.sampler.weights(global_xz.x, global_xz.z)
+      }).collect()
+    }).collect();
+    let mut biome_map = BiomeMap::new();
+    for (x, column) in biome_weights.iter().enumerate() {
+      for (z, weights) in column.iter().enumerate() {
+        biome_map.set(x, z, weights.dominant());
+      }
+    }
+    gen.data.biome_map = Some(biome_map);
+    gen.data.biome_weights = Some(biome_weights);
+    gen.data.biome_sampler = Some(Rc::clone(&self.sampler));
+  }
+}
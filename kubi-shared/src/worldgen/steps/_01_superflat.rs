@@ -0,0 +1,72 @@
+use std::rc::Rc;
+use glam::ivec3;
+use serde::{Serialize, Deserialize};
+use crate::{block::Block, chunk::CHUNK_SIZE};
+use super::super::{SeedThingy, WorldGenStep, WorldGenerator};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FlatLayer {
+  /// Block name, unknown blocks are replaced with air
+  pub block: String,
+  pub thickness: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SuperflatConfig {
+  /// Height of the bottom of the lowest layer
+  pub bottom: i32,
+  /// Layers, from the bottom to the top
+  pub layers: Vec<FlatLayer>,
+}
+
+impl Default for SuperflatConfig {
+  fn default() -> Self {
+    Self {
+      bottom: -4,
+      layers: vec![
+        FlatLayer { block: "stone".into(), thickness: 1 },
+        FlatLayer { block: "dirt".into(), thickness: 2 },
+        FlatLayer { block: "grass".into(), thickness: 1 },
+      ],
+    }
+  }
+}
+
+/// Replaces the terrain step, generating flat layers of blocks instead
+pub struct SuperflatStep {
+  /// Block and its height range
+  layers: Vec<(Block, i32, i32)>,
+  top: i32,
+}
+
+impl WorldGenStep for SuperflatStep {
+  type Config = SuperflatConfig;
+
+  fn initialize(generator: &WorldGenerator, _: &mut SeedThingy, config: &SuperflatConfig) -> Self {
+    let mut top = config.bottom;
+    let layers = config.layers.iter().map(|layer| {
+      let block = generator.block_by_name(&layer.block).unwrap_or(Block::Air);
+      let bottom = top;
+      top += layer.thickness as i32;
+      (block, bottom, top)
+    }).collect();
+    Self { layers, top }
+  }
+
+  fn generate(&mut self, gen: &mut WorldGenerator) {
+    for &(block, bottom, top) in &self.layers {
+      for y in gen.local_height(bottom)..gen.local_height(top) {
+        for x in 0..CHUNK_SIZE as i32 {
+          for z in 0..CHUNK_SIZE as i32 {
+            gen.place(ivec3(x, y, z), block);
+          }
+        }
+      }
+    }
+    //allows the following steps (like trees) to find the surface
+    let top = self.top;
+    gen.data.master_height_map = Some(vec![vec![top; CHUNK_SIZE]; CHUNK_SIZE]);
+    gen.data.height_sampler = Some(Rc::new(move |_, _| top));
+  }
+}
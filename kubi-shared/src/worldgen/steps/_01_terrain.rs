@@ -1,51 +1,206 @@
+use std::rc::Rc;
 use fastnoise_lite::{FastNoiseLite, FractalType};
 use glam::ivec3;
+use serde::{Serialize, Deserialize};
 use crate::{block::Block, chunk::CHUNK_SIZE};
-use super::super::{SeedThingy, WorldGenStep, WorldGenerator};
+use super::{
+  super::{biome::{Biome, BiomeWeights}, SeedThingy, WorldGenStep, WorldGenerator},
+  _00_biomes::BiomeSampler,
+};
 
-pub const MAX_TERAIN_HEIGHT: i32 = 32;
-pub const MIN_TERRAIN_HEIGHT: i32 = -MAX_TERAIN_HEIGHT;
-
-pub struct TerrainStep {
-  noise: FastNoiseLite,
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TerrainConfig {
+  /// Frequency of the large scale terrain noise
+  pub frequency: f32,
+  pub octaves: i32,
+  /// Frequency of the small scale terrain noise
+  pub roughness_frequency: f32,
+  /// Multiplier of the terrain height (`0` makes the terrain flat)
+  pub height_scale: f32,
 }
 
-impl WorldGenStep for TerrainStep {
-  fn initialize(_: &WorldGenerator, seeder: &mut SeedThingy) -> Self {
-    let mut noise = FastNoiseLite::with_seed(seeder.next_seed());
-    noise.set_fractal_type(Some(FractalType::FBm));
-    noise.set_fractal_octaves(Some(4));
-    noise.set_frequency(Some(0.003));
-    Self { noise }
+impl Default for TerrainConfig {
+  fn default() -> Self {
+    Self {
+      frequency: 0.003,
+      octaves: 4,
+      roughness_frequency: 0.03,
+      height_scale: 1.,
+    }
   }
+}
 
-  fn generate(&mut self, gen: &mut WorldGenerator) {
-    let is_oob_upper = gen.offset().y > MAX_TERAIN_HEIGHT;
-    if is_oob_upper { return }
-
-    let is_oob_lower = (gen.offset().y + CHUNK_SIZE as i32) < MIN_TERRAIN_HEIGHT;
-    if is_oob_lower {
-      for x in 0..CHUNK_SIZE as i32 {
-        for y in 0..CHUNK_SIZE as i32 {
-          for z in 0..CHUNK_SIZE as i32 {
-            gen.place(ivec3(x, y, z), Block::Stone);
-          }
-        }
-      }
-      return
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LegacyTerrainConfig {
+  pub frequency: f32,
+  pub octaves: i32,
+  /// Max distance between the terrain surface and y = 0
+  pub height: i32,
+}
+
+impl Default for LegacyTerrainConfig {
+  fn default() -> Self {
+    Self {
+      frequency: 0.003,
+      octaves: 4,
+      height: 32,
     }
+  }
+}
+
+/// Fill columns of the chunk with stone up to their terrain height,
+/// `height_map` is only computed if the terrain surface may cross the chunk
+fn place_terrain(
+  gen: &mut WorldGenerator,
+  (min_height, max_height): (i32, i32),
+  height_map: impl FnOnce(&WorldGenerator) -> Vec<Vec<i32>>,
+) {
+  let is_oob_upper = gen.offset().y > max_height;
+  if is_oob_upper { return }
 
-    let mut height_map = vec![vec![0; CHUNK_SIZE]; CHUNK_SIZE];
+  let is_oob_lower = (gen.offset().y + CHUNK_SIZE as i32) < min_height;
+  if is_oob_lower {
     for x in 0..CHUNK_SIZE as i32 {
-      for z in 0..CHUNK_SIZE as i32 {
-        let global_xz = gen.global_position(ivec3(x, 0, z));
-        let height = (self.noise.get_noise_2d(global_xz.x as f64, global_xz.z as f64) * MAX_TERAIN_HEIGHT as f32) as i32;
-        height_map[x as usize][z as usize] = height;
-        for y in 0..gen.local_height(height) {
+      for y in 0..CHUNK_SIZE as i32 {
+        for z in 0..CHUNK_SIZE as i32 {
           gen.place(ivec3(x, y, z), Block::Stone);
         }
       }
     }
-    gen.data.master_height_map = Some(height_map);
+    return
+  }
+
+  let height_map = height_map(gen);
+  for x in 0..CHUNK_SIZE as i32 {
+    for z in 0..CHUNK_SIZE as i32 {
+      for y in 0..gen.local_height(height_map[x as usize][z as usize]) {
+        gen.place(ivec3(x, y, z), Block::Stone);
+      }
+    }
+  }
+  gen.data.master_height_map = Some(height_map);
+}
+
+/// Computes the terrain height of any column
+pub struct TerrainSampler {
+  noise: FastNoiseLite,
+  rough_noise: FastNoiseLite,
+  height_scale: f32,
+  biomes: Option<Rc<BiomeSampler>>,
+}
+
+impl TerrainSampler {
+  /// Terrain height of a column with known biome weights
+  fn height_with_weights(&self, x: i32, z: i32, weights: &BiomeWeights) -> i32 {
+    let (x, z) = (x as f64, z as f64);
+    //height profile is blended between biomes to avoid cliffs at biome borders
+    ((
+      weights.blend(|biome| biome.height) +
+      weights.blend(|biome| biome.height_variation) * self.noise.get_noise_2d(x, z) +
+      weights.blend(|biome| biome.roughness) * self.rough_noise.get_noise_2d(x, z)
+    ) * self.height_scale) as i32
+  }
+
+  pub fn height(&self, x: i32, z: i32) -> i32 {
+    let weights = self.biomes.as_ref().map(|biomes| biomes.weights(x, z)).unwrap_or_default();
+    self.height_with_weights(x, z, &weights)
+  }
+}
+
+pub struct TerrainStep {
+  sampler: Rc<TerrainSampler>,
+  /// Lowest and highest possible terrain height
+  height_range: (i32, i32),
+}
+
+impl WorldGenStep for TerrainStep {
+  type Config = TerrainConfig;
+
+  fn initialize(gen: &WorldGenerator, seeder: &mut SeedThingy, config: &TerrainConfig) -> Self {
+    let mut noise = FastNoiseLite::with_seed(seeder.next_seed());
+    noise.set_fractal_type(Some(FractalType::FBm));
+    noise.set_fractal_octaves(Some(config.octaves));
+    noise.set_frequency(Some(config.frequency));
+
+    let mut rough_noise = FastNoiseLite::with_seed(seeder.next_seed());
+    rough_noise.set_fractal_type(Some(FractalType::FBm));
+    rough_noise.set_fractal_octaves(Some(2));
+    rough_noise.set_frequency(Some(config.roughness_frequency));
+
+    //noise is in the -1..=1 range
+    let (min, max) = Biome::ALL.iter()
+      .map(|biome| {
+        let params = biome.params();
+        let variation = params.height_variation + params.roughness;
+        let (a, b) = ((params.height - variation) * config.height_scale, (params.height + variation) * config.height_scale);
+        (a.min(b), a.max(b))
+      })
+      .fold((f32::MAX, f32::MIN), |(min, max), (a, b)| (min.min(a), max.max(b)));
+    let height_range = (min.floor() as i32, max.ceil() as i32);
+
+    Self {
+      sampler: Rc::new(TerrainSampler {
+        noise,
+        rough_noise,
+        height_scale: config.height_scale,
+        biomes: gen.data.biome_sampler.clone(),
+      }),
+      height_range,
+    }
+  }
+
+  fn generate(&mut self, gen: &mut WorldGenerator) {
+    let sampler = Rc::clone(&self.sampler);
+    gen.data.height_sampler = Some(Rc::new(move |x, z| sampler.height(x, z)));
+
+    place_terrain(gen, self.height_range, |gen| {
+      (0..CHUNK_SIZE as i32).map(|x| {
+        (0..CHUNK_SIZE as i32).map(|z| {
+          let global_xz = gen.global_position(ivec3(x, 0, z));
+          let weights = gen.data.biome_weights(x as usize, z as usize);
+          self.sampler.height_with_weights(global_xz.x, global_xz.z, &weights)
+        }).collect()
+      }).collect()
+    });
+  }
+}
+
+/// Terrain of worlds created before biomes: a single fractal noise scaled to `±height`, ignoring biomes
+pub struct LegacyTerrainStep {
+  noise: Rc<FastNoiseLite>,
+  height: i32,
+}
+
+impl LegacyTerrainStep {
+  fn height(noise: &FastNoiseLite, height: i32, x: i32, z: i32) -> i32 {
+    (noise.get_noise_2d(x as f64, z as f64) * height as f32) as i32
+  }
+}
+
+impl WorldGenStep for LegacyTerrainStep {
+  type Config = LegacyTerrainConfig;
+
+  fn initialize(_: &WorldGenerator, seeder: &mut SeedThingy, config: &LegacyTerrainConfig) -> Self {
+    let mut noise = FastNoiseLite::with_seed(seeder.next_seed());
+    noise.set_fractal_type(Some(FractalType::FBm));
+    noise.set_fractal_octaves(Some(config.octaves));
+    noise.set_frequency(Some(config.frequency));
+    Self { noise: Rc::new(noise), height: config.height }
+  }
+
+  fn generate(&mut self, gen: &mut WorldGenerator) {
+    let (noise, height) = (Rc::clone(&self.noise), self.height);
+    gen.data.height_sampler = Some(Rc::new(move |x, z| Self::height(&noise, height, x, z)));
+
+    place_terrain(gen, (-self.height, self.height), |gen| {
+      (0..CHUNK_SIZE as i32).map(|x| {
+        (0..CHUNK_SIZE as i32).map(|z| {
+          let global_xz = gen.global_position(ivec3(x, 0, z));
+          Self::height(&self.noise, self.height, global_xz.x, global_xz.z)
+        }).collect()
+      }).collect()
+    });
   }
 }
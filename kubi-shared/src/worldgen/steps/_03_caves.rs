@@ -1,10 +1,23 @@
 use fastnoise_lite::{FastNoiseLite, FractalType};
 use glam::ivec3;
+use serde::{Serialize, Deserialize};
 use crate::{block::Block, chunk::CHUNK_SIZE};
-use super::{
-  super::{SeedThingy, WorldGenStep, WorldGenerator},
-  _01_terrain::MAX_TERAIN_HEIGHT,
-};
+use super::super::{SeedThingy, WorldGenStep, WorldGenerator};
+
+/// Caves get too small to exist above this height (see `cave_size`)
+const MAX_CAVE_HEIGHT: i32 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CavesConfig {
+  pub frequency: f32,
+}
+
+impl Default for CavesConfig {
+  fn default() -> Self {
+    Self { frequency: 0.01 }
+  }
+}
 
 pub struct CaveStep {
   a: FastNoiseLite,
@@ -12,22 +25,26 @@ pub struct CaveStep {
 }
 
 impl WorldGenStep for CaveStep {
-  fn initialize(_: &WorldGenerator, seeder: &mut SeedThingy) -> Self {
+  type Config = CavesConfig;
+
+  fn initialize(_: &WorldGenerator, seeder: &mut SeedThingy, config: &CavesConfig) -> Self {
     let mut a = FastNoiseLite::with_seed(seeder.next_seed());
     a.set_fractal_type(Some(FractalType::FBm));
     a.set_fractal_octaves(Some(2));
+    a.set_frequency(Some(config.frequency));
 
     let mut b = FastNoiseLite::with_seed(seeder.next_seed());
     b.set_fractal_type(Some(FractalType::FBm));
     b.set_fractal_octaves(Some(2));
+    b.set_frequency(Some(config.frequency));
 
     Self { a, b }
   }
 
   fn generate(&mut self, gen: &mut WorldGenerator) {
-    // If chunk's lower bound is above max terrain height,
+    // If chunk's lower bound is above max cave height,
     // ...we can skip this step as caves cannot exist here
-    if gen.offset().y > MAX_TERAIN_HEIGHT { return }
+    if gen.offset().y > MAX_CAVE_HEIGHT { return }
 
     for x in 0..CHUNK_SIZE as i32 {
       for y in 0..CHUNK_SIZE as i32 {
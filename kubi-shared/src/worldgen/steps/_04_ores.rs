@@ -0,0 +1,272 @@
+use glam::{ivec3, IVec3};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+use serde::{Serialize, Deserialize};
+use crate::{block::Block, chunk::CHUNK_SIZE};
+use super::super::{SeedThingy, WorldGenStep, WorldGenerator};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OreConfig {
+  /// Block name, unknown blocks are skipped
+  pub block: String,
+  /// Veins start between these heights (min inclusive, max exclusive)
+  pub min_height: i32,
+  pub max_height: i32,
+  /// Average amount of veins in a chunk inside of the height range
+  pub veins_per_chunk: f32,
+  /// Amount of steps of the random walk a vein is made of (at most `CHUNK_SIZE`)
+  pub vein_size: u32,
+}
+
+impl OreConfig {
+  fn new(block: &str, (min_height, max_height): (i32, i32), veins_per_chunk: f32, vein_size: u32) -> Self {
+    Self { block: block.into(), min_height, max_height, veins_per_chunk, vein_size }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct OresConfig {
+  pub ores: Vec<OreConfig>,
+}
+
+impl Default for OresConfig {
+  fn default() -> Self {
+    Self {
+      ores: vec![
+        OreConfig::new("coal_ore", (-96, 32), 12., 12),
+        OreConfig::new("iron_ore", (-128, 0), 8., 8),
+        OreConfig::new("gold_ore", (-256, -32), 3., 7),
+        OreConfig::new("diamond_ore", (-512, -64), 1.5, 5),
+      ],
+    }
+  }
+}
+
+struct Ore {
+  block: Block,
+  seed: i32,
+  height_range: (i32, i32),
+  veins_per_chunk: f32,
+  vein_size: i32,
+}
+
+/// Replaces stone with ore veins
+///
+/// Veins are random walks starting in random positions of each chunk,
+/// veins starting in neighboring chunks are computed too, so that they continue across chunk borders
+pub struct OresStep {
+  ores: Vec<Ore>,
+}
+
+impl OresStep {
+  fn place_vein(gen: &mut WorldGenerator, ore: &Ore, rng: &mut Xoshiro256StarStar, start: IVec3) {
+    const DIRECTIONS: [IVec3; 6] = [IVec3::X, IVec3::NEG_X, IVec3::Y, IVec3::NEG_Y, IVec3::Z, IVec3::NEG_Z];
+    let mut position = start;
+    for _ in 0..ore.vein_size {
+      if gen.query_global(position) == Some(Block::Stone) {
+        gen.place_global(position, ore.block);
+      }
+      position += DIRECTIONS[rng.gen_range(0..DIRECTIONS.len())];
+    }
+  }
+}
+
+impl WorldGenStep for OresStep {
+  type Config = OresConfig;
+
+  fn initialize(generator: &WorldGenerator, seeder: &mut SeedThingy, config: &OresConfig) -> Self {
+    Self {
+      ores: config.ores.iter().enumerate().filter_map(|(index, ore)| {
+        //seeds are derived from the block name (and how many times it was listed before),
+        //so that other ores and the following steps don't depend on the list of ores
+        let repeat = config.ores[..index].iter().filter(|other| other.block == ore.block).count();
+        let seed = seeder.named_seed(&format!("ores/{}/{}", ore.block, repeat));
+        Some(Ore {
+          block: generator.block_by_name(&ore.block)?,
+          seed,
+          height_range: (ore.min_height, ore.max_height),
+          veins_per_chunk: ore.veins_per_chunk,
+          vein_size: ore.vein_size.min(CHUNK_SIZE as u32) as i32,
+        })
+      }).collect(),
+    }
+  }
+
+  fn generate(&mut self, gen: &mut WorldGenerator) {
+    let chunk_position = gen.chunk_position;
+    for ore in &self.ores {
+      //veins can only reach the chunk from its direct neighbors, as they are at most CHUNK_SIZE long
+      for offset_x in -1..=1 {
+        for offset_y in -1..=1 {
+          for offset_z in -1..=1 {
+            let source = chunk_position + ivec3(offset_x, offset_y, offset_z);
+            let source_offset = source * CHUNK_SIZE as i32;
+            let (min_height, max_height) = ore.height_range;
+            if source_offset.y >= max_height || source_offset.y + (CHUNK_SIZE as i32) <= min_height { continue }
+
+            let mut rng = Xoshiro256StarStar::seed_from_u64(gen.seeded_hash((source.x, source.y, source.z, ore.seed)));
+            let mut vein_count = ore.veins_per_chunk as u32;
+            if rng.gen::<f32>() < ore.veins_per_chunk.fract() {
+              vein_count += 1;
+            }
+            for _ in 0..vein_count {
+              let start = source_offset + ivec3(
+                rng.gen_range(0..CHUNK_SIZE as i32),
+                rng.gen_range(0..CHUNK_SIZE as i32),
+                rng.gen_range(0..CHUNK_SIZE as i32),
+              );
+              //the rng must advance the same way in every chunk, so the walk is skipped only after it's seeded
+              let mut vein_rng = Xoshiro256StarStar::seed_from_u64(rng.gen());
+              if !(min_height..max_height).contains(&start.y) { continue }
+              Self::place_vein(gen, ore, &mut vein_rng, start);
+            }
+          }
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::{ivec3, IVec3};
+  use crate::{
+    block::{Block, BlockRegistry},
+    chunk::CHUNK_SIZE,
+    height::WorldHeight,
+    worldgen::{
+      generate_world,
+      hash::HashVersion,
+      preset::{StepConfig, WorldGenPreset},
+      steps::_01_superflat::{FlatLayer, SuperflatConfig},
+      structures::StructureTemplates,
+    },
+  };
+  use super::{OreConfig, OresConfig};
+
+  const SEED: u64 = 0xfeb_face_dead_cafe;
+
+  /// Solid stone from y = -512 to 0, with ores
+  fn preset(ores: OresConfig) -> WorldGenPreset {
+    WorldGenPreset {
+      name: "ore_test".into(),
+      steps: vec![
+        StepConfig::Superflat(SuperflatConfig {
+          bottom: -512,
+          layers: vec![FlatLayer { block: "stone".into(), thickness: 512 }],
+        }),
+        StepConfig::Ores(ores),
+      ],
+      hash_version: HashVersion::Stable,
+    }
+  }
+
+  /// Global positions of all `block`s in the chunks
+  fn find_blocks(registry: &BlockRegistry, preset: &WorldGenPreset, seed: u64, chunks: &[IVec3], block: Block) -> Vec<IVec3> {
+    let mut found = Vec::new();
+    for &chunk in chunks {
+      let (blocks, _, _) = generate_world(chunk, seed, preset, &StructureTemplates::default(), WorldHeight::default(), registry, None).unwrap();
+      for x in 0..CHUNK_SIZE as i32 {
+        for y in 0..CHUNK_SIZE as i32 {
+          for z in 0..CHUNK_SIZE as i32 {
+            if blocks.get(ivec3(x, y, z)) == block {
+              found.push(chunk * CHUNK_SIZE as i32 + ivec3(x, y, z));
+            }
+          }
+        }
+      }
+    }
+    found
+  }
+
+  fn chunks(x: i32, y: std::ops::Range<i32>, z: i32) -> Vec<IVec3> {
+    (0..x).flat_map(|x| y.clone().flat_map(move |y| (0..z).map(move |z| ivec3(x, y, z)))).collect()
+  }
+
+  #[test]
+  fn veins_stay_in_height_range() {
+    let registry = BlockRegistry::builtin();
+    let config = OresConfig::default();
+    let preset = preset(config.clone());
+    let chunks = chunks(2, -16..0, 2);
+    let mut totals = Vec::new();
+    for ore in &config.ores {
+      let block = registry.by_name(&ore.block).unwrap();
+      let found = find_blocks(&registry, &preset, SEED, &chunks, block);
+      assert!(!found.is_empty(), "no {} generated", ore.block);
+      //veins start inside of the range, but may walk out of it
+      let margin = ore.vein_size as i32;
+      for position in &found {
+        assert!(
+          (ore.min_height - margin..ore.max_height + margin).contains(&position.y),
+          "{} at y = {} is outside of its height range", ore.block, position.y
+        );
+      }
+      totals.push(found.len());
+    }
+    //the default ores are listed from the most to the least common
+    assert!(totals.windows(2).all(|pair| pair[0] > pair[1]), "unexpected ore totals {:?}", totals);
+  }
+
+  #[test]
+  fn vein_density_matches_config() {
+    const VEINS: f32 = 10.;
+    const SIZE: u32 = 10;
+    let registry = BlockRegistry::builtin();
+    let preset = preset(OresConfig {
+      ores: vec![OreConfig::new("coal_ore", (-1024, 1024), VEINS, SIZE)],
+    });
+    let chunks = chunks(4, -8..-4, 4);
+    let found = find_blocks(&registry, &preset, SEED, &chunks, registry.by_name("coal_ore").unwrap());
+
+    //a random walk of SIZE steps covers between 2 and SIZE blocks, about 83% of them on average
+    let per_chunk = found.len() as f32 / chunks.len() as f32;
+    let expected = VEINS * SIZE as f32;
+    assert!(
+      (expected * 0.7..=expected * 0.95).contains(&per_chunk),
+      "{} ore blocks per chunk, expected about {}", per_chunk, expected * 0.83
+    );
+
+    //with 10 veins per chunk every chunk should contain some
+    for &chunk in &chunks {
+      let min = chunk * CHUNK_SIZE as i32;
+      let max = min + CHUNK_SIZE as i32;
+      assert!(
+        found.iter().any(|position| position.cmpge(min).all() && position.cmplt(max).all()),
+        "no ores in chunk {}", chunk
+      );
+    }
+  }
+
+  #[test]
+  fn ores_are_deterministic() {
+    let registry = BlockRegistry::builtin();
+    let preset = preset(OresConfig::default());
+    let chunks = chunks(2, -4..-2, 2);
+    let block = registry.by_name("coal_ore").unwrap();
+    let first = find_blocks(&registry, &preset, SEED, &chunks, block);
+    assert_eq!(first, find_blocks(&registry, &preset, SEED, &chunks, block));
+    assert_ne!(first, find_blocks(&registry, &preset, SEED + 1, &chunks, block));
+  }
+
+  #[test]
+  fn ores_are_independent() {
+    //adding, removing or reordering other ores doesn't move the veins of an ore
+    let registry = BlockRegistry::builtin();
+    let chunks = chunks(2, -4..-2, 2);
+    let block = registry.by_name("coal_ore").unwrap();
+    let mut ores = OresConfig::default().ores;
+    let all = find_blocks(&registry, &preset(OresConfig { ores: ores.clone() }), SEED, &chunks, block);
+    ores.reverse();
+    let reversed = find_blocks(&registry, &preset(OresConfig { ores: ores.clone() }), SEED, &chunks, block);
+    ores.retain(|ore| ore.block == "coal_ore");
+    let alone = find_blocks(&registry, &preset(OresConfig { ores }), SEED, &chunks, block);
+    assert!(!alone.is_empty());
+    //other ores may only take the place of some of the coal
+    for found in [all, reversed] {
+      assert!(found.iter().all(|position| alone.contains(position)));
+      assert!(found.len() * 10 >= alone.len() * 9, "{} of {} coal ores left", found.len(), alone.len());
+    }
+  }
+}
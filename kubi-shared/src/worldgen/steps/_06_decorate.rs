@@ -0,0 +1,55 @@
+use glam::ivec3;
+use crate::{block::Block, chunk::CHUNK_SIZE, worldgen::SeedThingy};
+use super::{
+  _02_water::WATER_LEVEL,
+  super::{WorldGenStep, WorldGenerator},
+};
+
+/// Place tall grass on the surface above water level, where `grows` returns `true`\
+/// (`grows` gets the local column and a hash of the global one)
+fn place_tall_grass(gen: &mut WorldGenerator, grows: impl Fn(&WorldGenerator, usize, usize, u64) -> bool) {
+  if gen.data.master_height_map.is_none() { return }
+
+  for x in 0..CHUNK_SIZE as i32 {
+    for z in 0..CHUNK_SIZE as i32 {
+      let global_xz = gen.global_position(ivec3(x, 0, z));
+
+      let terrain_height = gen.data.master_height_map.as_ref().unwrap()[x as usize][z as usize];
+
+      //Place tall grass
+      if terrain_height >= WATER_LEVEL {
+        if let Some(local_y) = gen.local_y_position(terrain_height) {
+          if grows(gen, x as usize, z as usize, gen.seeded_hash((global_xz.x, global_xz.z, 0x050))) {
+            gen.place_if_empty(ivec3(x, local_y, z), Block::TallGrass);
+          }
+        }
+      }
+    }
+  }
+}
+
+pub struct DecorateStep;
+
+impl WorldGenStep for DecorateStep {
+  type Config = ();
+  fn initialize(_: &WorldGenerator, _: &mut SeedThingy, _: &()) -> Self { Self }
+
+  fn generate(&mut self, gen: &mut WorldGenerator) {
+    place_tall_grass(gen, |gen, x, z, hash| {
+      let grass_chance = gen.data.biome_weights(x, z).blend(|biome| biome.grass_chance);
+      ((hash & 0xff) as f32) < grass_chance * 256.
+    });
+  }
+}
+
+/// Tall grass of worlds created before biomes, on 1 in 16 surface blocks
+pub struct LegacyDecorateStep;
+
+impl WorldGenStep for LegacyDecorateStep {
+  type Config = ();
+  fn initialize(_: &WorldGenerator, _: &mut SeedThingy, _: &()) -> Self { Self }
+
+  fn generate(&mut self, gen: &mut WorldGenerator) {
+    place_tall_grass(gen, |_, _, _, hash| (hash & 0xf) == 0xf);
+  }
+}
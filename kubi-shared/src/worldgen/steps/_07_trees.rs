@@ -0,0 +1,78 @@
+use fastnoise_lite::{FastNoiseLite, NoiseType};
+use glam::{ivec3, IVec3};
+use serde::{Serialize, Deserialize};
+use crate::{chunk::CHUNK_SIZE, worldgen::SeedThingy};
+use super::_02_water::WATER_LEVEL;
+use crate::worldgen::{
+  WorldGenStep, WorldGenerator,
+  biome::Biome,
+  structures::{Structure, TreeStructure},
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TreesConfig {
+  /// Frequency of the density noise (higher = smaller forests)
+  pub frequency: f32,
+  /// Multiplier of the tree density
+  pub density: f32,
+}
+
+impl Default for TreesConfig {
+  fn default() -> Self {
+    Self {
+      frequency: 0.008,
+      density: 1.,
+    }
+  }
+}
+
+pub struct TreesStep {
+  density_noise: FastNoiseLite,
+  density: f32,
+}
+
+impl WorldGenStep for TreesStep {
+  type Config = TreesConfig;
+
+  fn initialize(_: &WorldGenerator, seeder: &mut SeedThingy, config: &TreesConfig) -> Self {
+    let mut density_noise = FastNoiseLite::with_seed(seeder.next_seed());
+    density_noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+    density_noise.set_frequency(Some(config.frequency));
+    Self { density_noise, density: config.density }
+  }
+
+  fn generate(&mut self, gen: &mut WorldGenerator) {
+    //trees grow on the terrain surface
+    if gen.data.height_sampler.is_none() { return }
+
+    //the density is at most 1 * the highest biome density, columns with a hash above that can't have a tree
+    let max_density = Biome::ALL.iter().map(|biome| biome.params().tree_density).fold(0., f32::max) * self.density;
+    let max_threshold = (max_density * 7.).round() as u64;
+
+    //trees growing in nearby columns of the neighbouring chunks may reach into this one
+    let chunk_min = gen.offset();
+    let chunk_max = chunk_min + IVec3::splat(CHUNK_SIZE as i32);
+    for x in chunk_min.x - TreeStructure::RADIUS..chunk_max.x + TreeStructure::RADIUS {
+      for z in chunk_min.z - TreeStructure::RADIUS..chunk_max.z + TreeStructure::RADIUS {
+        let hash = gen.seeded_hash((x, z, 0x060)) & 0xff;
+        if hash >= max_threshold { continue }
+
+        let biome_density = gen.biome_weights_at(x, z).blend(|biome| biome.tree_density);
+        let mut density = self.density_noise.get_noise_2d(x as f64, z as f64) * 0.5 + 0.5;
+        density = density.powi(3) * biome_density * self.density;
+        if hash >= (density * 7.).round() as u64 { continue }
+
+        let Some(terrain_height) = gen.surface_height(x, z) else { continue };
+        if terrain_height < WATER_LEVEL { continue }
+
+        let tree = TreeStructure::default();
+        let root = ivec3(x, terrain_height, z);
+        let (min, max) = tree.bounds(root);
+        if min.y < chunk_max.y && max.y > chunk_min.y {
+          tree.place(gen, root);
+        }
+      }
+    }
+  }
+}
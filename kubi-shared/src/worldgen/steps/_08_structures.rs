@@ -0,0 +1,34 @@
+use serde::{Serialize, Deserialize};
+use super::super::{structures::StructureKind, SeedThingy, WorldGenStep, WorldGenerator};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct StructuresConfig {
+  /// Kinds of structures to generate
+  pub structures: Vec<StructureKind>,
+}
+
+impl Default for StructuresConfig {
+  fn default() -> Self {
+    Self { structures: StructureKind::ALL.to_vec() }
+  }
+}
+
+/// Places the parts of multi-chunk structures that intersect the chunk
+pub struct StructuresStep {
+  structures: Vec<StructureKind>,
+}
+
+impl WorldGenStep for StructuresStep {
+  type Config = StructuresConfig;
+
+  fn initialize(_: &WorldGenerator, _: &mut SeedThingy, config: &StructuresConfig) -> Self {
+    Self { structures: config.structures.clone() }
+  }
+
+  fn generate(&mut self, gen: &mut WorldGenerator) {
+    for &kind in &self.structures {
+      kind.place(gen);
+    }
+  }
+}
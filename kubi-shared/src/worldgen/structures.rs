@@ -1,9 +1,132 @@
-use glam::IVec3;
+//! Structures
+//!
+//! Small structures (like trees) are placed from a single position with [`Structure`]:\
+//! the step placing them also visits the positions in the neighbouring chunks close enough to reach into the chunk,
+//! and only the blocks inside of the chunk are placed (see [`WorldGenerator::place_global`]).
+//!
+//! Large structures (villages, ruins, dungeons...) implement [`MultiChunkStructure`] instead:\
+//! the world is divided into square regions, each one containing at most one start of each kind of structure,
+//! decided by the seed alone.\
+//! Every chunk computes the pieces of the structures starting in nearby regions
+//! and places the parts of the pieces that intersect it,
+//! so structures don't depend on the order in which chunks are generated
+
+use glam::{ivec2, ivec3, IVec2, IVec3};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+use serde::{Serialize, Deserialize};
+use crate::chunk::CHUNK_SIZE;
 use super::WorldGenerator;
 
 mod tree;
+mod template;
+mod village;
+mod ruins;
+mod dungeon;
+
 pub use tree::TreeStructure;
+pub use template::{StructureTemplate, StructureTemplates, TemplateKind, TemplatePiece};
+pub use village::Village;
+pub use ruins::Ruins;
+pub use dungeon::Dungeon;
 
+/// Small structure placed from a single position, see the module docs
 pub trait Structure {
+  /// Place the structure at `root_pos` (global coordinates), only blocks inside of the current chunk are placed
   fn place(&self, gen: &mut WorldGenerator, root_pos: IVec3);
 }
+
+/// Part of a multi-chunk structure
+pub trait StructurePiece {
+  /// Bounding box of the piece in global coordinates (min inclusive, max exclusive)
+  fn bounds(&self) -> (IVec3, IVec3);
+
+  /// Place the piece, only blocks inside of the current chunk are placed\
+  /// (see [`WorldGenerator::place_global`])
+  fn place(&self, gen: &mut WorldGenerator);
+}
+
+pub type StructurePieces = Vec<Box<dyn StructurePiece>>;
+
+/// Structure spanning multiple chunks, see the module docs
+pub trait MultiChunkStructure {
+  /// Size of the regions, in blocks
+  const SPACING: i32;
+  /// Max horizontal distance between the start of the structure and its blocks\
+  /// (blocks further away may be cut off)
+  const RADIUS: i32;
+  /// Chance of a region containing the structure
+  const CHANCE: f32;
+  /// Mixed into the hash, so that different kinds of structures don't start at the same places
+  const SALT: u64;
+
+  /// Lay out the pieces of a structure starting at the `start` column (global x, z)\
+  /// Returns `None` if the structure can't be generated there (e.g. in water)
+  ///
+  /// This is called by every chunk near the start, so the result must only depend on the seed,
+  /// the world generator samplers and the `rng`
+  fn pieces(gen: &WorldGenerator, start: IVec2, rng: &mut Xoshiro256StarStar) -> Option<StructurePieces>;
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum StructureKind {
+  Village,
+  Ruins,
+  Dungeon,
+}
+
+impl StructureKind {
+  pub const ALL: [Self; 3] = [Self::Village, Self::Ruins, Self::Dungeon];
+
+  /// Place the parts of the structures of this kind that intersect the chunk
+  pub(super) fn place(self, gen: &mut WorldGenerator) {
+    match self {
+      Self::Village => place_structures::<Village>(gen),
+      Self::Ruins => place_structures::<Ruins>(gen),
+      Self::Dungeon => place_structures::<Dungeon>(gen),
+    }
+  }
+}
+
+fn place_structures<T: MultiChunkStructure>(gen: &mut WorldGenerator) {
+  let chunk_min = gen.offset();
+  let chunk_max = chunk_min + IVec3::splat(CHUNK_SIZE as i32);
+
+  //regions which may contain a start close enough to reach the chunk
+  let spacing = IVec2::splat(T::SPACING);
+  let region_min = (ivec2(chunk_min.x, chunk_min.z) - T::RADIUS).div_euclid(spacing);
+  let region_max = (ivec2(chunk_max.x, chunk_max.z) + T::RADIUS).div_euclid(spacing);
+
+  for region_x in region_min.x..=region_max.x {
+    for region_z in region_min.y..=region_max.y {
+      let hash = gen.seeded_hash((region_x, region_z, T::SALT));
+      if (hash & 0xffff) as f32 >= T::CHANCE * 65536. { continue }
+
+      let mut rng = Xoshiro256StarStar::seed_from_u64(hash);
+      let start = ivec2(region_x, region_z) * spacing + ivec2(
+        rng.gen_range(0..T::SPACING),
+        rng.gen_range(0..T::SPACING),
+      );
+      let Some(pieces) = T::pieces(gen, start, &mut rng) else { continue };
+
+      for piece in pieces {
+        let (min, max) = piece.bounds();
+        if min.cmplt(chunk_max).all() && max.cmpgt(chunk_min).all() {
+          piece.place(gen);
+        }
+      }
+    }
+  }
+}
+
+/// Rotate a position inside of a box of `size` (x, z) by `rotation` quarter turns
+fn rotate(position: IVec3, size: IVec2, rotation: u8) -> IVec3 {
+  let IVec3 { x, y, z } = position;
+  match rotation & 3 {
+    0 => ivec3(x, y, z),
+    1 => ivec3(size.y - 1 - z, y, x),
+    2 => ivec3(size.x - 1 - x, y, size.y - 1 - z),
+    _ => ivec3(z, y, size.x - 1 - x),
+  }
+}
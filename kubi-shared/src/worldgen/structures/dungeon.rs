@@ -0,0 +1,69 @@
+use glam::{ivec3, IVec2, IVec3};
+use rand::Rng;
+use rand_xoshiro::Xoshiro256StarStar;
+use crate::{block::Block, worldgen::WorldGenerator};
+use super::{MultiChunkStructure, StructurePiece, StructurePieces};
+
+/// Min amount of blocks between the ceiling of the dungeon and the surface
+const MIN_COVER: i32 = 6;
+
+/// Dungeon: an underground cobblestone room, lit by a torch
+pub struct Dungeon;
+
+impl MultiChunkStructure for Dungeon {
+  const SPACING: i32 = 64;
+  const RADIUS: i32 = 8;
+  const CHANCE: f32 = 0.4;
+  const SALT: u64 = 0xd0e;
+
+  fn pieces(gen: &WorldGenerator, start: IVec2, rng: &mut Xoshiro256StarStar) -> Option<StructurePieces> {
+    //walls included
+    let size = ivec3(rng.gen_range(5..=9), 5, rng.gen_range(5..=9));
+    let y = rng.gen_range(-48..-12);
+    let min = ivec3(start.x - size.x / 2, y, start.y - size.z / 2);
+    let room = DungeonRoom { min, max: min + size };
+
+    //check all corners, terrain may be lower than at the center
+    let surface = [
+      (room.min.x, room.min.z), (room.max.x - 1, room.min.z),
+      (room.min.x, room.max.z - 1), (room.max.x - 1, room.max.z - 1),
+    ].into_iter().map(|(x, z)| gen.surface_height(x, z)).min()??;
+    if room.max.y + MIN_COVER > surface { return None }
+
+    let pieces: StructurePieces = vec![Box::new(room)];
+    Some(pieces)
+  }
+}
+
+/// Room with cobblestone walls, floor and ceiling
+pub struct DungeonRoom {
+  min: IVec3,
+  max: IVec3,
+}
+
+impl StructurePiece for DungeonRoom {
+  fn bounds(&self) -> (IVec3, IVec3) {
+    (self.min, self.max)
+  }
+
+  fn place(&self, gen: &mut WorldGenerator) {
+    for x in self.min.x..self.max.x {
+      for y in self.min.y..self.max.y {
+        for z in self.min.z..self.max.z {
+          let position = ivec3(x, y, z);
+          let is_wall = position.cmpeq(self.min).any() || position.cmpeq(self.max - 1).any();
+          //walls are only placed in solid ground, so that they don't stick out into caves
+          if is_wall {
+            if gen.query_global(position).is_some_and(|block| block != Block::Air && block != Block::Water) {
+              gen.place_global(position, Block::Cobblestone);
+            }
+          } else {
+            gen.place_global(position, Block::Air);
+          }
+        }
+      }
+    }
+    let center = (self.min + self.max) / 2;
+    gen.place_global(ivec3(center.x, self.min.y + 1, center.z), Block::Torch);
+  }
+}
@@ -0,0 +1,28 @@
+use glam::{ivec3, IVec2};
+use rand::Rng;
+use rand_xoshiro::Xoshiro256StarStar;
+use crate::worldgen::{steps::_02_water::WATER_LEVEL, WorldGenerator};
+use super::{MultiChunkStructure, StructurePieces, TemplateKind, TemplatePiece};
+
+/// Ruins: a partially buried and decayed building
+pub struct Ruins;
+
+impl MultiChunkStructure for Ruins {
+  const SPACING: i32 = 192;
+  const RADIUS: i32 = 16;
+  const CHANCE: f32 = 0.35;
+  const SALT: u64 = 0x4a1e5;
+
+  fn pieces(gen: &WorldGenerator, start: IVec2, rng: &mut Xoshiro256StarStar) -> Option<StructurePieces> {
+    let height = gen.surface_height(start.x, start.y)?;
+    if height <= WATER_LEVEL { return None }
+
+    let template = gen.templates.random(TemplateKind::Ruins, rng)?;
+    let depth = rng.gen_range(0..=2);
+    let integrity = rng.gen_range(0.6..0.85);
+    let ruin = TemplatePiece::centered(template, ivec3(start.x, height - 1 - depth, start.y), rng.gen_range(0..4))
+      .with_integrity(integrity);
+    let pieces: StructurePieces = vec![Box::new(ruin)];
+    Some(pieces)
+  }
+}
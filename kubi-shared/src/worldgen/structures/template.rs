@@ -0,0 +1,415 @@
+//! Structure templates, blocks of a building loaded from a file
+//!
+//! Template files are TOML:
+//! ```toml
+//! name = "well"
+//! kind = "village_center"
+//! foundation = "cobblestone"
+//! palette = { c = "cobblestone", w = "water", p = "planks" }
+//! layers = [
+//!   ["ccc", "cwc", "ccc"],
+//!   ["c c", "   ", "c c"],
+//!   ["ppp", "ppp", "ppp"],
+//! ]
+//! ```
+//! Layers go from the bottom to the top, each layer is a list of rows along +z,
+//! each row a string of palette characters along +x.\
+//! Spaces leave the existing block unchanged, `.` is always air.\
+//! The bottom layer replaces the surface block, the optional foundation block fills the gap
+//! between the bottom layer and the terrain below it
+
+use std::{ffi::OsStr, fs, hash::Hasher, path::Path, sync::Arc};
+use glam::{ivec2, ivec3, IVec2, IVec3};
+use hashbrown::HashMap;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+use anyhow::{Context, Result, bail, ensure};
+use crate::{block::Block, worldgen::{hash::StableHasher, WorldGenerator}};
+use super::{rotate, StructurePiece};
+
+/// Templates shipped with the game
+const BUILTIN_TEMPLATES: &[&str] = &[
+  include_str!("../../../../assets/structures/well.toml"),
+  include_str!("../../../../assets/structures/small_house.toml"),
+  include_str!("../../../../assets/structures/large_house.toml"),
+  include_str!("../../../../assets/structures/ruin.toml"),
+];
+
+/// Max depth of the foundation below the bottom layer
+const MAX_FOUNDATION_DEPTH: i32 = 8;
+
+/// Decides which structures use the template
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateKind {
+  VillageCenter,
+  VillageHouse,
+  Ruins,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TemplateFile {
+  name: String,
+  kind: TemplateKind,
+  #[serde(default)]
+  foundation: Option<String>,
+  palette: HashMap<char, String>,
+  layers: Vec<Vec<String>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TemplateCell {
+  Keep,
+  Air,
+  Block(u8),
+}
+
+#[derive(Clone, Debug)]
+pub struct StructureTemplate {
+  pub name: String,
+  pub kind: TemplateKind,
+  /// Size along x, y, z
+  pub size: IVec3,
+  /// Block names, unknown blocks are skipped when placing the template
+  palette: Vec<String>,
+  foundation: Option<String>,
+  /// Indexed by `(y * size.z + z) * size.x + x`
+  cells: Vec<TemplateCell>,
+}
+
+impl StructureTemplate {
+  pub fn parse(data: &str) -> Result<Self> {
+    let file: TemplateFile = toml::from_str(data)?;
+    ensure!(!file.layers.is_empty(), "template {:?} has no layers", file.name);
+
+    let mut palette_chars = HashMap::new();
+    let mut palette = Vec::with_capacity(file.palette.len());
+    //sorted, so that the cells (and the content hash) don't depend on the map order
+    let mut entries: Vec<(char, String)> = file.palette.into_iter().collect();
+    entries.sort();
+    for (symbol, block) in entries {
+      ensure!(symbol != ' ' && symbol != '.', "{:?} can't be used in the palette of {:?}", symbol, file.name);
+      ensure!(palette.len() < u8::MAX as usize, "palette of {:?} is too large", file.name);
+      palette_chars.insert(symbol, palette.len() as u8);
+      palette.push(block);
+    }
+
+    let size = ivec3(
+      file.layers.iter().flatten().map(|row| row.chars().count()).max().unwrap_or(0) as i32,
+      file.layers.len() as i32,
+      file.layers.iter().map(|layer| layer.len()).max().unwrap_or(0) as i32,
+    );
+    ensure!(size.x > 0 && size.z > 0, "template {:?} is empty", file.name);
+
+    //short rows and layers are padded with unchanged blocks
+    let mut cells = vec![TemplateCell::Keep; (size.x * size.y * size.z) as usize];
+    for (y, layer) in file.layers.iter().enumerate() {
+      for (z, row) in layer.iter().enumerate() {
+        for (x, symbol) in row.chars().enumerate() {
+          let cell = match symbol {
+            ' ' => TemplateCell::Keep,
+            '.' => TemplateCell::Air,
+            _ => match palette_chars.get(&symbol) {
+              Some(&index) => TemplateCell::Block(index),
+              None => bail!("unknown character {:?} in template {:?}", symbol, file.name),
+            },
+          };
+          cells[(y * size.z as usize + z) * size.x as usize + x] = cell;
+        }
+      }
+    }
+
+    Ok(Self {
+      name: file.name,
+      kind: file.kind,
+      size,
+      palette,
+      foundation: file.foundation,
+      cells,
+    })
+  }
+
+  fn cell(&self, position: IVec3) -> TemplateCell {
+    self.cells[((position.y * self.size.z + position.z) * self.size.x + position.x) as usize]
+  }
+
+  /// Size after rotating by `rotation` quarter turns
+  pub fn rotated_size(&self, rotation: u8) -> IVec3 {
+    match rotation & 1 {
+      0 => self.size,
+      _ => ivec3(self.size.z, self.size.y, self.size.x),
+    }
+  }
+}
+
+/// Set of templates structures pick from
+#[derive(Clone, Debug, Default)]
+pub struct StructureTemplates {
+  templates: Vec<Arc<StructureTemplate>>,
+}
+
+impl StructureTemplates {
+  /// Templates shipped with the game
+  pub fn builtin() -> Self {
+    let mut templates = Self::default();
+    for data in BUILTIN_TEMPLATES {
+      templates.add(StructureTemplate::parse(data).expect("built-in structure template is invalid"));
+    }
+    templates
+  }
+
+  /// Built-in templates, plus all `.toml` files in the directory\
+  /// Templates with the same name as a built-in one replace it
+  pub fn load_dir(path: &Path) -> Result<Self> {
+    let mut paths = fs::read_dir(path)?
+      .map(|entry| entry.map(|entry| entry.path()))
+      .collect::<Result<Vec<_>, _>>()?;
+    //keep the order stable, as structures pick templates by their index
+    paths.sort();
+
+    let mut templates = Self::builtin();
+    for path in paths {
+      if path.extension() != Some(OsStr::new("toml")) { continue }
+      let data = fs::read_to_string(&path)?;
+      let template = StructureTemplate::parse(&data).with_context(|| format!("invalid structure template {:?}", path))?;
+      templates.add(template);
+    }
+    Ok(templates)
+  }
+
+  /// Add a template, replacing the one with the same name
+  pub fn add(&mut self, template: StructureTemplate) {
+    let template = Arc::new(template);
+    match self.templates.iter_mut().find(|existing| existing.name == template.name) {
+      Some(existing) => *existing = template,
+      None => self.templates.push(template),
+    }
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Arc<StructureTemplate>> {
+    self.templates.iter().find(|template| template.name == name)
+  }
+
+  pub fn of_kind(&self, kind: TemplateKind) -> impl Iterator<Item = &Arc<StructureTemplate>> {
+    self.templates.iter().filter(move |template| template.kind == kind)
+  }
+
+  /// Pick a random template of the `kind`
+  pub fn random(&self, kind: TemplateKind, rng: &mut impl Rng) -> Option<Arc<StructureTemplate>> {
+    let count = self.of_kind(kind).count();
+    if count == 0 { return None }
+    self.of_kind(kind).nth(rng.gen_range(0..count)).cloned()
+  }
+
+  pub fn len(&self) -> usize {
+    self.templates.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.templates.is_empty()
+  }
+
+  /// Hash of the contents and order of the templates, stored in the save file to detect changes\
+  /// (different templates change the layout of structures, see [`WorldSaveFile::check_structure_templates`](crate::data::WorldSaveFile::check_structure_templates))
+  pub fn content_hash(&self) -> u64 {
+    fn write_str(hasher: &mut StableHasher, string: &str) {
+      hasher.write_usize(string.len());
+      hasher.write(string.as_bytes());
+    }
+    let mut hasher = StableHasher::new(0);
+    for template in &self.templates {
+      write_str(&mut hasher, &template.name);
+      hasher.write_u8(template.kind as u8);
+      hasher.write_i32(template.size.x);
+      hasher.write_i32(template.size.y);
+      hasher.write_i32(template.size.z);
+      hasher.write_usize(template.palette.len());
+      for block in &template.palette {
+        write_str(&mut hasher, block);
+      }
+      hasher.write_u8(template.foundation.is_some() as u8);
+      write_str(&mut hasher, template.foundation.as_deref().unwrap_or_default());
+      for cell in &template.cells {
+        hasher.write_u16(match cell {
+          TemplateCell::Keep => 0,
+          TemplateCell::Air => 1,
+          TemplateCell::Block(index) => 2 + *index as u16,
+        });
+      }
+    }
+    hasher.finish()
+  }
+}
+
+/// Placed structure template
+pub struct TemplatePiece {
+  pub template: Arc<StructureTemplate>,
+  /// Global position of the min corner (after rotation)
+  pub origin: IVec3,
+  /// Quarter turns around the y axis
+  pub rotation: u8,
+  /// Chance of each block being placed, lower values make the structure look decayed
+  pub integrity: f32,
+}
+
+impl TemplatePiece {
+  /// Template with the center of its bottom layer at `position`
+  pub fn centered(template: Arc<StructureTemplate>, position: IVec3, rotation: u8) -> Self {
+    let size = template.rotated_size(rotation);
+    Self {
+      template,
+      origin: position - ivec3(size.x / 2, 0, size.z / 2),
+      rotation,
+      integrity: 1.,
+    }
+  }
+
+  pub fn with_integrity(self, integrity: f32) -> Self {
+    Self { integrity, ..self }
+  }
+
+  /// Horizontal bounds (min inclusive, max exclusive)
+  pub fn bounds_xz(&self) -> (IVec2, IVec2) {
+    let size = self.template.rotated_size(self.rotation);
+    (ivec2(self.origin.x, self.origin.z), ivec2(self.origin.x + size.x, self.origin.z + size.z))
+  }
+}
+
+impl StructurePiece for TemplatePiece {
+  fn bounds(&self) -> (IVec3, IVec3) {
+    let foundation_depth = if self.template.foundation.is_some() { MAX_FOUNDATION_DEPTH } else { 0 };
+    let min = self.origin - IVec3::Y * foundation_depth;
+    (min, self.origin + self.template.rotated_size(self.rotation))
+  }
+
+  fn place(&self, gen: &mut WorldGenerator) {
+    let template = &self.template;
+    let palette: Vec<Option<Block>> = template.palette.iter().map(|name| gen.block_by_name(name)).collect();
+    let size_xz = ivec2(template.size.x, template.size.z);
+
+    for y in 0..template.size.y {
+      for z in 0..template.size.z {
+        for x in 0..template.size.x {
+          let block = match template.cell(ivec3(x, y, z)) {
+            TemplateCell::Keep => continue,
+            TemplateCell::Air => Block::Air,
+            TemplateCell::Block(index) => match palette[index as usize] {
+              Some(block) => block,
+              None => continue,
+            },
+          };
+          let position = self.origin + rotate(ivec3(x, y, z), size_xz, self.rotation);
+          if self.integrity < 1. && (gen.seeded_hash((position.x, position.y, position.z, 0x451)) & 0xff) as f32 >= self.integrity * 256. {
+            continue
+          }
+          gen.place_global(position, block);
+        }
+      }
+    }
+
+    //fill the gap below the bottom layer
+    let Some(foundation) = template.foundation.as_deref().and_then(|name| gen.block_by_name(name)) else { return };
+    for z in 0..template.size.z {
+      for x in 0..template.size.x {
+        if template.cell(ivec3(x, 0, z)) == TemplateCell::Keep { continue }
+        let position = self.origin + rotate(ivec3(x, 0, z), size_xz, self.rotation);
+        let surface = gen.surface_height(position.x, position.z).unwrap_or(position.y);
+        for y in surface.max(position.y - MAX_FOUNDATION_DEPTH)..position.y {
+          gen.place_global(ivec3(position.x, y, position.z), foundation);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::ivec3;
+  use super::{StructureTemplate, StructureTemplates, TemplateCell, TemplateKind};
+
+  const HUT: &str = r#"
+    name = "hut"
+    kind = "village_house"
+    foundation = "cobblestone"
+    palette = { p = "planks", w = "wood" }
+    layers = [
+      ["ppp", "p.p", "ppp"],
+      ["w w", "", "w"],
+    ]
+  "#;
+
+  #[test]
+  fn template_cells() {
+    let template = StructureTemplate::parse(HUT).unwrap();
+    assert_eq!((template.name.as_str(), template.kind), ("hut", TemplateKind::VillageHouse));
+    assert_eq!(template.size, ivec3(3, 2, 3));
+    assert_eq!(template.foundation.as_deref(), Some("cobblestone"));
+
+    let planks = template.palette.iter().position(|block| block == "planks").unwrap() as u8;
+    let wood = template.palette.iter().position(|block| block == "wood").unwrap() as u8;
+    assert_eq!(template.cell(ivec3(0, 0, 0)), TemplateCell::Block(planks));
+    assert_eq!(template.cell(ivec3(1, 0, 1)), TemplateCell::Air);
+    assert_eq!(template.cell(ivec3(2, 1, 0)), TemplateCell::Block(wood));
+    assert_eq!(template.cell(ivec3(1, 1, 0)), TemplateCell::Keep);
+    //short rows and missing rows are padded with unchanged blocks
+    assert_eq!(template.cell(ivec3(1, 1, 1)), TemplateCell::Keep);
+    assert_eq!(template.cell(ivec3(2, 1, 2)), TemplateCell::Keep);
+    assert_eq!(template.rotated_size(1), ivec3(3, 2, 3));
+  }
+
+  #[test]
+  fn invalid_templates() {
+    let invalid = [
+      //unknown character
+      r#"name = "a"
+      kind = "ruins"
+      palette = { p = "planks" }
+      layers = [["px"]]"#,
+      //no layers
+      r#"name = "b"
+      kind = "ruins"
+      palette = {}
+      layers = []"#,
+      //empty layers
+      r#"name = "c"
+      kind = "ruins"
+      palette = {}
+      layers = [[]]"#,
+      //reserved palette character
+      r#"name = "d"
+      kind = "ruins"
+      palette = { "." = "planks" }
+      layers = [["."]]"#,
+      //unknown kind
+      r#"name = "e"
+      kind = "castle"
+      palette = {}
+      layers = [["."]]"#,
+    ];
+    for data in invalid {
+      assert!(StructureTemplate::parse(data).is_err(), "template should be invalid: {data}");
+    }
+  }
+
+  #[test]
+  fn templates_replace_by_name() {
+    let mut templates = StructureTemplates::builtin();
+    assert!(!templates.is_empty());
+    let count = templates.len();
+    let hash = templates.content_hash();
+    assert_eq!(StructureTemplates::builtin().content_hash(), hash);
+
+    templates.add(StructureTemplate::parse(HUT).unwrap());
+    assert_eq!(templates.len(), count + 1);
+    let with_hut = templates.content_hash();
+    assert_ne!(with_hut, hash);
+
+    //a template with the same name replaces the existing one
+    templates.add(StructureTemplate::parse(&HUT.replace("w w", "www")).unwrap());
+    assert_eq!(templates.len(), count + 1);
+    assert_eq!(templates.get("hut").unwrap().cell(ivec3(1, 1, 0)), TemplateCell::Block(
+      templates.get("hut").unwrap().palette.iter().position(|block| block == "wood").unwrap() as u8
+    ));
+    assert_ne!(templates.content_hash(), with_hut);
+  }
+}
@@ -13,17 +13,29 @@ impl Default for TreeStructure {
   }
 }
 
+impl TreeStructure {
+  /// Max horizontal distance between the stem and the leaves
+  pub const RADIUS: i32 = 2;
+
+  /// Bounding box of a tree growing at `root` (min inclusive, max exclusive)
+  pub fn bounds(&self, root: IVec3) -> (IVec3, IVec3) {
+    (
+      root - IVec3::new(Self::RADIUS, 1, Self::RADIUS),
+      root + IVec3::new(Self::RADIUS + 1, self.height + 2, Self::RADIUS + 1),
+    )
+  }
+}
+
 impl Structure for TreeStructure {
   fn place(&self, gen: &mut WorldGenerator, root: IVec3) {
     //check the block below the tree, if it's grass, replace it with dirt
-    //XXX: This won't work if root.y == 0
-    if root.y != 0 && gen.query(root - IVec3::Y) == Block::Grass {
-      gen.place(root - IVec3::Y, Block::Dirt);
+    if gen.query_global(root - IVec3::Y) == Some(Block::Grass) {
+      gen.place_global(root - IVec3::Y, Block::Dirt);
     }
 
     //Tree stem
     for y in root.y..root.y + self.height {
-      gen.place_or_queue(IVec3::new(root.x, y, root.z), Block::Wood);
+      gen.place_global(IVec3::new(root.x, y, root.z), Block::Wood);
     }
 
     //Tree leaves
@@ -35,8 +47,8 @@ impl Structure for TreeStructure {
     //   |
 
     for y in 0..=4_i32 {
-      for x in -2..=2_i32 {
-        for z in -2..=2_i32 {
+      for x in -Self::RADIUS..=Self::RADIUS {
+        for z in -Self::RADIUS..=Self::RADIUS {
           //Do not overwrite the stem
           if y < 3 && x == 0 && z == 0 {
             continue
@@ -50,7 +62,7 @@ impl Structure for TreeStructure {
             root.y + self.height - 3 + y,
             root.z + z
           );
-          gen.place_or_queue(position, Block::Leaf);
+          gen.place_global(position, Block::Leaf);
         }
       }
     }
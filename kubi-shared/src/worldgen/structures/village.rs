@@ -0,0 +1,109 @@
+use std::f32::consts::TAU;
+use glam::{ivec2, ivec3, IVec2, IVec3};
+use rand::Rng;
+use rand_xoshiro::Xoshiro256StarStar;
+use crate::{
+  block::Block,
+  worldgen::{biome::Biome, steps::_02_water::WATER_LEVEL, WorldGenerator},
+};
+use super::{MultiChunkStructure, StructurePiece, StructurePieces, TemplateKind, TemplatePiece};
+
+/// Houses are placed this far from the center, so that the largest templates stay within `RADIUS`
+const HOUSE_DISTANCE: (i32, i32) = (10, 30);
+
+/// Max height difference between the village center and a house
+const MAX_SLOPE: i32 = 6;
+
+/// Village: a center (like a well) surrounded by houses, connected by cobblestone paths
+pub struct Village;
+
+impl MultiChunkStructure for Village {
+  const SPACING: i32 = 384;
+  const RADIUS: i32 = 48;
+  const CHANCE: f32 = 0.5;
+  const SALT: u64 = 0x7111a9e;
+
+  fn pieces(gen: &WorldGenerator, start: IVec2, rng: &mut Xoshiro256StarStar) -> Option<StructurePieces> {
+    if !matches!(gen.biome_at(start.x, start.y), Biome::Plains | Biome::Desert | Biome::Snowy) {
+      return None
+    }
+    let height = gen.surface_height(start.x, start.y)?;
+    if height <= WATER_LEVEL { return None }
+
+    let templates = gen.templates;
+    let center = TemplatePiece::centered(
+      templates.random(TemplateKind::VillageCenter, rng)?,
+      ivec3(start.x, height - 1, start.y),
+      rng.gen_range(0..4),
+    );
+
+    //paths go first, so that buildings are placed over them
+    let mut paths: StructurePieces = Vec::new();
+    let mut buildings: Vec<TemplatePiece> = vec![center];
+
+    let house_count = rng.gen_range(3..=6);
+    for i in 0..house_count {
+      let angle = (i as f32 + rng.gen_range(0.0..0.6)) / house_count as f32 * TAU;
+      let distance = rng.gen_range(HOUSE_DISTANCE.0..=HOUSE_DISTANCE.1) as f32;
+      let rotation = rng.gen_range(0..4);
+      let Some(template) = templates.random(TemplateKind::VillageHouse, rng) else { break };
+
+      let position = start + ivec2((angle.cos() * distance) as i32, (angle.sin() * distance) as i32);
+      let Some(house_height) = gen.surface_height(position.x, position.y) else { continue };
+      if house_height <= WATER_LEVEL || (house_height - height).abs() > MAX_SLOPE { continue }
+
+      let house = TemplatePiece::centered(template, ivec3(position.x, house_height - 1, position.y), rotation);
+      let (min, max) = house.bounds_xz();
+      let overlaps = buildings.iter().any(|other| {
+        let (other_min, other_max) = other.bounds_xz();
+        min.cmplt(other_max + 1).all() && max.cmpgt(other_min - 1).all()
+      });
+      if overlaps { continue }
+
+      paths.push(Box::new(PathPiece::new(gen, start, position)));
+      buildings.push(house);
+    }
+
+    let mut pieces = paths;
+    pieces.extend(buildings.into_iter().map(|building| Box::new(building) as Box<dyn StructurePiece>));
+    Some(pieces)
+  }
+}
+
+/// Cobblestone path following the terrain
+pub struct PathPiece {
+  blocks: Vec<IVec3>,
+}
+
+impl PathPiece {
+  fn new(gen: &WorldGenerator, from: IVec2, to: IVec2) -> Self {
+    let steps = (to - from).abs().max_element();
+    let blocks = (0..=steps).filter_map(|step| {
+      let t = step as f32 / steps.max(1) as f32;
+      let xz = from + ((to - from).as_vec2() * t).round().as_ivec2();
+      let height = gen.surface_height(xz.x, xz.y)?;
+      //don't build paths on water
+      (height > WATER_LEVEL).then_some(ivec3(xz.x, height - 1, xz.y))
+    }).collect();
+    Self { blocks }
+  }
+}
+
+impl StructurePiece for PathPiece {
+  fn bounds(&self) -> (IVec3, IVec3) {
+    //the block above the path is cleared too
+    self.blocks.iter().fold(
+      (IVec3::MAX, IVec3::MIN),
+      |(min, max), &block| (min.min(block), max.max(block + ivec3(1, 2, 1))),
+    )
+  }
+
+  fn place(&self, gen: &mut WorldGenerator) {
+    for &block in &self.blocks {
+      gen.place_global(block, Block::Cobblestone);
+      if gen.query_global(block + IVec3::Y) == Some(Block::TallGrass) {
+        gen.place_global(block + IVec3::Y, Block::Air);
+      }
+    }
+  }
+}
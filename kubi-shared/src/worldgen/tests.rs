@@ -0,0 +1,290 @@
+//! World generation regression tests
+//!
+//! `golden.txt` contains hashes of chunks generated with every built-in preset, for a few seeds and positions.\
+//! Any change to the world generator that changes generated worlds fails `golden_hashes`,
+//! if the change is intended, run the tests with `KUBI_BLESS_WORLDGEN=1` to update the file
+//! (and mention it in the changelog, as existing worlds will have seams at the borders of new chunks)
+//!
+//! Float functions like `exp` and `sin` come from the platform's libm,
+//! so the hashes are only guaranteed to match on the same platform, `golden_hashes` only runs on x86_64 Linux.\
+//! The legacy preset is also checked with [`HashVersion::Legacy`], which existing legacy worlds use
+
+use std::{env, fs, hash::{Hash, Hasher}, sync::LazyLock};
+use glam::{ivec3, IVec3};
+use hashbrown::HashMap;
+use crate::{block::{Block, BlockRegistry, BlockState}, chunk::{BlockData, CHUNK_SIZE}, height::WorldHeight};
+use super::{
+  generate_biome_map,
+  generate_world,
+  hash::{legacy_hash, stable_hash, HashVersion, SplitMix64, StableHasher},
+  preset::{StepConfig, WorldGenPreset, WorldGenPresets, LEGACY_PRESET},
+  steps::_01_superflat::{FlatLayer, SuperflatConfig},
+  structures::StructureTemplates,
+  SeedThingy,
+};
+
+const GOLDEN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/worldgen/golden.txt");
+const BLESS_VAR: &str = "KUBI_BLESS_WORLDGEN";
+
+const SEEDS: [u64; 2] = [0, 0xfeb_face_dead_cafe];
+
+static REGISTRY: LazyLock<BlockRegistry> = LazyLock::new(BlockRegistry::builtin);
+static TEMPLATES: LazyLock<StructureTemplates> = LazyLock::new(StructureTemplates::builtin);
+
+/// Surface, underground, sky and negative coordinates
+const CHUNKS: [IVec3; 8] = [
+  ivec3(0, 0, 0),
+  ivec3(0, -1, 0),
+  ivec3(-1, -1, -1),
+  ivec3(-3, 0, 5),
+  ivec3(7, -2, -4),
+  ivec3(2, 3, 1),
+  ivec3(0, -8, 0),
+  ivec3(11, 0, -13),
+];
+
+/// Hash of everything the world generator outputs for a chunk\
+/// Blocks are hashed by name, so that changes to the block ids don't affect it
+fn chunk_hash(preset: &WorldGenPreset, seed: u64, position: IVec3) -> u64 {
+  let (blocks, queue, biomes) = generate_world(position, seed, preset, &TEMPLATES, WorldHeight::default(), &REGISTRY, None).unwrap();
+  let mut hasher = StableHasher::new(0);
+  for x in 0..CHUNK_SIZE as i32 {
+    for y in 0..CHUNK_SIZE as i32 {
+      for z in 0..CHUNK_SIZE as i32 {
+        let state = blocks.get_state(ivec3(x, y, z));
+        hasher.write(REGISTRY.get(state.block).name.as_bytes());
+        hasher.write_u16(state.properties.0);
+      }
+    }
+  }
+  for block in &queue {
+    (block.position.x, block.position.y, block.position.z).hash(&mut hasher);
+    hasher.write(REGISTRY.get(block.block_type).name.as_bytes());
+    hasher.write_u16(block.properties.0);
+    hasher.write_u8(block.soft as u8);
+  }
+  for x in 0..CHUNK_SIZE {
+    for z in 0..CHUNK_SIZE {
+      hasher.write_u8(biomes.get(x, z) as u8);
+    }
+  }
+  hasher.finish()
+}
+
+/// `preset seed x y z` of every golden hash
+fn golden_key(preset: &str, seed: u64, position: IVec3) -> String {
+  format!("{} {:#x} {} {} {}", preset, seed, position.x, position.y, position.z)
+}
+
+#[test]
+#[cfg_attr(
+  not(all(target_arch = "x86_64", target_os = "linux")),
+  ignore = "golden hashes depend on the float functions of the platform"
+)]
+fn golden_hashes() {
+  //every built-in preset with the stable hash, and the legacy preset as used by legacy worlds
+  let mut presets: Vec<(String, WorldGenPreset)> = WorldGenPresets::builtin().presets.into_iter()
+    .map(|preset| (preset.name.clone(), preset))
+    .collect();
+  presets.push((
+    format!("{}/legacy_hash", LEGACY_PRESET),
+    WorldGenPreset { hash_version: HashVersion::Legacy, ..WorldGenPreset::legacy() },
+  ));
+
+  let mut actual = Vec::new();
+  for (name, preset) in &presets {
+    for seed in SEEDS {
+      for position in CHUNKS {
+        actual.push((golden_key(name, seed, position), chunk_hash(preset, seed, position)));
+      }
+    }
+  }
+
+  if env::var_os(BLESS_VAR).is_some() {
+    let data: String = actual.iter().map(|(key, hash)| format!("{} {:016x}\n", key, hash)).collect();
+    fs::write(GOLDEN_PATH, data).unwrap();
+    return
+  }
+
+  let golden_data = fs::read_to_string(GOLDEN_PATH).unwrap_or_default();
+  let golden: HashMap<&str, &str> = golden_data.lines()
+    .filter_map(|line| line.rsplit_once(' '))
+    .collect();
+  let mismatches: Vec<String> = actual.iter()
+    .filter_map(|(key, hash)| {
+      let hash = format!("{:016x}", hash);
+      match golden.get(key.as_str()) {
+        Some(&expected) if expected == hash => None,
+        Some(&expected) => Some(format!("{}: expected {}, got {}", key, expected, hash)),
+        None => Some(format!("{}: missing, got {}", key, hash)),
+      }
+    })
+    .collect();
+  assert!(
+    mismatches.is_empty(),
+    "generated chunks don't match {}:\n{}\nif this is intended, run the tests with {}=1 to update it",
+    GOLDEN_PATH, mismatches.join("\n"), BLESS_VAR
+  );
+}
+
+#[test]
+fn generation_is_deterministic() {
+  let preset = WorldGenPreset::default();
+  for position in CHUNKS {
+    assert_eq!(
+      chunk_hash(&preset, SEEDS[1], position),
+      chunk_hash(&preset, SEEDS[1], position),
+      "chunk {} differs between runs", position
+    );
+  }
+  assert_ne!(chunk_hash(&preset, SEEDS[0], IVec3::ZERO), chunk_hash(&preset, SEEDS[1], IVec3::ZERO));
+}
+
+#[test]
+fn height_limits_are_applied() {
+  let height = WorldHeight { min: -40, max: 40, bedrock: 2 };
+  let preset = WorldGenPreset {
+    name: "height_test".into(),
+    steps: vec![StepConfig::Superflat(SuperflatConfig {
+      bottom: -100,
+      layers: vec![FlatLayer { block: "stone".into(), thickness: 200 }],
+    })],
+    hash_version: HashVersion::Stable,
+  };
+  let bedrock = REGISTRY.by_name("bedrock").unwrap();
+  for chunk_y in -3..3 {
+    let (blocks, _, _) = generate_world(ivec3(0, chunk_y, 0), SEEDS[0], &preset, &TEMPLATES, height, &REGISTRY, None).unwrap();
+    for y in 0..CHUNK_SIZE as i32 {
+      let global_y = chunk_y * CHUNK_SIZE as i32 + y;
+      let expected = match global_y {
+        global_y if !(-40..40).contains(&global_y) => Block::Air,
+        global_y if global_y < -38 => bedrock,
+        _ => Block::Stone,
+      };
+      for x in 0..CHUNK_SIZE as i32 {
+        for z in 0..CHUNK_SIZE as i32 {
+          assert_eq!(blocks.get(ivec3(x, y, z)), expected, "unexpected block at y = {}", global_y);
+        }
+      }
+    }
+  }
+}
+
+/// Generate the chunks in `order`, applying queued blocks like the game does
+/// (to chunks that are already generated, or to chunks generated later)
+fn generate_in_order(preset: &WorldGenPreset, seed: u64, order: &[IVec3]) -> HashMap<IVec3, BlockData> {
+  let mut chunks: HashMap<IVec3, BlockData> = HashMap::new();
+  let mut pending = Vec::new();
+  for &position in order {
+    let (blocks, queue, _) = generate_world(position, seed, preset, &TEMPLATES, WorldHeight::default(), &REGISTRY, None).unwrap();
+    chunks.insert(position, blocks);
+    pending.extend(queue);
+    pending.retain(|block| {
+      let chunk_position = block.position.div_euclid(IVec3::splat(CHUNK_SIZE as i32));
+      let Some(blocks) = chunks.get_mut(&chunk_position) else { return true };
+      let local = block.position.rem_euclid(IVec3::splat(CHUNK_SIZE as i32));
+      if !block.soft || blocks.get(local) == Block::Air {
+        blocks.set_state(local, block.state());
+      }
+      false
+    });
+  }
+  chunks
+}
+
+fn chunk_states(blocks: &BlockData) -> Vec<BlockState> {
+  (0..CHUNK_SIZE as i32).flat_map(|x| (0..CHUNK_SIZE as i32).flat_map(move |y| {
+    (0..CHUNK_SIZE as i32).map(move |z| blocks.get_state(ivec3(x, y, z)))
+  })).collect()
+}
+
+#[test]
+fn generation_order_does_not_matter() {
+  //structures (like trees) near chunk borders must look the same no matter which chunk is generated first,
+  //or whether the neighbouring chunk is generated at all
+  let preset = WorldGenPreset::default();
+  for seed in SEEDS {
+    for position in [ivec3(0, 0, 0), ivec3(-3, 0, 5), ivec3(11, 0, -13)] {
+      for neighbor in [position + IVec3::X, position + IVec3::Z, position + IVec3::Y] {
+        let forward = generate_in_order(&preset, seed, &[position, neighbor]);
+        let backward = generate_in_order(&preset, seed, &[neighbor, position]);
+        for chunk in [position, neighbor] {
+          let alone = generate_in_order(&preset, seed, &[chunk]);
+          assert!(
+            chunk_states(&forward[&chunk]) == chunk_states(&backward[&chunk]),
+            "chunk {} depends on the generation order (seed {:#x})", chunk, seed
+          );
+          assert!(
+            chunk_states(&forward[&chunk]) == chunk_states(&alone[&chunk]),
+            "chunk {} depends on its neighbours (seed {:#x})", chunk, seed
+          );
+        }
+      }
+    }
+  }
+}
+
+#[test]
+fn stable_hash_known_values() {
+  //computed from the SplitMix64 reference implementation, these must never change
+  assert_eq!(stable_hash(0xfeb_face_dead_cafe, (1, 2, 0x060)), 0x0f6f_db2c_d755_fb82);
+  assert_eq!(stable_hash(42, -1), 0x04f8_5115_39f8_d3ee);
+  //integers are hashed as 64-bit values, independently of their type and the platform
+  assert_eq!(stable_hash(42, -1_i8), stable_hash(42, -1_i64));
+  assert_eq!(stable_hash(42, 7_usize), stable_hash(42, 7_u64));
+  //order matters
+  assert_ne!(stable_hash(0, (1, 0)), stable_hash(0, (0, 1)));
+}
+
+#[test]
+fn seeds_known_values() {
+  let mut rng = SplitMix64::new(0);
+  assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+  assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+
+  let mut seeder = SeedThingy::new(0xfeb_face_dead_cafe);
+  assert_eq!(seeder.next_seed(), 1342386860);
+  assert_eq!(seeder.next_seed(), 50491977);
+  assert_eq!(seeder.next_seed(), 1410837028);
+
+  //worlds created before the stable hash rotate the world seed
+  let mut legacy = SeedThingy::with_version(0xfeb_face_dead_cafe, HashVersion::Legacy);
+  assert_eq!(legacy.next_seed(), (0xfeb_face_dead_cafe_u64.rotate_left(3) & 0x7fffffff) as i32);
+  assert_eq!(legacy.next_seed(), (0xfeb_face_dead_cafe_u64.rotate_left(6) & 0x7fffffff) as i32);
+
+  //named seeds don't depend on (or advance) the sequence
+  let named = seeder.named_seed("biomes/temperature");
+  assert_eq!(SeedThingy::new(0xfeb_face_dead_cafe).named_seed("biomes/temperature"), named);
+  assert_ne!(seeder.named_seed("biomes/humidity"), named);
+  assert!(named >= 0);
+}
+
+#[test]
+fn legacy_hash_known_values() {
+  //outputs of `DefaultHasher::new()` that legacy worlds were generated with, these must never change
+  assert_eq!(legacy_hash(0xfeb_face_dead_cafe, (1, 2, 0x060)), 0x23e0_1e02_dfa7_527b);
+  assert_eq!(legacy_hash(42, -1), 0xac70_f944_35b1_894c);
+  assert_eq!(legacy_hash(0, (5, -7, 0x050)), 0xfe56_bc20_1892_c6ea);
+}
+
+#[test]
+fn legacy_hash_version_is_kept() {
+  //legacy worlds must not switch to the stable hash (that would create seams at the borders of new chunks)
+  let stable = WorldGenPreset::legacy();
+  let legacy = WorldGenPreset { hash_version: HashVersion::Legacy, ..stable.clone() };
+  assert_eq!(chunk_hash(&legacy, SEEDS[1], IVec3::ZERO), chunk_hash(&legacy, SEEDS[1], IVec3::ZERO));
+  assert_ne!(chunk_hash(&legacy, SEEDS[1], IVec3::ZERO), chunk_hash(&stable, SEEDS[1], IVec3::ZERO));
+}
+
+#[test]
+fn biome_map_matches_generated_chunks() {
+  for preset in WorldGenPresets::builtin().presets {
+    for position in CHUNKS {
+      let (_, _, biomes) = generate_world(position, SEEDS[1], &preset, &TEMPLATES, WorldHeight::default(), &REGISTRY, None).unwrap();
+      assert_eq!(
+        generate_biome_map(position, SEEDS[1], &preset, &REGISTRY), biomes,
+        "biome map of chunk {} doesn't match the generated one ({})", position, preset.name
+      );
+    }
+  }
+}
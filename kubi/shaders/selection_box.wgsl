@@ -7,6 +7,7 @@ var<uniform> camera: CameraUniform;
 
 struct SboxUniform {
   position: vec3<f32>,
+  progress: f32,
 };
 
 @group(1) @binding(0)
@@ -23,5 +24,6 @@ fn vs_main(
 fn fs_main(
   @builtin(position) in: vec4<f32>,
 ) -> @location(0) vec4<f32> {
-  return vec4<f32>(0.0, 0.0, 0.0, 0.5);
+  // darken the selection box as the block is being mined
+  return vec4<f32>(0.0, 0.0, 0.0, 0.5 + 0.4 * sbox.progress);
 }
@@ -5,11 +5,38 @@ struct CameraUniform {
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
+struct ChunkOrigin {
+  origin: vec3<f32>,
+};
+
+// used by vs_main, one origin per draw call (picked with a dynamic offset)
+@group(2) @binding(0)
+var<uniform> chunk: ChunkOrigin;
+
+// used by vs_main_indirect, origins of all chunks (picked with the instance index)
+@group(2) @binding(1)
+var<storage, read> chunks: array<ChunkOrigin>;
+
+// see TextureAnimation
+struct TextureAnimation {
+  first_layer: u32,
+  frames: u32,
+  frame_time: f32,
+  _padding: u32,
+};
+
+struct BlockTextures {
+  time: f32,
+  animations: array<TextureAnimation, 256>,
+};
+
+@group(0) @binding(2)
+var<uniform> block_textures: BlockTextures;
+
+// packed vertex, see ChunkVertex for the layout
 struct VertexInput {
-  @location(0) position: vec3<f32>,
-  @location(1) normal: vec3<f32>,
-  @location(2) uv: vec2<f32>,
-  @location(3) tex_index: u32,
+  @location(0) position: u32,
+  @location(1) data: u32,
 }
 
 struct VertexOutput {
@@ -17,18 +44,104 @@ struct VertexOutput {
   @location(0) uv: vec2<f32>,
   @location(1) normal: vec3<f32>,
   @location(2) @interpolate(flat)tex_index: u32,
+  @location(3) light: f32,
 };
 
+// brightness of a light level (0-15), each level is 80% as bright as the one above
+fn light_curve(level: u32) -> f32 {
+  return max(pow(0.8, f32(15u - level)), 0.04);
+}
+
+// layer of the current frame of a texture, the first frame is in the texture's own layer
+fn texture_layer(index: u32) -> u32 {
+  let animation = block_textures.animations[index];
+  let frame = u32(block_textures.time / animation.frame_time) % animation.frames;
+  if (frame == 0u) {
+    return index;
+  }
+  return animation.first_layer + frame - 1u;
+}
+
+fn vertex(in: VertexInput, origin: vec3<f32>) -> VertexOutput {
+  let position = vec3<f32>(
+    f32(in.position & 1023u),
+    f32((in.position >> 10u) & 1023u),
+    f32((in.position >> 20u) & 1023u),
+  ) / 16.0;
+  let tex_index = in.data & 255u;
+  let light = (in.data >> 8u) & 255u;
+  let ao = (in.data >> 16u) & 3u;
+  let face = (in.data >> 18u) & 15u;
+  let rotate_uv = ((in.data >> 22u) & 1u) != 0u;
+
+  // cube faces (top, back, left, right, front, bottom), then front/back sides of the two diagonal faces
+  let s = 0.70710678;
+  var normals = array<vec3<f32>, 10>(
+    vec3<f32>(0.0, 1.0, 0.0),
+    vec3<f32>(0.0, 0.0, -1.0),
+    vec3<f32>(-1.0, 0.0, 0.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(0.0, 0.0, 1.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+    vec3<f32>(-s, 0.0, s),
+    vec3<f32>(s, 0.0, -s),
+    vec3<f32>(s, 0.0, s),
+    vec3<f32>(-s, 0.0, -s),
+  );
+  // directions the texture coordinates increase along, on each face
+  var u_axes = array<vec3<f32>, 10>(
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(0.0, 0.0, -1.0),
+    vec3<f32>(0.0, 0.0, 1.0),
+    vec3<f32>(-1.0, 0.0, 0.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+  );
+  var v_axes = array<vec3<f32>, 10>(
+    vec3<f32>(0.0, 0.0, -1.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+    vec3<f32>(0.0, 0.0, 1.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+  );
+
+  // uvs are derived from the position, so that textures repeat across merged faces
+  var uv = vec2<f32>(dot(position, u_axes[face]), dot(position, v_axes[face]));
+  if (rotate_uv) {
+    uv = vec2<f32>(uv.y, -uv.x);
+  }
+
+  var out: VertexOutput;
+  out.uv = uv;
+  out.normal = normals[face];
+  out.tex_index = texture_layer(tex_index);
+  out.light = light_curve(max((light >> 4u) & 15u, light & 15u)) * (0.55 + 0.15 * f32(ao));
+  out.clip_position = camera.view_proj * vec4<f32>(origin + position, 1.0);
+  return out;
+}
+
 @vertex
 fn vs_main(
   in: VertexInput,
 ) -> VertexOutput {
-  var out: VertexOutput;
-  out.uv = in.uv;
-  out.normal = in.normal;
-  out.tex_index = in.tex_index;
-  out.clip_position = camera.view_proj * vec4<f32>(in.position, 1.0);
-  return out;
+  return vertex(in, chunk.origin);
+}
+
+@vertex
+fn vs_main_indirect(
+  in: VertexInput,
+  @builtin(instance_index) instance: u32,
+) -> VertexOutput {
+  return vertex(in, chunks[instance].origin);
 }
 
 @group(0) @binding(0)
@@ -39,11 +152,12 @@ var s_diffuse: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-  // not actual lighting; makes it easier to distinguish unlit faces
-  let light: f32 =
+  // slightly darken faces depending on their direction, makes it easier to tell them apart
+  let shade: f32 =
     abs(in.normal.x) + .85 *
     abs(in.normal.y) + .65 *
     abs(in.normal.z);
+  let light = shade * in.light;
 
   let color: vec4<f32> =
     textureSample(t_diffuse, s_diffuse, in.uv, in.tex_index)
@@ -58,5 +172,6 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 
 @fragment
 fn fs_main_trans(in: VertexOutput) -> @location(0) vec4<f32> {
-  return textureSample(t_diffuse, s_diffuse, in.uv, in.tex_index);
+  let color = textureSample(t_diffuse, s_diffuse, in.uv, in.tex_index);
+  return vec4<f32>(color.rgb * in.light, color.a);
 }
@@ -1,9 +1,11 @@
-use shipyard::{UniqueViewMut, UniqueView, View, IntoIter, ViewMut, EntitiesViewMut, Workload, IntoWorkload};
+use glam::IVec3;
+use shipyard::{UniqueViewMut, UniqueView, View, IntoIter, ViewMut, EntitiesViewMut, Workload, IntoWorkload, Component};
 use winit::keyboard::KeyCode;
 use kubi_shared::{
-  block::Block,
+  block::{Block, BlockProperties, Facing, SharedBlockRegistry},
+  height::WorldHeight,
   queue::QueuedBlock,
-  player::PlayerHolding,
+  player::{GameMode, Inventory, PlayerHolding},
 };
 use crate::{
   player::MainPlayer,
@@ -16,8 +18,11 @@ use crate::{
     EventComponent,
     player_actions::PlayerActionEvent
   },
+  delta_time::DeltaTime,
 };
 
+/// In creative mode, number keys pick a block directly\
+/// In survival mode, they select the inventory slot with the same index
 const BLOCK_KEY_MAP: &[(KeyCode, Block)] = &[
   (KeyCode::Digit1, Block::Cobblestone),
   (KeyCode::Digit2, Block::Planks),
@@ -27,59 +32,145 @@ const BLOCK_KEY_MAP: &[(KeyCode, Block)] = &[
   (KeyCode::Digit6, Block::Stone),
   (KeyCode::Digit7, Block::Torch),
   (KeyCode::Digit8, Block::Leaf),
+  (KeyCode::Digit9, Block::Wood),
 ];
 
+/// Progress of mining the block the player is currently looking at (survival mode only)
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct MiningProgress {
+  /// Position of the block being mined
+  pub position: Option<IVec3>,
+  /// Mining progress, in range `0..=1`
+  pub progress: f32,
+}
+
 fn pick_block_with_number_keys(
   main_player: View<MainPlayer>,
+  gamemodes: View<GameMode>,
   mut holding: ViewMut<PlayerHolding>,
+  mut inventories: ViewMut<Inventory>,
   input: UniqueView<RawKbmInputState>,
+  registry: UniqueView<SharedBlockRegistry>,
 ) {
-  let Some((_, holding)) = (&main_player, &mut holding).iter().next() else { return };
-  for &(key, block) in BLOCK_KEY_MAP {
+  let Some((_, &gamemode, holding, inventory)) = (&main_player, &gamemodes, &mut holding, &mut inventories).iter().next() else { return };
+  for (index, &(key, block)) in BLOCK_KEY_MAP.iter().enumerate() {
     if input.keyboard_state.contains(key as u32) {
-      holding.0 = Some(block);
-      return
+      match gamemode.has_survival_rules() {
+        true => inventory.select(index),
+        false => holding.0 = Some(block),
+      }
+      break
     }
   }
+  //In survival mode, the player can only hold what's in their inventory
+  if gamemode.has_survival_rules() {
+    holding.0 = inventory.selected_block(&registry);
+  }
 }
 
 fn block_placement_system(
   main_player: View<MainPlayer>,
+  gamemodes: View<GameMode>,
   holding: View<PlayerHolding>,
   raycast: View<LookingAtBlock>,
+  mut inventories: ViewMut<Inventory>,
+  mut mining: ViewMut<MiningProgress>,
   input: UniqueView<Inputs>,
   prev_input: UniqueView<PrevInputs>,
+  dt: UniqueView<DeltaTime>,
+  height: UniqueView<WorldHeight>,
+  registry: UniqueView<SharedBlockRegistry>,
   mut block_event_queue: UniqueViewMut<BlockUpdateQueue>,
   mut entities: EntitiesViewMut,
   mut events: ViewMut<EventComponent>,
   mut player_events: ViewMut<PlayerActionEvent>,
 ) {
+  //get components
+  let Some((_, &gamemode, block, ray, inventory, mining)) = (
+    &main_player, &gamemodes, &holding, &raycast, &mut inventories, &mut mining
+  ).iter().next() else { return };
+
+  //Spectators can't interact with the world
+  let Some(ray) = ray.0.filter(|_| gamemode.can_modify_world()) else {
+    *mining = MiningProgress::default();
+    return
+  };
+
   let action_place = input.action_b && !prev_input.0.action_b;
-  let action_break = input.action_a && !prev_input.0.action_a;
+  let action_break = match gamemode.has_survival_rules() {
+    //In survival mode, the button has to be held until the block is mined
+    true => {
+      let hardness = registry.get(ray.block).hardness;
+      match (input.action_a && !action_place, hardness) {
+        (true, Some(hardness)) => {
+          if mining.position != Some(ray.block_position) {
+            *mining = MiningProgress {
+              position: Some(ray.block_position),
+              progress: 0.,
+            };
+            entities.add_entity(
+              (&mut events, &mut player_events),
+              (EventComponent, PlayerActionEvent::StartedMining { position: ray.block_position })
+            );
+          }
+          mining.progress += match hardness > 0. {
+            true => dt.0.as_secs_f32() / hardness,
+            false => 1.,
+          };
+          mining.progress >= 1.
+        },
+        _ => {
+          *mining = MiningProgress::default();
+          false
+        }
+      }
+    },
+    false => input.action_a && !prev_input.0.action_a,
+  };
+
   if action_place ^ action_break {
-    //get components
-    let Some((_, ray, block)) = (&main_player, &raycast, &holding).iter().next() else { return };
-    let Some(ray) = ray.0 else { return };
     //get coord and block type
-    let (place_position, place_block) = if action_place {
-      if block.0.is_none() { return }
+    let (place_position, place_block, place_properties) = if action_place {
+      let Some(place_block) = block.0 else { return };
       let position = (ray.position - ray.direction * (RAYCAST_STEP + 0.001)).floor().as_ivec3();
-      (position, block.0.unwrap())
+      //orient the block based on the face it was placed against
+      let properties = Facing::from_normal(position - ray.block_position)
+        .map(|facing| registry.get(place_block).placement_properties(facing))
+        .unwrap_or_default();
+      (position, place_block, properties)
     } else {
-      (ray.block_position, Block::Air)
+      (ray.block_position, Block::Air, BlockProperties::NONE)
     };
+    //blocks can't be changed outside of the build height or in the bedrock floor
+    if !height.can_modify(place_position.y) {
+      *mining = MiningProgress::default();
+      return
+    }
+    //update the inventory
+    if gamemode.has_survival_rules() {
+      if action_place {
+        if !inventory.take_block(place_block, &registry) { return }
+      } else {
+        *mining = MiningProgress::default();
+        if let Some(drop) = registry.get(ray.block).drops {
+          inventory.add_single(drop, &registry);
+        }
+      }
+    }
     //queue place
     block_event_queue.0.push(QueuedBlock {
       position: place_position,
       block_type: place_block,
+      properties: place_properties,
       soft: place_block != Block::Air,
     });
     //send event
     entities.add_entity(
-      (&mut events, &mut player_events), 
+      (&mut events, &mut player_events),
       (EventComponent, PlayerActionEvent::UpdatedBlock {
         position: place_position,
         block: place_block,
+        properties: place_properties,
       })
     );
   }
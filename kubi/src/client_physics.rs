@@ -2,7 +2,7 @@
 //TODO move this to shared
 use glam::{vec3, Mat4, Vec3, Vec3Swizzles};
 use shipyard::{track, AllStoragesView, Component, IntoIter, Unique, UniqueView, ViewMut};
-use kubi_shared::{block::{Block, CollisionType}, transform::Transform};
+use kubi_shared::{block::{Block, BlockRegistry, CollisionType, SharedBlockRegistry}, entity::GRAVITY, player::PLAYER_EYE_HEIGHT, transform::Transform};
 use crate::{delta_time::DeltaTime, world::ChunkStorage};
 
 #[derive(Unique)]
@@ -15,7 +15,7 @@ pub struct GlobalClPhysicsConfig {
 impl Default for GlobalClPhysicsConfig {
   fn default() -> Self {
     Self {
-      gravity: Vec3::new(0., -9.8, 0.),
+      gravity: Vec3::new(0., -GRAVITY, 0.),
       iterations: 10,
     }
   }
@@ -59,7 +59,7 @@ impl Default for ClPhysicsActor {
     Self {
       //HACK: for player
       disable: false,
-      offset: vec3(0., 1.5, 0.),
+      offset: vec3(0., PLAYER_EYE_HEIGHT, 0.),
       forces: Vec3::ZERO,
       frame_velocity: Vec3::ZERO,
       velocity: Vec3::ZERO,
@@ -75,21 +75,21 @@ impl Default for ClPhysicsActor {
 }
 
 trait BlockCollisionExt {
-  fn collision_type(&self) -> CollisionType;
-  fn is_solid(&self) -> bool {
-    self.collision_type() == CollisionType::Solid
+  fn collision_type(&self, registry: &BlockRegistry) -> CollisionType;
+  fn is_solid(&self, registry: &BlockRegistry) -> bool {
+    self.collision_type(registry) == CollisionType::Solid
   }
 }
 
 impl BlockCollisionExt for Option<Block> {
-  fn collision_type(&self) -> CollisionType {
-    self.unwrap_or(Block::Air).descriptor().collision
+  fn collision_type(&self, registry: &BlockRegistry) -> CollisionType {
+    registry.get(self.unwrap_or(Block::Air)).collision
   }
 }
 
 impl BlockCollisionExt for Block {
-  fn collision_type(&self) -> CollisionType {
-    self.descriptor().collision
+  fn collision_type(&self, registry: &BlockRegistry) -> CollisionType {
+    registry.get(*self).collision
   }
 }
 
@@ -104,6 +104,7 @@ pub fn update_client_physics_late(
   mut transforms: ViewMut<Transform, track::All>,
   conf: UniqueView<GlobalClPhysicsConfig>,
   world: UniqueView<ChunkStorage>,
+  registry: UniqueView<SharedBlockRegistry>,
   dt: UniqueView<DeltaTime>,
 ) {
   for (actor, mut transform) in (&mut actors, &mut transforms).iter() {
@@ -128,8 +129,8 @@ pub fn update_client_physics_late(
     let actor_block_below = world.get_block(actor_block_pos_slightly_below);
 
     //update flags
-    actor.flag_collision = actor_block.is_solid();
-    actor.flag_ground = actor.flag_collision || actor_block_below.is_solid();
+    actor.flag_collision = actor_block.is_solid(&registry);
+    actor.flag_ground = actor.flag_collision || actor_block_below.is_solid(&registry);
 
     //push actor back out of the block
     if actor.flag_collision {
@@ -1,6 +1,6 @@
 use shipyard::{Component, View, ViewMut, EntitiesViewMut, IntoIter, track};
 use glam::{IVec3, Quat, Vec3};
-use kubi_shared::block::Block;
+use kubi_shared::{block::{Block, BlockProperties}, entity::DamageCause};
 use crate::{
   client_physics::ClPhysicsActor, player::MainPlayer, transform::Transform
 };
@@ -17,7 +17,16 @@ pub enum PlayerActionEvent {
   UpdatedBlock {
     position: IVec3,
     block: Block,
+    properties: BlockProperties,
   },
+  StartedMining {
+    position: IVec3,
+  },
+  Damaged {
+    amount: u8,
+    cause: DamageCause,
+  },
+  RequestedRespawn,
 }
 
 pub fn generate_move_events(
@@ -1,20 +1,130 @@
-use std::{fs::File, path::Path, io::{Read, Seek}};
-use anyhow::Result;
+use std::{fs::{self, File}, io::{Cursor, Read, Seek}, path::{Path, PathBuf}, sync::Mutex};
+use anyhow::{Context, Result};
+use serde::Deserialize;
 use shipyard::Unique;
 
 pub trait ReadOnly: Read + Seek {}
 impl<T: Read + Seek> ReadOnly for T {}
 
+/// Directory resource packs are loaded from (either as directories or zip archives)
+pub const RESOURCE_PACK_DIRECTORY: &str = "./resourcepacks/";
+
+/// Name of the manifest file in the root of each resource pack
+const PACK_MANIFEST: &str = "pack.toml";
+
+/// Contents of `pack.toml`
+#[derive(Deserialize, Clone, Debug)]
+pub struct PackManifest {
+  pub name: String,
+  #[serde(default)]
+  pub description: String,
+}
+
+/// Where the files of a resource pack are stored
+enum PackSource {
+  Directory(PathBuf),
+  /// Files are read into memory when opened
+  Archive(Mutex<zip::ZipArchive<File>>),
+}
+
+impl PackSource {
+  fn open(path: &Path) -> Result<Self> {
+    if path.is_dir() {
+      return Ok(Self::Directory(path.to_owned()))
+    }
+    Ok(Self::Archive(Mutex::new(zip::ZipArchive::new(File::open(path)?)?)))
+  }
+
+  fn open_file(&self, path: &Path) -> Result<Box<dyn ReadOnly>> {
+    match self {
+      Self::Directory(root) => Ok(Box::new(File::open(root.join(path))?)),
+      Self::Archive(archive) => {
+        //zip archives always use forward slashes
+        let name = path.to_string_lossy().replace('\\', "/");
+        let mut archive = archive.lock().unwrap();
+        let mut file = archive.by_name(&name)?;
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut data)?;
+        Ok(Box::new(Cursor::new(data)))
+      }
+    }
+  }
+}
+
+/// A set of assets overriding the built-in ones with the same paths
+pub struct ResourcePack {
+  /// File name in the resource pack directory
+  pub id: String,
+  pub manifest: PackManifest,
+  source: PackSource,
+}
+
+impl ResourcePack {
+  pub fn open(path: &Path) -> Result<Self> {
+    let id = path.file_name().context("invalid pack path")?.to_string_lossy().into_owned();
+    let source = PackSource::open(path)?;
+    let mut manifest = String::new();
+    source.open_file(Path::new(PACK_MANIFEST))
+      .context("missing pack manifest")?
+      .read_to_string(&mut manifest)?;
+    let manifest = toml::from_str(&manifest).context("invalid pack manifest")?;
+    Ok(Self { id, manifest, source })
+  }
+}
+
+/// Find all valid resource packs in the [`RESOURCE_PACK_DIRECTORY`]
+pub fn find_resource_packs() -> Vec<ResourcePack> {
+  let Ok(entries) = fs::read_dir(RESOURCE_PACK_DIRECTORY) else {
+    return Vec::new()
+  };
+  let mut packs: Vec<ResourcePack> = entries
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.path())
+    .filter(|path| path.is_dir() || path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")))
+    .filter_map(|path| {
+      ResourcePack::open(&path)
+        .inspect_err(|error| log::warn!("skipping resource pack {path:?}: {error:?}"))
+        .ok()
+    })
+    .collect();
+  packs.sort_by(|a, b| a.id.cmp(&b.id));
+  packs
+}
+
+/// Name of the built-in assets layer (see [`AssetManager::open_asset_layers`])
+pub const BASE_LAYER: &str = "base";
+
+/// Directory the built-in assets are loaded from
+#[cfg(not(target_os = "android"))]
+const BASE_ASSET_DIRECTORY: &str = "./assets/";
+
 #[derive(Unique)]
 pub struct AssetManager {
   #[cfg(target_os = "android")]
   pub(crate) app: android_activity::AndroidApp,
+  #[cfg(not(target_os = "android"))]
+  base_directory: PathBuf,
+  /// Enabled resource packs, highest priority first
+  packs: Vec<ResourcePack>,
 }
 
 impl AssetManager {
-  pub fn open_asset(&self, path: &Path) -> Result<Box<dyn ReadOnly>> {
+  pub fn new(
+    #[cfg(target_os = "android")]
+    app: android_activity::AndroidApp,
+  ) -> Self {
+    Self {
+      #[cfg(target_os = "android")]
+      app,
+      #[cfg(not(target_os = "android"))]
+      base_directory: PathBuf::from(BASE_ASSET_DIRECTORY),
+      packs: Vec::new(),
+    }
+  }
+
+  /// Open a built-in asset, ignoring resource packs
+  pub fn open_base_asset(&self, path: &Path) -> Result<Box<dyn ReadOnly>> {
     #[cfg(target_os = "android")] {
-      use anyhow::Context;
       use std::ffi::CString;
       let asset_manager = self.app.asset_manager();
       let path_cstr = CString::new(path.to_string_lossy().as_bytes())?;
@@ -22,8 +132,106 @@ impl AssetManager {
       Ok(Box::new(handle))
     }
     #[cfg(not(target_os = "android"))] {
-      let asset_path = Path::new("./assets/").join(path);
+      let asset_path = self.base_directory.join(path);
       Ok(Box::new(File::open(asset_path)?))
     }
   }
+
+  /// Open an asset from the enabled resource pack with the highest priority that has it,
+  /// or the built-in one if none do
+  pub fn open_asset(&self, path: &Path) -> Result<Box<dyn ReadOnly>> {
+    for pack in &self.packs {
+      if let Ok(file) = pack.source.open_file(path) {
+        return Ok(file)
+      }
+    }
+    self.open_base_asset(path)
+  }
+
+  /// Open an asset from every layer that has it (enabled packs by priority, then the built-in assets),
+  /// along with the id of the layer it came from ([`BASE_LAYER`] for built-in assets)\
+  /// Useful if the asset may turn out to be invalid, and the next layer should be tried then
+  pub fn open_asset_layers(&self, path: &Path) -> Vec<(&str, Box<dyn ReadOnly>)> {
+    let mut layers: Vec<(&str, Box<dyn ReadOnly>)> = self.packs.iter()
+      .filter_map(|pack| Some((pack.id.as_str(), pack.source.open_file(path).ok()?)))
+      .collect();
+    if let Ok(file) = self.open_base_asset(path) {
+      layers.push((BASE_LAYER, file));
+    }
+    layers
+  }
+
+  /// Ids of the enabled resource packs, highest priority first
+  pub fn pack_ids(&self) -> impl Iterator<Item = &str> {
+    self.packs.iter().map(|pack| pack.id.as_str())
+  }
+
+  /// Enable resource packs from the [`RESOURCE_PACK_DIRECTORY`] (highest priority first)\
+  /// Packs that fail to load are skipped
+  pub fn set_packs(&mut self, ids: &[String]) {
+    let mut old_packs = std::mem::take(&mut self.packs);
+    for id in ids {
+      //don't reopen packs that are already enabled
+      let pack = match old_packs.iter().position(|pack| &pack.id == id) {
+        Some(index) => old_packs.swap_remove(index),
+        None => match ResourcePack::open(&Path::new(RESOURCE_PACK_DIRECTORY).join(id)) {
+          Ok(pack) => pack,
+          Err(error) => {
+            log::error!("failed to load resource pack {id}: {error:?}");
+            continue
+          }
+        }
+      };
+      log::info!("resource pack enabled: {} ({id})", pack.manifest.name);
+      self.packs.push(pack);
+    }
+  }
+}
+
+#[cfg(all(test, not(target_os = "android")))]
+mod tests {
+  use super::*;
+
+  fn write_files(root: &Path, files: &[(&str, &str)]) {
+    fs::create_dir_all(root).unwrap();
+    for (name, contents) in files {
+      fs::write(root.join(name), contents).unwrap();
+    }
+  }
+
+  fn read_asset(assman: &AssetManager, path: &str) -> String {
+    let mut contents = String::new();
+    assman.open_asset(Path::new(path)).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+  }
+
+  #[test]
+  fn higher_packs_override_lower_packs_and_builtin_assets() {
+    let root = std::env::temp_dir().join(format!("kubi-pack-test-{}", std::process::id()));
+    write_files(&root.join("base"), &[("a.txt", "base a"), ("b.txt", "base b"), ("c.txt", "base c")]);
+    write_files(&root.join("low"), &[(PACK_MANIFEST, "name = \"Low\""), ("a.txt", "low a"), ("b.txt", "low b")]);
+    write_files(&root.join("high"), &[(PACK_MANIFEST, "name = \"High\""), ("a.txt", "high a")]);
+
+    let mut assman = AssetManager::new();
+    assman.base_directory = root.join("base");
+    assert_eq!(read_asset(&assman, "a.txt"), "base a");
+
+    assman.packs = vec![
+      ResourcePack::open(&root.join("high")).unwrap(),
+      ResourcePack::open(&root.join("low")).unwrap(),
+    ];
+    assert_eq!(read_asset(&assman, "a.txt"), "high a");
+    assert_eq!(read_asset(&assman, "b.txt"), "low b");
+    assert_eq!(read_asset(&assman, "c.txt"), "base c");
+    assert!(assman.open_asset(Path::new("missing.txt")).is_err());
+    let layers: Vec<&str> = assman.open_asset_layers(Path::new("a.txt")).into_iter().map(|(id, _)| id).collect();
+    assert_eq!(layers, ["high", "low", BASE_LAYER]);
+
+    //swapping the priority changes which pack wins
+    assman.packs.reverse();
+    assert_eq!(read_asset(&assman, "a.txt"), "low a");
+    assert_eq!(read_asset(&assman, "b.txt"), "low b");
+
+    fs::remove_dir_all(&root).unwrap();
+  }
 }
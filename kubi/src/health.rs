@@ -0,0 +1,160 @@
+use glam::{Mat4, Vec3};
+use shipyard::{track, EntitiesViewMut, IntoIter, IntoWorkload, SystemModificator, UniqueView, View, ViewMut, Workload};
+use kubi_shared::{
+  block::SharedBlockRegistry,
+  entity::{BreathState, DamageCause, FallDamageState, Health},
+  player::{GameMode, PLAYER_SPAWN_POINT},
+};
+use crate::{
+  client_physics::ClPhysicsActor,
+  delta_time::DeltaTime,
+  events::{player_actions::PlayerActionEvent, EventComponent},
+  input::{Inputs, PrevInputs},
+  networking::is_singleplayer,
+  player::MainPlayer,
+  transform::Transform,
+  world::ChunkStorage,
+};
+
+fn fall_damage(
+  main_player: View<MainPlayer>,
+  gamemodes: View<GameMode>,
+  healths: View<Health>,
+  actors: View<ClPhysicsActor>,
+  transforms: View<Transform>,
+  world: UniqueView<ChunkStorage>,
+  registry: UniqueView<SharedBlockRegistry>,
+  mut fall: ViewMut<FallDamageState>,
+  mut entities: EntitiesViewMut,
+  mut events: ViewMut<EventComponent>,
+  mut player_events: ViewMut<PlayerActionEvent>,
+) {
+  let Some((_, &gamemode, health, actor, transform, fall)) = (
+    &main_player, &gamemodes, &healths, &actors, &transforms, &mut fall
+  ).iter().next() else { return };
+
+  //Landing in water breaks the fall
+  let feet_position = transform.0.to_scale_rotation_translation().2 - actor.offset;
+  let in_fluid = world.get_block(feet_position.floor().as_ivec3())
+    .is_some_and(|block| registry.get(block).submerge.is_some());
+
+  let cancelled = actor.disable || in_fluid || health.is_dead() || !gamemode.has_survival_rules();
+  let Some(amount) = fall.update(feet_position.y, actor.on_ground(), cancelled) else { return };
+  entities.add_entity(
+    (&mut events, &mut player_events),
+    (EventComponent, PlayerActionEvent::Damaged {
+      amount,
+      cause: DamageCause::Fall,
+    })
+  );
+}
+
+fn drowning_damage(
+  main_player: View<MainPlayer>,
+  gamemodes: View<GameMode>,
+  healths: View<Health>,
+  transforms: View<Transform>,
+  world: UniqueView<ChunkStorage>,
+  registry: UniqueView<SharedBlockRegistry>,
+  dt: UniqueView<DeltaTime>,
+  mut breath: ViewMut<BreathState>,
+  mut entities: EntitiesViewMut,
+  mut events: ViewMut<EventComponent>,
+  mut player_events: ViewMut<PlayerActionEvent>,
+) {
+  let Some((_, &gamemode, health, transform, breath)) = (
+    &main_player, &gamemodes, &healths, &transforms, &mut breath
+  ).iter().next() else { return };
+
+  let head_position = transform.0.to_scale_rotation_translation().2;
+  let submerged = world.get_block(head_position.floor().as_ivec3())
+    .is_some_and(|block| registry.get(block).submerge.is_some());
+
+  let submerged = submerged && !health.is_dead() && gamemode.has_survival_rules();
+  let Some(amount) = breath.update(submerged, dt.0.as_secs_f32()) else { return };
+  entities.add_entity(
+    (&mut events, &mut player_events),
+    (EventComponent, PlayerActionEvent::Damaged {
+      amount,
+      cause: DamageCause::Drowning,
+    })
+  );
+}
+
+fn request_respawn(
+  main_player: View<MainPlayer>,
+  healths: View<Health>,
+  input: UniqueView<Inputs>,
+  prev_input: UniqueView<PrevInputs>,
+  mut entities: EntitiesViewMut,
+  mut events: ViewMut<EventComponent>,
+  mut player_events: ViewMut<PlayerActionEvent>,
+) {
+  let Some((_, health)) = (&main_player, &healths).iter().next() else { return };
+  if !health.is_dead() || !(input.jump && !prev_input.0.jump) {
+    return
+  }
+  entities.add_entity(
+    (&mut events, &mut player_events),
+    (EventComponent, PlayerActionEvent::RequestedRespawn)
+  );
+}
+
+/// Apply damage and respawn requests directly (in singleplayer, there's no server to do it for us)
+pub fn apply_health_events_locally(
+  main_player: View<MainPlayer>,
+  player_events: View<PlayerActionEvent>,
+  mut healths: ViewMut<Health>,
+  mut transforms: ViewMut<Transform, track::All>,
+  mut actors: ViewMut<ClPhysicsActor>,
+  mut fall: ViewMut<FallDamageState>,
+) {
+  let Some((_, health, mut transform, actor, fall)) = (
+    &main_player, &mut healths, &mut transforms, &mut actors, &mut fall
+  ).iter().next() else { return };
+  for event in player_events.iter() {
+    match *event {
+      PlayerActionEvent::Damaged { amount, cause } => {
+        if health.is_dead() { continue }
+        health.damage(amount);
+        log::info!("took {amount} damage ({cause:?}), health: {}", health.current);
+      },
+      PlayerActionEvent::RequestedRespawn => {
+        if !health.is_dead() { continue }
+        health.reset();
+        respawn_at(&mut transform, actor, fall, PLAYER_SPAWN_POINT);
+        log::info!("respawned");
+      },
+      _ => (),
+    }
+  }
+}
+
+/// Move the player to `position`, keeping the rotation
+pub fn respawn_at(
+  transform: &mut Transform,
+  actor: &mut ClPhysicsActor,
+  fall: &mut FallDamageState,
+  position: Vec3,
+) {
+  let (scale, rotation, _) = transform.0.to_scale_rotation_translation();
+  transform.0 = Mat4::from_scale_rotation_translation(scale, rotation, position);
+  actor.velocity = Vec3::ZERO;
+  *fall = FallDamageState::default();
+}
+
+pub fn is_main_player_alive(
+  main_player: View<MainPlayer>,
+  healths: View<Health>,
+) -> bool {
+  (&main_player, &healths).iter().next().is_none_or(|(_, health)| !health.is_dead())
+}
+
+pub fn update_health() -> Workload {
+  (
+    //in multiplayer, damage is dealt by the server
+    fall_damage.run_if(is_singleplayer),
+    drowning_damage.run_if(is_singleplayer),
+    request_respawn,
+  ).into_sequential_workload()
+}
@@ -1,10 +1,24 @@
 use shipyard::{AllStoragesView, UniqueViewMut};
 use std::{env, net::SocketAddr, path::Path};
+use anyhow::{Context, Result};
+use kubi_shared::{height::WorldHeight, worldgen::preset::WorldGenPreset};
 use crate::{
   networking::{GameType, ServerAddress},
   state::{GameState, NextState}
 };
-use kubi_shared::data::{io_thread::IOThreadManager, open_local_save_file};
+use crate::world::registry::{open_local_world, LocalWorldGenerator};
+
+/// Parse build height limits written as `min,max` (with the default bedrock floor)
+fn parse_world_height(value: &str) -> Result<WorldHeight> {
+  let (min, max) = value.split_once(',').context("expected min,max")?;
+  let height = WorldHeight {
+    min: min.trim().parse()?,
+    max: max.trim().parse()?,
+    ..WorldHeight::default()
+  };
+  height.validate()?;
+  Ok(height)
+}
 
 pub fn initialize_from_args(
   all_storages: AllStoragesView,
@@ -15,12 +29,23 @@ pub fn initialize_from_args(
   if cfg!(target_os = "android") || (args.get(1) == Some(&"android".into())) {
     // TODO REMOVE: temporarily bypass menu on Android as hUI (0.1.0-alpha.5) doesnt play well with touchscreens (yet? :3)
     // TODO REMOVE: disable save files on Android as they're stored in relative path rn
+    all_storages.add_unique(LocalWorldGenerator::new(WorldGenPreset::default()));
+    all_storages.add_unique(WorldHeight::default());
     all_storages.add_unique(GameType::Singleplayer);
     all_storages.borrow::<UniqueViewMut<NextState>>().unwrap().0 = Some(GameState::LoadingWorld);
   } else if args.get(1) == Some(&"play".into()) {
+    // The second argument is the world generator preset (only used if the world doesn't exist yet)
+    let preset = match args.get(2) {
+      Some(name) => WorldGenPreset::builtin(name).expect("unknown world generator preset"),
+      None => WorldGenPreset::default(),
+    };
+    // The third argument is the build height range as `min,max` (also only used if the world doesn't exist yet)
+    let height = match args.get(3) {
+      Some(range) => parse_world_height(range).expect("invalid build height range"),
+      None => WorldHeight::default(),
+    };
     // Open the local save file
-    let save_file = open_local_save_file(Path::new("./world.kubi")).expect("failed to open save file");
-    all_storages.add_unique(IOThreadManager::new(save_file));
+    open_local_world(&all_storages, Path::new("./world.kubi"), preset, height).expect("failed to open save file");
     // Switch the state and kick off the world loading
     all_storages.add_unique(GameType::Singleplayer);
     all_storages.borrow::<UniqueViewMut<NextState>>().unwrap().0 = Some(GameState::LoadingWorld);
@@ -4,6 +4,7 @@
   clippy::enum_variant_names,
   clippy::type_complexity
 )]
+#![cfg_attr(all(test, feature = "nightly"), feature(test))]
 #![forbid(
   static_mut_refs,
   unsafe_op_in_unsafe_fn,
@@ -35,6 +36,8 @@ pub(crate) use ui::{
   chat_ui,
   crosshair_ui,
   settings_ui,
+  health_ui,
+  debug_ui,
   shutdown_screen,
   main_menu,
 };
@@ -59,21 +62,25 @@ pub(crate) mod color;
 pub(crate) mod filesystem;
 pub(crate) mod client_physics;
 pub(crate) mod chat;
+pub(crate) mod health;
 
 use world::{
   init_game_world,
   loading::{save_on_exit, update_loaded_world_around_player},
-  queue::apply_queued_blocks,
+  queue::{apply_queued_blocks, save_unloaded_queued_blocks},
   raycast::update_raycasts,
+  registry::init_block_registry,
+  ticks::update_block_ticks,
+  falling_blocks::update_falling_blocks,
   tasks::ChunkTaskManager,
 };
 use player::{spawn_player, MainPlayer};
-use prefabs::load_prefabs;
+use prefabs::{load_prefabs, init_resource_packs, update_resource_packs, update_block_texture_animations};
 use settings::{load_settings, GameSettings};
 use camera::compute_cameras;
 use events::{clear_events, process_winit_events, player_actions::generate_move_events};
 use input::{init_input, process_inputs};
-use player_controller::{debug_switch_ctl_type, update_player_controllers};
+use player_controller::{debug_switch_gamemode, update_player_controllers};
 use rendering::{BackgroundColor, Renderer, init_rendering, render_master, update_rendering_early, update_rendering_late};
 use block_placement::update_block_placement;
 use delta_time::{DeltaTime, init_delta_time};
@@ -94,12 +101,16 @@ use chat_ui::render_chat;
 use chat::init_chat_manager;
 use crosshair_ui::{init_crosshair_image, draw_crosshair};
 use settings_ui::render_settings_ui;
+use health_ui::{render_health_bar, render_death_screen};
+use debug_ui::render_culling_stats;
+use health::{apply_health_events_locally, is_main_player_alive, update_health};
 use hui_integration::hui_process_winit_events;
 
 /// stuff required to init the renderer and other basic systems
 fn pre_startup() -> Workload {
   (
     load_settings,
+    init_resource_packs,
   ).into_sequential_workload()
 }
 
@@ -107,6 +118,7 @@ fn startup() -> Workload {
   (
     init_fixed_timestamp_storage,
     kubi_ui_init,
+    init_block_registry,
     load_prefabs,
     init_rendering,
     insert_lock_state,
@@ -125,6 +137,8 @@ fn startup() -> Workload {
 fn update() -> Workload {
   (
     update_rendering_early,
+    update_resource_packs,
+    update_block_texture_animations,
     debug_toggle_lock,
     update_cursor_lock_state,
     process_inputs,
@@ -151,16 +165,24 @@ fn update() -> Workload {
       update_loaded_world_around_player,
     ).into_sequential_workload().run_if(is_ingame_or_loading),
     (
-      debug_switch_ctl_type,
-      update_player_controllers,
+      debug_switch_gamemode.run_if(is_singleplayer),
+      update_player_controllers.run_if(is_main_player_alive),
       update_client_physics_late,
       generate_move_events,
       update_raycasts,
-      update_block_placement,
+      update_block_placement.run_if(is_main_player_alive),
+      update_health,
+      apply_health_events_locally.run_if(is_singleplayer),
+      update_block_ticks.run_if(is_singleplayer),
+      update_falling_blocks,
       apply_queued_blocks,
+      save_unloaded_queued_blocks.run_if(is_singleplayer),
       //UI:
       render_chat,
       draw_crosshair,
+      render_health_bar,
+      render_death_screen,
+      render_culling_stats,
       render_settings_ui.run_if(f1_held_settings_condition),
     ).into_sequential_workload().run_if(is_ingame),
     (
@@ -255,10 +277,10 @@ pub fn kubi_main(
   let mut world = World::new();
 
   //Init assman
-  world.add_unique(AssetManager {
+  world.add_unique(AssetManager::new(
     #[cfg(target_os = "android")]
-    app: app.clone()
-  });
+    app.clone()
+  ));
 
   //Register workloads
   world.add_workload(pre_startup);
@@ -31,11 +31,16 @@ use world::{
   inject_network_responses_into_manager_queue,
   send_block_place_events,
   recv_block_place_events,
+  recv_falling_block_events,
 };
 use player::{
   init_client_map,
   send_player_movement_events,
+  send_player_health_events,
   receive_player_movement_events, 
+  receive_player_health_events,
+  receive_player_respawn_events,
+  receive_inventory_changed_events,
   receive_player_connect_events,
   receive_player_disconnect_events,
 };
@@ -137,10 +142,14 @@ pub fn update_networking() -> Workload {
       (
         receive_player_connect_events,
         receive_player_disconnect_events,
+        recv_falling_block_events,
       ).into_workload(),
       (
         recv_block_place_events,
         receive_player_movement_events,
+        receive_player_health_events,
+        receive_player_respawn_events,
+        receive_inventory_changed_events,
       ).into_workload()
     ).into_sequential_workload().run_if(is_join_state::<{ClientJoinState::Joined as u8}>).run_if(is_ingame_or_loading),
     inject_network_responses_into_manager_queue.run_if(is_ingame_or_loading).skip_if_missing_unique::<ChunkTaskManager>(),
@@ -152,6 +161,7 @@ pub fn update_networking_late() -> Workload {
     (
       send_block_place_events,
       send_player_movement_events,
+      send_player_health_events,
     ).into_workload().run_if(is_join_state::<{ClientJoinState::Joined as u8}>),
     flush_client.into_workload().make_fixed(NET_TICKRATE, 1)
   ).into_sequential_workload()
@@ -1,12 +1,19 @@
 use shipyard::{AllStoragesView, AllStoragesViewMut, IntoIter, Unique, UniqueViewMut, View};
 use uflow::{client::Event as ClientEvent, SendMode};
-use kubi_shared::networking::{
-  messages::{ClientToServerMessage, ServerToClientMessage, ServerToClientMessageType},
-  state::ClientJoinState,
-  channels::Channel,
+use kubi_shared::{
+  block::SharedBlockRegistry,
+  networking::{
+    messages::{ClientToServerMessage, ServerToClientMessage, ServerToClientMessageType},
+    state::ClientJoinState,
+    channels::Channel,
+  },
 };
 use rand::prelude::*;
-use crate::{chat::ChatHistory, player::{spawn_local_player_multiplayer, spawn_remote_player_multiplayer}};
+use crate::{
+  chat::ChatHistory,
+  player::{spawn_local_player_multiplayer, spawn_remote_player_multiplayer},
+  prefabs::reload_block_textures,
+};
 use super::{UdpClient, NetworkEvent};
 
 const USERNAME_BANK: &[&str] = &[
@@ -81,14 +88,26 @@ pub fn check_server_hello_response(
   let client_id = init.user.client_id;
   let username = init.user.username.clone();
 
+  //Use the block registry of the server (chunks haven't been requested yet, so this is safe)
+  *storages.borrow::<UniqueViewMut<SharedBlockRegistry>>().unwrap() = SharedBlockRegistry::new(init.block_registry);
+  storages.run(reload_block_textures);
+
+  //Chunks outside of the world height are never requested from the server
+  storages.add_unique(init.world_height);
+
   //Add components to main player
-  spawn_local_player_multiplayer(&mut storages, init.user);
+  spawn_local_player_multiplayer(&mut storages, init.user, init.inventory);
 
   //Init players
   for init_data in init.users {
     spawn_remote_player_multiplayer(&mut storages, init_data);
   }
 
+  //Blocks that started falling before we joined
+  for block in init.falling_blocks {
+    storages.add_entity(block);
+  }
+
   // Set state to connected
   let mut join_state = storages.borrow::<UniqueViewMut<ClientJoinState>>().unwrap();
   *join_state = ClientJoinState::Joined;
@@ -1,8 +1,10 @@
 use glam::Mat4;
-use shipyard::{UniqueViewMut, View, IntoIter, AllStoragesView, AllStoragesViewMut, UniqueView, ViewMut, Get};
+use shipyard::{track, UniqueViewMut, View, IntoIter, AllStoragesView, AllStoragesViewMut, UniqueView, ViewMut, Get};
 use uflow::{SendMode, client::Event as ClientEvent};
 use kubi_shared::{
   transform::Transform,
+  entity::{FallDamageState, Health},
+  player::Inventory,
   networking::{
     channels::Channel,
     client::{ClientIdMap, Username},
@@ -11,8 +13,10 @@ use kubi_shared::{
 };
 use crate::{
   chat::ChatHistory,
+  client_physics::ClPhysicsActor,
   events::player_actions::PlayerActionEvent,
-  player::spawn_remote_player_multiplayer,
+  health::respawn_at,
+  player::{spawn_remote_player_multiplayer, MainPlayer},
 };
 use super::{UdpClient, NetworkEvent};
 
@@ -42,6 +46,90 @@ pub fn send_player_movement_events(
   }
 }
 
+pub fn send_player_health_events(
+  actions: View<PlayerActionEvent>,
+  mut client: UniqueViewMut<UdpClient>,
+) {
+  for event in actions.iter() {
+    //damage is dealt by the server, only respawn requests are sent
+    if !matches!(event, PlayerActionEvent::RequestedRespawn) {
+      continue
+    }
+    client.0.send(
+      postcard::to_allocvec(&ClientToServerMessage::Respawn).unwrap().into_boxed_slice(),
+      Channel::SysEvt as usize,
+      SendMode::Reliable
+    );
+  }
+}
+
+pub fn receive_player_health_events(
+  mut healths: ViewMut<Health>,
+  network_events: View<NetworkEvent>,
+  id_map: UniqueView<ClientIdMap>
+) {
+  for event in network_events.iter() {
+    let ClientEvent::Receive(data) = &event.0 else {
+      continue
+    };
+
+    if !event.is_message_of_type::<{ServerToClientMessageType::PlayerHealthChanged as u8}>() {
+      continue
+    }
+
+    let Ok(parsed_message) = postcard::from_bytes(data) else {
+      log::error!("Malformed message");
+      continue
+    };
+
+    let ServerToClientMessage::PlayerHealthChanged { client_id, health } = parsed_message else { unreachable!() };
+
+    let Some(&ent_id) = id_map.0.get(&client_id) else {
+      log::error!("Not in client-id map");
+      continue
+    };
+
+    let Ok(mut player_health) = (&mut healths).get(ent_id) else {
+      log::error!("Player entity has no health");
+      continue
+    };
+
+    *player_health = health;
+  }
+}
+
+pub fn receive_player_respawn_events(
+  main_player: View<MainPlayer>,
+  mut transforms: ViewMut<Transform, track::All>,
+  mut actors: ViewMut<ClPhysicsActor>,
+  mut fall: ViewMut<FallDamageState>,
+  network_events: View<NetworkEvent>,
+) {
+  for event in network_events.iter() {
+    let ClientEvent::Receive(data) = &event.0 else {
+      continue
+    };
+
+    if !event.is_message_of_type::<{ServerToClientMessageType::PlayerRespawn as u8}>() {
+      continue
+    }
+
+    let Ok(parsed_message) = postcard::from_bytes(data) else {
+      log::error!("Malformed message");
+      continue
+    };
+
+    let ServerToClientMessage::PlayerRespawn { position } = parsed_message else { unreachable!() };
+
+    let Some((_, mut transform, actor, fall)) = (&main_player, &mut transforms, &mut actors, &mut fall).iter().next() else {
+      log::error!("Main player not found");
+      continue
+    };
+
+    respawn_at(&mut transform, actor, fall, position);
+  }
+}
+
 pub fn receive_player_movement_events(
   mut transforms: ViewMut<Transform>,
   network_events: View<NetworkEvent>,
@@ -70,13 +158,48 @@ pub fn receive_player_movement_events(
       continue
     };
 
-    let mut transform = (&mut transforms).get(ent_id)
-      .expect("invalid player entity id");
+    let Ok(mut transform) = (&mut transforms).get(ent_id) else {
+      log::error!("Player entity has no transform");
+      continue
+    };
 
     transform.0 = Mat4::from_rotation_translation(direction, position);
   }
 }
 
+pub fn receive_inventory_changed_events(
+  main_player: View<MainPlayer>,
+  mut inventories: ViewMut<Inventory>,
+  network_events: View<NetworkEvent>,
+) {
+  for event in network_events.iter() {
+    let ClientEvent::Receive(data) = &event.0 else {
+      continue
+    };
+
+    if !event.is_message_of_type::<{ServerToClientMessageType::InventoryChanged as u8}>() {
+      continue
+    }
+
+    let Ok(parsed_message) = postcard::from_bytes(data) else {
+      log::error!("Malformed message");
+      continue
+    };
+
+    let ServerToClientMessage::InventoryChanged { inventory } = parsed_message else { unreachable!() };
+
+    let Some((_, player_inventory)) = (&main_player, &mut inventories).iter().next() else {
+      log::error!("Main player has no inventory");
+      continue
+    };
+
+    //Keep the slot selection, as it's client-side only
+    let selected = player_inventory.selected();
+    *player_inventory = inventory;
+    player_inventory.select(selected);
+  }
+}
+
 pub fn receive_player_connect_events(
   mut storages: AllStoragesViewMut,
 ) {
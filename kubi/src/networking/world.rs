@@ -1,4 +1,4 @@
-use shipyard::{UniqueView, UniqueViewMut, View, IntoIter};
+use shipyard::{AllStoragesViewMut, UniqueView, UniqueViewMut, View, IntoIter, IntoWithId};
 use uflow::{client::Event as ClientEvent, SendMode};
 use lz4_flex::decompress_size_prepended;
 use anyhow::{Result, Context};
@@ -7,7 +7,8 @@ use kubi_shared::{
     messages::{ClientToServerMessage, ServerToClientMessage, ServerToClientMessageType},
     channels::Channel,
   },
-  queue::QueuedBlock
+  queue::QueuedBlock,
+  falling_block::FallingBlock,
 };
 use crate::{
   events::player_actions::PlayerActionEvent, 
@@ -35,12 +36,13 @@ pub fn inject_network_responses_into_manager_queue(
       let NetworkEvent(ClientEvent::Receive(data)) = &event else { unreachable!() };
       let packet = decompress_chunk_packet(data).expect("Chunk decode failed");
       let ServerToClientMessage::ChunkResponse {
-        chunk, data, queued
+        chunk, data, queued, biomes
       } = packet else { unreachable!() };
       manager.add_sussy_response(ChunkTaskResponse::ChunkWorldgenDone {
         position: chunk,
         chunk_data: data,
-        queued
+        queued,
+        biomes,
       });
     }
   }
@@ -51,17 +53,20 @@ pub fn send_block_place_events(
   mut client: UniqueViewMut<UdpClient>,
 ) {
   for event in action_events.iter() {
-    let PlayerActionEvent::UpdatedBlock { position, block } = event else {
-      continue
-    };
-    client.0.send(
-      postcard::to_allocvec(&ClientToServerMessage::QueueBlock {
+    let message = match *event {
+      PlayerActionEvent::UpdatedBlock { position, block, properties } => ClientToServerMessage::QueueBlock {
         item: QueuedBlock {
-          position: *position,
-          block_type: *block,
+          position,
+          block_type: block,
+          properties,
           soft: false
         }
-      }).unwrap().into_boxed_slice(),
+      },
+      PlayerActionEvent::StartedMining { position } => ClientToServerMessage::StartMining { position },
+      _ => continue,
+    };
+    client.0.send(
+      postcard::to_allocvec(&message).unwrap().into_boxed_slice(),
       Channel::Block as usize,
       SendMode::Reliable,
     );
@@ -89,3 +94,44 @@ pub fn recv_block_place_events(
     queue.0.push(item);
   }
 }
+
+pub fn recv_falling_block_events(
+  mut storages: AllStoragesViewMut,
+) {
+  let messages: Vec<ServerToClientMessage> = storages.borrow::<View<NetworkEvent>>().unwrap().iter().filter_map(|event| {
+    let ClientEvent::Receive(data) = &event.0 else {
+      return None
+    };
+    if !(
+      event.is_message_of_type::<{ServerToClientMessageType::FallingBlockSpawned as u8}>() ||
+      event.is_message_of_type::<{ServerToClientMessageType::FallingBlockRemoved as u8}>()
+    ) {
+      return None
+    };
+    let Ok(parsed_message) = postcard::from_bytes(data) else {
+      log::error!("Malformed message");
+      return None
+    };
+    Some(parsed_message)
+  }).collect();
+
+  for message in messages {
+    match message {
+      ServerToClientMessage::FallingBlockSpawned { block } => {
+        storages.add_entity(block);
+      },
+      ServerToClientMessage::FallingBlockRemoved { id } => {
+        let entity_id = storages.borrow::<View<FallingBlock>>().unwrap()
+          .iter().with_id()
+          .find(|(_, block)| block.id == id)
+          .map(|(entity_id, _)| entity_id);
+        let Some(entity_id) = entity_id else {
+          log::warn!("Removed falling block {id} doesn't exist");
+          continue
+        };
+        storages.delete_entity(entity_id);
+      },
+      _ => unreachable!(),
+    }
+  }
+}
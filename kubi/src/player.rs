@@ -1,15 +1,15 @@
 use glam::Mat4;
 use shipyard::{Component, AllStoragesViewMut, UniqueViewMut};
 use kubi_shared::{
-  entity::{Entity, Health},
-  player::{Player, PLAYER_HEALTH, PlayerHolding},
-  block::Block,
+  entity::{BreathState, Entity, FallDamageState, Health},
+  player::{GameMode, Inventory, Player, PlayerHolding, PLAYER_HEALTH, PLAYER_SPAWN_POINT},
   networking::{
     client::{Username, Client, ClientIdMap},
     messages::ClientInitData
   }
 };
 use crate::{
+  block_placement::MiningProgress,
   camera::Camera,
   client_physics::ClPhysicsActor,
   player_controller::PlayerController,
@@ -29,20 +29,26 @@ pub fn spawn_player (
     MainPlayer,
     Entity,
     Health::new(PLAYER_HEALTH),
-    Transform::default(),
+    Transform(Mat4::from_translation(PLAYER_SPAWN_POINT)),
     Camera::default(),
     PlayerController::DEFAULT_FPS_CTL,
     LookingAtBlock::default(),
-    PlayerHolding(Some(Block::Cobblestone)),
+    PlayerHolding::default(),
     Username("LocalPlayer".into()),
   ),(
     ClPhysicsActor::default(),
+    GameMode::default(),
+    Inventory::new(),
+    MiningProgress::default(),
+    FallDamageState::default(),
+    BreathState::default(),
   )));
 }
 
 pub fn spawn_local_player_multiplayer (
   storages: &mut AllStoragesViewMut,
-  init: ClientInitData
+  init: ClientInitData,
+  inventory: Inventory,
 ) {
   log::info!("spawning local multiplayer player");
   let entity_id = storages.add_entity(((
@@ -59,6 +65,11 @@ pub fn spawn_local_player_multiplayer (
   ),(
     Username(init.username),
     ClPhysicsActor::default(),
+    init.gamemode,
+    inventory,
+    MiningProgress::default(),
+    FallDamageState::default(),
+    BreathState::default(),
   )));
 
   //Add ourself to the client id map
@@ -81,6 +92,7 @@ pub fn spawn_remote_player_multiplayer(
     init.health,
     Transform(Mat4::from_rotation_translation(init.direction, init.position)),
     PlayerHolding::default(),
+    init.gamemode,
   ));
 
   //Add it to the client id map
@@ -2,11 +2,13 @@ use glam::{EulerRot, Mat4, Quat, Vec2, Vec3, Vec3Swizzles};
 use shipyard::{track, Component, Get, IntoIter, IntoWithId, IntoWorkload, UniqueView, View, ViewMut, Workload};
 use winit::keyboard::KeyCode;
 use std::f32::consts::PI;
+use kubi_shared::player::GameMode;
 use crate::{
   client_physics::ClPhysicsActor,
   cursor_lock::CursorLock,
   delta_time::DeltaTime,
   input::{Inputs, PrevInputs, RawKbmInputState},
+  player::MainPlayer,
   settings::GameSettings,
   transform::Transform
 };
@@ -33,10 +35,19 @@ impl PlayerController {
     control_type: PlayerControllerType::FpsCtl,
     speed: 10.,
   };
+
+  /// Get the default controller for a game mode
+  pub const fn for_gamemode(gamemode: GameMode) -> Self {
+    match gamemode {
+      GameMode::Survival => Self::DEFAULT_FPS_CTL,
+      GameMode::Creative | GameMode::Spectator => Self::DEFAULT_FLY_CAM,
+    }
+  }
 }
 
 pub fn update_player_controllers() -> Workload {
   (
+    switch_ctl_type_on_gamemode_change,
     update_look,
     update_movement
   ).into_sequential_workload()
@@ -123,18 +134,35 @@ fn update_movement(
   }
 }
 
-pub fn debug_switch_ctl_type(
+fn switch_ctl_type_on_gamemode_change(
+  gamemodes: View<GameMode>,
   mut controllers: ViewMut<PlayerController>,
   mut actors: ViewMut<ClPhysicsActor>,
+) {
+  for (&gamemode, controller, actor) in (&gamemodes, &mut controllers, &mut actors).iter() {
+    let new_controller = PlayerController::for_gamemode(gamemode);
+    if controller.control_type == new_controller.control_type {
+      continue
+    }
+    actor.disable = new_controller.control_type == PlayerControllerType::FlyCam;
+    *controller = new_controller;
+  }
+}
+
+/// Switch game modes with F4 (survival), F5 (creative) and F6 (spectator)\
+/// Only allowed in singleplayer, as game modes are managed by the server in multiplayer
+pub fn debug_switch_gamemode(
+  main_player: View<MainPlayer>,
+  mut gamemodes: ViewMut<GameMode>,
   kbm_state: UniqueView<RawKbmInputState>,
 ) {
-  for (controller, actor) in (&mut controllers, &mut actors).iter() {
+  for (_, gamemode) in (&main_player, &mut gamemodes).iter() {
     if kbm_state.keyboard_state.contains(KeyCode::F4 as u32) {
-      *controller = PlayerController::DEFAULT_FPS_CTL;
-      actor.disable = false;
+      *gamemode = GameMode::Survival;
     } else if kbm_state.keyboard_state.contains(KeyCode::F5 as u32) {
-      *controller = PlayerController::DEFAULT_FLY_CAM;
-      actor.disable = true;
+      *gamemode = GameMode::Creative;
+    } else if kbm_state.keyboard_state.contains(KeyCode::F6 as u32) {
+      *gamemode = GameMode::Spectator;
     }
   }
 }
@@ -2,13 +2,15 @@ use std::{io::{BufReader, Read}, path::{Path, PathBuf}};
 use bytemuck::{Pod, Zeroable};
 use hui::text::FontHandle;
 use shipyard::{AllStoragesView, NonSendSync, Unique, UniqueView, UniqueViewMut};
-use kubi_shared::block::BlockTexture;
-use crate::{filesystem::AssetManager, hui_integration::UiState, rendering::{BufferPair, Renderer}};
+use kubi_shared::block::SharedBlockRegistry;
+use crate::{filesystem::AssetManager, hui_integration::UiState, rendering::{BufferPair, Renderer}, settings::GameSettings, delta_time::DeltaTime};
 
 //TODO move to rendering module
 
 mod loader;
+mod animation;
 use loader::{load_texture2darray_prefab, load_texture2d_prefab, load_obj_prefab};
+use animation::{BlockTextureUniform, TextureAnimation};
 
 #[derive(Clone, Copy, Default, Pod, Zeroable)]
 #[repr(C, packed)]
@@ -31,37 +33,13 @@ impl ModelVertex {
   };
 }
 
-pub trait AssetPaths {
-  fn file_name(self) -> &'static str;
-}
-
-impl AssetPaths for BlockTexture {
-  fn file_name(self) -> &'static str {
-    match self {
-      Self::Stone         => "stone.png",
-      Self::Dirt          => "dirt.png",
-      Self::GrassTop      => "grass_top.png",
-      Self::GrassSide     => "grass_side.png",
-      Self::Sand          => "sand.png",
-      Self::Bedrock       => "bedrock.png",
-      Self::Wood          => "wood.png",
-      Self::WoodTop       => "wood_top.png",
-      Self::Leaf          => "leaf.png",
-      Self::Torch         => "torch.png",
-      Self::TallGrass     => "tall_grass.png",
-      Self::Snow          => "snow.png",
-      Self::GrassSideSnow => "grass_side_snow.png",
-      Self::Cobblestone   => "cobblestone.png",
-      Self::Planks        => "planks.png",
-      Self::WaterSolid    => "solid_water.png",
-      Self::Water         => "water.png",
-    }
-  }
-}
-
 #[derive(Unique)]
 pub struct GpuPrefabs {
+  /// Names of the textures loaded into `block_diffuse_texture`, indexed by `BlockTexture`
+  pub block_texture_names: Vec<String>,
   pub block_diffuse_texture: wgpu::Texture,
+  /// Animations of block textures, bound along with the texture array
+  pub block_texture_uniform: BlockTextureUniform,
   pub block_diffuse_bind_group_layout: wgpu::BindGroupLayout,
   pub block_diffuse_bind_group: wgpu::BindGroup,
   pub player_model_diffuse_texture: wgpu::Texture,
@@ -74,59 +52,40 @@ pub struct GpuPrefabs {
 #[repr(transparent)]
 pub struct UiFontPrefab(pub FontHandle);
 
-pub fn load_prefabs(
-  storages: AllStoragesView,
-  renderer: UniqueView<Renderer>,
-  mut ui: NonSendSync<UniqueViewMut<UiState>>,
-  assman: UniqueView<AssetManager>
-) {
-  log::info!("Loading textures...");
-  let block_diffuse_texture = load_texture2darray_prefab::<BlockTexture>(
-    &renderer,
-    &assman,
-    "blocks".into(),
-  );
+/// Load textures of all blocks in the current block registry
+fn load_block_textures(
+  renderer: &Renderer,
+  assman: &AssetManager,
+  names: &[String],
+) -> (wgpu::Texture, Vec<TextureAnimation>) {
+  let file_names: Vec<String> = names.iter().map(|name| format!("{name}.png")).collect();
+  load_texture2darray_prefab(renderer, assman, "blocks".into(), &file_names)
+}
 
-  log::info!("Creating bing groups");
-  let block_diffuse_view = block_diffuse_texture.create_view(&wgpu::TextureViewDescriptor {
+fn create_block_diffuse_bind_group(
+  renderer: &Renderer,
+  layout: &wgpu::BindGroupLayout,
+  texture: &wgpu::Texture,
+  uniform: &BlockTextureUniform,
+) -> wgpu::BindGroup {
+  let block_diffuse_view = texture.create_view(&wgpu::TextureViewDescriptor {
     label: Some("block_texture_view"),
     ..Default::default()
   });
   let block_diffuse_sampler = renderer.device().create_sampler(&wgpu::SamplerDescriptor {
     label: Some("block_diffuse_sampler"),
-    address_mode_u: wgpu::AddressMode::ClampToEdge,
-    address_mode_v: wgpu::AddressMode::ClampToEdge,
+    //textures are repeated across merged faces (see greedy meshing)
+    address_mode_u: wgpu::AddressMode::Repeat,
+    address_mode_v: wgpu::AddressMode::Repeat,
     address_mode_w: wgpu::AddressMode::ClampToEdge,
     mag_filter: wgpu::FilterMode::Nearest,
     min_filter: wgpu::FilterMode::Linear,
     mipmap_filter: wgpu::FilterMode::Nearest,
     ..Default::default()
   });
-  let block_diffuse_bind_group_layout = renderer.device()
-    .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-      label: Some("block_diffuse_bind_group_layout"),
-      entries: &[
-        wgpu::BindGroupLayoutEntry {
-          binding: 0,
-          visibility: wgpu::ShaderStages::FRAGMENT,
-          ty: wgpu::BindingType::Texture {
-            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-            view_dimension: wgpu::TextureViewDimension::D2Array,
-            multisampled: false,
-          },
-          count: None,
-        },
-        wgpu::BindGroupLayoutEntry {
-          binding: 1,
-          visibility: wgpu::ShaderStages::FRAGMENT,
-          ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-          count: None,
-        }
-      ]
-    });
-  let block_diffuse_bind_group = renderer.device().create_bind_group(&wgpu::BindGroupDescriptor {
+  renderer.device().create_bind_group(&wgpu::BindGroupDescriptor {
     label: Some("block_diffuse_bind_group"),
-    layout: &block_diffuse_bind_group_layout,
+    layout,
     entries: &[
       wgpu::BindGroupEntry {
         binding: 0,
@@ -135,16 +94,27 @@ pub fn load_prefabs(
       wgpu::BindGroupEntry {
         binding: 1,
         resource: wgpu::BindingResource::Sampler(&block_diffuse_sampler),
-      }
+      },
+      wgpu::BindGroupEntry {
+        binding: 2,
+        resource: uniform.buffer.as_entire_binding(),
+      },
     ]
-  });
+  })
+}
 
-  let player_model_diffuse_texture = load_texture2d_prefab(&renderer, &assman, &PathBuf::from("playermodel1.png"));
-  let player_model_diffuse_view = player_model_diffuse_texture.create_view(&wgpu::TextureViewDescriptor {
+/// Load the player model and its texture, returning the texture, its bind group and the model
+fn load_player_model(
+  renderer: &Renderer,
+  assman: &AssetManager,
+  layout: &wgpu::BindGroupLayout,
+) -> (wgpu::Texture, wgpu::BindGroup, BufferPair) {
+  let texture = load_texture2d_prefab(renderer, assman, &PathBuf::from("playermodel1.png"));
+  let view = texture.create_view(&wgpu::TextureViewDescriptor {
     label: Some("player_model_texture_view"),
     ..Default::default()
   });
-  let player_model_diffuse_sampler = renderer.device().create_sampler(&wgpu::SamplerDescriptor {
+  let sampler = renderer.device().create_sampler(&wgpu::SamplerDescriptor {
     label: Some("player_model_sampler"),
     address_mode_u: wgpu::AddressMode::ClampToEdge,
     address_mode_v: wgpu::AddressMode::ClampToEdge,
@@ -154,6 +124,80 @@ pub fn load_prefabs(
     mipmap_filter: wgpu::FilterMode::Nearest,
     ..Default::default()
   });
+  let bind_group = renderer.device().create_bind_group(&wgpu::BindGroupDescriptor {
+    label: Some("player_model_bind_group"),
+    layout,
+    entries: &[
+      wgpu::BindGroupEntry {
+        binding: 0,
+        resource: wgpu::BindingResource::TextureView(&view),
+      },
+      wgpu::BindGroupEntry {
+        binding: 1,
+        resource: wgpu::BindingResource::Sampler(&sampler),
+      }
+    ]
+  });
+  let model = load_obj_prefab(renderer, assman, &PathBuf::from("playermodel1.obj"));
+  (texture, bind_group, model)
+}
+
+pub fn load_prefabs(
+  storages: AllStoragesView,
+  renderer: UniqueView<Renderer>,
+  mut ui: NonSendSync<UniqueViewMut<UiState>>,
+  assman: UniqueView<AssetManager>,
+  registry: UniqueView<SharedBlockRegistry>,
+) {
+  log::info!("Loading textures...");
+  let block_texture_names = registry.textures().to_vec();
+  let (block_diffuse_texture, block_animations) = load_block_textures(&renderer, &assman, &block_texture_names);
+  let block_texture_uniform = BlockTextureUniform::new(&renderer);
+  if let Err(error) = block_texture_uniform.set_animations(&renderer, &block_animations) {
+    log::error!("failed to set block texture animations: {error}");
+  }
+
+  log::info!("Creating bing groups");
+  let block_diffuse_bind_group_layout = renderer.device()
+    .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("block_diffuse_bind_group_layout"),
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: 0,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2Array,
+            multisampled: false,
+          },
+          count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+          binding: 1,
+          visibility: wgpu::ShaderStages::FRAGMENT,
+          ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+          count: None,
+        },
+        //the current frame of animated textures is picked in the vertex shader
+        wgpu::BindGroupLayoutEntry {
+          binding: 2,
+          visibility: wgpu::ShaderStages::VERTEX,
+          ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+          },
+          count: None,
+        },
+      ]
+    });
+  let block_diffuse_bind_group = create_block_diffuse_bind_group(
+    &renderer,
+    &block_diffuse_bind_group_layout,
+    &block_diffuse_texture,
+    &block_texture_uniform,
+  );
+
   let player_model_diffuse_bind_group_layout = renderer.device()
     .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
       label: Some("player_model_bind_group_layout"),
@@ -176,25 +220,16 @@ pub fn load_prefabs(
         }
       ]
     });
-  let player_model_diffuse_bind_group = renderer.device().create_bind_group(&wgpu::BindGroupDescriptor {
-    label: Some("player_model_bind_group"),
-    layout: &player_model_diffuse_bind_group_layout,
-    entries: &[
-      wgpu::BindGroupEntry {
-        binding: 0,
-        resource: wgpu::BindingResource::TextureView(&player_model_diffuse_view),
-      },
-      wgpu::BindGroupEntry {
-        binding: 1,
-        resource: wgpu::BindingResource::Sampler(&player_model_diffuse_sampler),
-      }
-    ]
-  });
-
-  let player_model = load_obj_prefab(&renderer, &assman, &PathBuf::from("playermodel1.obj"));
+  let (
+    player_model_diffuse_texture,
+    player_model_diffuse_bind_group,
+    player_model,
+  ) = load_player_model(&renderer, &assman, &player_model_diffuse_bind_group_layout);
 
   storages.add_unique_non_send_sync(GpuPrefabs {
+    block_texture_names,
     block_diffuse_texture,
+    block_texture_uniform,
     block_diffuse_bind_group_layout,
     block_diffuse_bind_group,
     player_model_diffuse_texture,
@@ -243,3 +278,81 @@ pub fn load_prefabs(
 
   //renderer.display.release_shader_compiler();
 }
+
+/// Reload the block texture array if the textures used by the current block registry changed\
+/// (for example, after receiving the block registry from the server)
+pub fn reload_block_textures(
+  renderer: UniqueView<Renderer>,
+  assman: UniqueView<AssetManager>,
+  mut prefabs: NonSendSync<UniqueViewMut<GpuPrefabs>>,
+  registry: UniqueView<SharedBlockRegistry>,
+) {
+  let names = registry.textures();
+  if prefabs.block_texture_names == names {
+    return
+  }
+  replace_block_textures(&renderer, &assman, &mut prefabs, names);
+}
+
+fn replace_block_textures(
+  renderer: &Renderer,
+  assman: &AssetManager,
+  prefabs: &mut GpuPrefabs,
+  names: &[String],
+) {
+  log::info!("Reloading block textures...");
+  let (texture, animations) = load_block_textures(renderer, assman, names);
+  if let Err(error) = prefabs.block_texture_uniform.set_animations(renderer, &animations) {
+    log::error!("failed to set block texture animations: {error}");
+  }
+  prefabs.block_diffuse_bind_group = create_block_diffuse_bind_group(
+    renderer,
+    &prefabs.block_diffuse_bind_group_layout,
+    &texture,
+    &prefabs.block_texture_uniform,
+  );
+  prefabs.block_diffuse_texture = texture;
+  prefabs.block_texture_names = names.to_vec();
+}
+
+/// Enable the resource packs selected in the settings
+pub fn init_resource_packs(
+  mut assman: UniqueViewMut<AssetManager>,
+  mut settings: UniqueViewMut<GameSettings>,
+) {
+  assman.set_packs(&settings.resource_packs);
+  //forget packs that failed to load
+  settings.resource_packs = assman.pack_ids().map(String::from).collect();
+}
+
+/// Switch resource packs if they were changed in the settings,
+/// and reload all assets they can override (block textures, and the player model and its texture)
+pub fn update_resource_packs(
+  renderer: UniqueView<Renderer>,
+  mut assman: UniqueViewMut<AssetManager>,
+  mut settings: UniqueViewMut<GameSettings>,
+  mut prefabs: NonSendSync<UniqueViewMut<GpuPrefabs>>,
+) {
+  if assman.pack_ids().eq(settings.resource_packs.iter().map(String::as_str)) {
+    return
+  }
+  assman.set_packs(&settings.resource_packs);
+  settings.resource_packs = assman.pack_ids().map(String::from).collect();
+  let names = prefabs.block_texture_names.clone();
+  replace_block_textures(&renderer, &assman, &mut prefabs, &names);
+
+  log::info!("Reloading the player model...");
+  let (texture, bind_group, model) = load_player_model(&renderer, &assman, &prefabs.player_model_diffuse_bind_group_layout);
+  prefabs.player_model_diffuse_texture = texture;
+  prefabs.player_model_diffuse_bind_group = bind_group;
+  prefabs.player_model = model;
+}
+
+/// Advance the time used to animate block textures
+pub fn update_block_texture_animations(
+  renderer: UniqueView<Renderer>,
+  dt: UniqueView<DeltaTime>,
+  mut prefabs: NonSendSync<UniqueViewMut<GpuPrefabs>>,
+) {
+  prefabs.block_texture_uniform.advance(&renderer, dt.0.as_secs_f32());
+}
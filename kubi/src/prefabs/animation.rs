@@ -0,0 +1,169 @@
+//! Animated block textures
+//!
+//! Animated textures have all of their frames stacked vertically in the PNG,
+//! and a `<name>.toml` file next to it with the time each frame is shown for:
+//! ```toml
+//! frame_time = 0.25 #seconds
+//! ```
+//! The first frame is stored in the texture's own layer of the block texture array (so meshes don't need to change),
+//! and the other ones are appended as extra layers after all textures.\
+//! The vertex shader picks the current frame using the animation table and the time in [`BlockTextureUniform`]
+
+use anyhow::{ensure, Result};
+use bytemuck::{Pod, Zeroable};
+use serde::Deserialize;
+use wgpu::util::DeviceExt;
+use crate::rendering::Renderer;
+
+/// Max number of block textures (they're indexed by an `u8`)
+pub const MAX_BLOCK_TEXTURES: usize = 256;
+
+/// Time wraps around after this many seconds, so that it doesn't lose precision
+const TIME_WRAP: f32 = 3600.;
+
+/// Contents of the `<name>.toml` file of an animated texture
+#[derive(Deserialize)]
+pub struct AnimationMetadata {
+  pub frame_time: f32,
+}
+
+/// Animation of a block texture, as seen by the shader
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct TextureAnimation {
+  /// Layer of the second frame (the first frame is in the texture's own layer)
+  pub first_layer: u32,
+  /// Static textures have a single frame
+  pub frames: u32,
+  pub frame_time: f32,
+  pub _padding: u32,
+}
+
+impl TextureAnimation {
+  pub const STATIC: Self = Self {
+    first_layer: 0,
+    frames: 1,
+    frame_time: 1.,
+    _padding: 0,
+  };
+}
+
+/// A block texture to lay out in the texture array (see [`layout_layers`])
+pub struct LayerTexture<'a> {
+  pub name: &'a str,
+  pub frames: usize,
+  /// `None` for static textures
+  pub frame_time: Option<f32>,
+}
+
+/// Lay out the layers of the block texture array: first frames of all textures, then the other frames of animated ones\
+/// Animations that would need more than `max_layers` layers in total are skipped with a warning (their textures stay static)\
+/// Returns the `(texture, frame)` shown in each layer, and the animation of each texture
+pub fn layout_layers(textures: &[LayerTexture], max_layers: usize) -> (Vec<(usize, usize)>, Vec<TextureAnimation>) {
+  let mut layers: Vec<(usize, usize)> = (0..textures.len()).map(|texture| (texture, 0)).collect();
+  let mut animations = Vec::with_capacity(textures.len());
+  for (index, texture) in textures.iter().enumerate() {
+    let Some(frame_time) = texture.frame_time.filter(|_| texture.frames > 1) else {
+      animations.push(TextureAnimation::STATIC);
+      continue
+    };
+    if layers.len() + texture.frames - 1 > max_layers {
+      log::warn!(
+        "animating {} needs {} more texture layers, but only {} are available, it won't be animated",
+        texture.name, texture.frames - 1, max_layers.saturating_sub(layers.len())
+      );
+      animations.push(TextureAnimation::STATIC);
+      continue
+    }
+    animations.push(TextureAnimation {
+      first_layer: layers.len() as u32,
+      frames: texture.frames as u32,
+      frame_time,
+      _padding: 0,
+    });
+    layers.extend((1..texture.frames).map(|frame| (index, frame)));
+  }
+  (layers, animations)
+}
+
+const TIME_SIZE: u64 = 16;
+const ANIMATION_SIZE: u64 = std::mem::size_of::<TextureAnimation>() as u64;
+
+/// Current time and animations of all block textures, bound next to the block texture array
+pub struct BlockTextureUniform {
+  pub buffer: wgpu::Buffer,
+  time: f32,
+}
+
+impl BlockTextureUniform {
+  pub fn new(renderer: &Renderer) -> Self {
+    //all textures are static until the animations are set
+    let mut contents = vec![0; TIME_SIZE as usize];
+    contents.extend_from_slice(bytemuck::cast_slice(&[TextureAnimation::STATIC; MAX_BLOCK_TEXTURES]));
+    let buffer = renderer.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("block_texture_uniform_buffer"),
+      contents: &contents,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+    Self { buffer, time: 0. }
+  }
+
+  /// Replace the animation table (indexed by `BlockTexture`)\
+  /// Fails if there are more than [`MAX_BLOCK_TEXTURES`] animations, the current table is kept then
+  pub fn set_animations(&self, renderer: &Renderer, animations: &[TextureAnimation]) -> Result<()> {
+    ensure!(
+      animations.len() <= MAX_BLOCK_TEXTURES,
+      "too many block textures to animate ({}, max {MAX_BLOCK_TEXTURES})", animations.len()
+    );
+    let mut table = vec![TextureAnimation::STATIC; MAX_BLOCK_TEXTURES];
+    table[..animations.len()].copy_from_slice(animations);
+    renderer.queue().write_buffer(&self.buffer, TIME_SIZE, bytemuck::cast_slice(&table));
+    Ok(())
+  }
+
+  /// Advance the animation time by `delta` seconds
+  pub fn advance(&mut self, renderer: &Renderer, delta: f32) {
+    self.time = (self.time + delta) % TIME_WRAP;
+    renderer.queue().write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.time));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn texture(name: &str, frames: usize, frame_time: Option<f32>) -> LayerTexture<'_> {
+    LayerTexture { name, frames, frame_time }
+  }
+
+  #[test]
+  fn animations_that_dont_fit_are_skipped() {
+    let textures = [
+      texture("stone", 1, None),
+      texture("water", 3, Some(0.5)),
+      texture("lava", 4, Some(0.5)),
+      texture("fire", 2, Some(0.25)),
+    ];
+    let (layers, animations) = layout_layers(&textures, 7);
+    //lava needs 3 more layers and only 1 is left after water, but fire still fits
+    assert_eq!(layers, [(0, 0), (1, 0), (2, 0), (3, 0), (1, 1), (1, 2), (3, 1)]);
+    assert_eq!(animations, [
+      TextureAnimation::STATIC,
+      TextureAnimation { first_layer: 4, frames: 3, frame_time: 0.5, _padding: 0 },
+      TextureAnimation::STATIC,
+      TextureAnimation { first_layer: 6, frames: 2, frame_time: 0.25, _padding: 0 },
+    ]);
+  }
+
+  #[test]
+  fn all_animations_fit() {
+    let textures = [
+      texture("water", 3, Some(0.5)),
+      texture("lava", 4, Some(0.5)),
+    ];
+    let (layers, animations) = layout_layers(&textures, 256);
+    assert_eq!(layers.len(), 7);
+    assert_eq!(animations[1].first_layer, 4);
+    assert_eq!(animations[1].frames, 4);
+  }
+}
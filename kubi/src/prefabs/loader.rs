@@ -1,53 +1,104 @@
 use glam::UVec2;
-use strum::IntoEnumIterator;
+use image::{imageops::{self, FilterType}, RgbaImage};
 use rayon::prelude::*;
 use wgpu::util::{DeviceExt, TextureDataOrder};
-use std::{io::BufReader, path::{Path, PathBuf}};
+use std::{io::{BufReader, Read}, path::{Path, PathBuf}};
 use crate::{filesystem::AssetManager, prefabs::ModelVertex, rendering::{BufferPair, Renderer}};
-use super::AssetPaths;
+use super::animation::{layout_layers, AnimationMetadata, LayerTexture, TextureAnimation};
+
+/// Load the frames of a texture from the first layer it's valid in (see [`AssetManager::open_asset_layers`])
+/// and the time each frame is shown for (see [`super::animation`]), from the same layer\
+/// Frames must be square, so that they can all be scaled to the same size
+fn load_layer_texture(assman: &AssetManager, path: &Path) -> (Vec<RgbaImage>, Option<f32>) {
+  let mut metadata_layers = assman.open_asset_layers(&path.with_extension("toml"));
+
+  for (layer, reader) in assman.open_asset_layers(path) {
+    let frame_time = metadata_layers.iter_mut()
+      .find(|(metadata_layer, _)| *metadata_layer == layer)
+      .and_then(|(_, reader)| {
+        let mut metadata = String::new();
+        reader.read_to_string(&mut metadata).ok()?;
+        toml::from_str::<AnimationMetadata>(&metadata)
+          .inspect_err(|error| log::warn!("{layer}: invalid animation metadata for {path:?}: {error}"))
+          .ok()
+      })
+      .map(|metadata| metadata.frame_time)
+      .filter(|&frame_time| frame_time > 0.);
+
+    let image = match image::load(BufReader::new(reader), image::ImageFormat::Png) {
+      Ok(image) => image.to_rgba8(),
+      Err(error) => {
+        log::warn!("{layer}: failed to load texture {path:?}: {error}");
+        continue
+      }
+    };
+    let (width, height) = image.dimensions();
+    let valid = match frame_time {
+      Some(_) => width > 0 && height % width == 0,
+      None => width > 0 && width == height,
+    };
+    if !valid {
+      log::warn!("{layer}: texture {path:?} must be square (or a strip of square frames if animated), but it's {width}x{height}");
+      continue
+    }
+    let frames = (0..(height / width))
+      .map(|frame| imageops::crop_imm(&image, 0, frame * width, width, width).to_image())
+      .collect();
+    return (frames, frame_time)
+  }
+  panic!("Failed to load texture {path:?}")
+}
 
-pub fn load_texture2darray_prefab<T: AssetPaths + IntoEnumIterator>(
+/// Load a texture array, with a layer for each texture and extra layers for animation frames\
+/// Returns the texture and the animation of each texture
+pub fn load_texture2darray_prefab(
   renderer: &Renderer,
   assman: &AssetManager,
   directory: PathBuf,
-) -> wgpu::Texture {
+  tex_files: &[String],
+) -> (wgpu::Texture, Vec<TextureAnimation>) {
   log::info!("started loading {}", directory.as_os_str().to_str().unwrap());
 
   //Load raw images
-  let tex_files: Vec<&'static str> = T::iter().map(|x| x.file_name()).collect();
-  let raw_images: Vec<(Vec<u8>, UVec2)> = tex_files.par_iter().map(|&file_name| {
+  let textures: Vec<(Vec<RgbaImage>, Option<f32>)> = tex_files.par_iter().map(|file_name| {
     log::info!("loading texture {}", file_name);
-
-    //Get path to the image and open the file
-    let reader = {
-      let path = directory.join(file_name);
-      BufReader::new(assman.open_asset(&path).expect("Failed to open texture file"))
-    };
-
-    //Parse image data
-    let (image_data, dimensions) = {
-      let image = image::load(
-        reader,
-        image::ImageFormat::Png
-      ).unwrap().to_rgba8();
-      let dimensions = image.dimensions();
-      (image.into_raw(), dimensions)
-    };
-    (image_data, UVec2::from(dimensions))
+    load_layer_texture(assman, &directory.join(file_name))
   }).collect();
 
-  assert!(!raw_images.is_empty(), "no images loaded");
-  //TODO: check same size
+  assert!(!textures.is_empty(), "no images loaded");
+
+  //Lay out the layers, animation frames are only added while they fit in the texture array
+  let max_layers = renderer.device().limits().max_texture_array_layers as usize;
+  let layer_textures: Vec<LayerTexture> = textures.iter().zip(tex_files)
+    .map(|((frames, frame_time), file_name)| LayerTexture {
+      name: file_name,
+      frames: frames.len(),
+      frame_time: *frame_time,
+    })
+    .collect();
+  let (layout, animations) = layout_layers(&layer_textures, max_layers);
+  let layers: Vec<&RgbaImage> = layout.into_iter()
+    .map(|(texture, frame)| &textures[texture].0[frame])
+    .collect();
+
+  //Scale all images to the size of the largest one (resource packs may use different resolutions)
+  let max_size = renderer.device().limits().max_texture_dimension_2d;
+  let size = layers.iter().map(|image| image.width()).max().unwrap().min(max_size);
+  let size = UVec2::splat(size);
 
   log::info!("done loading texture files, uploading to the gpu");
 
-  let size = raw_images[0].1;
-  let layers = raw_images.len() as u32;
+  let layer_count = layers.len() as u32;
 
   //Concat data into a single vec
-  let mut data = Vec::with_capacity((size.x * size.y * layers * 4) as usize);
-  for (layer_data, _) in raw_images {
-    data.extend_from_slice(&layer_data);
+  let mut data = Vec::with_capacity((size.x * size.y * layer_count * 4) as usize);
+  for image in layers {
+    if image.width() == size.x {
+      data.extend_from_slice(image);
+    } else {
+      log::debug!("rescaling texture from {}px to {}px", image.width(), size.x);
+      data.extend_from_slice(&imageops::resize(image, size.x, size.y, FilterType::Nearest));
+    }
   }
 
   //Upload images to the GPU
@@ -57,7 +108,7 @@ pub fn load_texture2darray_prefab<T: AssetPaths + IntoEnumIterator>(
     size: wgpu::Extent3d {
       width: size.x,
       height: size.y,
-      depth_or_array_layers: layers,
+      depth_or_array_layers: layer_count,
     },
     dimension: wgpu::TextureDimension::D2,
     format: wgpu::TextureFormat::Rgba8UnormSrgb,
@@ -67,12 +118,13 @@ pub fn load_texture2darray_prefab<T: AssetPaths + IntoEnumIterator>(
     view_formats: &[],
   };
 
-  renderer.device().create_texture_with_data(
+  let texture = renderer.device().create_texture_with_data(
     renderer.queue(),
     desc,
     TextureDataOrder::MipMajor,
     &data
-  )
+  );
+  (texture, animations)
 }
 
 pub fn load_texture2d_prefab(
@@ -58,6 +58,7 @@ pub fn update_rendering_late() -> Workload {
     (
       selection_box::update_selection_box_render_state,
       entities::update_entities_render_state,
+      world::update_falling_blocks_mesh,
       smoverlay::update_smoverlay_render_state,
     ).into_workload().run_if(is_ingame),
   ).into_workload()
@@ -82,7 +83,7 @@ pub fn render_master(storages: AllStoragesViewMut) {
     storages.run_with_data(world::draw_world, &mut data);
     storages.run_with_data(selection_box::draw_selection_box, &mut data);
     storages.run_with_data(entities::render_entities, &mut data);
-    storages.run_with_data(world::rpass_submit_trans_bundle, &mut data);
+    storages.run_with_data(world::draw_world_trans, &mut data);
     storages.run_with_data(smoverlay::render_submerged_view, &mut data);
   }
   storages.run_with_data(kubi_ui_draw, &mut data);
@@ -24,6 +24,8 @@ pub struct Renderer {
   queue: wgpu::Queue,
   surface_config: wgpu::SurfaceConfiguration,
   size: PhysicalSize<u32>,
+  indirect_draw: bool,
+  base_vertex: bool,
   // pub depth_texture: wgpu::Texture,
 }
 
@@ -119,11 +121,30 @@ impl Renderer {
     log::info!("Features: {:?}", adapter.features());
     log::info!("Limits: {:?}", adapter.limits());
 
+    //multi draw indirect needs chunk origins to be in a storage buffer (indexed by the instance index)
+    let indirect_features = wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE;
+    let indirect_draw =
+      adapter.features().contains(indirect_features) &&
+      adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE) &&
+      adapter.limits().max_storage_buffers_per_shader_stage >= 1;
+    log::info!("indirect draw: {}", if indirect_draw { "supported" } else { "not supported" });
+    let base_vertex = adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::BASE_VERTEX);
+    log::info!("base vertex: {}", if base_vertex { "supported" } else { "not supported" });
+
+    let mut required_limits = wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits());
+    if indirect_draw {
+      required_limits.max_storage_buffers_per_shader_stage = 1;
+      required_limits.max_storage_buffer_binding_size = adapter.limits().max_storage_buffer_binding_size;
+    }
+
     let (device, queue) = adapter.request_device(
       &wgpu::DeviceDescriptor {
         label: None,
-        required_features: wgpu::Features::empty(),
-        required_limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+        required_features: match indirect_draw {
+          true => indirect_features,
+          false => wgpu::Features::empty(),
+        },
+        required_limits,
         memory_hints: wgpu::MemoryHints::Performance,
       },
       None,
@@ -133,7 +154,7 @@ impl Renderer {
     surface_config.present_mode = get_vsync_mode(settings.vsync);
     surface.configure(&device, &surface_config);
 
-    Self { window, instance, surface, device, queue, surface_config, size }
+    Self { window, instance, surface, device, queue, surface_config, size, indirect_draw, base_vertex }
   }
 
   pub fn reload_settings(&mut self, settings: &GameSettings) {
@@ -201,4 +222,14 @@ impl Renderer {
   pub fn surface_config(&self) -> &wgpu::SurfaceConfiguration {
     &self.surface_config
   }
+
+  /// Whether chunks can be drawn with multi draw indirect (otherwise, they're drawn one by one)
+  pub fn supports_indirect_draw(&self) -> bool {
+    self.indirect_draw
+  }
+
+  /// Whether indexed draws can use a base vertex (not supported on WebGL)
+  pub fn supports_base_vertex(&self) -> bool {
+    self.base_vertex
+  }
 }
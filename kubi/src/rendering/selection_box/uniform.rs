@@ -2,6 +2,7 @@ use glam::Vec3;
 use shipyard::{IntoIter, UniqueView, View};
 use bytemuck::{Pod, Zeroable};
 use crate::{
+  block_placement::MiningProgress,
   player::MainPlayer,
   rendering::Renderer,
   world::raycast::LookingAtBlock,
@@ -12,7 +13,8 @@ use super::SboxRenderState;
 #[repr(C, packed)]
 pub struct SelectionBoxUniformData {
   pub position: [f32; 3],
-  pub _padding: u32,
+  /// Mining progress of the selected block, in range `0..=1`
+  pub progress: f32,
 }
 
 pub struct SelectionBoxUniform {
@@ -37,7 +39,7 @@ pub fn init_selection_box_uniform(
     label: Some("selection_box_bind_group_layout"),
     entries: &[wgpu::BindGroupLayoutEntry {
       binding: 0,
-      visibility: wgpu::ShaderStages::VERTEX,
+      visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
       ty: wgpu::BindingType::Buffer {
         ty: wgpu::BufferBindingType::Uniform,
         has_dynamic_offset: false,
@@ -67,16 +69,21 @@ pub fn update_selection_box_uniform(
   renderer: UniqueView<Renderer>,
   state: UniqueView<SboxRenderState>,
   lookat: View<LookingAtBlock>,
+  mining: View<MiningProgress>,
   player: View<MainPlayer>,
 ) {
   //TODO: only update if changed
   if let Some((LookingAtBlock(Some(lookat)), _)) = (&lookat, &player).iter().next() {
+    let progress = (&mining, &player).iter().next()
+      .filter(|(mining, _)| mining.position == Some(lookat.block_position))
+      .map(|(mining, _)| mining.progress.min(1.))
+      .unwrap_or(0.);
     renderer.queue().write_buffer(
       &state.uniform.buffer,
       0,
       bytemuck::cast_slice(&[SelectionBoxUniformData {
         position: (lookat.position.floor() + Vec3::splat(0.5)).to_array(),
-        _padding: 0,
+        progress,
       }]),
     );
   };
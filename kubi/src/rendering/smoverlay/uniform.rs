@@ -1,5 +1,5 @@
 use bytemuck::{Pod, Zeroable};
-use kubi_shared::transform::Transform;
+use kubi_shared::{block::SharedBlockRegistry, transform::Transform};
 use shipyard::{IntoIter, UniqueView, UniqueViewMut, View};
 use crate::{player::MainPlayer, rendering::Renderer, world::ChunkStorage};
 use super::SmOverlayRenderState;
@@ -68,6 +68,7 @@ pub fn update_sm_uniform(
   plr: View<MainPlayer>,
   trans: View<Transform>,
   world: UniqueView<ChunkStorage>,
+  registry: UniqueView<SharedBlockRegistry>,
 ) {
   state.uniform.internal_do_render_flag = false;
 
@@ -75,7 +76,7 @@ pub fn update_sm_uniform(
   let plr_pos = plr_trans.0.to_scale_rotation_translation().2;
   let block_at_pos = world.get_block(plr_pos.floor().as_ivec3());
   let Some(block_at_pos) = block_at_pos  else { return };
-  let Some(color) = block_at_pos.descriptor().submerge else { return };
+  let Some(color) = registry.get(block_at_pos).submerge else { return };
 
   let new_data = SmUniformData {
     color: color.to_array()
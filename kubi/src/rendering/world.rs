@@ -1,32 +1,122 @@
-use glam::Vec3;
+use std::ops::Range;
+use glam::{IVec3, Vec3};
 use shipyard::{AllStoragesView, IntoIter, NonSendSync, Unique, UniqueView, UniqueViewMut, View};
-use kubi_shared::chunk::CHUNK_SIZE;
+use wgpu::util::DeviceExt;
+use kubi_shared::{block::SharedBlockRegistry, chunk::CHUNK_SIZE, falling_block::FallingBlock, height::WorldHeight};
 use crate::{
   camera::Camera,
   prefabs::GpuPrefabs,
-  world::{ChunkMeshStorage, ChunkStorage},
+  settings::GameSettings,
+  world::{mesh::generate_free_block_mesh, ChunkMeshStorage, ChunkStorage},
 };
-use super::{camera_uniform::CameraUniformBuffer, depth::DepthTexture, RenderCtx, Renderer};
+use super::{camera_uniform::CameraUniformBuffer, depth::DepthTexture, BufferPair, RenderCtx, Renderer};
 
 mod pipeline;
 mod vertex;
+mod origin;
+mod occlusion;
+mod allocator;
+mod mesh_buffers;
+mod draw_list;
 pub use vertex::ChunkVertex;
+pub use origin::ChunkOriginBuffer;
+pub use mesh_buffers::{ChunkMeshBuffers, MeshAllocation};
+use draw_list::ChunkDrawList;
+
+/// Meshes of all falling blocks, stored in a single buffer pair
+pub struct FallingBlocksMesh {
+  pub buffers: BufferPair,
+  /// Index range and origin of each block
+  pub blocks: Vec<(Range<u32>, Vec3)>,
+}
+
+/// Number of chunks drawn and culled in the last frame (chunks with empty meshes are not counted)
+#[derive(Clone, Copy, Default)]
+pub struct WorldRenderStats {
+  pub drawn: usize,
+  pub frustum_culled: usize,
+  pub occlusion_culled: usize,
+}
 
 #[derive(Unique)]
 pub struct WorldRenderState {
   pub pipeline: wgpu::RenderPipeline,
   pub pipeline_trans: wgpu::RenderPipeline,
-  pub trans_bundle: Option<wgpu::RenderBundle>,
+  pub origins: ChunkOriginBuffer,
+  main_draws: ChunkDrawList,
+  /// Transparent chunk meshes, recorded in [`draw_world`] and drawn in [`draw_world_trans`]
+  trans_draws: ChunkDrawList,
+  /// Rebuilt every frame
+  pub falling_blocks: Option<FallingBlocksMesh>,
+  pub stats: WorldRenderStats,
 }
 
 pub fn init_world_render_state(storages: AllStoragesView) {
-  let (pipeline, pipeline_trans) = storages.run(pipeline::init_world_pipeline);
+  let renderer = storages.borrow::<UniqueView<Renderer>>().unwrap();
+  let origins = ChunkOriginBuffer::new(&renderer, renderer.supports_indirect_draw());
+  let main_draws = ChunkDrawList::new(&renderer, "chunk_indirect_buffer");
+  let trans_draws = ChunkDrawList::new(&renderer, "chunk_trans_indirect_buffer");
+  let (pipeline, pipeline_trans) = storages.run_with_data(pipeline::init_world_pipeline, &origins);
   storages.add_unique(WorldRenderState {
     pipeline, pipeline_trans,
-    trans_bundle: None,
+    origins,
+    main_draws,
+    trans_draws,
+    falling_blocks: None,
+    stats: WorldRenderStats::default(),
   })
 }
 
+pub fn update_falling_blocks_mesh(
+  mut state: UniqueViewMut<WorldRenderState>,
+  renderer: UniqueView<Renderer>,
+  falling_blocks: View<FallingBlock>,
+  world: UniqueView<ChunkStorage>,
+  registry: UniqueView<SharedBlockRegistry>,
+) {
+  if falling_blocks.is_empty() {
+    state.falling_blocks = None;
+    return
+  }
+
+  let mut vertices = Vec::new();
+  let mut indices = Vec::new();
+  let mut blocks = Vec::new();
+  for block in falling_blocks.iter() {
+    //use the light of the block the center of the falling block is in, fully lit if it's not loaded
+    let light = world.get_packed_light((block.position + Vec3::splat(0.5)).floor().as_ivec3()).unwrap_or(0xff);
+    let (block_vertices, block_indices) = generate_free_block_mesh(&registry, block.state, light);
+    let index_start = indices.len() as u32;
+    blocks.push((index_start..(index_start + block_indices.len() as u32), block.position));
+    //base vertex is not supported on WebGL, so indices point directly into the vertex buffer
+    let vertex_start = vertices.len() as u32;
+    vertices.extend(block_vertices);
+    indices.extend(block_indices.into_iter().map(|index| index + vertex_start));
+  }
+
+  let vertex = renderer.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    label: Some("falling_blocks_vertex_buffer"),
+    contents: bytemuck::cast_slice(&vertices),
+    usage: wgpu::BufferUsages::VERTEX,
+  });
+
+  let index = renderer.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    label: Some("falling_blocks_index_buffer"),
+    contents: bytemuck::cast_slice(&indices),
+    usage: wgpu::BufferUsages::INDEX,
+  });
+
+  state.falling_blocks = Some(FallingBlocksMesh {
+    buffers: BufferPair {
+      vertex,
+      vertex_len: vertices.len() as u32,
+      index,
+      index_len: indices.len() as u32,
+    },
+    blocks,
+  });
+}
+
 pub fn draw_world(
   ctx: &mut RenderCtx,
   mut state: UniqueViewMut<WorldRenderState>,
@@ -37,10 +127,70 @@ pub fn draw_world(
   camera: View<Camera>,
   chunks: UniqueView<ChunkStorage>,
   meshes: NonSendSync<UniqueView<ChunkMeshStorage>>,
-  //settings: UniqueView<GameSettings>,
+  settings: UniqueView<GameSettings>,
+  height: UniqueView<WorldHeight>,
 ) {
   let camera = camera.iter().next().expect("No cameras in the scene");
 
+  let chunk_in_frustum = |position: IVec3| {
+    let minp = position.as_vec3() * CHUNK_SIZE as f32;
+    let maxp = minp + Vec3::splat(CHUNK_SIZE as f32);
+    camera.frustum.is_box_visible(minp, maxp)
+  };
+
+  //Find chunks that are not hidden behind other chunks
+  let reachable_chunks = match settings.occlusion_culling {
+    true => {
+      let camera_position = camera.view_matrix.inverse().w_axis.truncate();
+      let camera_chunk = (camera_position / CHUNK_SIZE as f32).floor().as_ivec3();
+      occlusion::find_visible_chunks(&chunks, &meshes, &height, camera_chunk, chunk_in_frustum)
+    },
+    false => None,
+  };
+
+  //Find visible chunks, record their draw calls and upload their origins
+  //(this has to happen before drawing, as the origin buffer may get replaced)
+  let state = &mut *state;
+  state.origins.clear();
+  state.main_draws.clear();
+  state.trans_draws.clear();
+  state.stats = WorldRenderStats::default();
+  for (&position, chunk) in &chunks.chunks {
+    if let Some(key) = chunk.mesh_index {
+      let mesh = meshes.get(key).expect("Mesh index pointing to nothing");
+      let world_position = position.as_vec3() * CHUNK_SIZE as f32;
+
+      //Skip if mesh is empty
+      if mesh.main.is_empty() && mesh.trans.is_empty() {
+        continue
+      }
+
+      //Frustum culling
+      if !chunk_in_frustum(position) {
+        state.stats.frustum_culled += 1;
+        continue
+      }
+
+      //Occlusion culling
+      if reachable_chunks.as_ref().is_some_and(|reachable| !reachable.contains(&position)) {
+        state.stats.occlusion_culled += 1;
+        continue
+      }
+
+      state.stats.drawn += 1;
+      let origin = state.origins.push(world_position);
+      state.main_draws.push(&mesh.main, origin);
+      state.trans_draws.push(&mesh.trans, origin);
+    }
+  }
+  let falling_block_origins: Vec<u32> = match &state.falling_blocks {
+    Some(falling_blocks) => falling_blocks.blocks.iter().map(|&(_, origin)| state.origins.push(origin)).collect(),
+    None => Vec::new(),
+  };
+  state.origins.upload(&renderer);
+  state.main_draws.upload(&renderer);
+  state.trans_draws.upload(&renderer);
+
   let mut render_pass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
     label: Some("rpass_draw_world"),
     color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -66,79 +216,42 @@ pub fn draw_world(
   render_pass.set_bind_group(0, &textures.block_diffuse_bind_group, &[]);
   render_pass.set_bind_group(1, &camera_ubo.camera_bind_group, &[]);
 
-  let mut trans_bundle_used = false;
-  let mut trans_bundle = renderer.device().create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
-    label: Some("trans_bundle_encoder"),
-    color_formats: &[Some(renderer.surface_config().format)],
-    depth_stencil: Some(wgpu::RenderBundleDepthStencil {
-      format: depth.depth_texture.format(),
-      depth_read_only: true,
-      stencil_read_only: true,
-    }),
-    sample_count: 1,
-    multiview: None,
-  });
-
-  trans_bundle.set_pipeline(&state.pipeline_trans);
-  trans_bundle.set_bind_group(0, &textures.block_diffuse_bind_group, &[]);
-  trans_bundle.set_bind_group(1, &camera_ubo.camera_bind_group, &[]);
-
-  for (&position, chunk) in &chunks.chunks {
-    if let Some(key) = chunk.mesh_index {
-      let mesh = meshes.get(key).expect("Mesh index pointing to nothing");
-      let world_position = position.as_vec3() * CHUNK_SIZE as f32;
-
-      //Skip if mesh is empty
-      if mesh.main.index.size() == 0 && mesh.trans.index.size() == 0 {
-        continue
-      }
-
-      //Frustum culling
-      let minp = world_position;
-      let maxp = world_position + Vec3::splat(CHUNK_SIZE as f32);
-      if !camera.frustum.is_box_visible(minp, maxp) {
-        continue
-      }
-
-      //Draw chunk mesh
-      if mesh.main.index_len > 0 {
-        render_pass.set_index_buffer(mesh.main.index.slice(..), wgpu::IndexFormat::Uint32);
-        render_pass.set_vertex_buffer(0, mesh.main.vertex.slice(..));
-        render_pass.draw_indexed(0..mesh.main.index_len, 0, 0..1);
-      }
+  //Draw chunk meshes
+  if !state.main_draws.is_empty() {
+    render_pass.set_index_buffer(meshes.buffers().index_buffer().slice(..), wgpu::IndexFormat::Uint32);
+    render_pass.set_vertex_buffer(0, meshes.buffers().vertex_buffer().slice(..));
+    state.main_draws.draw(&mut render_pass, &state.origins, 2);
+  }
 
-      //Draw transparent chunk mesh
-      if mesh.trans.index_len > 0 {
-        trans_bundle_used = true;
-        trans_bundle.set_index_buffer(mesh.trans.index.slice(..), wgpu::IndexFormat::Uint32);
-        trans_bundle.set_vertex_buffer(0, mesh.trans.vertex.slice(..));
-        trans_bundle.draw_indexed(0..mesh.trans.index_len, 0, 0..1);
+  //Draw falling blocks
+  if let Some(falling_blocks) = &state.falling_blocks {
+    let mesh = &falling_blocks.buffers;
+    if mesh.index_len > 0 {
+      render_pass.set_index_buffer(mesh.index.slice(..), wgpu::IndexFormat::Uint32);
+      render_pass.set_vertex_buffer(0, mesh.vertex.slice(..));
+      for ((indices, _), &origin) in falling_blocks.blocks.iter().zip(&falling_block_origins) {
+        let instances = state.origins.bind(&mut render_pass, 2, origin);
+        render_pass.draw_indexed(indices.clone(), 0, instances);
       }
     }
   }
-
-  drop(render_pass);
-
-  if trans_bundle_used {
-    let bundle = trans_bundle.finish(&wgpu::RenderBundleDescriptor {
-      label: Some("trans_bundle"),
-    });
-    state.trans_bundle = Some(bundle);
-  } else {
-    state.trans_bundle = None;
-  }
 }
 
-pub fn rpass_submit_trans_bundle(
+/// Draw transparent chunk meshes recorded in [`draw_world`]\
+/// This happens after everything else is drawn, so that it can be seen through them
+pub fn draw_world_trans(
   ctx: &mut RenderCtx,
   state: UniqueView<WorldRenderState>,
   depth: UniqueView<DepthTexture>,
+  textures: UniqueView<GpuPrefabs>,
+  camera_ubo: UniqueView<CameraUniformBuffer>,
+  meshes: NonSendSync<UniqueView<ChunkMeshStorage>>,
 ) {
-  let Some(bundle) = state.trans_bundle.as_ref() else {
+  if state.trans_draws.is_empty() {
     return
-  };
-  let mut rpass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-    label: Some("rpass_submit_trans_bundle"),
+  }
+  let mut render_pass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+    label: Some("rpass_draw_world_trans"),
     color_attachments: &[Some(wgpu::RenderPassColorAttachment {
       view: ctx.surface_view,
       resolve_target: None,
@@ -157,5 +270,10 @@ pub fn rpass_submit_trans_bundle(
     }),
     ..Default::default()
   });
-  rpass.execute_bundles(Some(bundle));
+  render_pass.set_pipeline(&state.pipeline_trans);
+  render_pass.set_bind_group(0, &textures.block_diffuse_bind_group, &[]);
+  render_pass.set_bind_group(1, &camera_ubo.camera_bind_group, &[]);
+  render_pass.set_index_buffer(meshes.buffers().index_buffer().slice(..), wgpu::IndexFormat::Uint32);
+  render_pass.set_vertex_buffer(0, meshes.buffers().vertex_buffer().slice(..));
+  state.trans_draws.draw(&mut render_pass, &state.origins, 2);
 }
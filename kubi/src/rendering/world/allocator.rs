@@ -0,0 +1,226 @@
+//! Sub-allocation of ranges from a larger space (like a GPU buffer)
+//!
+//! This only does the bookkeeping, so it works (and can be tested) without a GPU
+
+use std::ops::Range;
+
+/// First-fit range allocator
+///
+/// Free ranges are kept sorted, and merged with their neighbors when freed,
+/// so that freeing everything always leaves a single free range behind
+#[derive(Debug)]
+pub struct RangeAllocator {
+  capacity: u32,
+  /// Sorted by offset, no two ranges touch each other
+  free: Vec<Range<u32>>,
+}
+
+impl RangeAllocator {
+  pub fn new(capacity: u32) -> Self {
+    Self {
+      capacity,
+      free: match capacity {
+        0 => Vec::new(),
+        _ => vec![0..capacity],
+      },
+    }
+  }
+
+  pub fn capacity(&self) -> u32 {
+    self.capacity
+  }
+
+  /// Total amount of free space
+  pub fn free_space(&self) -> u32 {
+    self.free.iter().map(|range| range.end - range.start).sum()
+  }
+
+  /// Size of the largest free range (the largest possible allocation)
+  pub fn largest_free_range(&self) -> u32 {
+    self.free.iter().map(|range| range.end - range.start).max().unwrap_or(0)
+  }
+
+  /// Fraction of the free space that's not part of the largest free range, in range `0..=1`\
+  /// `0` means all free space is in one piece
+  pub fn fragmentation(&self) -> f32 {
+    match self.free_space() {
+      0 => 0.,
+      free_space => 1. - self.largest_free_range() as f32 / free_space as f32,
+    }
+  }
+
+  /// Allocate a range of `size`, returns `None` if there's no free range large enough\
+  /// Empty allocations always succeed, and don't take up any space
+  pub fn allocate(&mut self, size: u32) -> Option<Range<u32>> {
+    if size == 0 {
+      return Some(0..0)
+    }
+    let index = self.free.iter().position(|range| range.end - range.start >= size)?;
+    let range = &mut self.free[index];
+    let allocated = range.start..(range.start + size);
+    range.start += size;
+    if range.is_empty() {
+      self.free.remove(index);
+    }
+    Some(allocated)
+  }
+
+  /// Free a range returned by [`Self::allocate`]
+  pub fn free(&mut self, range: Range<u32>) {
+    if range.is_empty() {
+      return
+    }
+    debug_assert!(range.end <= self.capacity, "range out of bounds");
+    let index = self.free.partition_point(|free| free.start < range.start);
+    debug_assert!(index == 0 || self.free[index - 1].end <= range.start, "double free");
+    debug_assert!(index == self.free.len() || range.end <= self.free[index].start, "double free");
+
+    let merges_previous = index > 0 && self.free[index - 1].end == range.start;
+    let merges_next = index < self.free.len() && self.free[index].start == range.end;
+    match (merges_previous, merges_next) {
+      (true, true) => {
+        self.free[index - 1].end = self.free[index].end;
+        self.free.remove(index);
+      },
+      (true, false) => self.free[index - 1].end = range.end,
+      (false, true) => self.free[index].start = range.start,
+      (false, false) => self.free.insert(index, range),
+    }
+  }
+
+  /// Move all live allocations next to each other at the start of the space, leaving a single free range at the end
+  ///
+  /// `ranges` must contain every live allocation, they're updated to their new location\
+  /// Returns the moves (`old offset, new offset, length`) that have to be applied to the data
+  pub fn compact(&mut self, ranges: &mut [&mut Range<u32>]) -> Vec<(u32, u32, u32)> {
+    ranges.sort_by_key(|range| range.start);
+    let mut moves = Vec::with_capacity(ranges.len());
+    let mut offset = 0;
+    for range in ranges.iter_mut().filter(|range| range.end > range.start) {
+      let length = range.end - range.start;
+      moves.push((range.start, offset, length));
+      **range = offset..(offset + length);
+      offset += length;
+    }
+    debug_assert!(offset + self.free_space() == self.capacity, "not all allocations were given");
+    self.free = match offset < self.capacity {
+      true => vec![offset..self.capacity],
+      false => Vec::new(),
+    };
+    moves
+  }
+
+  /// Grow the space to `capacity`, the added space at the end is free
+  pub fn grow(&mut self, capacity: u32) {
+    assert!(capacity >= self.capacity, "can't shrink the space");
+    let old_capacity = self.capacity;
+    self.capacity = capacity;
+    self.free(old_capacity..capacity);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn allocations_are_consecutive() {
+    let mut allocator = RangeAllocator::new(100);
+    assert_eq!(allocator.allocate(10), Some(0..10));
+    assert_eq!(allocator.allocate(20), Some(10..30));
+    assert_eq!(allocator.free_space(), 70);
+  }
+
+  #[test]
+  fn out_of_space() {
+    let mut allocator = RangeAllocator::new(100);
+    assert_eq!(allocator.allocate(60), Some(0..60));
+    assert_eq!(allocator.allocate(60), None);
+    assert_eq!(allocator.allocate(40), Some(60..100));
+    assert_eq!(allocator.allocate(1), None);
+  }
+
+  #[test]
+  fn empty_allocations() {
+    let mut allocator = RangeAllocator::new(0);
+    assert_eq!(allocator.allocate(0), Some(0..0));
+    allocator.free(0..0);
+    assert_eq!(allocator.free_space(), 0);
+  }
+
+  #[test]
+  fn freed_space_is_reused() {
+    let mut allocator = RangeAllocator::new(100);
+    let a = allocator.allocate(30).unwrap();
+    let _b = allocator.allocate(30).unwrap();
+    allocator.free(a);
+    assert_eq!(allocator.allocate(20), Some(0..20));
+    assert_eq!(allocator.allocate(20), Some(60..80));
+  }
+
+  #[test]
+  fn free_ranges_are_merged() {
+    let mut allocator = RangeAllocator::new(100);
+    let a = allocator.allocate(10).unwrap();
+    let b = allocator.allocate(10).unwrap();
+    let c = allocator.allocate(10).unwrap();
+    let d = allocator.allocate(10).unwrap();
+
+    //merging with the next range
+    allocator.free(b);
+    allocator.free(a);
+    assert_eq!(allocator.largest_free_range(), 60);
+
+    //merging with the previous range
+    allocator.free(c);
+    assert_eq!(allocator.largest_free_range(), 60);
+
+    //merging with both
+    allocator.free(d);
+    assert_eq!(allocator.largest_free_range(), 100);
+    assert_eq!(allocator.allocate(100), Some(0..100));
+  }
+
+  #[test]
+  fn fragmentation() {
+    let mut allocator = RangeAllocator::new(100);
+    let ranges: Vec<_> = (0..10).map(|_| allocator.allocate(10).unwrap()).collect();
+    for range in ranges.into_iter().step_by(2) {
+      allocator.free(range);
+    }
+    assert_eq!(allocator.free_space(), 50);
+    assert_eq!(allocator.largest_free_range(), 10);
+    assert_eq!(allocator.allocate(11), None);
+  }
+
+  #[test]
+  fn compaction() {
+    let mut allocator = RangeAllocator::new(100);
+    let mut ranges: Vec<_> = (0..10).map(|_| allocator.allocate(10).unwrap()).collect();
+    let mut live: Vec<_> = ranges.drain(..).enumerate().filter_map(|(index, range)| match index % 2 {
+      0 => { allocator.free(range); None },
+      _ => Some(range),
+    }).collect();
+    assert!((allocator.fragmentation() - 0.8).abs() < 1e-6);
+
+    let moves = allocator.compact(&mut live.iter_mut().collect::<Vec<_>>());
+    assert_eq!(moves, vec![(10, 0, 10), (30, 10, 10), (50, 20, 10), (70, 30, 10), (90, 40, 10)]);
+    assert_eq!(live, vec![0..10, 10..20, 20..30, 30..40, 40..50]);
+    assert_eq!(allocator.fragmentation(), 0.);
+    assert_eq!(allocator.allocate(50), Some(50..100));
+
+    //live ranges are still tracked correctly
+    allocator.free(live.remove(0));
+    assert_eq!(allocator.allocate(10), Some(0..10));
+  }
+
+  #[test]
+  fn grow_merges_with_free_space_at_the_end() {
+    let mut allocator = RangeAllocator::new(100);
+    allocator.allocate(90).unwrap();
+    assert_eq!(allocator.allocate(20), None);
+    allocator.grow(200);
+    assert_eq!(allocator.largest_free_range(), 110);
+    assert_eq!(allocator.allocate(20), Some(90..110));
+  }
+}
@@ -0,0 +1,98 @@
+use wgpu::util::DrawIndexedIndirectArgs;
+use crate::rendering::Renderer;
+use super::{ChunkOriginBuffer, MeshAllocation};
+
+const ARGS_SIZE: u64 = std::mem::size_of::<DrawIndexedIndirectArgs>() as u64;
+
+/// Chunk draw calls recorded in a frame
+///
+/// If multi draw indirect is supported, they're uploaded to an indirect buffer and submitted with a single call,
+/// otherwise they're submitted one by one
+pub struct ChunkDrawList {
+  label: &'static str,
+  calls: Vec<DrawIndexedIndirectArgs>,
+  /// Indirect buffer and its capacity
+  indirect: Option<(wgpu::Buffer, usize)>,
+}
+
+impl ChunkDrawList {
+  const INITIAL_CAPACITY: usize = 1024;
+
+  pub fn new(renderer: &Renderer, label: &'static str) -> Self {
+    Self {
+      label,
+      calls: Vec::new(),
+      indirect: renderer.supports_indirect_draw().then(|| {
+        (Self::create_buffer(renderer, label, Self::INITIAL_CAPACITY), Self::INITIAL_CAPACITY)
+      }),
+    }
+  }
+
+  fn create_buffer(renderer: &Renderer, label: &'static str, capacity: usize) -> wgpu::Buffer {
+    renderer.device().create_buffer(&wgpu::BufferDescriptor {
+      label: Some(label),
+      size: ARGS_SIZE * capacity as u64,
+      usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false,
+    })
+  }
+
+  /// Remove all draw calls (call before recording the draw calls of a new frame)
+  pub fn clear(&mut self) {
+    self.calls.clear();
+  }
+
+  /// Draw `mesh` with the origin in `origin_slot`
+  pub fn push(&mut self, mesh: &MeshAllocation, origin_slot: u32) {
+    if mesh.is_empty() {
+      return
+    }
+    self.calls.push(DrawIndexedIndirectArgs {
+      index_count: mesh.indices.end - mesh.indices.start,
+      instance_count: 1,
+      first_index: mesh.indices.start,
+      base_vertex: mesh.base_vertex,
+      first_instance: origin_slot,
+    });
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.calls.is_empty()
+  }
+
+  /// Upload the draw calls to the indirect buffer (if used), growing it if needed
+  pub fn upload(&mut self, renderer: &Renderer) {
+    let Some((buffer, capacity)) = &mut self.indirect else {
+      return
+    };
+    if self.calls.len() > *capacity {
+      *capacity = self.calls.len().next_power_of_two();
+      *buffer = Self::create_buffer(renderer, self.label, *capacity);
+    }
+    if !self.calls.is_empty() {
+      let data: Vec<u8> = self.calls.iter().flat_map(|call| call.as_bytes()).copied().collect();
+      renderer.queue().write_buffer(buffer, 0, &data);
+    }
+  }
+
+  /// Submit all draw calls\
+  /// The pipeline, chunk mesh buffers and all bind groups except the origins must already be set
+  pub fn draw(&self, pass: &mut wgpu::RenderPass, origins: &ChunkOriginBuffer, origin_group: u32) {
+    if self.calls.is_empty() {
+      return
+    }
+    match &self.indirect {
+      Some((buffer, _)) => {
+        //all origins are in the same storage buffer
+        origins.bind(pass, origin_group, 0);
+        pass.multi_draw_indexed_indirect(buffer, 0, self.calls.len() as u32);
+      },
+      None => {
+        for call in &self.calls {
+          let instances = origins.bind(pass, origin_group, call.first_instance);
+          pass.draw_indexed(call.first_index..(call.first_index + call.index_count), call.base_vertex, instances);
+        }
+      },
+    }
+  }
+}
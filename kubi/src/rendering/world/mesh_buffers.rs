@@ -0,0 +1,205 @@
+use std::{borrow::Cow, ops::Range};
+use crate::rendering::Renderer;
+use super::{allocator::RangeAllocator, ChunkVertex};
+
+const VERTEX_SIZE: u64 = std::mem::size_of::<ChunkVertex>() as u64;
+const INDEX_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
+/// Buffers are compacted once more than this fraction of their free space is fragmented...
+const COMPACT_FRAGMENTATION: f32 = 0.5;
+/// ...and at least this fraction of the buffer is free
+const COMPACT_FREE_SPACE: f32 = 0.25;
+
+/// Location of a mesh in the [`ChunkMeshBuffers`]
+///
+/// If base vertex is supported, indices are relative to the first vertex of the mesh, so that the mesh can be moved.\
+/// Otherwise, they point directly into the vertex buffer (base vertex is not supported on WebGL)
+#[derive(Clone, Debug, Default)]
+pub struct MeshAllocation {
+  pub vertices: Range<u32>,
+  pub indices: Range<u32>,
+  /// Added to each index when drawing
+  pub base_vertex: i32,
+}
+
+impl MeshAllocation {
+  pub fn is_empty(&self) -> bool {
+    self.indices.is_empty()
+  }
+}
+
+/// A single growable buffer, split into ranges for each mesh
+struct SharedBuffer {
+  label: &'static str,
+  usage: wgpu::BufferUsages,
+  element_size: u64,
+  buffer: wgpu::Buffer,
+  allocator: RangeAllocator,
+  /// Largest capacity allowed by the device
+  max_capacity: u32,
+}
+
+impl SharedBuffer {
+  fn new(renderer: &Renderer, label: &'static str, usage: wgpu::BufferUsages, element_size: u64, capacity: u32) -> Self {
+    //data is written with the queue, and copied over when the buffer grows
+    let usage = usage | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+    let max_capacity = (renderer.device().limits().max_buffer_size / element_size).min(u32::MAX as u64) as u32;
+    let capacity = capacity.min(max_capacity);
+    Self {
+      label, usage, element_size, max_capacity,
+      buffer: Self::create_buffer(renderer, label, usage, element_size, capacity),
+      allocator: RangeAllocator::new(capacity),
+    }
+  }
+
+  fn create_buffer(renderer: &Renderer, label: &'static str, usage: wgpu::BufferUsages, element_size: u64, capacity: u32) -> wgpu::Buffer {
+    renderer.device().create_buffer(&wgpu::BufferDescriptor {
+      label: Some(label),
+      size: element_size * capacity as u64,
+      usage,
+      mapped_at_creation: false,
+    })
+  }
+
+  /// Replace the buffer with a larger one that can fit at least `size` more elements (if allowed by the device),
+  /// existing data is copied over
+  ///
+  /// Returns `false` if the buffer is already as large as it can get
+  fn grow(&mut self, renderer: &Renderer, size: u32) -> bool {
+    let capacity = self.allocator.capacity();
+    let new_capacity = capacity.saturating_mul(2).max(capacity.saturating_add(size)).min(self.max_capacity);
+    if new_capacity <= capacity {
+      return false
+    }
+    log::debug!(
+      "{}: growing from {capacity} to {new_capacity} elements ({} free, largest free range: {})",
+      self.label, self.allocator.free_space(), self.allocator.largest_free_range(),
+    );
+    let buffer = Self::create_buffer(renderer, self.label, self.usage, self.element_size, new_capacity);
+    let mut encoder = renderer.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
+      label: Some("chunk_mesh_buffer_grow_encoder"),
+    });
+    encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, self.buffer.size());
+    renderer.queue().submit([encoder.finish()]);
+    self.buffer = buffer;
+    self.allocator.grow(new_capacity);
+    true
+  }
+
+  /// Returns `None` if there's no space left, and the buffer can't grow any larger
+  fn allocate(&mut self, renderer: &Renderer, data: &[u8]) -> Option<Range<u32>> {
+    let size = (data.len() as u64 / self.element_size) as u32;
+    let range = match self.allocator.allocate(size) {
+      Some(range) => range,
+      None => {
+        if !self.grow(renderer, size) {
+          return None
+        }
+        self.allocator.allocate(size)?
+      }
+    };
+    if !data.is_empty() {
+      renderer.queue().write_buffer(&self.buffer, range.start as u64 * self.element_size, data);
+    }
+    Some(range)
+  }
+
+  fn should_compact(&self) -> bool {
+    self.allocator.fragmentation() > COMPACT_FRAGMENTATION &&
+    self.allocator.free_space() as f32 >= self.allocator.capacity() as f32 * COMPACT_FREE_SPACE
+  }
+
+  /// Move all live `ranges` next to each other (see [`RangeAllocator::compact`]), copying them into a new buffer
+  fn compact(&mut self, renderer: &Renderer, mut ranges: Vec<&mut Range<u32>>) {
+    let fragmentation = self.allocator.fragmentation();
+    let moves = self.allocator.compact(&mut ranges);
+    log::debug!("{}: compacting {} allocations (fragmentation: {:.2})", self.label, moves.len(), fragmentation);
+    let buffer = Self::create_buffer(renderer, self.label, self.usage, self.element_size, self.allocator.capacity());
+    let mut encoder = renderer.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
+      label: Some("chunk_mesh_buffer_compact_encoder"),
+    });
+    for (from, to, length) in moves {
+      encoder.copy_buffer_to_buffer(
+        &self.buffer, from as u64 * self.element_size,
+        &buffer, to as u64 * self.element_size,
+        length as u64 * self.element_size,
+      );
+    }
+    renderer.queue().submit([encoder.finish()]);
+    self.buffer = buffer;
+  }
+}
+
+/// Vertex and index buffers shared by all chunk meshes
+///
+/// Meshes are sub-allocated from them (see [`RangeAllocator`]), and the buffers grow when they run out of space
+/// (up to the largest buffer size supported by the device), so that all chunks can be drawn without switching buffers.\
+/// Once the free space gets too fragmented, live meshes are moved next to each other (see [`Self::compact`])
+pub struct ChunkMeshBuffers {
+  vertex: SharedBuffer,
+  index: SharedBuffer,
+  /// Whether base vertex is supported, and meshes can be moved around in the vertex buffer
+  base_vertex: bool,
+}
+
+impl ChunkMeshBuffers {
+  const INITIAL_VERTICES: u32 = 1 << 20;
+  const INITIAL_INDICES: u32 = 3 << 19;
+
+  pub fn new(renderer: &Renderer) -> Self {
+    Self {
+      vertex: SharedBuffer::new(renderer, "chunk_vertex_buffer", wgpu::BufferUsages::VERTEX, VERTEX_SIZE, Self::INITIAL_VERTICES),
+      index: SharedBuffer::new(renderer, "chunk_index_buffer", wgpu::BufferUsages::INDEX, INDEX_SIZE, Self::INITIAL_INDICES),
+      base_vertex: renderer.supports_base_vertex(),
+    }
+  }
+
+  /// Upload a mesh\
+  /// Returns `None` if the buffers are full and can't grow any larger
+  pub fn allocate(&mut self, renderer: &Renderer, vertices: &[ChunkVertex], indices: &[u32]) -> Option<MeshAllocation> {
+    let vertices = self.vertex.allocate(renderer, bytemuck::cast_slice(vertices))?;
+    let (indices, base_vertex) = match self.base_vertex {
+      true => (Cow::Borrowed(indices), vertices.start as i32),
+      false => (Cow::Owned(indices.iter().map(|index| index + vertices.start).collect()), 0),
+    };
+    let Some(indices) = self.index.allocate(renderer, bytemuck::cast_slice(&indices)) else {
+      self.vertex.allocator.free(vertices);
+      return None
+    };
+    Some(MeshAllocation { vertices, indices, base_vertex })
+  }
+
+  /// Free the space used by a mesh
+  pub fn free(&mut self, allocation: &MeshAllocation) {
+    self.vertex.allocator.free(allocation.vertices.clone());
+    self.index.allocator.free(allocation.indices.clone());
+  }
+
+  /// Check if the free space is fragmented enough to be worth compacting
+  pub fn should_compact(&self) -> bool {
+    self.index.should_compact() || (self.base_vertex && self.vertex.should_compact())
+  }
+
+  /// Move all meshes next to each other, merging the free space between them
+  ///
+  /// `meshes` must contain every live allocation, they're updated to their new location\
+  /// Vertices are only moved if base vertex is supported, as the indices would have to be rewritten otherwise
+  pub fn compact<'a>(&mut self, renderer: &Renderer, meshes: impl Iterator<Item = &'a mut MeshAllocation>) {
+    let mut meshes: Vec<&mut MeshAllocation> = meshes.collect();
+    self.index.compact(renderer, meshes.iter_mut().map(|mesh| &mut mesh.indices).collect());
+    if self.base_vertex {
+      self.vertex.compact(renderer, meshes.iter_mut().map(|mesh| &mut mesh.vertices).collect());
+      for mesh in meshes {
+        mesh.base_vertex = mesh.vertices.start as i32;
+      }
+    }
+  }
+
+  pub fn vertex_buffer(&self) -> &wgpu::Buffer {
+    &self.vertex.buffer
+  }
+
+  pub fn index_buffer(&self) -> &wgpu::Buffer {
+    &self.index.buffer
+  }
+}
@@ -0,0 +1,168 @@
+//! Occlusion culling of chunks hidden behind other chunks (cave culling)
+//!
+//! Starting from the chunk the camera is in, chunks are visited breadth-first,
+//! only ever moving away from the camera, and only leaving a chunk through faces connected
+//! to the face it was entered through (see [`ChunkVisibility`]).\
+//! Chunks that can't be reached this way can't be seen, like caves surrounded by stone\
+//! Chunks outside of the world height are never loaded, the layers right above and below it are visited as empty chunks
+
+use std::collections::VecDeque;
+use glam::IVec3;
+use hashbrown::HashSet;
+use kubi_shared::{block::Facing, height::WorldHeight};
+use crate::world::{mesh::visibility::ChunkVisibility, ChunkMeshStorage, ChunkStorage};
+
+/// Find all chunks that might be visible from `camera_chunk`\
+/// `in_frustum` tells if a chunk is inside of the view frustum, chunks outside of it are not visited
+///
+/// Returns `None` if the camera chunk is not loaded (nothing can be culled then)
+pub fn find_visible_chunks(
+  chunks: &ChunkStorage,
+  meshes: &ChunkMeshStorage,
+  height: &WorldHeight,
+  camera_chunk: IVec3,
+  in_frustum: impl Fn(IVec3) -> bool,
+) -> Option<HashSet<IVec3>> {
+  if !chunks.chunks.contains_key(&camera_chunk) {
+    return None
+  }
+
+  //chunks without a mesh (yet) might be see-through
+  let visibility_of = |position: IVec3| {
+    chunks.chunks.get(&position)
+      .and_then(|chunk| chunk.mesh_index)
+      .and_then(|index| meshes.get(index))
+      .map(|mesh| mesh.visibility)
+      .unwrap_or(ChunkVisibility::ALL)
+  };
+
+  Some(traverse(
+    camera_chunk,
+    |position| is_traversable(chunks, height, position),
+    visibility_of,
+    in_frustum,
+  ))
+}
+
+/// Can the chunk at `position` be visited?\
+/// Loaded chunks, and the layers of chunks right outside of the world height next to them
+/// (otherwise there would be no way over the top of the world)
+fn is_traversable(chunks: &ChunkStorage, height: &WorldHeight, position: IVec3) -> bool {
+  if chunks.chunks.contains_key(&position) {
+    return true
+  }
+  let range = height.chunk_range();
+  let inside = position.with_y(position.y.clamp(*range.start(), *range.end()));
+  (position.y - inside.y).abs() == 1 && chunks.chunks.contains_key(&inside)
+}
+
+/// Breadth-first search through the loaded chunks (see the module docs)
+fn traverse(
+  camera_chunk: IVec3,
+  is_loaded: impl Fn(IVec3) -> bool,
+  visibility_of: impl Fn(IVec3) -> ChunkVisibility,
+  in_frustum: impl Fn(IVec3) -> bool,
+) -> HashSet<IVec3> {
+  let mut visited = HashSet::new();
+  visited.insert(camera_chunk);
+  //position, face it was entered through, and directions taken to get there (as a bitmask)
+  let mut queue: VecDeque<(IVec3, Option<Facing>, u8)> = VecDeque::new();
+  queue.push_back((camera_chunk, None, 0));
+
+  while let Some((position, entered_through, directions)) = queue.pop_front() {
+    let visibility = visibility_of(position);
+    for direction in Facing::ALL {
+      //never go back towards the camera
+      if directions & (1 << direction.opposite() as u8) != 0 {
+        continue
+      }
+      if entered_through.is_some_and(|face| !visibility.connects(face, direction)) {
+        continue
+      }
+      let neighbor = position + direction.normal();
+      if visited.contains(&neighbor) || !is_loaded(neighbor) || !in_frustum(neighbor) {
+        continue
+      }
+      visited.insert(neighbor);
+      queue.push_back((neighbor, Some(direction.opposite()), directions | (1 << direction as u8)));
+    }
+  }
+
+  visited
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::ivec3;
+  use hashbrown::HashMap;
+  use super::*;
+
+  /// Chunks visible from the origin, in a world of the given chunk visibilities
+  fn visible(chunks: &HashMap<IVec3, ChunkVisibility>) -> HashSet<IVec3> {
+    traverse(IVec3::ZERO, |position| chunks.contains_key(&position), |position| chunks[&position], |_| true)
+  }
+
+  /// See-through chunks with x in `0..=3` and y, z in `-1..=1`
+  fn open_world() -> HashMap<IVec3, ChunkVisibility> {
+    (0..=3)
+      .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| ivec3(x, y, z))))
+      .map(|position| (position, ChunkVisibility::ALL))
+      .collect()
+  }
+
+  #[test]
+  fn open_chunks_are_all_visible() {
+    let chunks = open_world();
+    let visible = visible(&chunks);
+    assert_eq!(visible.len(), chunks.len());
+    //frustum culled chunks are not visited, and can't be seen through
+    let in_row = |position: IVec3| position.y == 0 && position.z == 0;
+    let visible = traverse(IVec3::ZERO, |position| chunks.contains_key(&position), |position| chunks[&position], in_row);
+    assert_eq!(visible, (0..=3).map(|x| ivec3(x, 0, 0)).collect::<HashSet<_>>());
+  }
+
+  #[test]
+  fn sealed_chunks_block_the_view() {
+    let mut chunks = open_world();
+    chunks.insert(ivec3(1, 0, 0), ChunkVisibility::NONE);
+    let visible = visible(&chunks);
+    //the sealed chunk itself can be seen, but not what's behind it
+    assert!(visible.contains(&ivec3(1, 0, 0)));
+    assert!(!visible.contains(&ivec3(2, 0, 0)));
+    assert!(!visible.contains(&ivec3(3, 0, 0)));
+    //chunks next to it can still be seen past it
+    assert!(visible.contains(&ivec3(2, 1, 0)));
+    assert!(visible.contains(&ivec3(3, 0, -1)));
+    assert_eq!(visible.len(), chunks.len() - 2);
+  }
+
+  #[test]
+  fn sealed_camera_surroundings() {
+    let mut chunks = open_world();
+    for position in [ivec3(1, 0, 0), ivec3(0, 1, 0), ivec3(0, -1, 0), ivec3(0, 0, 1), ivec3(0, 0, -1)] {
+      chunks.insert(position, ChunkVisibility::NONE);
+    }
+    //only the camera chunk and its direct neighbors are visible
+    let visible = visible(&chunks);
+    assert_eq!(visible.len(), 6);
+    assert!(visible.iter().all(|position| position.abs().element_sum() <= 1));
+  }
+
+  #[test]
+  fn layers_outside_of_the_world_height_are_traversable() {
+    use crate::world::chunk::{Chunk, CHUNK_SIZE};
+    //a single chunk high world
+    let height = WorldHeight { min: 0, max: CHUNK_SIZE as i32, bedrock: 0 };
+    let mut chunks = ChunkStorage::new();
+    for x in 0..=1 {
+      chunks.chunks.insert(ivec3(x, 0, 0), Chunk::new(ivec3(x, 0, 0)));
+    }
+    assert!(is_traversable(&chunks, &height, ivec3(1, 0, 0)));
+    assert!(is_traversable(&chunks, &height, ivec3(1, 1, 0)));
+    assert!(is_traversable(&chunks, &height, ivec3(0, -1, 0)));
+    //only the layer right next to the world height, above or below loaded chunks
+    assert!(!is_traversable(&chunks, &height, ivec3(0, 2, 0)));
+    assert!(!is_traversable(&chunks, &height, ivec3(2, 1, 0)));
+    assert!(!is_traversable(&chunks, &height, ivec3(2, 0, 0)));
+  }
+}
@@ -0,0 +1,159 @@
+use std::{num::NonZeroU64, ops::Range};
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use crate::rendering::Renderer;
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct ChunkOriginData {
+  origin: [f32; 3],
+  _padding: f32,
+}
+
+const ORIGIN_SIZE: u64 = std::mem::size_of::<ChunkOriginData>() as u64;
+
+/// Origins of all meshes drawn in a frame (chunk vertex positions are relative to them)
+///
+/// Each origin is stored in its own slot of a single buffer, and draw calls pick theirs either:
+/// - with the instance index, if the buffer is a storage buffer (this allows drawing many chunks with a single draw call)
+/// - with a dynamic offset otherwise (storage buffers are not available everywhere, like WebGL)
+pub struct ChunkOriginBuffer {
+  pub bind_group_layout: wgpu::BindGroupLayout,
+  buffer: wgpu::Buffer,
+  bind_group: wgpu::BindGroup,
+  storage: bool,
+  /// Distance between slots, dynamic offsets must be aligned to it
+  stride: u64,
+  capacity: usize,
+  data: Vec<u8>,
+}
+
+impl ChunkOriginBuffer {
+  const INITIAL_CAPACITY: usize = 256;
+
+  /// `storage` must only be used if storage buffers are supported in vertex shaders
+  pub fn new(renderer: &Renderer, storage: bool) -> Self {
+    let stride = match storage {
+      true => ORIGIN_SIZE,
+      false => (renderer.device().limits().min_uniform_buffer_offset_alignment as u64).max(ORIGIN_SIZE),
+    };
+    let bind_group_layout = renderer.device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("chunk_origin_bind_group_layout"),
+      entries: &[
+        wgpu::BindGroupLayoutEntry {
+          binding: Self::binding(storage),
+          visibility: wgpu::ShaderStages::VERTEX,
+          ty: wgpu::BindingType::Buffer {
+            ty: match storage {
+              true => wgpu::BufferBindingType::Storage { read_only: true },
+              false => wgpu::BufferBindingType::Uniform,
+            },
+            has_dynamic_offset: !storage,
+            min_binding_size: NonZeroU64::new(ORIGIN_SIZE),
+          },
+          count: None,
+        },
+      ],
+    });
+    let (buffer, bind_group) = Self::create_buffer(renderer, &bind_group_layout, storage, stride, Self::INITIAL_CAPACITY);
+    Self {
+      bind_group_layout,
+      buffer,
+      bind_group,
+      storage,
+      stride,
+      capacity: Self::INITIAL_CAPACITY,
+      data: Vec::new(),
+    }
+  }
+
+  /// The uniform and storage buffers use different bindings in the shader
+  const fn binding(storage: bool) -> u32 {
+    storage as u32
+  }
+
+  fn create_buffer(
+    renderer: &Renderer,
+    layout: &wgpu::BindGroupLayout,
+    storage: bool,
+    stride: u64,
+    capacity: usize,
+  ) -> (wgpu::Buffer, wgpu::BindGroup) {
+    let buffer = renderer.device().create_buffer(&wgpu::BufferDescriptor {
+      label: Some("chunk_origin_buffer"),
+      size: stride * capacity as u64,
+      usage: match storage {
+        true => wgpu::BufferUsages::STORAGE,
+        false => wgpu::BufferUsages::UNIFORM,
+      } | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false,
+    });
+    let bind_group = renderer.device().create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("chunk_origin_bind_group"),
+      layout,
+      entries: &[
+        wgpu::BindGroupEntry {
+          binding: Self::binding(storage),
+          resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: &buffer,
+            offset: 0,
+            //storage buffers are bound as a whole, and indexed in the shader
+            size: match storage {
+              true => None,
+              false => NonZeroU64::new(ORIGIN_SIZE),
+            },
+          }),
+        },
+      ],
+    });
+    (buffer, bind_group)
+  }
+
+  /// Whether origins are picked by the instance index (see [`ChunkOriginBuffer`])
+  pub fn is_storage(&self) -> bool {
+    self.storage
+  }
+
+  /// Remove all origins (call before adding the origins of a new frame)
+  pub fn clear(&mut self) {
+    self.data.clear();
+  }
+
+  /// Add an origin, returns its slot
+  pub fn push(&mut self, origin: Vec3) -> u32 {
+    let offset = self.data.len();
+    self.data.extend_from_slice(bytemuck::bytes_of(&ChunkOriginData {
+      origin: origin.to_array(),
+      _padding: 0.,
+    }));
+    self.data.resize(offset + self.stride as usize, 0);
+    (offset as u64 / self.stride) as u32
+  }
+
+  /// Upload the origins to the GPU, growing the buffer if needed\
+  /// This may replace the bind group, so it must be called before it's used
+  pub fn upload(&mut self, renderer: &Renderer) {
+    let count = self.data.len() / self.stride as usize;
+    if count > self.capacity {
+      self.capacity = count.next_power_of_two();
+      (self.buffer, self.bind_group) = Self::create_buffer(renderer, &self.bind_group_layout, self.storage, self.stride, self.capacity);
+    }
+    if !self.data.is_empty() {
+      renderer.queue().write_buffer(&self.buffer, 0, &self.data);
+    }
+  }
+
+  /// Bind the origin in `slot` to bind group `index`, returns the instance range to draw with
+  pub fn bind(&self, pass: &mut wgpu::RenderPass, index: u32, slot: u32) -> Range<u32> {
+    match self.storage {
+      true => {
+        pass.set_bind_group(index, &self.bind_group, &[]);
+        slot..(slot + 1)
+      },
+      false => {
+        pass.set_bind_group(index, &self.bind_group, &[(slot as u64 * self.stride) as u32]);
+        0..1
+      },
+    }
+  }
+}
@@ -1,10 +1,11 @@
 use shipyard::UniqueView;
 use crate::{
   prefabs::GpuPrefabs,
-  rendering::{camera_uniform::CameraUniformBuffer, depth::DepthTexture, world::ChunkVertex, Renderer}
+  rendering::{camera_uniform::CameraUniformBuffer, depth::DepthTexture, world::{ChunkOriginBuffer, ChunkVertex}, Renderer}
 };
 
 pub fn init_world_pipeline(
+  origins: &ChunkOriginBuffer,
   ren: UniqueView<Renderer>,
   depth: UniqueView<DepthTexture>,
   textures: UniqueView<GpuPrefabs>,
@@ -23,10 +24,17 @@ pub fn init_world_pipeline(
     bind_group_layouts: &[
       &textures.block_diffuse_bind_group_layout,
       &camera_ubo.camera_bind_group_layout,
+      &origins.bind_group_layout,
     ],
     push_constant_ranges: &[],
   });
 
+  //origins are picked differently depending on the buffer type (see ChunkOriginBuffer)
+  let vs_entry_point = match origins.is_storage() {
+    true => "vs_main_indirect",
+    false => "vs_main",
+  };
+
   log::info!("init_world_pipeline: create main pipeline");
 
   let pipeline_main = ren.device().create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -44,7 +52,7 @@ pub fn init_world_pipeline(
     }),
     vertex: wgpu::VertexState {
       module: &shader,
-      entry_point: Some("vs_main"),
+      entry_point: Some(vs_entry_point),
       compilation_options: wgpu::PipelineCompilationOptions::default(),
       buffers: &[
         ChunkVertex::LAYOUT,
@@ -88,7 +96,7 @@ pub fn init_world_pipeline(
     }),
     vertex: wgpu::VertexState {
       module: &shader,
-      entry_point: Some("vs_main"),
+      entry_point: Some(vs_entry_point),
       compilation_options: wgpu::PipelineCompilationOptions::default(),
       buffers: &[
         ChunkVertex::LAYOUT,
@@ -1,23 +1,61 @@
 use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
 
+/// Packed vertex of a chunk mesh
+///
+/// `position` is relative to the chunk origin (see [`super::ChunkOriginBuffer`]),
+/// in 1/[`ChunkVertex::POSITION_SCALE`] block units, with 10 bits per axis (`x` in the lowest bits)
+///
+/// `data` contains everything else:
+///
+/// | bits     | value                                                       |
+/// |----------|-------------------------------------------------------------|
+/// | `0..8`   | texture index                                               |
+/// | `8..16`  | light (sky light in the high, block light in the low nibble) |
+/// | `16..18` | ambient occlusion, `0` (fully occluded) to `3`              |
+/// | `18..22` | face (`0..6` cube faces, `6..10` sides of diagonal faces)   |
+/// | `22`     | rotate uv                                                   |
+///
+/// Normals are looked up from the face index, and UVs are derived from the position,
+/// so that textures repeat across merged faces
 #[derive(Clone, Copy, Pod, Zeroable)]
-#[repr(C, packed)]
+#[repr(C)]
 pub struct ChunkVertex {
-  pub position: [f32; 3],
-  pub normal: [f32; 3],
-  pub uv: [f32; 2],
-  pub tex_index: u32,
+  pub position: u32,
+  pub data: u32,
 }
 
 impl ChunkVertex {
+  /// Vertex positions are rounded to 1/`POSITION_SCALE` of a block
+  pub const POSITION_SCALE: f32 = 16.;
+
+  /// Index of the first diagonal face side (see [`ChunkVertex`])
+  pub const DIAGONAL_FACE_OFFSET: u8 = 6;
+
+  /// Panics if `position` can't be packed (it has to be within `0..64` blocks on all axes)
+  pub fn new(position: Vec3, face: u8, texture: u8, light: u8, ao: u8, rotate_uv: bool) -> Self {
+    //out of range positions would silently wrap around into other axes
+    assert!(
+      position.cmpge(Vec3::ZERO).all() && position.cmplt(Vec3::splat(1024. / Self::POSITION_SCALE)).all(),
+      "vertex position out of range: {position}"
+    );
+    let position = (position * Self::POSITION_SCALE).round().as_uvec3();
+    Self {
+      position: position.x | (position.y << 10) | (position.z << 20),
+      data: texture as u32
+        | ((light as u32) << 8)
+        | (((ao & 0b11) as u32) << 16)
+        | (((face & 0xf) as u32) << 18)
+        | ((rotate_uv as u32) << 22),
+    }
+  }
+
   pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
     array_stride: std::mem::size_of::<ChunkVertex>() as wgpu::BufferAddress,
     step_mode: wgpu::VertexStepMode::Vertex,
     attributes: &wgpu::vertex_attr_array![
-      0 => Float32x3,
-      1 => Float32x3,
-      2 => Float32x2,
-      3 => Uint32,
+      0 => Uint32,
+      1 => Uint32,
     ],
   };
 }
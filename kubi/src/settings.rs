@@ -9,6 +9,15 @@ pub struct FullscreenSettings {
   pub mode: FullscreenMode,
 }
 
+/// Shape of the area of loaded chunks around the player
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChunkLoadingShape {
+  /// Same distance in all directions
+  Sphere,
+  /// Vertical distance is set separately (see `GameSettings::vertical_render_distance`)
+  Column,
+}
+
 #[derive(Unique)]
 pub struct GameSettings {
   pub vsync: bool,
@@ -17,9 +26,24 @@ pub struct GameSettings {
   // pub max_anisotropy: Option<u16>,
   /// there's a 1 chunk border of loaded but invisible around this
   pub render_distance: u8,
+  /// Chunks between the render distance and this (horizontally) are rendered at a lower level of detail\
+  /// Always at least one more than `render_distance`
+  pub far_render_distance: u8,
+  pub loading_shape: ChunkLoadingShape,
+  /// Vertical render distance of the column loading shape
+  pub vertical_render_distance: u8,
   pub mouse_sensitivity: f32,
   pub debug_draw_current_chunk_border: bool,
   pub dynamic_crosshair: bool,
+  /// Merge chunk faces into larger quads (see `world::mesh::greedy`)\
+  /// Only affects chunks meshed after the change
+  pub greedy_meshing: bool,
+  /// Skip drawing chunks hidden behind other chunks (see `rendering::world::occlusion`)
+  pub occlusion_culling: bool,
+  /// Show the number of drawn and culled chunks
+  pub debug_culling_stats: bool,
+  /// Enabled resource packs (see `filesystem::AssetManager`), highest priority first
+  pub resource_packs: Vec<String>,
 }
 impl Default for GameSettings {
   fn default() -> Self {
@@ -33,9 +57,24 @@ impl Default for GameSettings {
         cfg!(target_os = "android") => 6,
         #[allow(unreachable_patterns)] _ => 7,
       },
+      far_render_distance: match true {
+        cfg!(debug_assertions) => 8,
+        cfg!(target_os = "android") => 10,
+        #[allow(unreachable_patterns)] _ => 14,
+      },
+      loading_shape: ChunkLoadingShape::Column,
+      vertical_render_distance: match true {
+        cfg!(debug_assertions) => 4,
+        cfg!(target_os = "android") => 4,
+        #[allow(unreachable_patterns)] _ => 6,
+      },
       mouse_sensitivity: 1.,
       debug_draw_current_chunk_border: false, //cfg!(not(target_os = "android")) && cfg!(debug_assertions),
       dynamic_crosshair: true,
+      greedy_meshing: true,
+      occlusion_culling: true,
+      debug_culling_stats: false,
+      resource_packs: Vec::new(),
     }
   }
 }
@@ -4,4 +4,6 @@ pub(crate) mod connecting_screen;
 pub(crate) mod shutdown_screen;
 pub(crate) mod chat_ui;
 pub(crate) mod crosshair_ui;
-pub(crate) mod settings_ui;
\ No newline at end of file
+pub(crate) mod settings_ui;
+pub(crate) mod health_ui;
+pub(crate) mod debug_ui;
\ No newline at end of file
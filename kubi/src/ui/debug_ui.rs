@@ -0,0 +1,36 @@
+use hui::{
+  element::{container::Container, text::Text, UiElementExt},
+  layout::Alignment,
+  size,
+};
+use shipyard::{NonSendSync, UniqueView, UniqueViewMut};
+use crate::{
+  hui_integration::UiState,
+  rendering::{world::WorldRenderState, Renderer},
+  settings::GameSettings,
+};
+
+pub fn render_culling_stats(
+  mut ui: NonSendSync<UniqueViewMut<UiState>>,
+  ren: UniqueView<Renderer>,
+  settings: UniqueView<GameSettings>,
+  world_render: UniqueView<WorldRenderState>,
+) {
+  if !settings.debug_culling_stats {
+    return
+  }
+  let stats = world_render.stats;
+  Container::default()
+    .with_size(size!(100%))
+    .with_align((Alignment::Begin, Alignment::Begin))
+    .with_padding(10.)
+    .with_children(|ui| {
+      Text::new(format!("Chunks drawn: {}", stats.drawn))
+        .add_child(ui);
+      Text::new(format!("Frustum culled: {}", stats.frustum_culled))
+        .add_child(ui);
+      Text::new(format!("Occlusion culled: {}", stats.occlusion_culled))
+        .add_child(ui);
+    })
+    .add_root(&mut ui.hui, ren.size_vec2());
+}
@@ -0,0 +1,67 @@
+use hui::{
+  element::{container::Container, progress_bar::ProgressBar, text::Text, UiElementExt},
+  layout::Alignment,
+  rect_frame,
+  size,
+};
+use shipyard::{IntoIter, NonSendSync, UniqueView, UniqueViewMut, View};
+use kubi_shared::{entity::Health, player::GameMode};
+use crate::{hui_integration::UiState, player::MainPlayer, rendering::Renderer};
+
+pub fn render_health_bar(
+  mut ui: NonSendSync<UniqueViewMut<UiState>>,
+  ren: UniqueView<Renderer>,
+  player: View<MainPlayer>,
+  gamemodes: View<GameMode>,
+  healths: View<Health>,
+) {
+  let Some((_, gamemode, health)) = (&player, &gamemodes, &healths).iter().next() else { return };
+  if !gamemode.has_survival_rules() || health.is_dead() {
+    return
+  }
+  Container::default()
+    .with_size(size!(100%))
+    .with_align((Alignment::Center, Alignment::End))
+    .with_padding(20.)
+    .with_children(|ui| {
+      ProgressBar::default()
+        .with_value(health.current as f32 / health.max as f32)
+        .with_size(size!(200, 12))
+        .with_background(rect_frame! {
+          color: (0.1, 0.1, 0.1, 0.75),
+          corner_radius: 2.
+        })
+        .with_foreground(rect_frame! {
+          color: (0.9, 0.15, 0.15),
+          corner_radius: 2.
+        })
+        .add_child(ui);
+    })
+    .add_root(&mut ui.hui, ren.size_vec2());
+}
+
+pub fn render_death_screen(
+  mut ui: NonSendSync<UniqueViewMut<UiState>>,
+  ren: UniqueView<Renderer>,
+  player: View<MainPlayer>,
+  healths: View<Health>,
+) {
+  let Some((_, health)) = (&player, &healths).iter().next() else { return };
+  if !health.is_dead() {
+    return
+  }
+  Container::default()
+    .with_size(size!(100%))
+    .with_background((0.5, 0., 0., 0.5))
+    .with_align(Alignment::Center)
+    .with_gap(10.)
+    .with_children(|ui| {
+      Text::new("You died!")
+        .with_text_size(48)
+        .add_child(ui);
+      Text::new("Press Space to respawn")
+        .with_text_size(16)
+        .add_child(ui);
+    })
+    .add_root(&mut ui.hui, ren.size_vec2());
+}
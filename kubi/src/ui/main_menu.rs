@@ -14,20 +14,25 @@ use hui::{
   rect_frame,
   size,
 };
-use kubi_shared::data::{io_thread::IOThreadManager, open_local_save_file};
-use settings_overlay::{not_settings_ui_shown, settings_overlay_logic};
+use settings_overlay::settings_overlay_logic;
+use resource_packs_overlay::{resource_packs_overlay_logic, ResourcePackList};
 use shipyard::{AllStoragesView, AllStoragesViewMut, IntoWorkload, NonSendSync, SystemModificator, Unique, UniqueView, UniqueViewMut, Workload, WorkloadModificator};
 use crate::{
   control_flow::RequestExit,
-  hui_integration::UiState, networking::GameType, rendering::Renderer, state::{GameState, NextState}};
+  hui_integration::UiState, networking::GameType, rendering::Renderer, state::{GameState, NextState},
+  world::registry::open_local_world,
+};
+use kubi_shared::{height::WorldHeight, worldgen::preset::WorldGenPreset};
 
 
 mod settings_overlay;
+mod resource_packs_overlay;
 
 #[derive(Clone, Copy)]
 enum MainMenuPage {
   TopMenu,
   Settings,
+  ResourcePacks,
   // ListWorlds {
   //   list: Vec<String>,
   // },
@@ -60,6 +65,11 @@ pub fn main_menu_leave(
   }
 }
 
+fn top_menu_shown(mms: Option<UniqueView<MainMenuState>>) -> bool {
+  let Some(mms) = mms else { return true };
+  matches!(mms.page, MainMenuPage::TopMenu)
+}
+
 pub fn render_main_menu_ui(
   mut hui: NonSendSync<UniqueViewMut<UiState>>,
   ren: UniqueView<Renderer>,
@@ -99,6 +109,7 @@ pub fn render_main_menu_ui(
                 ("Singleplayer", MainMenuSignal::PlayOffline),
                 ("Multiplayer", MainMenuSignal::PlayOnline),
                 ("Settings", MainMenuSignal::GotoPage(MainMenuPage::Settings)),
+                ("Resource Packs", MainMenuSignal::GotoPage(MainMenuPage::ResourcePacks)),
                 ("Quit", MainMenuSignal::Quit),
               ] {
                 Container::default()
@@ -134,8 +145,7 @@ fn main_menu_process_signals(
       MainMenuSignal::PlayOffline => {
         log::info!("play button pressed");
         // Open the local save file
-        let save_file = open_local_save_file(Path::new("./world.kubi")).expect("failed to open save file");
-        storages.add_unique(IOThreadManager::new(save_file));
+        open_local_world(&storages, Path::new("./world.kubi"), WorldGenPreset::default(), WorldHeight::default()).expect("failed to open save file");
         // Switch the state and kick off the world loading
         storages.add_unique(GameType::Singleplayer);
         storages.borrow::<UniqueViewMut<NextState>>().unwrap().0 = Some(GameState::LoadingWorld);
@@ -146,6 +156,8 @@ fn main_menu_process_signals(
       MainMenuSignal::GotoPage(page) => {
         log::info!("goto page button pressed");
         storages.add_unique(MainMenuState { page });
+        //rescan resource packs next time the page is opened
+        let _ = storages.remove_unique::<ResourcePackList>();
       }
       MainMenuSignal::Quit => {
         log::info!("quit button pressed");
@@ -158,8 +170,9 @@ fn main_menu_process_signals(
 
 pub fn update_main_menu() -> Workload {
   (
-    render_main_menu_ui.run_if(not_settings_ui_shown),
+    render_main_menu_ui.run_if(top_menu_shown),
     settings_overlay_logic,
+    resource_packs_overlay_logic,
     main_menu_process_signals,
   ).into_sequential_workload()
 }
\ No newline at end of file
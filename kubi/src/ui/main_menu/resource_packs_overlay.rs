@@ -0,0 +1,181 @@
+use glam::vec4;
+use hui::{
+  element::{
+    container::Container,
+    interactable::ElementInteractableExt,
+    text::Text,
+    ElementList,
+    UiElementExt
+  },
+  layout::{Alignment, Direction},
+  signal::Signal,
+  rect_frame,
+  size,
+};
+use shipyard::{AllStoragesView, IntoWorkload, NonSendSync, SystemModificator, Unique, UniqueView, UniqueViewMut, Workload, WorkloadModificator};
+use crate::{
+  filesystem::{find_resource_packs, PackManifest},
+  hui_integration::UiState,
+  main_menu::MainMenuPage,
+  rendering::Renderer,
+  settings::GameSettings,
+};
+use super::{settings_overlay::show_back_button, MainMenuState};
+
+/// Resource packs found in the resource pack directory, scanned when the page is opened
+#[derive(Unique)]
+pub(super) struct ResourcePackList {
+  packs: Vec<(String, PackManifest)>,
+}
+
+#[derive(Signal)]
+enum ResourcePackSignal {
+  Toggle(String),
+  /// Move the enabled pack at this index one place up (higher priority)
+  MoveUp(usize),
+  /// Move the enabled pack at this index one place down (lower priority)
+  MoveDown(usize),
+}
+
+fn resource_packs_shown(mms: Option<UniqueView<MainMenuState>>) -> bool {
+  let Some(mms) = mms else { return false };
+  matches!(mms.page, MainMenuPage::ResourcePacks)
+}
+
+fn scan_resource_packs(storages: AllStoragesView) {
+  log::info!("scanning for resource packs");
+  let packs = find_resource_packs()
+    .into_iter()
+    .map(|pack| (pack.id, pack.manifest))
+    .collect();
+  storages.add_unique(ResourcePackList { packs });
+}
+
+fn button(ui: &mut ElementList, text: &'static str, signal: impl Fn() -> ResourcePackSignal + 'static) {
+  Container::default()
+    .with_size(size!(auto, 30))
+    .with_padding((10., 0.))
+    .with_align(Alignment::Center)
+    .with_background(rect_frame! {
+      color: (0.3, 0.3, 0.3),
+      corner_radius: 3.,
+    })
+    .with_children(|ui| {
+      Text::new(text)
+        .add_child(ui);
+    })
+    .on_click(signal)
+    .add_child(ui);
+}
+
+fn render_resource_packs_ui(
+  mut ui: NonSendSync<UniqueViewMut<UiState>>,
+  ren: UniqueView<Renderer>,
+  list: UniqueView<ResourcePackList>,
+  mut settings: UniqueViewMut<GameSettings>,
+) {
+  //enabled packs first (by priority), then the rest
+  let enabled = &settings.resource_packs;
+  let mut rows: Vec<(&str, Option<&PackManifest>, Option<usize>)> = enabled.iter()
+    .map(|id| (id.as_str(), list.packs.iter().find(|(pack_id, _)| pack_id == id).map(|(_, manifest)| manifest)))
+    .enumerate()
+    .map(|(index, (id, manifest))| (id, manifest, Some(index)))
+    .collect();
+  rows.extend(
+    list.packs.iter()
+      .filter(|(id, _)| !enabled.contains(id))
+      .map(|(id, manifest)| (id.as_str(), Some(manifest), None))
+  );
+
+  Container::default()
+    .with_size(size!(100%))
+    .with_background((0., 0., 0., 0.5))
+    .with_align(Alignment::Center)
+    .with_children(|ui| {
+      Container::default()
+        .with_background(rect_frame! {
+          color: (0.2, 0.2, 0.2),
+          corner_radius: 8.
+        })
+        .with_size(size!(600, 580))
+        .with_gap(10.)
+        .with_padding(10.)
+        .with_children(|ui| {
+          Container::default()
+            .with_size(size!(100%, auto))
+            .with_align(Alignment::Center)
+            .with_children(|ui| {
+              Text::new("Resource Packs")
+                .with_text_size(32)
+                .add_child(ui);
+            })
+            .add_child(ui);
+
+          if rows.is_empty() {
+            Text::new("No resource packs found, put them in the resourcepacks directory")
+              .add_child(ui);
+          }
+
+          for &(id, manifest, enabled_index) in &rows {
+            Container::default()
+              .with_size(size!(100%, auto))
+              .with_direction(Direction::Horizontal)
+              .with_align((Alignment::Begin, Alignment::Center))
+              .with_gap(5.)
+              .with_children(|ui| {
+                Container::default()
+                  .with_size(size!(100%=, auto))
+                  .with_children(|ui| {
+                    Text::new(manifest.map(|manifest| manifest.name.clone()).unwrap_or_else(|| id.to_string()))
+                      .with_text_size(24)
+                      .add_child(ui);
+                    if let Some(manifest) = manifest.filter(|manifest| !manifest.description.is_empty()) {
+                      Text::new(manifest.description.clone())
+                        .with_color(vec4(0.7, 0.7, 0.7, 1.))
+                        .add_child(ui);
+                    }
+                  })
+                  .add_child(ui);
+                if let Some(index) = enabled_index {
+                  if index > 0 {
+                    button(ui, "Up", move || ResourcePackSignal::MoveUp(index));
+                  }
+                  if index + 1 < enabled.len() {
+                    button(ui, "Down", move || ResourcePackSignal::MoveDown(index));
+                  }
+                }
+                let toggle_id = id.to_string();
+                button(
+                  ui,
+                  if enabled_index.is_some() { "Disable" } else { "Enable" },
+                  move || ResourcePackSignal::Toggle(toggle_id.clone())
+                );
+              })
+              .add_child(ui);
+          }
+        })
+        .add_child(ui);
+    })
+    .add_root(&mut ui.hui, ren.size_vec2());
+
+  //changes are applied by `prefabs::update_resource_packs`
+  ui.hui.process_signals(|signal: ResourcePackSignal| match signal {
+    ResourcePackSignal::Toggle(id) => {
+      match settings.resource_packs.iter().position(|enabled_id| *enabled_id == id) {
+        Some(index) => { settings.resource_packs.remove(index); },
+        //newly enabled packs get the highest priority
+        None => settings.resource_packs.insert(0, id),
+      }
+    },
+    ResourcePackSignal::MoveUp(index) => settings.resource_packs.swap(index - 1, index),
+    ResourcePackSignal::MoveDown(index) => settings.resource_packs.swap(index, index + 1),
+  });
+}
+
+pub fn resource_packs_overlay_logic() -> Workload {
+  (
+    scan_resource_packs.run_if_missing_unique::<ResourcePackList>(),
+    render_resource_packs_ui,
+    show_back_button,
+  ).into_sequential_workload().run_if(resource_packs_shown)
+}
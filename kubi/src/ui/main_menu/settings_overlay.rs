@@ -26,13 +26,8 @@ pub fn settings_ui_shown(mms: Option<UniqueView<MainMenuState>>) -> bool {
   matches!(mms.page, MainMenuPage::Settings)
 }
 
-#[allow(clippy::just_underscores_and_digits)]
-pub fn not_settings_ui_shown(_0: Option<UniqueView<MainMenuState>>) -> bool {
-  !settings_ui_shown(_0)
-}
-
-// HACK: shows the back button over the settings UI
-fn show_settings_back_button(
+// HACK: shows the back button over the settings (or resource packs) UI
+pub(super) fn show_back_button(
   mut hui: NonSendSync<UniqueViewMut<UiState>>,
   ren: UniqueView<Renderer>,
 ) {
@@ -63,6 +58,6 @@ fn show_settings_back_button(
 pub fn settings_overlay_logic() -> Workload {
   (
     render_settings_ui2,
-    show_settings_back_button,
+    show_back_button,
   ).into_sequential_workload().run_if(settings_ui_shown)
 }
\ No newline at end of file
@@ -11,14 +11,20 @@ use crate::{
   hui_integration::UiState,
   input::RawKbmInputState,
   rendering::Renderer,
-  settings::GameSettings
+  settings::{ChunkLoadingShape, GameSettings},
 };
 
 #[derive(Signal)]
 enum SettingsSignal {
   SetRenderDistance(u8),
+  SetFarRenderDistance(u8),
+  SetVerticalRenderDistance(u8),
+  SetEnableSphericalLoading(bool),
   SetEnableDynamicCrosshair(bool),
   SetEnableVsync(bool),
+  SetEnableGreedyMeshing(bool),
+  SetEnableOcclusionCulling(bool),
+  SetEnableDebugCullingStats(bool),
   // SetEnableDebugChunkBorder(bool),
   SetMouseSensitivity(f32),
 }
@@ -91,7 +97,7 @@ pub fn render_settings_ui(
           color: (0.2, 0.2, 0.2),
           corner_radius: 8.
         })
-        .with_size(size!(600, 300))
+        .with_size(size!(600, 580))
         .with_direction(Direction::Horizontal)
         .with_gap(10.)
         .with_padding(10.)
@@ -117,6 +123,34 @@ pub fn render_settings_ui(
             .add_child(ui);
           Break.add_child(ui);
 
+          Text::new("Far Render Distance")
+            .add_child(ui);
+          Slider::new(settings.far_render_distance as f32 / 32.)
+            .with_size(size!(300, auto))
+            .on_change(|f| SettingsSignal::SetFarRenderDistance((f * 32.).round() as u8))
+            .add_child(ui);
+          Text::new(format!("{} Chunks", settings.far_render_distance.max(settings.render_distance + 1)))
+            .add_child(ui);
+          Break.add_child(ui);
+
+          Text::new("Vertical Render Distance")
+            .add_child(ui);
+          Slider::new(settings.vertical_render_distance as f32 / 16.)
+            .with_size(size!(300, auto))
+            .on_change(|f| SettingsSignal::SetVerticalRenderDistance((f * 16.).round() as u8))
+            .add_child(ui);
+          Text::new(format!("{} Chunks", settings.vertical_render_distance))
+            .add_child(ui);
+          Break.add_child(ui);
+
+          checkbox(
+            ui,
+            "Spherical Loading",
+            settings.loading_shape == ChunkLoadingShape::Sphere,
+            SettingsSignal::SetEnableSphericalLoading
+          );
+          Break.add_child(ui);
+
           checkbox(
             ui,
             "Vsync",
@@ -133,6 +167,30 @@ pub fn render_settings_ui(
           );
           Break.add_child(ui);
 
+          checkbox(
+            ui,
+            "Greedy Meshing",
+            settings.greedy_meshing,
+            SettingsSignal::SetEnableGreedyMeshing
+          );
+          Break.add_child(ui);
+
+          checkbox(
+            ui,
+            "Occlusion Culling",
+            settings.occlusion_culling,
+            SettingsSignal::SetEnableOcclusionCulling
+          );
+          Break.add_child(ui);
+
+          checkbox(
+            ui,
+            "Debug Culling Stats",
+            settings.debug_culling_stats,
+            SettingsSignal::SetEnableDebugCullingStats
+          );
+          Break.add_child(ui);
+
           // checkbox(
           //   ui,
           //   "Debug Chunk Border",
@@ -156,7 +214,16 @@ pub fn render_settings_ui(
 
   ui.hui.process_signals(|signal: SettingsSignal| match signal {
     SettingsSignal::SetRenderDistance(value) => settings.render_distance = value,
+    SettingsSignal::SetFarRenderDistance(value) => settings.far_render_distance = value,
+    SettingsSignal::SetVerticalRenderDistance(value) => settings.vertical_render_distance = value,
+    SettingsSignal::SetEnableSphericalLoading(value) => settings.loading_shape = match value {
+      true => ChunkLoadingShape::Sphere,
+      false => ChunkLoadingShape::Column,
+    },
     SettingsSignal::SetEnableDynamicCrosshair(value) => settings.dynamic_crosshair = value,
+    SettingsSignal::SetEnableGreedyMeshing(value) => settings.greedy_meshing = value,
+    SettingsSignal::SetEnableOcclusionCulling(value) => settings.occlusion_culling = value,
+    SettingsSignal::SetEnableDebugCullingStats(value) => settings.debug_culling_stats = value,
     SettingsSignal::SetEnableVsync(value) => {
       settings.vsync = value;
       ren.reload_settings(&settings);
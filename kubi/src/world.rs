@@ -1,10 +1,15 @@
 use nohash_hasher::BuildNoHashHasher;
-use shipyard::{Unique, AllStoragesView};
+use shipyard::{Unique, AllStoragesView, UniqueView};
 use glam::IVec3;
 use hashbrown::HashMap;
 use anyhow::{Result, Context};
+use crate::rendering::{world::{ChunkMeshBuffers, ChunkVertex, MeshAllocation}, Renderer};
 
-pub use kubi_shared::{worldgen, block::Block};
+pub use kubi_shared::{worldgen, block::{Block, BlockState}};
+use kubi_shared::{
+  falling_block::FallingBlockIds,
+  tick::{BlockAccess, RandomTicks, ScheduledTicks},
+};
 
 pub mod chunk;
 pub mod tasks;
@@ -13,10 +18,17 @@ pub mod mesh;
 pub mod neighbors;
 pub mod raycast;
 pub mod queue;
+pub mod registry;
+pub mod ticks;
+pub mod falling_blocks;
+pub mod light;
+pub mod region;
 
 use chunk::{Chunk, ChunkMesh, CHUNK_SIZE};
+use mesh::visibility::ChunkVisibility;
 use tasks::ChunkTaskManager;
 use queue::BlockUpdateQueue;
+use region::ChunkLoadingState;
 
 #[derive(Default, Unique)]
 pub struct ChunkStorage {
@@ -38,48 +50,90 @@ impl ChunkStorage {
     )
   }
   pub fn get_block(&self, position: IVec3) -> Option<Block> {
+    Some(self.get_block_state(position)?.block)
+  }
+  pub fn get_block_state(&self, position: IVec3) -> Option<BlockState> {
     let (chunk, block) = Self::to_chunk_coords(position);
     let block = self.chunks
       .get(&chunk)?
       .block_data.as_ref()?
-      .blocks.get(block.x as usize)?
-      .get(block.y as usize)?
-      .get(block.z as usize)?;
-    Some(*block)
+      .blocks.get_state(block);
+    Some(block)
   }
-  pub fn get_block_mut(&mut self, position: IVec3) -> Option<&mut Block> {
+  /// Returns `None` if the chunk is not loaded
+  pub fn set_block_state(&mut self, position: IVec3, state: BlockState) -> Option<()> {
     let (chunk, block) = Self::to_chunk_coords(position);
-    let block = self.chunks
+    self.chunks
       .get_mut(&chunk)?
       .block_data.as_mut()?
-      .blocks.get_mut(block.x as usize)?
-      .get_mut(block.y as usize)?
-      .get_mut(block.z as usize)?;
-    Some(block)
+      .blocks.set_state(block, state);
+    Some(())
   }
   pub fn new() -> Self {
     Self::default()
   }
 }
+impl BlockAccess for ChunkStorage {
+  fn get_block_state(&self, position: IVec3) -> Option<BlockState> {
+    Self::get_block_state(self, position)
+  }
+}
 
 // #[derive(Unique)]
 // pub struct WorldInfo {
 //   pub seed: u32,
 // }
 
-#[derive(Default, Unique)]
+/// Vertices and indices of a mesh
+pub type MeshData<'a> = (&'a [ChunkVertex], &'a [u32]);
+
+#[derive(Unique)]
 pub struct ChunkMeshStorage {
   meshes: HashMap<usize, ChunkMesh, BuildNoHashHasher<usize>>,
+  buffers: ChunkMeshBuffers,
   index: usize,
 }
 impl ChunkMeshStorage {
-  pub fn new() -> Self {
+  pub fn new(renderer: &Renderer) -> Self {
     Self {
       meshes: HashMap::with_capacity_and_hasher(250, BuildNoHashHasher::default()),
+      buffers: ChunkMeshBuffers::new(renderer),
       index: 0,
     }
   }
-  pub fn insert(&mut self, mesh: ChunkMesh) -> usize {
+  fn try_upload(&mut self, renderer: &Renderer, main: MeshData, trans: MeshData) -> Option<(MeshAllocation, MeshAllocation)> {
+    let main_allocation = self.buffers.allocate(renderer, main.0, main.1)?;
+    let Some(trans_allocation) = self.buffers.allocate(renderer, trans.0, trans.1) else {
+      self.buffers.free(&main_allocation);
+      return None
+    };
+    Some((main_allocation, trans_allocation))
+  }
+  fn upload(&mut self, renderer: &Renderer, main: MeshData, trans: MeshData, visibility: ChunkVisibility) -> ChunkMesh {
+    if self.buffers.should_compact() {
+      self.compact(renderer);
+    }
+    //if the mesh doesn't fit, even after compacting, the buffers can't grow any larger and the chunk is not drawn
+    let (main, trans) = self.try_upload(renderer, main, trans)
+      .or_else(|| {
+        self.compact(renderer);
+        self.try_upload(renderer, main, trans)
+      })
+      .unwrap_or_else(|| {
+        log::error!("chunk mesh buffers are full, mesh with {} vertices won't be drawn", main.0.len() + trans.0.len());
+        Default::default()
+      });
+    ChunkMesh { main, trans, visibility }
+  }
+  fn compact(&mut self, renderer: &Renderer) {
+    self.buffers.compact(renderer, self.meshes.values_mut().flat_map(|mesh| [&mut mesh.main, &mut mesh.trans]));
+  }
+  fn free(&mut self, mesh: &ChunkMesh) {
+    self.buffers.free(&mesh.main);
+    self.buffers.free(&mesh.trans);
+  }
+  pub fn insert(&mut self, renderer: &Renderer, main: MeshData, trans: MeshData, visibility: ChunkVisibility) -> usize {
+    let mesh = self.upload(renderer, main, trans, visibility);
     let index = self.index;
     debug_assert!(self.meshes.get(&index).is_none());
     unsafe {
@@ -88,25 +142,38 @@ impl ChunkMeshStorage {
     self.index += 1;
     index
   }
-  pub fn update(&mut self, key: usize, mesh: ChunkMesh) -> Result<()> {
-    *self.meshes.get_mut(&key).context("Chunk doesn't exist")? = mesh;
+  pub fn update(&mut self, renderer: &Renderer, key: usize, main: MeshData, trans: MeshData, visibility: ChunkVisibility) -> Result<()> {
+    let old_mesh = self.meshes.remove(&key).context("Chunk doesn't exist")?;
+    //free first, so that the new mesh can reuse the space
+    self.free(&old_mesh);
+    let mesh = self.upload(renderer, main, trans, visibility);
+    self.meshes.insert(key, mesh);
     Ok(())
   }
   pub fn remove(&mut self, key: usize) -> Result<()> {
-    self.meshes.remove(&key).context("Chunk doesn't exist")?;
+    let mesh = self.meshes.remove(&key).context("Chunk doesn't exist")?;
+    self.free(&mesh);
     Ok(())
   }
   pub fn get(&self, key: usize) -> Option<&ChunkMesh> {
     self.meshes.get(&key)
   }
+  pub fn buffers(&self) -> &ChunkMeshBuffers {
+    &self.buffers
+  }
 }
 
 pub fn init_game_world(
   storages: AllStoragesView,
 ) {
   log::info!("init_game_world called");
-  storages.add_unique_non_send_sync(ChunkMeshStorage::new());
+  let meshes = ChunkMeshStorage::new(&storages.borrow::<UniqueView<Renderer>>().unwrap());
+  storages.add_unique_non_send_sync(meshes);
   storages.add_unique(ChunkStorage::new());
   storages.add_unique(ChunkTaskManager::new());
   storages.add_unique(BlockUpdateQueue::new());
+  storages.add_unique(ScheduledTicks::new());
+  storages.add_unique(RandomTicks::new());
+  storages.add_unique(FallingBlockIds::new());
+  storages.add_unique(ChunkLoadingState::default());
 }
@@ -1,24 +1,39 @@
 use std::sync::Arc;
 use glam::IVec3;
 use atomic::Atomic;
-use kubi_shared::worldgen::AbortState;
-use crate::rendering::BufferPair;
+use kubi_shared::worldgen::{AbortState, biome::BiomeMap};
+use crate::rendering::world::MeshAllocation;
+use super::{light::LightData, mesh::visibility::ChunkVisibility};
 
 pub use kubi_shared::chunk::{CHUNK_SIZE, BlockData};
 
 pub struct ChunkData {
   pub blocks: BlockData,
+  pub light: LightData,
+  /// Biome of each column (sent by the server in multiplayer)
+  pub biomes: BiomeMap,
   //pub has_renderable_blocks: bool,
 }
 impl ChunkData {
+  /// Light is not calculated here, see [`super::light::light_chunk`]
+  pub fn new(blocks: BlockData, biomes: BiomeMap) -> Self {
+    Self {
+      blocks,
+      light: LightData::new(),
+      biomes,
+    }
+  }
   // pub fn update_metadata(&mut self) {
   //   todo!()
   // }
 }
 
+/// Location of the chunk meshes in the [`ChunkMeshBuffers`](crate::rendering::world::ChunkMeshBuffers)
 pub struct ChunkMesh {
-  pub main: BufferPair,
-  pub trans: BufferPair,
+  pub main: MeshAllocation,
+  pub trans: MeshAllocation,
+  /// Used for occlusion culling
+  pub visibility: ChunkVisibility,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
@@ -58,6 +73,9 @@ pub struct Chunk {
   pub abortion: Option<Arc<Atomic<AbortState>>>,
   pub mesh_dirty: bool,
   pub data_modified: bool,
+  /// Level of detail the chunk should be meshed at, `0` is full detail\
+  /// Each level halves the resolution of the mesh (see [`super::mesh::lod`])
+  pub lod: u8,
 }
 
 impl Chunk {
@@ -71,6 +89,7 @@ impl Chunk {
       abortion: None,
       mesh_dirty: false,
       data_modified: false,
+      lod: 0,
     }
   }
 }
@@ -0,0 +1,45 @@
+use shipyard::{AllStoragesViewMut, IntoIter, IntoWithId, UniqueView, UniqueViewMut, ViewMut};
+use kubi_shared::{
+  block::SharedBlockRegistry,
+  falling_block::{FallingBlock, FallingBlockUpdate},
+};
+use crate::{delta_time::DeltaTime, networking::GameType};
+use super::{queue::BlockUpdateQueue, ChunkStorage};
+
+/// Simulate falling blocks
+///
+/// In multiplayer, the fall is only simulated for display purposes,
+/// landed blocks stay in place until the server removes them\
+/// In singleplayer, falling blocks in unloaded chunks are removed (they were saved with the chunk as blocks)
+pub fn update_falling_blocks(
+  mut storages: AllStoragesViewMut,
+) {
+  let mut to_delete = Vec::new();
+  {
+    let world = storages.borrow::<UniqueView<ChunkStorage>>().unwrap();
+    let registry = storages.borrow::<UniqueView<SharedBlockRegistry>>().unwrap();
+    let dt = storages.borrow::<UniqueView<DeltaTime>>().unwrap();
+    let game_type = storages.borrow::<UniqueView<GameType>>().unwrap();
+    let mut queue = storages.borrow::<UniqueViewMut<BlockUpdateQueue>>().unwrap();
+    let mut falling_blocks = storages.borrow::<ViewMut<FallingBlock>>().unwrap();
+
+    for (entity_id, falling_block) in (&mut falling_blocks).iter().with_id() {
+      if *game_type == GameType::Singleplayer && !world.chunks.contains_key(&falling_block.chunk_position()) {
+        to_delete.push(entity_id);
+        continue
+      }
+      let update = falling_block.update(&*world, &registry, dt.0.as_secs_f32());
+      if *game_type != GameType::Singleplayer {
+        continue
+      }
+      match update {
+        FallingBlockUpdate::Falling => continue,
+        FallingBlockUpdate::Landed(item) => queue.0.push(item),
+      }
+      to_delete.push(entity_id);
+    }
+  }
+  for entity_id in to_delete {
+    storages.delete_entity(entity_id);
+  }
+}
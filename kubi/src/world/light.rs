@@ -0,0 +1,496 @@
+//! Sky light and block light
+//!
+//! Every block has two light levels (`0..=MAX_LIGHT_LEVEL`):
+//! - sky light, coming from above, which keeps its full strength while going straight down
+//! - block light, emitted by blocks like torches
+//!
+//! Light spreads between neighboring blocks using a flood fill, losing one level per block
+//! (plus the light opacity of the block it enters).\
+//! Block changes are handled incrementally: light that might have come through the changed block
+//! is removed first, and then filled back in from the remaining sources
+//!
+//! Chunks outside of the world height are never loaded, their blocks are implicit air with full sky light
+
+use std::collections::VecDeque;
+use glam::{ivec3, IVec3};
+use hashbrown::HashSet;
+use kubi_shared::{block::{BlockRegistry, BlockState, MAX_LIGHT_LEVEL}, height::WorldHeight};
+use super::{chunk::CHUNK_SIZE, ChunkStorage};
+
+const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+const DIRECTIONS: [IVec3; 6] = [
+  ivec3(1,  0,  0),
+  ivec3(-1, 0,  0),
+  ivec3(0,  1,  0),
+  ivec3(0, -1,  0),
+  ivec3(0,  0,  1),
+  ivec3(0,  0, -1),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightChannel {
+  Sky,
+  Block,
+}
+
+impl LightChannel {
+  pub const ALL: [Self; 2] = [Self::Sky, Self::Block];
+
+  const fn shift(self) -> u8 {
+    match self {
+      Self::Sky => 4,
+      Self::Block => 0,
+    }
+  }
+
+  /// Light level of blocks outside of the world height
+  const fn implicit_level(self) -> u8 {
+    match self {
+      Self::Sky => MAX_LIGHT_LEVEL,
+      Self::Block => 0,
+    }
+  }
+}
+
+/// Light levels of a single chunk
+///
+/// Each block uses a single byte, sky light in the high and block light in the low nibble
+/// (this packed form is also what ends up in the chunk mesh)
+#[derive(Clone)]
+pub struct LightData {
+  data: Box<[u8]>,
+}
+
+impl Default for LightData {
+  fn default() -> Self {
+    Self { data: vec![0; CHUNK_VOLUME].into_boxed_slice() }
+  }
+}
+
+impl LightData {
+  /// Create light data with all blocks completely dark
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Create light data with all blocks in direct sunlight (like chunks outside of the world height)
+  pub fn full_sky() -> Self {
+    let packed = LightChannel::Sky.implicit_level() << LightChannel::Sky.shift();
+    Self { data: vec![packed; CHUNK_VOLUME].into_boxed_slice() }
+  }
+
+  #[inline]
+  fn index(position: IVec3) -> usize {
+    (position.x as usize * CHUNK_SIZE + position.y as usize) * CHUNK_SIZE + position.z as usize
+  }
+
+  /// Get both light levels at `position` (in chunk-local coordinates), packed into a single byte
+  #[inline]
+  pub fn get_packed(&self, position: IVec3) -> u8 {
+    self.data[Self::index(position)]
+  }
+
+  /// Get the light level of `channel` at `position` (in chunk-local coordinates)
+  #[inline]
+  pub fn get(&self, position: IVec3, channel: LightChannel) -> u8 {
+    (self.get_packed(position) >> channel.shift()) & 0xf
+  }
+
+  /// Set the light level of `channel` at `position` (in chunk-local coordinates)
+  #[inline]
+  pub fn set(&mut self, position: IVec3, channel: LightChannel, level: u8) {
+    let value = &mut self.data[Self::index(position)];
+    *value = (*value & !(0xf << channel.shift())) | ((level & 0xf) << channel.shift());
+  }
+}
+
+impl ChunkStorage {
+  /// Returns `None` if the chunk is not loaded
+  pub fn get_light(&self, position: IVec3, channel: LightChannel) -> Option<u8> {
+    let (chunk, block) = Self::to_chunk_coords(position);
+    Some(self.chunks.get(&chunk)?.block_data.as_ref()?.light.get(block, channel))
+  }
+
+  /// Both light levels packed into a single byte (see [`LightData`])\
+  /// Returns `None` if the chunk is not loaded
+  pub fn get_packed_light(&self, position: IVec3) -> Option<u8> {
+    let (chunk, block) = Self::to_chunk_coords(position);
+    Some(self.chunks.get(&chunk)?.block_data.as_ref()?.light.get_packed(block))
+  }
+
+  fn set_light(&mut self, position: IVec3, channel: LightChannel, level: u8) -> Option<()> {
+    let (chunk, block) = Self::to_chunk_coords(position);
+    self.chunks
+      .get_mut(&chunk)?
+      .block_data.as_mut()?
+      .light.set(block, channel, level);
+    Some(())
+  }
+}
+
+/// Light level that spreads from a block with light `level` into its neighbor in `direction`
+fn spread(registry: &BlockRegistry, channel: LightChannel, level: u8, direction: IVec3, target: BlockState) -> u8 {
+  let opacity = registry.get(target.block).light_opacity();
+  if opacity >= MAX_LIGHT_LEVEL {
+    return 0
+  }
+  //direct sunlight doesn't get weaker going down
+  if channel == LightChannel::Sky && direction == IVec3::NEG_Y && level == MAX_LIGHT_LEVEL && opacity == 0 {
+    return MAX_LIGHT_LEVEL
+  }
+  level.saturating_sub(1 + opacity)
+}
+
+/// Flood fill of a single light channel
+struct LightUpdate<'a> {
+  world: &'a mut ChunkStorage,
+  registry: &'a BlockRegistry,
+  height: &'a WorldHeight,
+  channel: LightChannel,
+  add_queue: VecDeque<IVec3>,
+  remove_queue: VecDeque<(IVec3, u8)>,
+  /// Chunks with meshes affected by the light changes
+  dirty_chunks: HashSet<IVec3>,
+}
+
+impl<'a> LightUpdate<'a> {
+  fn new(world: &'a mut ChunkStorage, registry: &'a BlockRegistry, height: &'a WorldHeight, channel: LightChannel) -> Self {
+    Self {
+      world,
+      registry,
+      height,
+      channel,
+      add_queue: VecDeque::new(),
+      remove_queue: VecDeque::new(),
+      dirty_chunks: HashSet::new(),
+    }
+  }
+
+  /// Is `position` in a chunk outside of the world height?
+  fn is_implicit(&self, position: IVec3) -> bool {
+    let (chunk, _) = ChunkStorage::to_chunk_coords(position);
+    !self.height.contains_chunk(chunk.y)
+  }
+
+  /// Returns `None` if the chunk is not loaded
+  fn get(&self, position: IVec3) -> Option<u8> {
+    if self.is_implicit(position) {
+      return Some(self.channel.implicit_level())
+    }
+    self.world.get_light(position, self.channel)
+  }
+
+  fn set(&mut self, position: IVec3, level: u8) {
+    if self.world.set_light(position, self.channel, level).is_none() {
+      return
+    }
+    //faces of neighboring blocks use this light level, even if they're in another chunk
+    let (chunk, _) = ChunkStorage::to_chunk_coords(position);
+    self.dirty_chunks.insert(chunk);
+    for direction in DIRECTIONS {
+      let (neighbor_chunk, _) = ChunkStorage::to_chunk_coords(position + direction);
+      if neighbor_chunk != chunk {
+        self.dirty_chunks.insert(neighbor_chunk);
+      }
+    }
+  }
+
+  /// Light level emitted by the block at `position`
+  fn emission(&self, position: IVec3) -> u8 {
+    match self.channel {
+      LightChannel::Sky => 0,
+      LightChannel::Block => self.world.get_block_state(position)
+        .map(|state| self.registry.get(state.block).light_emission)
+        .unwrap_or(0),
+    }
+  }
+
+  /// Remove light that might have come from the blocks in the removal queue\
+  /// Blocks that are lit by other sources are added to the propagation queue
+  fn remove(&mut self) {
+    while let Some((position, level)) = self.remove_queue.pop_front() {
+      for direction in DIRECTIONS {
+        let neighbor = position + direction;
+        let Some(neighbor_level) = self.get(neighbor) else { continue };
+        if neighbor_level == 0 {
+          continue
+        }
+        //blocks outside of the world height always keep their light
+        if self.is_implicit(neighbor) {
+          self.add_queue.push_back(neighbor);
+          continue
+        }
+        let direct_sunlight = self.channel == LightChannel::Sky
+          && direction == IVec3::NEG_Y
+          && level == MAX_LIGHT_LEVEL
+          && neighbor_level == MAX_LIGHT_LEVEL;
+        if neighbor_level < level || direct_sunlight {
+          self.set(neighbor, 0);
+          self.remove_queue.push_back((neighbor, neighbor_level));
+          //light sources keep shining
+          let emission = self.emission(neighbor);
+          if emission > 0 {
+            self.set(neighbor, emission);
+            self.add_queue.push_back(neighbor);
+          }
+        } else {
+          self.add_queue.push_back(neighbor);
+        }
+      }
+    }
+  }
+
+  /// Spread light from the blocks in the propagation queue
+  fn propagate(&mut self) {
+    while let Some(position) = self.add_queue.pop_front() {
+      let Some(level) = self.get(position) else { continue };
+      if level == 0 {
+        continue
+      }
+      for direction in DIRECTIONS {
+        let neighbor = position + direction;
+        let Some(state) = self.world.get_block_state(neighbor) else { continue };
+        let Some(neighbor_level) = self.get(neighbor) else { continue };
+        let new_level = spread(self.registry, self.channel, level, direction, state);
+        if new_level > neighbor_level {
+          self.set(neighbor, new_level);
+          self.add_queue.push_back(neighbor);
+        }
+      }
+    }
+  }
+
+  fn finish(self) {
+    for position in self.dirty_chunks {
+      if let Some(chunk) = self.world.chunks.get_mut(&position) {
+        chunk.mesh_dirty = true;
+      }
+    }
+  }
+}
+
+/// Calculate the light of a newly loaded chunk, and update the light of its neighbors
+///
+/// Chunks above that are not loaded yet are assumed to be open sky,
+/// this gets corrected once they're loaded
+pub fn light_chunk(world: &mut ChunkStorage, registry: &BlockRegistry, height: &WorldHeight, chunk_position: IVec3) {
+  const SIZE: i32 = CHUNK_SIZE as i32;
+  let origin = chunk_position * SIZE;
+  if world.get_block_state(origin).is_none() {
+    return
+  }
+
+  //Light coming from the neighboring chunks
+  let neighbors = world.neighbors(chunk_position);
+  let border_light_sources = [
+    (neighbors.top,    IVec3::Y),
+    (neighbors.bottom, IVec3::NEG_Y),
+    (neighbors.left,   IVec3::NEG_X),
+    (neighbors.right,  IVec3::X),
+    (neighbors.front,  IVec3::Z),
+    (neighbors.back,   IVec3::NEG_Z),
+  ].into_iter().filter(|(chunk, direction)| {
+    !height.contains_chunk(chunk_position.y + direction.y) || chunk.is_some_and(|chunk| chunk.block_data.is_some())
+  }).flat_map(|(_, direction)| {
+    //layer of the neighboring chunk touching this one
+    (0..SIZE).flat_map(move |a| (0..SIZE).map(move |b| {
+      let local = match direction.to_array() {
+        [_, 0, 0] => ivec3(if direction.x > 0 { 0 } else { SIZE - 1 }, a, b),
+        [0, _, 0] => ivec3(a, if direction.y > 0 { 0 } else { SIZE - 1 }, b),
+        _ => ivec3(a, b, if direction.z > 0 { 0 } else { SIZE - 1 }),
+      };
+      origin + direction * SIZE + local
+    }))
+  }).collect::<Vec<_>>();
+
+  //Sky light
+  let mut update = LightUpdate::new(world, registry, height, LightChannel::Sky);
+  for x in 0..SIZE {
+    for z in 0..SIZE {
+      let mut level = update.get(origin + ivec3(x, SIZE, z)).unwrap_or(MAX_LIGHT_LEVEL);
+      for y in (0..SIZE).rev() {
+        let position = origin + ivec3(x, y, z);
+        let state = update.world.get_block_state(position).expect("chunk is loaded");
+        level = spread(registry, LightChannel::Sky, level, IVec3::NEG_Y, state);
+        if level == 0 {
+          break
+        }
+        update.set(position, level);
+        update.add_queue.push_back(position);
+      }
+      //the chunk below might have assumed that there's open sky above it
+      let below = origin + ivec3(x, -1, z);
+      if !update.is_implicit(below) && update.get(below) == Some(MAX_LIGHT_LEVEL) && update.get(origin + ivec3(x, 0, z)) != Some(MAX_LIGHT_LEVEL) {
+        update.set(below, 0);
+        update.remove_queue.push_back((below, MAX_LIGHT_LEVEL));
+      }
+    }
+  }
+  update.add_queue.extend(border_light_sources.iter().copied());
+  update.remove();
+  update.propagate();
+  update.finish();
+
+  //Block light
+  let mut update = LightUpdate::new(world, registry, height, LightChannel::Block);
+  for x in 0..SIZE {
+    for y in 0..SIZE {
+      for z in 0..SIZE {
+        let position = origin + ivec3(x, y, z);
+        let emission = update.emission(position);
+        if emission > 0 {
+          update.set(position, emission);
+          update.add_queue.push_back(position);
+        }
+      }
+    }
+  }
+  update.add_queue.extend(border_light_sources.iter().copied());
+  update.propagate();
+  update.finish();
+}
+
+/// Update the light around `position` after the block there has changed
+pub fn update_light(world: &mut ChunkStorage, registry: &BlockRegistry, height: &WorldHeight, position: IVec3) {
+  for channel in LightChannel::ALL {
+    let mut update = LightUpdate::new(world, registry, height, channel);
+    let Some(level) = update.get(position) else { return };
+    //remove the light that might have passed through the old block
+    update.set(position, 0);
+    update.remove_queue.push_back((position, level));
+    update.remove();
+    //the new block might be a light source...
+    let emission = update.emission(position);
+    if emission > 0 {
+      update.set(position, emission);
+      update.add_queue.push_back(position);
+    }
+    //...or let light from its neighbors through
+    update.add_queue.extend(DIRECTIONS.map(|direction| position + direction));
+    update.propagate();
+    update.finish();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use kubi_shared::{block::Block, worldgen::biome::BiomeMap};
+  use crate::world::chunk::{BlockData, Chunk, ChunkData};
+  use super::*;
+
+  /// World with the chunks at `chunks` loaded (filled with air), and `blocks` placed in them
+  fn test_world(chunks: &[IVec3], blocks: &[(IVec3, Block)]) -> ChunkStorage {
+    let mut world = ChunkStorage::new();
+    for &position in chunks {
+      let mut chunk = Chunk::new(position);
+      chunk.block_data = Some(ChunkData::new(BlockData::new(), BiomeMap::default()));
+      world.chunks.insert(position, chunk);
+    }
+    for &(position, block) in blocks {
+      world.set_block_state(position, BlockState::new(block)).expect("chunk is loaded");
+    }
+    world
+  }
+
+  #[test]
+  fn sky_light_under_overhang() {
+    let registry = BlockRegistry::builtin();
+    //stone roof covering the x < 16 half of the chunk
+    let roof: Vec<(IVec3, Block)> = (0..16)
+      .flat_map(|x| (0..CHUNK_SIZE as i32).map(move |z| (ivec3(x, 20, z), Block::Stone)))
+      .collect();
+    let mut world = test_world(&[IVec3::ZERO], &roof);
+    light_chunk(&mut world, &registry, &WorldHeight::default(), IVec3::ZERO);
+
+    let sky = |position| world.get_light(position, LightChannel::Sky).unwrap();
+    assert_eq!(sky(ivec3(8, 25, 16)), MAX_LIGHT_LEVEL);
+    assert_eq!(sky(ivec3(8, 20, 16)), 0);
+    //open side gets direct sunlight all the way down
+    assert_eq!(sky(ivec3(16, 0, 16)), MAX_LIGHT_LEVEL);
+    //below the roof, light fades with the distance from the open side
+    for x in 0..16 {
+      let expected = MAX_LIGHT_LEVEL.saturating_sub(16 - x as u8);
+      assert_eq!(sky(ivec3(x, 5, 16)), expected, "at x = {x}");
+      assert_eq!(sky(ivec3(x, 19, 3)), expected, "at x = {x}");
+    }
+  }
+
+  #[test]
+  fn block_light_falloff() {
+    let registry = BlockRegistry::builtin();
+    let torch = ivec3(16, 16, 16);
+    let emission = registry.get(Block::Torch).light_emission;
+    let mut world = test_world(&[IVec3::ZERO], &[(torch, Block::Torch), (torch + ivec3(0, 0, 2), Block::Stone)]);
+    light_chunk(&mut world, &registry, &WorldHeight::default(), IVec3::ZERO);
+
+    let block_light = |position| world.get_light(position, LightChannel::Block).unwrap();
+    assert_eq!(block_light(torch), emission);
+    for distance in 1..=emission as i32 + 1 {
+      let expected = emission.saturating_sub(distance as u8);
+      assert_eq!(block_light(torch + ivec3(distance, 0, 0)), expected);
+      assert_eq!(block_light(torch - ivec3(0, distance, 0)), expected);
+    }
+    //distance is measured along the axes
+    assert_eq!(block_light(torch + ivec3(3, -2, 1)), emission - 6);
+    //opaque blocks don't get lit, light goes around them
+    assert_eq!(block_light(torch + ivec3(0, 0, 2)), 0);
+    assert_eq!(block_light(torch + ivec3(0, 0, 3)), emission - 5);
+  }
+
+  #[test]
+  fn light_removed_after_breaking_source() {
+    let registry = BlockRegistry::builtin();
+    let torch = ivec3(16, 16, 16);
+    let other_torch = ivec3(4, 16, 16);
+    let emission = registry.get(Block::Torch).light_emission;
+    let mut world = test_world(&[IVec3::ZERO], &[(torch, Block::Torch), (other_torch, Block::Torch)]);
+    light_chunk(&mut world, &registry, &WorldHeight::default(), IVec3::ZERO);
+    assert_eq!(world.get_light(torch + IVec3::X * 4, LightChannel::Block), Some(emission - 4));
+
+    world.set_block_state(torch, BlockState::AIR).unwrap();
+    update_light(&mut world, &registry, &WorldHeight::default(), torch);
+
+    //only the light of the other torch is left
+    let block_light = |position| world.get_light(position, LightChannel::Block).unwrap();
+    for x in 0..CHUNK_SIZE as i32 {
+      let position = ivec3(x, 16, 16);
+      let expected = emission.saturating_sub((x - other_torch.x).unsigned_abs() as u8);
+      assert_eq!(block_light(position), expected, "at x = {x}");
+    }
+    assert_eq!(block_light(torch + ivec3(0, 5, 0)), 0);
+    //sky light is not affected
+    assert_eq!(world.get_light(torch, LightChannel::Sky), Some(MAX_LIGHT_LEVEL));
+  }
+
+  #[test]
+  fn sky_light_from_outside_the_world_height() {
+    let registry = BlockRegistry::builtin();
+    //a single chunk high world, with a stone roof covering the whole chunk
+    let height = WorldHeight { min: 0, max: CHUNK_SIZE as i32, bedrock: 0 };
+    let roof: Vec<(IVec3, Block)> = (0..CHUNK_SIZE as i32)
+      .flat_map(|x| (0..CHUNK_SIZE as i32).map(move |z| (ivec3(x, 20, z), Block::Stone)))
+      .collect();
+    let mut world = test_world(&[IVec3::ZERO], &roof);
+    light_chunk(&mut world, &registry, &height, IVec3::ZERO);
+
+    //the chunks above and below are implicit air with full sky light
+    let sky = |world: &ChunkStorage, position| world.get_light(position, LightChannel::Sky).unwrap();
+    assert_eq!(sky(&world, ivec3(5, 21, 5)), MAX_LIGHT_LEVEL);
+    for y in 0..20 {
+      assert_eq!(sky(&world, ivec3(5, y, 5)), MAX_LIGHT_LEVEL.saturating_sub(1 + y as u8), "at y = {y}");
+    }
+
+    //blocks next to the chunk below keep getting lit by it when the light around them is removed
+    let block = ivec3(5, 0, 5);
+    world.set_block_state(block, BlockState::new(Block::Stone)).unwrap();
+    update_light(&mut world, &registry, &height, block);
+    assert_eq!(sky(&world, block), 0);
+    assert_eq!(sky(&world, block + IVec3::X), MAX_LIGHT_LEVEL - 1);
+    assert_eq!(sky(&world, block + IVec3::Y), MAX_LIGHT_LEVEL - 3);
+    world.set_block_state(block, BlockState::AIR).unwrap();
+    update_light(&mut world, &registry, &height, block);
+    assert_eq!(sky(&world, block), MAX_LIGHT_LEVEL - 1);
+    assert_eq!(sky(&world, block + IVec3::Y), MAX_LIGHT_LEVEL - 2);
+  }
+}
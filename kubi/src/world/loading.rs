@@ -1,27 +1,34 @@
 use std::sync::Arc;
 use atomic::{Atomic, Ordering};
-use glam::{IVec3, ivec3};
+use glam::{IVec3, Vec3, ivec3};
 use kubi_shared::{
+  block::SharedBlockRegistry,
   data::io_thread::{IOCommand, IOResponse, IOThreadManager},
+  falling_block::FallingBlock,
+  height::WorldHeight,
   networking::{channels::Channel, messages::ClientToServerMessage},
-  worldgen::AbortState,
+  tick::ScheduledTicks,
+  worldgen::{AbortState, generate_biome_map},
 };
-use shipyard::{View, UniqueView, UniqueViewMut, IntoIter, Workload, IntoWorkload, NonSendSync, track};
+use shipyard::{View, UniqueView, UniqueViewMut, IntoIter, Workload, IntoWorkload, NonSendSync};
 use uflow::SendMode;
-use wgpu::util::DeviceExt;
 use crate::{
+  camera::Camera,
   networking::UdpClient,
   player::MainPlayer,
-  rendering::{BufferPair, Renderer},
+  rendering::Renderer,
   settings::GameSettings,
   state::GameState,
   transform::Transform,
 };
 use super::{
   ChunkStorage, ChunkMeshStorage,
-  chunk::{Chunk, DesiredChunkState, CHUNK_SIZE, ChunkMesh, CurrentChunkState, ChunkData},
+  chunk::{Chunk, DesiredChunkState, CHUNK_SIZE, CurrentChunkState, ChunkData},
   tasks::{ChunkTaskManager, ChunkTaskResponse, ChunkTask},
   queue::BlockUpdateQueue,
+  light::light_chunk,
+  registry::LocalWorldGenerator,
+  region::{ChunkLoadingState, LoadingRegion},
 };
 
 const WORLD_SEED: u64 = 0xfeb_face_dead_cafe;
@@ -39,20 +46,17 @@ pub fn update_loaded_world_around_player() -> Workload {
 
 pub fn update_chunks_if_player_moved(
   v_settings: UniqueView<GameSettings>,
+  v_height: UniqueView<WorldHeight>,
   v_local_player: View<MainPlayer>,
-  v_transform: View<Transform, track::All>,
+  v_transform: View<Transform>,
   mut vm_world: UniqueViewMut<ChunkStorage>,
+  mut vm_loading: UniqueViewMut<ChunkLoadingState>,
 ) {
-  //Check if the player actually moved 
-  //TODO fix this also triggers on rotation, only activate when the player crosses the chunk border
-  let Some((_, transform)) = (&v_local_player, v_transform.inserted_or_modified()).iter().next() else {
+  let Some((_, transform)) = (&v_local_player, &v_transform).iter().next() else {
     return
   };
 
-  //Read game settings
-  let load_distance = (v_settings.render_distance + 1) as i32;
-
-  //If it did, get it's position and current chunk
+  //Get the player position and current chunk
   let player_position = transform.0.to_scale_rotation_translation().2;
   let player_position_ivec3 = player_position.as_ivec3();
   let player_at_chunk = ivec3(
@@ -61,21 +65,26 @@ pub fn update_chunks_if_player_moved(
     player_position_ivec3.z.div_euclid(CHUNK_SIZE as i32),
   );
 
+  //Only do anything if the player crossed a chunk border (or the settings changed)
+  let region = LoadingRegion::new(player_at_chunk, &v_settings, &v_height);
+  if vm_loading.region == Some(region) {
+    return
+  }
+  vm_loading.region = Some(region);
+
   //Then, mark *ALL* chunks with ToUnload
   for (_, chunk) in &mut vm_world.chunks {
     chunk.desired_state = DesiredChunkState::Unloaded;
   }
 
-  //Then mark chunks that are near to the player
-  for x in -load_distance..=load_distance {
-    for y in -load_distance..=load_distance {
-      for z in -load_distance..=load_distance {
-        let chunk_pos_offset = ivec3(x, y, z);
-        let chunk_pos = player_at_chunk + chunk_pos_offset;
-        let is_border = {
-          chunk_pos_offset.x.abs() == load_distance ||
-          chunk_pos_offset.y.abs() == load_distance ||
-          chunk_pos_offset.z.abs() == load_distance
+  //Then mark chunks that are inside of the loading region
+  let (min, max) = region.bounds();
+  for x in min.x..=max.x {
+    for y in min.y..=max.y {
+      for z in min.z..=max.z {
+        let chunk_pos = ivec3(x, y, z);
+        let Some((desired, lod)) = region.chunk_state(chunk_pos) else {
+          continue
         };
         //If chunk doesn't exist create it
         let chunk = match vm_world.chunks.get_mut(&chunk_pos) {
@@ -88,11 +97,11 @@ pub fn update_chunks_if_player_moved(
             vm_world.chunks.get_mut(&chunk_pos).unwrap()
           }
         };
-        let desired = match is_border {
-          true  => DesiredChunkState::Loaded,
-          false => DesiredChunkState::Rendered,
-        };
         chunk.desired_state = desired;
+        if chunk.lod != lod {
+          chunk.lod = lod;
+          chunk.mesh_dirty = true;
+        }
       }
     }
   }
@@ -101,14 +110,27 @@ pub fn update_chunks_if_player_moved(
 fn process_state_changes(
   task_manager: UniqueView<ChunkTaskManager>,
   io: Option<UniqueView<IOThreadManager>>,
+  generator: Option<UniqueView<LocalWorldGenerator>>,
+  registry: UniqueView<SharedBlockRegistry>,
   mut udp_client: Option<UniqueViewMut<UdpClient>>,
   mut world: UniqueViewMut<ChunkStorage>,
   mut vm_meshes: NonSendSync<UniqueViewMut<ChunkMeshStorage>>,
+  mut ticks: UniqueViewMut<ScheduledTicks>,
+  settings: UniqueView<GameSettings>,
+  height: UniqueView<WorldHeight>,
+  state: UniqueView<GameState>,
+  mut loading: UniqueViewMut<ChunkLoadingState>,
+  v_local_player: View<MainPlayer>,
+  v_camera: View<Camera>,
+  falling_blocks: View<FallingBlock>,
 ) {
-  if !world.is_modified() {
+  if !world.is_modified() && !loading.backlog {
     return
   }
 
+  //Chunks waiting for a task to be started, these are started after all other state changes
+  let mut pending_tasks = Vec::new();
+
   //HACK: cant iterate over chunks.keys() or chunk directly!
   let hashmap_keys: Vec<IVec3> = world.chunks.keys().copied().collect();
   for position in hashmap_keys {
@@ -182,6 +204,45 @@ fn process_state_changes(
       // DesiredChunkState::Loaded | DesiredChunkState::Rendered:
       // Nothing -> Loading
       DesiredChunkState::Loaded | DesiredChunkState::Rendered if chunk.current_state == CurrentChunkState::Nothing => {
+        pending_tasks.push(position);
+      },
+
+      // DesiredChunkState::Rendered:
+      // Loaded -> CalculatingMesh
+      // Rendered (dirty) -> RecalculatingMesh
+      DesiredChunkState::Rendered if (chunk.current_state == CurrentChunkState::Loaded || chunk.mesh_dirty) => {
+        pending_tasks.push(position);
+      }
+
+      _ => {}, //panic!("Illegal state transition: {:?} -> {:?}", chunk.current_state, chunk.desired_state),
+    }
+  }
+
+  //Start the tasks, most important first (chunks the player can see, nearest first)
+  //The number of tasks started per frame is limited, so that new tasks with a higher priority don't have to wait
+  let center = loading.region.map(|region| region.center).unwrap_or_default();
+  let camera = (&v_local_player, &v_camera).iter().next().map(|(_, camera)| camera);
+  pending_tasks.sort_by_cached_key(|&position| {
+    let minp = position.as_vec3() * CHUNK_SIZE as f32;
+    let maxp = minp + Vec3::splat(CHUNK_SIZE as f32);
+    let visible = camera.is_some_and(|camera| camera.frustum.is_box_visible(minp, maxp));
+    (!visible, (position - center).length_squared())
+  });
+  let max_ops = match *state {
+    GameState::InGame => MAX_CHUNK_OPS_INGAME,
+    _ => MAX_CHUNK_OPS,
+  };
+  let mut ops: usize = 0;
+  loading.backlog = false;
+  for position in pending_tasks {
+    if ops >= max_ops {
+      loading.backlog = true;
+      break
+    }
+    let chunk = world.chunks.get_mut(&position).unwrap();
+    match chunk.current_state {
+      // Nothing -> Loading
+      CurrentChunkState::Nothing => {
         let mut abortion = None;
         //start load task
         if let Some(client) = &mut udp_client {
@@ -194,14 +255,15 @@ fn process_state_changes(
           );
         } else {
 
-          // If the chunk exists in the save file (and save file is there in the first place),
+          // If the chunk (or blocks queued for it) exists in the save file (and save file is there in the first place),
           // ... we'll try to load it
           // Otherwise, we'll run worldgen
+          // (blocks queued right before this may not be stored yet, they'll be applied the next time the chunk loads)
 
           let mut should_run_worldgen = true;
 
           if let Some(io) = &io {
-            if io.chunk_exists(position) {
+            if io.chunk_exists(position) || io.has_queued_blocks(position) {
               // Try to load the chunk from the save file
               // In case that fails, we will run worldgen once the IO thread responds
               io.send(IOCommand::LoadChunk { position });
@@ -211,8 +273,13 @@ fn process_state_changes(
 
           if should_run_worldgen {
             let atomic = Arc::new(Atomic::new(AbortState::Continue));
+            let generator = generator.as_ref().expect("no world generator in singleplayer");
             task_manager.spawn_task(ChunkTask::ChunkWorldgen {
               seed: WORLD_SEED,
+              generator: Arc::clone(&generator.preset),
+              templates: Arc::clone(&generator.templates),
+              registry: Arc::clone(&registry.0),
+              height: *height,
               position,
               abortion: Some(Arc::clone(&atomic)),
             });
@@ -229,19 +296,31 @@ fn process_state_changes(
         //log::trace!("Started loading chunk {position}");
       },
 
-      // DesiredChunkState::Rendered:
       // Loaded -> CalculatingMesh
       // Rendered (dirty) -> RecalculatingMesh
-      DesiredChunkState::Rendered if (chunk.current_state == CurrentChunkState::Loaded || chunk.mesh_dirty) => {
+      _ => {
         //get needed data
-        let Some(neighbors) = world.neighbors_all(position) else {
-          continue
-        };
-        let Some(data) = neighbors.mesh_data() else {
-          continue
+        let task = match chunk.lod {
+          0 => {
+            //chunks outside of the world height are never loaded, they're meshed against as empty (see mesh_data)
+            let Some(data) = world.neighbors(position).mesh_data(&height) else {
+              continue
+            };
+            ChunkTask::GenerateMesh {
+              data, position,
+              registry: Arc::clone(&registry.0),
+              greedy: settings.greedy_meshing,
+            }
+          },
+          lod => {
+            let Some(data) = world.neighbors(position).lod_mesh_data() else {
+              continue
+            };
+            ChunkTask::GenerateLodMesh { data, position, registry: Arc::clone(&registry.0), lod }
+          },
         };
         //spawn task
-        task_manager.spawn_task(ChunkTask::GenerateMesh { data, position });
+        task_manager.spawn_task(task);
         //Update chunk state
         let chunk = world.chunks.get_mut(&position).unwrap();
         if chunk.mesh_dirty {
@@ -253,10 +332,9 @@ fn process_state_changes(
         chunk.abortion = None; //Can never abort at this point
         // ===========
         //log::trace!("Started generating mesh for chunk {position}");
-      }
-
-      _ => {}, //panic!("Illegal state transition: {:?} -> {:?}", chunk.current_state, chunk.desired_state),
+      },
     }
+    ops += 1;
   }
 
   //Now, separately process state change the state from Nothing to Unloading or Unloaded
@@ -294,18 +372,31 @@ fn process_state_changes(
 
       if let Some(io) = &io {
         if let Some(block_data) = &chunk.block_data {
+          //Falling blocks in the chunk are saved as blocks (their entities are removed in update_falling_blocks)
+          let mut falling = falling_blocks.iter()
+            .filter(|falling_block| falling_block.chunk_position() == position)
+            .peekable();
           // Only save the chunk if it has been modified
-          if chunk.data_modified {
+          if chunk.data_modified || falling.peek().is_some() {
             // log::debug!("issue save command");
             chunk.data_modified = false;
+            let mut data = block_data.blocks.clone();
+            let mut pending_ticks = ticks.pending_in_chunk(position);
+            for falling_block in falling {
+              falling_block.persist(&registry, &mut data, &mut pending_ticks);
+            }
             io.send(IOCommand::SaveChunk {
               position,
-              data: block_data.blocks.clone(),
+              data,
+              ticks: pending_ticks,
             });
           }
         }
       }
 
+      //Ticks can't run in unloaded chunks (they're saved with the chunk instead)
+      ticks.remove_chunk(position);
+
       return false
     }
     true
@@ -316,11 +407,15 @@ fn process_state_changes(
 fn process_completed_tasks(
   task_manager: UniqueView<ChunkTaskManager>,
   io: Option<UniqueView<IOThreadManager>>,
+  generator: Option<UniqueView<LocalWorldGenerator>>,
+  registry: UniqueView<SharedBlockRegistry>,
+  height: UniqueView<WorldHeight>,
   mut world: UniqueViewMut<ChunkStorage>,
   mut meshes: NonSendSync<UniqueViewMut<ChunkMeshStorage>>,
   renderer: UniqueView<Renderer>,
   state: UniqueView<GameState>,
   mut queue: UniqueViewMut<BlockUpdateQueue>,
+  mut ticks: UniqueViewMut<ScheduledTicks>,
 ) {
   let mut ops: usize = 0;
 
@@ -328,12 +423,17 @@ fn process_completed_tasks(
 
   // Process IO first
   if let Some(io) = &io {
+    let generator = generator.as_ref().expect("no world generator in singleplayer");
     for response in io.poll() {
-      let IOResponse::ChunkLoaded { position, data } = response else {
+      let IOResponse::ChunkLoaded { position, data, ticks: pending_ticks, queued } = response else {
         //TODO this is bad
         panic!("Unexpected IO response: {:?}", response);
       };
 
+      //push blocks queued while the chunk wasn't loaded
+      //(if the chunk gets discarded, they'll be saved again by save_unloaded_queued_blocks)
+      queue.0.extend(queued);
+
       //check if chunk exists
       let Some(chunk) = world.chunks.get_mut(&position) else {
         log::warn!("LOADED blocks data discarded: chunk doesn't exist");
@@ -352,16 +452,20 @@ fn process_completed_tasks(
       // check if we actually got the data
       if let Some(data) = data {
         // If we did get the data, yay :3
-        chunk.block_data = Some(ChunkData {
-          blocks: data
-        });
+        chunk.block_data = Some(ChunkData::new(data, generate_biome_map(position, WORLD_SEED, &generator.preset, &registry)));
         chunk.current_state = CurrentChunkState::Loaded;
+        ticks.restore(&pending_ticks);
+        light_chunk(&mut world, &registry, &height, position);
       } else {
         // If we didn't get the data, we need to run worldgen
-        // XXX: will this ever happen? we should always have the data in the save file
+        // (this happens if only blocks queued for the chunk were saved)
         let atomic = Arc::new(Atomic::new(AbortState::Continue));
         task_manager.spawn_task(ChunkTask::ChunkWorldgen {
           seed: WORLD_SEED,
+          generator: Arc::clone(&generator.preset),
+          templates: Arc::clone(&generator.templates),
+          registry: Arc::clone(&registry.0),
+          height: *height,
           position,
           abortion: Some(Arc::clone(&atomic)),
         });
@@ -382,7 +486,7 @@ fn process_completed_tasks(
 
   for res in task_manager.poll() {
     match res {
-      ChunkTaskResponse::ChunkWorldgenDone { position, chunk_data, mut queued } => {
+      ChunkTaskResponse::ChunkWorldgenDone { position, chunk_data, mut queued, biomes } => {
         //TODO this can fuck shit up really badly if io op gets overwritten by worldgen chunk
         //TODO only accept if loading stage, not loaded
 
@@ -406,13 +510,14 @@ fn process_completed_tasks(
         }
 
         //set the block data
-        chunk.block_data = Some(ChunkData {
-          blocks: chunk_data
-        });
+        chunk.block_data = Some(ChunkData::new(chunk_data, biomes));
 
         //update chunk state
         chunk.current_state = CurrentChunkState::Loaded;
 
+        //calculate light
+        light_chunk(&mut world, &registry, &height, position);
+
         //push queued blocks
         queue.0.append(&mut queued);
         drop(queued); //`queued` is empty after `append`
@@ -424,6 +529,7 @@ fn process_completed_tasks(
         position,
         vertices, indices,
         trans_vertices, trans_indices,
+        visibility,
       } => {
         //check if chunk exists
         let Some(chunk) = world.chunks.get_mut(&position) else {
@@ -441,53 +547,12 @@ fn process_completed_tasks(
         //TODO: Skip if mesh is empty? (i.e. set to None)
         //TODO
 
-        let vtx_buffer = renderer.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
-          label: Some("chunk_vertex_buffer"),
-          contents: bytemuck::cast_slice(&vertices),
-          usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::VERTEX,
-        });
-
-        let idx_buffer = renderer.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
-          label: Some("chunk_vertex_buffer"),
-          contents: bytemuck::cast_slice(&indices),
-          usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::INDEX,
-        });
-
-        let vtx_buffer_trans = renderer.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
-          label: Some("chunk_trans_vertex_buffer"),
-          contents: bytemuck::cast_slice(&trans_vertices),
-          usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::VERTEX,
-        });
-
-        let idx_buffer_trans = renderer.device().create_buffer_init(&wgpu::util::BufferInitDescriptor {
-          label: Some("chunk_trans_index_buffer"),
-          contents: bytemuck::cast_slice(&trans_indices),
-          usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::INDEX,
-        });
-
-        let main_buffer_pair = BufferPair {
-          vertex: vtx_buffer,
-          vertex_len: vertices.len() as u32,
-          index: idx_buffer,
-          index_len: indices.len() as u32,
-        };
-
-        let trans_buffer_pair = BufferPair {
-          vertex: vtx_buffer_trans,
-          vertex_len: trans_vertices.len() as u32,
-          index: idx_buffer_trans,
-          index_len: trans_indices.len() as u32,
-        };
-
-        let mesh = ChunkMesh {
-          main: main_buffer_pair,
-          trans: trans_buffer_pair,
-        };
-
+        let main = (&vertices[..], &indices[..]);
+        let trans = (&trans_vertices[..], &trans_indices[..]);
         if let Some(index) = chunk.mesh_index {
-          meshes.update(index, mesh).expect("Mesh update failed");
+          meshes.update(&renderer, index, main, trans, visibility).expect("Mesh update failed");
         } else {
-          let mesh_index = meshes.insert(mesh);
+          let mesh_index = meshes.insert(&renderer, main, trans, visibility);
           chunk.mesh_index = Some(mesh_index);
         }
 
@@ -505,10 +570,14 @@ fn process_completed_tasks(
   }
 }
 
-/// Save all modified chunks to the disk
+/// Save all modified chunks (and blocks that are still queued) to the disk
 pub fn save_on_exit(
   io: Option<UniqueView<IOThreadManager>>,
   world: UniqueView<ChunkStorage>,
+  ticks: UniqueView<ScheduledTicks>,
+  queue: UniqueView<BlockUpdateQueue>,
+  registry: UniqueView<SharedBlockRegistry>,
+  falling_blocks: View<FallingBlock>,
 ) {
   let Some(io) = io else {
     log::warn!("no IO thread manager, skipping save on exit");
@@ -516,12 +585,25 @@ pub fn save_on_exit(
   };
   for (&position, chunk) in &world.chunks {
     if let Some(block_data) = &chunk.block_data {
-      if chunk.data_modified {
+      //Falling blocks are saved as blocks at their current position
+      let mut falling = falling_blocks.iter()
+        .filter(|falling_block| falling_block.chunk_position() == position)
+        .peekable();
+      if chunk.data_modified || falling.peek().is_some() {
+        let mut data = block_data.blocks.clone();
+        let mut pending_ticks = ticks.pending_in_chunk(position);
+        for falling_block in falling {
+          falling_block.persist(&registry, &mut data, &mut pending_ticks);
+        }
         io.send(IOCommand::SaveChunk {
           position,
-          data: block_data.blocks.clone(),
+          data,
+          ticks: pending_ticks,
         });
       }
     }
   }
+  if !queue.0.is_empty() {
+    io.send(IOCommand::QueueBlocks { blocks: queue.0.clone() });
+  }
 }
@@ -1,54 +1,101 @@
 use glam::{ivec3, IVec3};
 use strum::IntoEnumIterator;
-use kubi_shared::block::{Block, RenderType, Transparency};
+use kubi_shared::{
+  block::{Axis, BlockRegistry, BlockState, BlockTexture, CubeTexture, RenderType, Transparency},
+  fluid::fluid_surface_height,
+};
 use crate::world::chunk::CHUNK_SIZE;
 use crate::rendering::world::ChunkVertex;
 
 pub mod data;
+pub mod ao;
 mod builder;
+mod greedy;
+pub mod lod;
+pub mod visibility;
 
 use data::MeshGenData;
 use builder::{MeshBuilder, CubeFace, DiagonalFace};
+use ao::{face_ao, AO_NONE};
+use greedy::{FaceKey, GreedyMesher};
 
-pub fn generate_mesh(position: IVec3, data: MeshGenData) -> (
+/// Generate the mesh of a chunk (relative to the chunk origin)\
+/// `greedy` enables greedy meshing of opaque faces (see [`greedy`])
+pub fn generate_mesh(data: MeshGenData, registry: &BlockRegistry, greedy: bool) -> (
   (Vec<ChunkVertex>, Vec<u32>),
   (Vec<ChunkVertex>, Vec<u32>),
 ) {
-  let get_block = |pos: IVec3| -> Block {
+  const SIZE: i32 = CHUNK_SIZE as i32;
+  let get_block = |pos: IVec3| -> BlockState {
     if pos.x < 0 {
-      data.block_data_neg_x[(CHUNK_SIZE as i32 + pos.x) as usize][pos.y as usize][pos.z as usize]
-    } else if pos.x >= CHUNK_SIZE as i32 {
-      data.block_data_pos_x[pos.x as usize - CHUNK_SIZE][pos.y as usize][pos.z as usize]
+      data.block_data_neg_x.get_state(pos + ivec3(SIZE, 0, 0))
+    } else if pos.x >= SIZE {
+      data.block_data_pos_x.get_state(pos - ivec3(SIZE, 0, 0))
     } else if pos.y < 0 {
-      data.block_data_neg_y[pos.x as usize][(CHUNK_SIZE as i32 + pos.y) as usize][pos.z as usize]
-    } else if pos.y >= CHUNK_SIZE as i32 {
-      data.block_data_pos_y[pos.x as usize][pos.y as usize - CHUNK_SIZE][pos.z as usize]
+      data.block_data_neg_y.get_state(pos + ivec3(0, SIZE, 0))
+    } else if pos.y >= SIZE {
+      data.block_data_pos_y.get_state(pos - ivec3(0, SIZE, 0))
     } else if pos.z < 0 {
-      data.block_data_neg_z[pos.x as usize][pos.y as usize][(CHUNK_SIZE as i32 + pos.z) as usize]
-    } else if pos.z >= CHUNK_SIZE as i32 {
-      data.block_data_pos_z[pos.x as usize][pos.y as usize][pos.z as usize - CHUNK_SIZE]
+      data.block_data_neg_z.get_state(pos + ivec3(0, 0, SIZE))
+    } else if pos.z >= SIZE {
+      data.block_data_pos_z.get_state(pos - ivec3(0, 0, SIZE))
     } else {
-      data.block_data[pos.x as usize][pos.y as usize][pos.z as usize]
+      data.block_data.get_state(pos)
     }
   };
+  let get_light = |pos: IVec3| -> u8 {
+    if pos.x < 0 {
+      data.light_data_neg_x.get_packed(pos + ivec3(SIZE, 0, 0))
+    } else if pos.x >= SIZE {
+      data.light_data_pos_x.get_packed(pos - ivec3(SIZE, 0, 0))
+    } else if pos.y < 0 {
+      data.light_data_neg_y.get_packed(pos + ivec3(0, SIZE, 0))
+    } else if pos.y >= SIZE {
+      data.light_data_pos_y.get_packed(pos - ivec3(0, SIZE, 0))
+    } else if pos.z < 0 {
+      data.light_data_neg_z.get_packed(pos + ivec3(0, 0, SIZE))
+    } else if pos.z >= SIZE {
+      data.light_data_pos_z.get_packed(pos - ivec3(0, 0, SIZE))
+    } else {
+      data.light_data.get_packed(pos)
+    }
+  };
+  let casts_ao = |pos: IVec3| -> bool {
+    //blocks in diagonal neighbor chunks are not available
+    let outside = pos.cmplt(IVec3::ZERO) | pos.cmpge(IVec3::splat(SIZE));
+    if outside.bitmask().count_ones() > 1 {
+      return false
+    }
+    matches!(
+      registry.get(get_block(pos).block).render,
+      RenderType::Cube(Transparency::Solid | Transparency::Binary, _)
+    )
+  };
 
-  let mut builder = MeshBuilder::new_with_offset((position * CHUNK_SIZE as i32).as_vec3());
-  let mut trans_builder = MeshBuilder::new_with_offset((position * CHUNK_SIZE as i32).as_vec3());
+  let mut builder = MeshBuilder::new();
+  let mut trans_builder = MeshBuilder::new();
+  let mut greedy_mesher = greedy.then(GreedyMesher::new);
 
   for x in 0..CHUNK_SIZE as i32 {
     for y in 0..CHUNK_SIZE as i32 {
       for z in 0..CHUNK_SIZE as i32 {
         let coord = ivec3(x, y, z);
-        let block = get_block(coord);
-        let descriptor = block.descriptor();
+        let state = get_block(coord);
+        let block = state.block;
+        let descriptor = registry.get(block);
         match descriptor.render {
           RenderType::None => continue,
           RenderType::Cube(trans_type, textures) => {
+            //fluids are lower than a full block, unless there's more of the same fluid above
+            let height = match descriptor.fluid && get_block(coord + IVec3::Y).block != block {
+              true => fluid_surface_height(state.properties.level()),
+              false => 1.,
+            };
             for face in CubeFace::iter() {
               let facing_direction = face.normal();
               let facing_coord = coord + facing_direction;
-              let facing_block = get_block(facing_coord);
-              let facing_descriptor = facing_block.descriptor();
+              let facing_block = get_block(facing_coord).block;
+              let facing_descriptor = registry.get(facing_block);
               let face_obstructed = match trans_type {
                 Transparency::Solid => matches!(facing_descriptor.render, RenderType::Cube(Transparency::Solid, _)),
                 Transparency::Binary | Transparency::Trans => {
@@ -62,34 +109,45 @@ pub fn generate_mesh(position: IVec3, data: MeshGenData) -> (
                 },
               };
               if !face_obstructed {
-                let face_texture = match face {
-                  CubeFace::Top    => textures.top,
-                  CubeFace::Front  => textures.front,
-                  CubeFace::Left   => textures.left,
-                  CubeFace::Right  => textures.right,
-                  CubeFace::Back   => textures.back,
-                  CubeFace::Bottom => textures.bottom,
-                };
+                let axis = state.properties.facing().axis();
+                let (face_texture, rotate_uv) = oriented_face_texture(&textures, face, axis);
                 let target_builder = match trans_type {
                   Transparency::Trans => &mut trans_builder,
                   _ => &mut builder,
                 };
-                target_builder.add_face(face, coord, face_texture as u8);
+                //faces are lit by the light of the block they're facing
+                let light = get_light(facing_coord);
+                let ao = face_ao(face, coord, casts_ao);
+                if let Some(greedy_mesher) = &mut greedy_mesher {
+                  if matches!(trans_type, Transparency::Solid) && height == 1. && ao.iter().all(|&x| x == ao[0]) {
+                    greedy_mesher.add_face(face, coord, FaceKey {
+                      texture: face_texture.0,
+                      rotate_uv,
+                      light,
+                      ao: ao[0],
+                    });
+                    continue
+                  }
+                }
+                target_builder.add_face_with_height(face, coord, face_texture.0, rotate_uv, height, light, ao);
               }
             }
           },
           RenderType::Cross(textures) => {
+            let light = get_light(coord);
             builder.add_diagonal_face(
               coord, 
               DiagonalFace::LeftZ, 
-              textures.0.front as u8, 
-              textures.0.back as u8
+              textures.0.front.0, 
+              textures.0.back.0,
+              light
             );
             builder.add_diagonal_face(
               coord, 
               DiagonalFace::RigthZ, 
-              textures.1.front as u8, 
-              textures.1.back as u8
+              textures.1.front.0, 
+              textures.1.back.0,
+              light
             );
           },
         }
@@ -97,5 +155,55 @@ pub fn generate_mesh(position: IVec3, data: MeshGenData) -> (
     }
   }
 
+  if let Some(greedy_mesher) = greedy_mesher {
+    greedy_mesher.finish(&mut builder);
+  }
+
   (builder.finish(), trans_builder.finish())
 }
+
+/// Generate a mesh for a block that's not aligned to the block grid (like falling blocks)\
+/// The mesh is relative to the block origin, and should be drawn with its own chunk origin\
+/// All faces are drawn, transparent blocks are drawn as if they were opaque\
+/// The block is lit uniformly with the packed `light` level
+pub fn generate_free_block_mesh(registry: &BlockRegistry, state: BlockState, light: u8) -> (Vec<ChunkVertex>, Vec<u32>) {
+  let mut builder = MeshBuilder::new();
+  match registry.get(state.block).render {
+    RenderType::None => (),
+    RenderType::Cube(_, textures) => {
+      let axis = state.properties.facing().axis();
+      for face in CubeFace::iter() {
+        let (face_texture, rotate_uv) = oriented_face_texture(&textures, face, axis);
+        builder.add_face(face, IVec3::ZERO, face_texture.0, rotate_uv, light, [AO_NONE; 4]);
+      }
+    },
+    RenderType::Cross(textures) => {
+      builder.add_diagonal_face(IVec3::ZERO, DiagonalFace::LeftZ, textures.0.front.0, textures.0.back.0, light);
+      builder.add_diagonal_face(IVec3::ZERO, DiagonalFace::RigthZ, textures.1.front.0, textures.1.back.0, light);
+    },
+  }
+  builder.finish()
+}
+
+/// Get the texture of a cube face, taking the block orientation into account\
+/// Returns the texture and whether the UVs should be rotated by 90 degrees
+///
+/// Blocks not aligned with the Y axis are rotated so that their top face points along `axis`
+fn oriented_face_texture(textures: &CubeTexture, face: CubeFace, axis: Axis) -> (BlockTexture, bool) {
+  let normal = face.normal();
+  //normal of the face in the block's own (unrotated) space
+  let (local_normal, rotate_uv) = match axis {
+    Axis::Y => (normal, false),
+    Axis::X => (ivec3(-normal.y, normal.x, normal.z), normal.x == 0),
+    Axis::Z => (ivec3(normal.x, normal.z, -normal.y), normal.x != 0),
+  };
+  let texture = match local_normal.to_array() {
+    [0, 1, 0]  => textures.top,
+    [0, -1, 0] => textures.bottom,
+    [-1, 0, 0] => textures.left,
+    [1, 0, 0]  => textures.right,
+    [0, 0, -1] => textures.back,
+    _          => textures.front,
+  };
+  (texture, rotate_uv)
+}
@@ -0,0 +1,133 @@
+//! Per-vertex ambient occlusion
+//!
+//! Each vertex of a cube face gets darker depending on the blocks touching it
+//! in the layer in front of the face (two along the edges and one in the corner)
+
+use glam::{IVec3, Vec3};
+use super::builder::CubeFace;
+
+/// AO value of a vertex that's not occluded at all
+pub const AO_NONE: u8 = 3;
+
+/// AO value of a single vertex, from `0` (fully occluded) to [`AO_NONE`]
+///
+/// If both sides are occluded, the corner is hidden behind them and doesn't matter
+pub const fn vertex_ao(side1: bool, side2: bool, corner: bool) -> u8 {
+  if side1 && side2 {
+    return 0
+  }
+  AO_NONE - (side1 as u8 + side2 as u8 + corner as u8)
+}
+
+/// AO values of the vertices of `face` of the block at `coord` (in the same order as the face vertices)\
+/// `occludes` tells if the block at a position casts ambient occlusion
+pub fn face_ao(face: CubeFace, coord: IVec3, occludes: impl Fn(IVec3) -> bool) -> [u8; 4] {
+  let normal = face.normal();
+  let front = coord + normal;
+  face.vertices().map(|vertex| {
+    //direction from the center of the face towards the vertex, along both axes of the face
+    let offset = (vertex * 2. - Vec3::ONE).as_ivec3() * (IVec3::ONE - normal.abs());
+    let mut sides = (0..3).filter(|&axis| offset[axis] != 0).map(|axis| {
+      let mut side = IVec3::ZERO;
+      side[axis] = offset[axis];
+      side
+    });
+    let (side1, side2) = (sides.next().unwrap(), sides.next().unwrap());
+    vertex_ao(
+      occludes(front + side1),
+      occludes(front + side2),
+      occludes(front + side1 + side2),
+    )
+  })
+}
+
+/// Check if a quad should be split along the other diagonal
+///
+/// Quads are split into triangles along the diagonal between vertices `1` and `2` by default,
+/// which makes the AO look different depending on the orientation of the face.\
+/// Splitting along the brighter diagonal keeps it consistent
+pub const fn flip_quad(ao: [u8; 4]) -> bool {
+  ao[0] + ao[3] > ao[1] + ao[2]
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::ivec3;
+  use super::*;
+
+  #[test]
+  fn vertex_ao_values() {
+    assert_eq!(vertex_ao(false, false, false), 3);
+    assert_eq!(vertex_ao(false, false, true), 2);
+    assert_eq!(vertex_ao(true, false, false), 2);
+    assert_eq!(vertex_ao(true, false, true), 1);
+    assert_eq!(vertex_ao(false, true, true), 1);
+    assert_eq!(vertex_ao(true, true, false), 0);
+    assert_eq!(vertex_ao(true, true, true), 0);
+  }
+
+  #[test]
+  fn open_face_is_not_occluded() {
+    for face in [CubeFace::Top, CubeFace::Bottom, CubeFace::Left, CubeFace::Right, CubeFace::Front, CubeFace::Back] {
+      assert_eq!(face_ao(face, IVec3::ZERO, |_| false), [AO_NONE; 4]);
+    }
+  }
+
+  #[test]
+  fn top_face_next_to_wall() {
+    //wall of blocks along the -X side, one block higher than the face
+    let ao = face_ao(CubeFace::Top, IVec3::ZERO, |position| position.x == -1 && position.y == 1);
+    for (vertex, ao) in CubeFace::Top.vertices().into_iter().zip(ao) {
+      match vertex.x == 0. {
+        true => assert_eq!(ao, 1),
+        false => assert_eq!(ao, AO_NONE),
+      }
+    }
+  }
+
+  #[test]
+  fn top_face_in_corner() {
+    //walls along the -X and -Z sides
+    let ao = face_ao(CubeFace::Top, IVec3::ZERO, |position| {
+      position.y == 1 && (position.x == -1 || position.z == -1)
+    });
+    for (vertex, ao) in CubeFace::Top.vertices().into_iter().zip(ao) {
+      let expected = match (vertex.x == 0., vertex.z == 0.) {
+        (true, true) => 0,
+        (true, false) | (false, true) => 1,
+        (false, false) => AO_NONE,
+      };
+      assert_eq!(ao, expected);
+    }
+  }
+
+  #[test]
+  fn top_face_with_corner_block() {
+    //single block touching only the (+X, +Z) corner
+    let ao = face_ao(CubeFace::Top, IVec3::ZERO, |position| position == ivec3(1, 1, 1));
+    for (vertex, ao) in CubeFace::Top.vertices().into_iter().zip(ao) {
+      match vertex.x == 1. && vertex.z == 1. {
+        true => assert_eq!(ao, 2),
+        false => assert_eq!(ao, AO_NONE),
+      }
+    }
+  }
+
+  #[test]
+  fn blocks_behind_the_face_dont_occlude() {
+    //everything except the layer in front of the face is solid
+    let ao = face_ao(CubeFace::Right, IVec3::ZERO, |position| position.x != 1);
+    assert_eq!(ao, [AO_NONE; 4]);
+  }
+
+  #[test]
+  fn quads_split_along_brighter_diagonal() {
+    assert!(!flip_quad([AO_NONE; 4]));
+    //single dark corner on the default diagonal
+    assert!(flip_quad([AO_NONE, 0, AO_NONE, AO_NONE]));
+    assert!(flip_quad([AO_NONE, AO_NONE, 1, AO_NONE]));
+    //single dark corner off the default diagonal
+    assert!(!flip_quad([0, AO_NONE, AO_NONE, AO_NONE]));
+    assert!(!flip_quad([AO_NONE, AO_NONE, AO_NONE, 1]));
+  }
+}
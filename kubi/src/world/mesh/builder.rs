@@ -1,7 +1,7 @@
 use strum::EnumIter;
 use glam::{ivec3, vec3, IVec3, Vec3};
-use std::f32::consts::FRAC_1_SQRT_2;
 use crate::rendering::world::ChunkVertex;
+use super::ao::{flip_quad, AO_NONE};
 
 #[repr(usize)]
 #[derive(Clone, Copy, Debug, EnumIter)]
@@ -17,6 +17,11 @@ impl CubeFace {
   pub const fn normal(self) -> IVec3 {
     CUBE_FACE_NORMALS_IVEC3[self as usize]
   }
+
+  /// Corners of the face, relative to the block origin
+  pub const fn vertices(self) -> [Vec3; 4] {
+    CUBE_FACE_VERTICES[self as usize]
+  }
 }
 
 const CUBE_FACE_VERTICES: [[Vec3; 4]; 6] = [
@@ -35,15 +40,9 @@ const CUBE_FACE_NORMALS_IVEC3: [IVec3; 6] = [
   ivec3( 0,  0,  1),
   ivec3( 0, -1,  0)
 ];
-const CUBE_FACE_NORMALS: [Vec3; 6] = [
-  vec3(0., 1., 0.),
-  vec3(0., 0., -1.),
-  vec3(-1.,0., 0.),
-  vec3(1., 0., 0.),
-  vec3(0., 0., 1.),
-  vec3(0., -1.,0.)
-];
 const CUBE_FACE_INDICES: [u32; 6] = [0, 1, 2, 2, 1, 3];
+/// Same as `CUBE_FACE_INDICES`, but split along the other diagonal
+const CUBE_FACE_INDICES_FLIPPED: [u32; 6] = [0, 1, 3, 0, 3, 2];
 
 #[repr(usize)]
 pub enum DiagonalFace {
@@ -64,30 +63,13 @@ const CROSS_FACES: [[Vec3; 4]; 2] = [
     vec3(1., 1., 0.),
   ]
 ];
-const CROSS_FACE_NORMALS: [Vec3; 2] = [
-  vec3(-FRAC_1_SQRT_2, 0., FRAC_1_SQRT_2),
-  vec3( FRAC_1_SQRT_2, 0., FRAC_1_SQRT_2),
-];
-const CROSS_FACE_NORMALS_BACK: [Vec3; 2] = [
-  vec3( FRAC_1_SQRT_2, 0., -FRAC_1_SQRT_2),
-  vec3(-FRAC_1_SQRT_2, 0., -FRAC_1_SQRT_2),
-];
 const CROSS_FACE_INDICES: [u32; 12] = [
   0, 1, 2, 2, 1, 3, //Front side
   6, 5, 4, 7, 5, 6, //Back side
 ];
 
-
-const UV_COORDS: [[f32; 2]; 4] = [
-  [0., 1.],
-  [0., 0.],
-  [1., 1.],
-  [1., 0.],
-];
-
 #[derive(Default)]
 pub struct MeshBuilder {
-  offset: Vec3,
   vertex_buffer: Vec<ChunkVertex>,
   index_buffer: Vec<u32>,
   idx_counter: u32,
@@ -97,56 +79,66 @@ impl MeshBuilder {
     Self::default()
   }
 
-  pub fn new_with_offset(offset: Vec3) -> Self {
-    Self { offset, ..Self::new() }
+  /// `light` is the packed light level in front of the face (see [`crate::world::light::LightData`])\
+  /// `ao` is the ambient occlusion of each vertex (see [`super::ao`])
+  pub fn add_face(&mut self, face: CubeFace, coord: IVec3, texture: u8, rotate_uv: bool, light: u8, ao: [u8; 4]) {
+    self.add_face_with_height(face, coord, texture, rotate_uv, 1., light, ao);
+  }
+
+  /// Add a face of a block that's only `height` tall (like fluids)\
+  /// Side textures are cut off instead of being squashed
+  pub fn add_face_with_height(&mut self, face: CubeFace, coord: IVec3, texture: u8, rotate_uv: bool, height: f32, light: u8, ao: [u8; 4]) {
+    let coord = coord.as_vec3();
+
+    //Push vertices
+    self.vertex_buffer.reserve(4);
+    for (i, mut position) in face.vertices().into_iter().enumerate() {
+      if position.y == 1. {
+        position.y = height;
+      }
+      self.vertex_buffer.push(ChunkVertex::new(coord + position, face as u8, texture, light, ao[i], rotate_uv));
+    }
+
+    //Push indices
+    let indices = if flip_quad(ao) { CUBE_FACE_INDICES_FLIPPED } else { CUBE_FACE_INDICES };
+    self.index_buffer.extend_from_slice(&indices.map(|x| x + self.idx_counter));
+
+    //Increment idx counter
+    self.idx_counter += 4;
   }
 
-  pub fn add_face(&mut self, face: CubeFace, coord: IVec3, texture: u8) {
+  /// Add a face of a box covering `size` blocks starting at `coord` (used by greedy meshing and LOD)\
+  /// The texture is repeated across the face
+  pub fn add_merged_face(&mut self, face: CubeFace, coord: IVec3, size: IVec3, texture: u8, rotate_uv: bool, light: u8, ao: [u8; 4]) {
     let coord = coord.as_vec3();
-    let face_index = face as usize;
-    
+    let size = size.as_vec3();
+
     //Push vertices
-    let norm = CUBE_FACE_NORMALS[face_index];
-    let vert = CUBE_FACE_VERTICES[face_index];
     self.vertex_buffer.reserve(4);
-    for i in 0..4 {
-      self.vertex_buffer.push(ChunkVertex {
-        position: (coord + vert[i] + self.offset).to_array(),
-        normal: norm.to_array(),
-        uv: UV_COORDS[i],
-        tex_index: texture as u32
-      });
+    for (i, position) in face.vertices().into_iter().enumerate() {
+      self.vertex_buffer.push(ChunkVertex::new(coord + position * size, face as u8, texture, light, ao[i], rotate_uv));
     }
 
     //Push indices
-    self.index_buffer.extend_from_slice(&CUBE_FACE_INDICES.map(|x| x + self.idx_counter));
+    let indices = if flip_quad(ao) { CUBE_FACE_INDICES_FLIPPED } else { CUBE_FACE_INDICES };
+    self.index_buffer.extend_from_slice(&indices.map(|x| x + self.idx_counter));
 
     //Increment idx counter
     self.idx_counter += 4;
   }
 
-  pub fn add_diagonal_face(&mut self, coord: IVec3, face_type: DiagonalFace, front_texture: u8, back_texture: u8) {
+  pub fn add_diagonal_face(&mut self, coord: IVec3, face_type: DiagonalFace, front_texture: u8, back_texture: u8, light: u8) {
     //Push vertices
     let face_type = face_type as usize;
     let vertices = CROSS_FACES[face_type];
-    let normal_front = CROSS_FACE_NORMALS[face_type].to_array();
-    let normal_back = CROSS_FACE_NORMALS_BACK[face_type].to_array();
+    let face_front = ChunkVertex::DIAGONAL_FACE_OFFSET + 2 * face_type as u8;
+    let face_back = face_front + 1;
     self.vertex_buffer.reserve(8);
-    for i in 0..4 { //push front vertices
-      self.vertex_buffer.push(ChunkVertex {
-        position: (coord.as_vec3() + vertices[i] + self.offset).to_array(),
-        normal: normal_front,
-        uv: UV_COORDS[i],
-        tex_index: front_texture as u32
-      })
+    for vertex in vertices { //push front vertices
+      self.vertex_buffer.push(ChunkVertex::new(coord.as_vec3() + vertex, face_front, front_texture, light, AO_NONE, false));
     }
-    for i in 0..4 { //push back vertices
-      self.vertex_buffer.push(ChunkVertex {
-        position: (coord.as_vec3() + vertices[i] + self.offset).to_array(),
-        normal: normal_back,
-        uv: UV_COORDS[i],
-        tex_index: back_texture as u32
-      })
+    for vertex in vertices { //push back vertices
+      self.vertex_buffer.push(ChunkVertex::new(coord.as_vec3() + vertex, face_back, back_texture, light, AO_NONE, false));
     }
 
     //Push indices
@@ -156,26 +148,6 @@ impl MeshBuilder {
     self.idx_counter += 8;
   }
 
-  //XXX: needs offset supprt
-  // pub fn add_model(&mut self, position: Vec3, vertices: &[ChunkVertex], indices: Option<&[u32]>) {
-  //   //push vertices
-  //   self.vertex_buffer.extend(vertices.iter().map(|vertex| {
-  //     let mut vertex = *vertex;
-  //     vertex.position[0] += position.x;
-  //     vertex.position[0] += position.y;
-  //     vertex.position[0] += position.z;
-  //     vertex
-  //   }));
-  //   //push indices
-  //   if let Some(indices) = indices {
-  //     self.index_buffer.extend(indices.iter().map(|x| x + self.idx_counter));
-  //   } else {
-  //     self.index_buffer.extend(0..(self.vertex_buffer.len() as u32));
-  //   }
-  //   //increment idx counter
-  //   self.idx_counter += vertices.len() as u32;
-  // }
-
   pub fn finish(self) -> (Vec<ChunkVertex>, Vec<u32>) {
     (self.vertex_buffer, self.index_buffer)
   }
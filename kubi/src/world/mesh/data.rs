@@ -1,8 +1,11 @@
+use kubi_shared::height::WorldHeight;
 use crate::world::{
-  neighbors::AllChunkNeighbors,
-  chunk::BlockData
+  neighbors::ChunkNeighbors,
+  chunk::{BlockData, Chunk},
+  light::LightData,
 };
 
+#[derive(Clone)]
 pub struct MeshGenData {
   pub block_data: BlockData,
   pub block_data_pos_z: BlockData,
@@ -11,24 +14,71 @@ pub struct MeshGenData {
   pub block_data_neg_y: BlockData,
   pub block_data_pos_x: BlockData,
   pub block_data_neg_x: BlockData,
+  pub light_data: LightData,
+  pub light_data_pos_z: LightData,
+  pub light_data_neg_z: LightData,
+  pub light_data_pos_y: LightData,
+  pub light_data_neg_y: LightData,
+  pub light_data_pos_x: LightData,
+  pub light_data_neg_x: LightData,
 }
-impl AllChunkNeighbors<'_> {
-  pub fn mesh_data(&self) -> Option<MeshGenData> {
-    let center_block_data = self.center.block_data.as_ref()?;
-    let front_block_data = self.front.block_data.as_ref()?;
-    let back_block_data = self.back.block_data.as_ref()?;
-    let top_block_data = self.top.block_data.as_ref()?;
-    let bottom_block_data = self.bottom.block_data.as_ref()?;
-    let right_block_data = self.right.block_data.as_ref()?;
-    let left_block_data = self.left.block_data.as_ref()?;
+impl ChunkNeighbors<'_> {
+  /// Returns `None` if the center chunk or any of its neighbors is not loaded\
+  /// Chunks above and below the world height are never loaded, they're meshed against as air with full sky light
+  pub fn mesh_data(&self, height: &WorldHeight) -> Option<MeshGenData> {
+    let center = self.center?;
+    let center_block_data = center.block_data.as_ref()?;
+    let data_of = |chunk: Option<&Chunk>, y: i32| -> Option<(BlockData, LightData)> {
+      if !height.contains_chunk(y) {
+        return Some((BlockData::new(), LightData::full_sky()))
+      }
+      let data = chunk?.block_data.as_ref()?;
+      Some((data.blocks.clone(), data.light.clone()))
+    };
+    let y = center.position.y;
+    let (block_data_pos_z, light_data_pos_z) = data_of(self.front, y)?;
+    let (block_data_neg_z, light_data_neg_z) = data_of(self.back, y)?;
+    let (block_data_pos_y, light_data_pos_y) = data_of(self.top, y + 1)?;
+    let (block_data_neg_y, light_data_neg_y) = data_of(self.bottom, y - 1)?;
+    let (block_data_pos_x, light_data_pos_x) = data_of(self.right, y)?;
+    let (block_data_neg_x, light_data_neg_x) = data_of(self.left, y)?;
     Some(MeshGenData {
       block_data: center_block_data.blocks.clone(),
-      block_data_pos_z: front_block_data.blocks.clone(),
-      block_data_neg_z: back_block_data.blocks.clone(),
-      block_data_pos_y: top_block_data.blocks.clone(),
-      block_data_neg_y: bottom_block_data.blocks.clone(),
-      block_data_pos_x: right_block_data.blocks.clone(),
-      block_data_neg_x: left_block_data.blocks.clone(),
+      block_data_pos_z,
+      block_data_neg_z,
+      block_data_pos_y,
+      block_data_neg_y,
+      block_data_pos_x,
+      block_data_neg_x,
+      light_data: center_block_data.light.clone(),
+      light_data_pos_z,
+      light_data_neg_z,
+      light_data_pos_y,
+      light_data_neg_y,
+      light_data_pos_x,
+      light_data_neg_x,
+    })
+  }
+}
+
+/// Data needed to mesh a chunk at a lower level of detail (see [`super::lod`])\
+/// Horizontal neighbors are not used, and the chunks above and below are optional
+#[derive(Clone)]
+pub struct LodMeshGenData {
+  pub block_data: BlockData,
+  pub light_data: LightData,
+  pub block_data_pos_y: Option<BlockData>,
+  pub block_data_neg_y: Option<BlockData>,
+}
+impl ChunkNeighbors<'_> {
+  pub fn lod_mesh_data(&self) -> Option<LodMeshGenData> {
+    let center_block_data = self.center?.block_data.as_ref()?;
+    let blocks_of = |chunk: Option<&Chunk>| Some(chunk?.block_data.as_ref()?.blocks.clone());
+    Some(LodMeshGenData {
+      block_data: center_block_data.blocks.clone(),
+      light_data: center_block_data.light.clone(),
+      block_data_pos_y: blocks_of(self.top),
+      block_data_neg_y: blocks_of(self.bottom),
     })
   }
 }
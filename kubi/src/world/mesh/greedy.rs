@@ -0,0 +1,322 @@
+//! Greedy meshing
+//!
+//! Neighboring coplanar faces that look exactly the same (texture, light and ambient occlusion)
+//! are merged into larger quads, with the texture repeated across them.\
+//! Only opaque, full-sized faces with the same AO on all corners are merged,
+//! everything else is still meshed one face at a time
+
+use glam::IVec3;
+use strum::IntoEnumIterator;
+use crate::world::chunk::CHUNK_SIZE;
+use super::builder::{CubeFace, MeshBuilder};
+
+const SIZE: i32 = CHUNK_SIZE as i32;
+const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// Everything that has to match for two faces to be merged
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FaceKey {
+  pub texture: u8,
+  pub rotate_uv: bool,
+  pub light: u8,
+  /// AO value of all four corners
+  pub ao: u8,
+}
+
+/// Collects faces of a chunk, and merges them once all of them are known
+pub struct GreedyMesher {
+  /// Faces facing each direction (indexed by `CubeFace as usize`), by block position
+  faces: Vec<Option<FaceKey>>,
+}
+
+impl GreedyMesher {
+  pub fn new() -> Self {
+    Self {
+      faces: vec![None; 6 * CHUNK_VOLUME],
+    }
+  }
+
+  fn index(face: CubeFace, coord: IVec3) -> usize {
+    face as usize * CHUNK_VOLUME + ((coord.x * SIZE + coord.y) * SIZE + coord.z) as usize
+  }
+
+  fn get(&self, face: CubeFace, coord: IVec3) -> Option<FaceKey> {
+    self.faces[Self::index(face, coord)]
+  }
+
+  /// Add a face of the block at `coord` (in chunk-local coordinates)
+  pub fn add_face(&mut self, face: CubeFace, coord: IVec3, key: FaceKey) {
+    self.faces[Self::index(face, coord)] = Some(key);
+  }
+
+  /// Merge the collected faces and add them to the `builder`
+  pub fn finish(mut self, builder: &mut MeshBuilder) {
+    for face in CubeFace::iter() {
+      //the face is merged along the two axes perpendicular to its normal
+      let normal = face.normal().abs();
+      let normal_axis = (0..3).find(|&axis| normal[axis] != 0).unwrap();
+      let u_axis = (normal_axis + 1) % 3;
+      let v_axis = (normal_axis + 2) % 3;
+      let coord = |layer: i32, u: i32, v: i32| {
+        let mut coord = IVec3::ZERO;
+        coord[normal_axis] = layer;
+        coord[u_axis] = u;
+        coord[v_axis] = v;
+        coord
+      };
+
+      for layer in 0..SIZE {
+        for v in 0..SIZE {
+          let mut u = 0;
+          while u < SIZE {
+            let Some(key) = self.get(face, coord(layer, u, v)) else {
+              u += 1;
+              continue
+            };
+
+            //grow along the u axis first...
+            let mut width = 1;
+            while u + width < SIZE && self.get(face, coord(layer, u + width, v)) == Some(key) {
+              width += 1;
+            }
+
+            //...then along the v axis, as long as the whole row matches
+            let mut height = 1;
+            while v + height < SIZE && (0..width).all(|du| self.get(face, coord(layer, u + du, v + height)) == Some(key)) {
+              height += 1;
+            }
+
+            //remove the merged faces, so that they're not used again
+            for dv in 0..height {
+              for du in 0..width {
+                self.faces[Self::index(face, coord(layer, u + du, v + dv))] = None;
+              }
+            }
+
+            let mut size = IVec3::ONE;
+            size[u_axis] = width;
+            size[v_axis] = height;
+            builder.add_merged_face(face, coord(layer, u, v), size, key.texture, key.rotate_uv, key.light, [key.ao; 4]);
+
+            u += width;
+          }
+        }
+      }
+    }
+  }
+}
+
+#[cfg(all(test, feature = "nightly"))]
+mod benches {
+  extern crate test;
+  use test::Bencher;
+  use glam::ivec3;
+  use rand::{rngs::SmallRng, Rng, SeedableRng};
+  use kubi_shared::block::{Block, BlockRegistry, BlockState};
+  use crate::world::{chunk::BlockData, light::{LightChannel, LightData}, mesh::{data::MeshGenData, generate_mesh}};
+  use super::*;
+
+  /// Mesh data with the chunk and all its neighbors made by `block_at`, lit by full sunlight
+  fn mesh_data(block_at: impl Fn(IVec3) -> Block) -> MeshGenData {
+    let mut blocks = BlockData::new();
+    let mut light = LightData::new();
+    for x in 0..SIZE {
+      for y in 0..SIZE {
+        for z in 0..SIZE {
+          let position = ivec3(x, y, z);
+          blocks.set(position, block_at(position));
+          light.set(position, LightChannel::Sky, 15);
+        }
+      }
+    }
+    blocks.compact();
+    MeshGenData {
+      block_data: blocks.clone(),
+      block_data_pos_z: blocks.clone(),
+      block_data_neg_z: blocks.clone(),
+      block_data_pos_y: BlockData::filled(BlockState::AIR),
+      block_data_neg_y: BlockData::filled(BlockState::new(Block::Stone)),
+      block_data_pos_x: blocks.clone(),
+      block_data_neg_x: blocks,
+      light_data: light.clone(),
+      light_data_pos_z: light.clone(),
+      light_data_neg_z: light.clone(),
+      light_data_pos_y: light.clone(),
+      light_data_neg_y: light.clone(),
+      light_data_pos_x: light.clone(),
+      light_data_neg_x: light,
+    }
+  }
+
+  /// Flat terrain, the best case for greedy meshing
+  fn flat() -> MeshGenData {
+    mesh_data(|position| match position.y {
+      0..=15 => Block::Stone,
+      16 => Block::Grass,
+      _ => Block::Air,
+    })
+  }
+
+  /// Random blocks, the worst case for greedy meshing
+  fn noise() -> MeshGenData {
+    let mut rng = SmallRng::seed_from_u64(0xdead_beef);
+    let blocks: Vec<bool> = (0..CHUNK_VOLUME).map(|_| rng.gen_bool(0.5)).collect();
+    mesh_data(|position| match blocks[((position.x * SIZE + position.y) * SIZE + position.z) as usize] {
+      true => Block::Stone,
+      false => Block::Air,
+    })
+  }
+
+  #[bench]
+  fn flat_naive(b: &mut Bencher) {
+    let (data, registry) = (flat(), BlockRegistry::builtin());
+    b.iter(|| generate_mesh(data.clone(), &registry, false));
+  }
+
+  #[bench]
+  fn flat_greedy(b: &mut Bencher) {
+    let (data, registry) = (flat(), BlockRegistry::builtin());
+    b.iter(|| generate_mesh(data.clone(), &registry, true));
+  }
+
+  #[bench]
+  fn noise_naive(b: &mut Bencher) {
+    let (data, registry) = (noise(), BlockRegistry::builtin());
+    b.iter(|| generate_mesh(data.clone(), &registry, false));
+  }
+
+  #[bench]
+  fn noise_greedy(b: &mut Bencher) {
+    let (data, registry) = (noise(), BlockRegistry::builtin());
+    b.iter(|| generate_mesh(data.clone(), &registry, true));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::ivec3;
+  use hashbrown::HashMap;
+  use rand::{rngs::SmallRng, Rng, SeedableRng};
+  use kubi_shared::block::{Block, BlockRegistry};
+  use crate::{
+    rendering::world::ChunkVertex,
+    world::{chunk::BlockData, light::{LightChannel, LightData}, mesh::{data::MeshGenData, generate_mesh}},
+  };
+  use super::*;
+
+  /// Texture, rotate uv, light and the AO of each corner (by position relative to the block, in vertex units)
+  type FaceLook = (u8, bool, u8, Vec<(IVec3, u8)>);
+
+  /// Random terrain-like chunk, with some other blocks mixed in and uneven light
+  fn random_chunk(rng: &mut SmallRng) -> (BlockData, LightData) {
+    const BLOCKS: &[Block] = &[Block::Stone, Block::Dirt, Block::Leaf, Block::Wood, Block::Water, Block::Air];
+    let mut blocks = BlockData::new();
+    let mut light = LightData::new();
+    let surface: Vec<i32> = (0..SIZE * SIZE).map(|_| rng.gen_range(8..24)).collect();
+    for x in 0..SIZE {
+      for y in 0..SIZE {
+        for z in 0..SIZE {
+          let position = ivec3(x, y, z);
+          let height = surface[(x * SIZE + z) as usize];
+          let block = match y.cmp(&height) {
+            _ if rng.gen_bool(0.05) => BLOCKS[rng.gen_range(0..BLOCKS.len())],
+            std::cmp::Ordering::Less => Block::Stone,
+            std::cmp::Ordering::Equal => Block::Grass,
+            std::cmp::Ordering::Greater => Block::Air,
+          };
+          blocks.set(position, block);
+          let level = match rng.gen_bool(0.1) {
+            true => rng.gen_range(0..=15),
+            false => 15,
+          };
+          light.set(position, LightChannel::Sky, level);
+        }
+      }
+    }
+    blocks.compact();
+    (blocks, light)
+  }
+
+  fn random_mesh_data(seed: u64) -> MeshGenData {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut chunk = || random_chunk(&mut rng);
+    let (block_data, light_data) = chunk();
+    let (block_data_pos_z, light_data_pos_z) = chunk();
+    let (block_data_neg_z, light_data_neg_z) = chunk();
+    let (block_data_pos_y, light_data_pos_y) = chunk();
+    let (block_data_neg_y, light_data_neg_y) = chunk();
+    let (block_data_pos_x, light_data_pos_x) = chunk();
+    let (block_data_neg_x, light_data_neg_x) = chunk();
+    MeshGenData {
+      block_data, block_data_pos_z, block_data_neg_z, block_data_pos_y, block_data_neg_y, block_data_pos_x, block_data_neg_x,
+      light_data, light_data_pos_z, light_data_neg_z, light_data_pos_y, light_data_neg_y, light_data_pos_x, light_data_neg_x,
+    }
+  }
+
+  /// Split all quads of a mesh into the faces of single blocks, by face direction and block position
+  fn block_faces(vertices: &[ChunkVertex]) -> HashMap<(usize, IVec3), FaceLook> {
+    let scale = ChunkVertex::POSITION_SCALE as i32;
+    let position = |vertex: &ChunkVertex| ivec3(
+      (vertex.position & 0x3ff) as i32,
+      ((vertex.position >> 10) & 0x3ff) as i32,
+      ((vertex.position >> 20) & 0x3ff) as i32,
+    );
+    let mut faces = HashMap::new();
+    assert_eq!(vertices.len() % 4, 0);
+    for quad in vertices.chunks(4) {
+      let data = quad[0].data;
+      let (texture, light, rotate_uv) = ((data & 0xff) as u8, ((data >> 8) & 0xff) as u8, (data >> 22) & 1 != 0);
+      let face_index = ((data >> 18) & 0xf) as usize;
+      let face = CubeFace::iter().find(|&face| face as usize == face_index).expect("not a cube face");
+      assert!(quad.iter().all(|vertex| vertex.data & !(0b11 << 16) == data & !(0b11 << 16)), "quad vertices differ");
+      let ao = |vertex: &ChunkVertex| ((vertex.data >> 16) & 0b11) as u8;
+
+      let min = quad.iter().map(position).reduce(IVec3::min).unwrap();
+      let max = quad.iter().map(position).reduce(IVec3::max).unwrap();
+      let normal = face.normal();
+      let normal_axis = (0..3).find(|&axis| normal[axis] != 0).unwrap();
+      //number of blocks covered along each axis (faces of fluids are less than a block tall)
+      let cells = ((max - min) / scale).max(IVec3::ONE);
+      //merged faces always have the same AO on all corners
+      let merged = cells.element_product() > 1;
+      assert!(!merged || quad.iter().all(|vertex| ao(vertex) == ao(&quad[0])), "merged face with uneven AO");
+
+      for dx in 0..cells.x {
+        for dy in 0..cells.y {
+          for dz in 0..cells.z {
+            let mut block = min / scale + ivec3(dx, dy, dz);
+            //faces pointing in the positive direction lie on the far side of the block
+            if normal[normal_axis] > 0 {
+              block[normal_axis] = (min[normal_axis] - 1) / scale;
+            }
+            let mut corners: Vec<(IVec3, u8)> = match merged {
+              true => face.vertices().into_iter()
+                .map(|vertex| ((vertex * ChunkVertex::POSITION_SCALE).as_ivec3(), ao(&quad[0])))
+                .collect(),
+              false => quad.iter()
+                .map(|vertex| (position(vertex) - block * scale, ao(vertex)))
+                .collect(),
+            };
+            corners.sort_by_key(|&(corner, _)| corner.to_array());
+            let previous = faces.insert((face_index, block), (texture, rotate_uv, light, corners));
+            assert!(previous.is_none(), "overlapping faces at {block}");
+          }
+        }
+      }
+    }
+    faces
+  }
+
+  #[test]
+  fn greedy_matches_naive() {
+    let registry = BlockRegistry::builtin();
+    for seed in 0..4 {
+      let data = random_mesh_data(seed);
+      let (naive, naive_trans) = generate_mesh(data.clone(), &registry, false);
+      let (greedy, greedy_trans) = generate_mesh(data, &registry, true);
+      assert!(greedy.0.len() < naive.0.len(), "nothing was merged");
+      assert_eq!(block_faces(&naive.0), block_faces(&greedy.0), "opaque faces differ (seed {seed})");
+      assert_eq!(block_faces(&naive_trans.0), block_faces(&greedy_trans.0), "transparent faces differ (seed {seed})");
+    }
+  }
+}
@@ -0,0 +1,173 @@
+//! Level of detail meshes of distant chunks
+//!
+//! At level `lod`, the chunk is downsampled into cells of `2^lod` blocks along each axis,
+//! and each cell is meshed as a single big block.\
+//! A cell is filled with its most common cube block if at least half of it is made of cube blocks,
+//! smaller details (like plants) are lost at that distance anyway
+//!
+//! Neighboring chunks may use a different level of detail, so faces on the horizontal chunk borders
+//! are never culled. They act as skirts, covering the gaps between the mismatched meshes
+
+use glam::IVec3;
+use strum::IntoEnumIterator;
+use kubi_shared::block::{BlockRegistry, BlockState, RenderType, Transparency, MAX_LIGHT_LEVEL};
+use crate::{
+  rendering::world::ChunkVertex,
+  world::{chunk::{BlockData, CHUNK_SIZE}, light::LightData},
+};
+use super::{
+  ao::AO_NONE,
+  builder::{CubeFace, MeshBuilder},
+  data::LodMeshGenData,
+  oriented_face_texture,
+};
+
+/// Coarsest level of detail (cells of 8x8x8 blocks)
+pub const MAX_LOD: u8 = 3;
+
+/// Light of the skirt faces, which have no cell in front of them
+const SKIRT_LIGHT: u8 = MAX_LIGHT_LEVEL << 4;
+
+/// Most common cube block in the cell at `cell` (in cell coordinates, cells being `scale` blocks wide)\
+/// Returns `None` if less than half of the cell is made of cube blocks
+fn downsample_cell(registry: &BlockRegistry, blocks: &BlockData, cell: IVec3, scale: i32) -> Option<BlockState> {
+  let is_cube = |state: &BlockState| matches!(registry.get(state.block).render, RenderType::Cube(..));
+
+  //uniform chunks (like air or stone) are really common
+  if blocks.is_uniform() {
+    return Some(blocks.palette()[0]).filter(is_cube)
+  }
+
+  //cells contain only a handful of different blocks, a vec is faster than a map here
+  let mut counts: Vec<(BlockState, u32)> = Vec::new();
+  let origin = cell * scale;
+  for x in 0..scale {
+    for y in 0..scale {
+      for z in 0..scale {
+        let state = blocks.get_state(origin + IVec3::new(x, y, z));
+        if !is_cube(&state) {
+          continue
+        }
+        match counts.iter_mut().find(|(counted, _)| *counted == state) {
+          Some((_, count)) => *count += 1,
+          None => counts.push((state, 1)),
+        }
+      }
+    }
+  }
+
+  let total: u32 = counts.iter().map(|(_, count)| count).sum();
+  if total * 2 < (scale * scale * scale) as u32 {
+    return None
+  }
+  counts.into_iter().max_by_key(|&(_, count)| count).map(|(state, _)| state)
+}
+
+/// Brightest sky and block light levels in the cell, packed (see [`LightData`])
+fn downsample_light(light: &LightData, cell: IVec3, scale: i32) -> u8 {
+  let (mut sky, mut block) = (0, 0);
+  let origin = cell * scale;
+  for x in 0..scale {
+    for y in 0..scale {
+      for z in 0..scale {
+        let packed = light.get_packed(origin + IVec3::new(x, y, z));
+        sky = sky.max(packed >> 4);
+        block = block.max(packed & 0xf);
+      }
+    }
+  }
+  (sky << 4) | block
+}
+
+/// Generate the mesh of a chunk at level of detail `lod` (relative to the chunk origin)\
+/// `lod` must be in `1..=MAX_LOD`
+pub fn generate_lod_mesh(data: LodMeshGenData, registry: &BlockRegistry, lod: u8) -> (
+  (Vec<ChunkVertex>, Vec<u32>),
+  (Vec<ChunkVertex>, Vec<u32>),
+) {
+  debug_assert!((1..=MAX_LOD).contains(&lod));
+  let scale = 1 << lod;
+  let cells_per_axis = CHUNK_SIZE as i32 / scale;
+  let index = |cell: IVec3| ((cell.x * cells_per_axis + cell.y) * cells_per_axis + cell.z) as usize;
+
+  //downsample the whole chunk first, as every cell is looked at up to 7 times
+  let cell_count = (cells_per_axis * cells_per_axis * cells_per_axis) as usize;
+  let mut cells = vec![None; cell_count];
+  let mut cell_light = vec![0; cell_count];
+  for x in 0..cells_per_axis {
+    for y in 0..cells_per_axis {
+      for z in 0..cells_per_axis {
+        let cell = IVec3::new(x, y, z);
+        cells[index(cell)] = downsample_cell(registry, &data.block_data, cell, scale);
+        cell_light[index(cell)] = downsample_light(&data.light_data, cell, scale);
+      }
+    }
+  }
+
+  //only the layer of cells touching this chunk is needed from the chunks above and below
+  let layer_index = |cell: IVec3| (cell.x * cells_per_axis + cell.z) as usize;
+  let downsample_layer = |blocks: &BlockData, y: i32| -> Vec<Option<BlockState>> {
+    let mut layer = vec![None; (cells_per_axis * cells_per_axis) as usize];
+    for x in 0..cells_per_axis {
+      for z in 0..cells_per_axis {
+        let cell = IVec3::new(x, y, z);
+        layer[layer_index(cell)] = downsample_cell(registry, blocks, cell, scale);
+      }
+    }
+    layer
+  };
+  let layer_below = data.block_data_neg_y.as_ref().map(|below| downsample_layer(below, cells_per_axis - 1));
+  let layer_above = data.block_data_pos_y.as_ref().map(|above| downsample_layer(above, 0));
+
+  //Returns `None` for cells that are not available (outside of the chunk horizontally, or in unloaded chunks)
+  let get_cell = |cell: IVec3| -> Option<Option<BlockState>> {
+    if cell.x < 0 || cell.x >= cells_per_axis || cell.z < 0 || cell.z >= cells_per_axis {
+      None
+    } else if cell.y < 0 {
+      Some(layer_below.as_ref()?[layer_index(cell)])
+    } else if cell.y >= cells_per_axis {
+      Some(layer_above.as_ref()?[layer_index(cell)])
+    } else {
+      Some(cells[index(cell)])
+    }
+  };
+
+  let mut builder = MeshBuilder::new();
+  let mut trans_builder = MeshBuilder::new();
+
+  for x in 0..cells_per_axis {
+    for y in 0..cells_per_axis {
+      for z in 0..cells_per_axis {
+        let cell = IVec3::new(x, y, z);
+        let Some(state) = cells[index(cell)] else { continue };
+        let RenderType::Cube(trans_type, textures) = registry.get(state.block).render else {
+          unreachable!("cells only contain cube blocks")
+        };
+        for face in CubeFace::iter() {
+          let facing_cell = cell + face.normal();
+          let facing_state = get_cell(facing_cell);
+          let face_obstructed = match facing_state.flatten().map(|facing| (facing, registry.get(facing.block).render)) {
+            Some((_, RenderType::Cube(Transparency::Solid, _))) => true,
+            Some((facing, _)) => !matches!(trans_type, Transparency::Solid) && facing.block == state.block,
+            None => false,
+          };
+          if face_obstructed {
+            continue
+          }
+          let (face_texture, rotate_uv) = oriented_face_texture(&textures, face, state.properties.facing().axis());
+          let light = match facing_cell.cmpge(IVec3::ZERO).all() && facing_cell.cmplt(IVec3::splat(cells_per_axis)).all() {
+            true => cell_light[index(facing_cell)],
+            false => SKIRT_LIGHT,
+          };
+          let target_builder = match trans_type {
+            Transparency::Trans => &mut trans_builder,
+            _ => &mut builder,
+          };
+          target_builder.add_merged_face(face, cell * scale, IVec3::splat(scale), face_texture.0, rotate_uv, light, [AO_NONE; 4]);
+        }
+      }
+    }
+  }
+
+  (builder.finish(), trans_builder.finish())
+}
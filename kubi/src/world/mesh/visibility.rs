@@ -0,0 +1,179 @@
+//! Chunk visibility graph, used for occlusion culling
+//!
+//! Two faces of a chunk are connected if they can be seen from each other through the chunk,
+//! that is, if there's a path of non-opaque blocks between them.\
+//! This is found by flood filling every group of non-opaque blocks, and connecting all faces it touches
+
+use glam::IVec3;
+use kubi_shared::block::{BlockRegistry, Facing, RenderType, Transparency};
+use crate::world::chunk::{BlockData, CHUNK_SIZE};
+
+const SIZE: i32 = CHUNK_SIZE as i32;
+const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// Which faces of a chunk are connected to each other (indexed by `Facing as usize`)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChunkVisibility([u8; 6]);
+
+impl ChunkVisibility {
+  /// No faces are connected (the chunk is completely opaque)
+  pub const NONE: Self = Self([0; 6]);
+  /// All faces are connected to each other
+  pub const ALL: Self = Self([0b111111; 6]);
+
+  pub fn connects(self, a: Facing, b: Facing) -> bool {
+    self.0[a as usize] & (1 << b as usize) != 0
+  }
+
+  /// Connect all faces in the `faces` bitmask to each other
+  fn connect_all(&mut self, faces: u8) {
+    for face in 0..6 {
+      if faces & (1 << face) != 0 {
+        self.0[face] |= faces;
+      }
+    }
+  }
+}
+
+/// Faces of the chunk the block at `position` touches, as a bitmask
+fn touched_faces(position: IVec3) -> u8 {
+  let mut faces = 0;
+  for facing in Facing::ALL {
+    let neighbor = position + facing.normal();
+    if neighbor.cmplt(IVec3::ZERO).any() || neighbor.cmpge(IVec3::splat(SIZE)).any() {
+      faces |= 1 << facing as u8;
+    }
+  }
+  faces
+}
+
+/// Find out which faces of the chunk are connected
+pub fn compute_visibility(blocks: &BlockData, registry: &BlockRegistry) -> ChunkVisibility {
+  let is_opaque = |position: IVec3| matches!(
+    registry.get(blocks.get_state(position).block).render,
+    RenderType::Cube(Transparency::Solid, _)
+  );
+
+  //uniform chunks (like air or stone) are really common
+  if blocks.is_uniform() {
+    return match is_opaque(IVec3::ZERO) {
+      true => ChunkVisibility::NONE,
+      false => ChunkVisibility::ALL,
+    }
+  }
+
+  let index = |position: IVec3| ((position.x * SIZE + position.y) * SIZE + position.z) as usize;
+  let mut visited = vec![false; CHUNK_VOLUME];
+  let mut visibility = ChunkVisibility::NONE;
+  let mut stack = Vec::new();
+
+  for x in 0..SIZE {
+    for y in 0..SIZE {
+      for z in 0..SIZE {
+        let start = IVec3::new(x, y, z);
+        if visited[index(start)] || is_opaque(start) {
+          continue
+        }
+
+        //flood fill the group of blocks `start` belongs to
+        let mut faces = 0;
+        visited[index(start)] = true;
+        stack.push(start);
+        while let Some(position) = stack.pop() {
+          faces |= touched_faces(position);
+          for facing in Facing::ALL {
+            let neighbor = position + facing.normal();
+            if neighbor.cmplt(IVec3::ZERO).any() || neighbor.cmpge(IVec3::splat(SIZE)).any() {
+              continue
+            }
+            if visited[index(neighbor)] || is_opaque(neighbor) {
+              continue
+            }
+            visited[index(neighbor)] = true;
+            stack.push(neighbor);
+          }
+        }
+        visibility.connect_all(faces);
+
+        //nothing more to find
+        if visibility == ChunkVisibility::ALL {
+          return visibility
+        }
+      }
+    }
+  }
+
+  visibility
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::ivec3;
+  use kubi_shared::block::{Block, BlockState};
+  use super::*;
+
+  /// Chunk filled with `block`, except for the positions where `air` returns `true`
+  fn chunk(block: Block, air: impl Fn(IVec3) -> bool) -> BlockData {
+    let mut blocks = BlockData::filled(BlockState::new(block));
+    for x in 0..SIZE {
+      for y in 0..SIZE {
+        for z in 0..SIZE {
+          let position = ivec3(x, y, z);
+          if air(position) {
+            blocks.set(position, Block::Air);
+          }
+        }
+      }
+    }
+    blocks
+  }
+
+  /// All pairs of different faces
+  fn face_pairs() -> impl Iterator<Item = (Facing, Facing)> {
+    Facing::ALL.into_iter().flat_map(|a| Facing::ALL.into_iter().filter(move |&b| b != a).map(move |b| (a, b)))
+  }
+
+  #[test]
+  fn uniform_chunks() {
+    let registry = BlockRegistry::builtin();
+    assert_eq!(compute_visibility(&BlockData::new(), &registry), ChunkVisibility::ALL);
+    assert_eq!(compute_visibility(&BlockData::filled(BlockState::new(Block::Stone)), &registry), ChunkVisibility::NONE);
+    //see-through blocks don't block the view
+    assert_eq!(compute_visibility(&BlockData::filled(BlockState::new(Block::Leaf)), &registry), ChunkVisibility::ALL);
+  }
+
+  #[test]
+  fn hollow_chunk() {
+    let registry = BlockRegistry::builtin();
+    //air inside of a stone shell doesn't connect anything
+    let hollow = chunk(Block::Stone, |position| position.cmpgt(IVec3::ZERO).all() && position.cmplt(IVec3::splat(SIZE - 1)).all());
+    assert_eq!(compute_visibility(&hollow, &registry), ChunkVisibility::NONE);
+
+    //a vertical shaft through it only connects the top and the bottom
+    let shaft = chunk(Block::Stone, |position| position.x == 8 && position.z == 8);
+    let visibility = compute_visibility(&shaft, &registry);
+    for (a, b) in face_pairs() {
+      let expected = matches!((a, b), (Facing::Up, Facing::Down) | (Facing::Down, Facing::Up));
+      assert_eq!(visibility.connects(a, b), expected, "{a:?} - {b:?}");
+    }
+  }
+
+  #[test]
+  fn wall_splits_chunk() {
+    let registry = BlockRegistry::builtin();
+    //stone wall across the whole chunk at x = 16
+    let wall = chunk(Block::Stone, |position| position.x != 16);
+    let visibility = compute_visibility(&wall, &registry);
+    assert!(!visibility.connects(Facing::East, Facing::West));
+    assert!(!visibility.connects(Facing::West, Facing::East));
+    //both halves touch the other four faces
+    for (a, b) in face_pairs().filter(|&(a, b)| (a, b) != (Facing::East, Facing::West) && (a, b) != (Facing::West, Facing::East)) {
+      assert!(visibility.connects(a, b), "{a:?} - {b:?}");
+    }
+
+    //a single hole in the wall connects the halves again
+    let mut holed = wall;
+    holed.set(ivec3(16, 3, 30), Block::Air);
+    assert_eq!(compute_visibility(&holed, &registry), ChunkVisibility::ALL);
+  }
+}
@@ -1,7 +1,15 @@
 use glam::{IVec3, ivec3};
-use kubi_shared::{block::Block, chunk::CHUNK_SIZE, queue::QueuedBlock};
-use shipyard::{UniqueViewMut, Unique};
-use super::ChunkStorage;
+use kubi_shared::{
+  block::{Block, SharedBlockRegistry},
+  chunk::CHUNK_SIZE,
+  data::io_thread::{IOCommand, IOThreadManager},
+  height::WorldHeight,
+  queue::QueuedBlock,
+  tick::ScheduledTicks,
+};
+use shipyard::{IntoWorkload, UniqueView, UniqueViewMut, Unique, Workload};
+use crate::{fixed_timestamp::FixedTimestamp, networking::GameType};
+use super::{light::update_light, ChunkStorage};
 
 #[derive(Unique, Default, Clone)]
 #[repr(transparent)]
@@ -14,18 +22,27 @@ impl BlockUpdateQueue {
 
 pub fn apply_queued_blocks(
   mut queue: UniqueViewMut<BlockUpdateQueue>,
-  mut world: UniqueViewMut<ChunkStorage>
+  mut world: UniqueViewMut<ChunkStorage>,
+  mut ticks: UniqueViewMut<ScheduledTicks>,
+  game_type: UniqueView<GameType>,
+  registry: UniqueView<SharedBlockRegistry>,
+  height: UniqueView<WorldHeight>,
 ) {
   //maybe i need to check for desired/current state here before marking as  dirty?
   queue.0.retain(|&event| {
-    if let Some(block) = world.get_block_mut(event.position) {
-      if event.soft && *block != Block::Air {
+    if let Some(current) = world.get_block_state(event.position) {
+      if event.soft && current.block != Block::Air {
         return false
       }
-      if event.block_type == *block {
+      if event.state() == current {
         return false
       }
-      *block = event.block_type;
+      world.set_block_state(event.position, event.state());
+      update_light(&mut world, &registry, &height, event.position);
+      //let nearby blocks react to the change (in multiplayer, block ticks are handled by the server)
+      if *game_type == GameType::Singleplayer {
+        ticks.schedule_updates(&*world, &registry, event.position);
+      }
       //mark chunk as dirty
       let (chunk_pos, block_pos) = ChunkStorage::to_chunk_coords(event.position);
       let chunk = world.chunks.get_mut(&chunk_pos).expect("This error should never happen, if it does then something is super fucked up and the whole project needs to be burnt down.");
@@ -55,3 +72,31 @@ pub fn apply_queued_blocks(
     true
   });
 }
+
+/// How often blocks queued for chunks that aren't loaded are moved to the save file
+const QUEUED_BLOCKS_SAVE_RATE_MILLIS: u16 = 5000;
+
+/// Move blocks queued for chunks that aren't loaded to the save file\
+/// They'll be sent back by the IO thread when the chunk gets loaded
+fn move_unloaded_queued_blocks(
+  mut queue: UniqueViewMut<BlockUpdateQueue>,
+  world: UniqueView<ChunkStorage>,
+  io: Option<UniqueView<IOThreadManager>>,
+) {
+  let Some(io) = io else { return };
+  let (unloaded, loaded): (Vec<QueuedBlock>, Vec<QueuedBlock>) = queue.0.drain(..).partition(|item| {
+    let chunk_position = item.position.div_euclid(IVec3::splat(CHUNK_SIZE as i32));
+    !world.chunks.contains_key(&chunk_position)
+  });
+  queue.0 = loaded;
+  if !unloaded.is_empty() {
+    io.send(IOCommand::QueueBlocks { blocks: unloaded });
+  }
+}
+
+/// Periodically save blocks queued for chunks that aren't loaded, in batches\
+/// (every write to the save file is synced, doing it every frame would be slow)\
+/// Blocks that are still queued on exit are saved by `save_on_exit`
+pub fn save_unloaded_queued_blocks() -> Workload {
+  move_unloaded_queued_blocks.into_workload().make_fixed(QUEUED_BLOCKS_SAVE_RATE_MILLIS, 0)
+}
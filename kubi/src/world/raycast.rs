@@ -1,6 +1,6 @@
 use glam::{Vec3, IVec3};
 use shipyard::{View, Component, ViewMut, IntoIter, UniqueView, track};
-use kubi_shared::block::Block;
+use kubi_shared::block::{Block, BlockRegistry, SharedBlockRegistry};
 use crate::transform::Transform;
 use super::ChunkStorage;
 
@@ -17,14 +17,14 @@ pub struct RaycastReport {
 
 impl ChunkStorage {
   //this is probably pretty slow...
-  pub fn raycast(&self, origin: Vec3, direction: Vec3, limit: Option<f32>) -> Option<RaycastReport> {
+  pub fn raycast(&self, registry: &BlockRegistry, origin: Vec3, direction: Vec3, limit: Option<f32>) -> Option<RaycastReport> {
     debug_assert!(direction.is_normalized(), "Ray direction not normalized");
     let mut position = origin;
     let mut length = 0.;
     loop {
       let block_position = position.floor().as_ivec3();
       if let Some(block) = self.get_block(block_position) {
-        if block.descriptor().raycast_collision {
+        if registry.get(block).raycast_collision {
           return Some(RaycastReport { 
             length,
             position,
@@ -52,6 +52,7 @@ pub fn update_raycasts(
   transform: View<Transform, track::All>,
   mut raycast: ViewMut<LookingAtBlock>,
   world: UniqueView<ChunkStorage>,
+  registry: UniqueView<SharedBlockRegistry>,
 ) {
   //idk if this check is even needed
   if !(world.is_inserted_or_modified() || (transform.inserted_or_modified(), &raycast).iter().next().is_some()) {
@@ -60,6 +61,6 @@ pub fn update_raycasts(
   for (transform, report) in (&transform, &mut raycast).iter() {
     let (_, rotation, position) = transform.0.to_scale_rotation_translation();
     let direction = (rotation.normalize() * Vec3::NEG_Z).normalize();
-    *report = LookingAtBlock(world.raycast(position, direction, Some(30.)));
+    *report = LookingAtBlock(world.raycast(&registry, position, direction, Some(30.)));
   }
 }
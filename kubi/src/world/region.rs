@@ -0,0 +1,176 @@
+//! Area of loaded chunks around the player
+
+use glam::IVec3;
+use shipyard::Unique;
+use kubi_shared::height::WorldHeight;
+use crate::settings::{ChunkLoadingShape, GameSettings};
+use super::{chunk::DesiredChunkState, mesh::lod::MAX_LOD};
+
+const DIRECTIONS: [IVec3; 6] = [
+  IVec3::X, IVec3::NEG_X,
+  IVec3::Y, IVec3::NEG_Y,
+  IVec3::Z, IVec3::NEG_Z,
+];
+
+/// Chunks that should be loaded around the player, and how
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LoadingRegion {
+  /// Chunk the player is in
+  pub center: IVec3,
+  shape: ChunkLoadingShape,
+  /// Chunks within this distance are rendered at full detail
+  render_distance: i32,
+  /// Only used by [`ChunkLoadingShape::Column`]
+  vertical_distance: i32,
+  /// Chunks within this distance are rendered at a lower level of detail
+  far_distance: i32,
+  /// Lowest and highest y coordinate of chunks that can contain blocks (see [`WorldHeight`])
+  vertical_limits: (i32, i32),
+}
+
+impl LoadingRegion {
+  pub fn new(center: IVec3, settings: &GameSettings, height: &WorldHeight) -> Self {
+    let render_distance = settings.render_distance as i32;
+    let chunk_range = height.chunk_range();
+    Self {
+      center,
+      shape: settings.loading_shape,
+      render_distance,
+      vertical_distance: settings.vertical_render_distance as i32,
+      //full detail chunks need their neighbors loaded, so there's always at least one ring of LOD chunks around them
+      far_distance: (settings.far_render_distance as i32).max(render_distance + 1),
+      vertical_limits: (*chunk_range.start(), *chunk_range.end()),
+    }
+  }
+
+  /// Is the chunk inside of the world height?\
+  /// Chunks outside of it are empty, they're never loaded (neighboring chunks are meshed against them as air)
+  fn in_world(&self, position: IVec3) -> bool {
+    (self.vertical_limits.0..=self.vertical_limits.1).contains(&position.y)
+  }
+
+  /// Distance of a chunk from the center (in chunks, rounded down)\
+  /// Columns only take the horizontal distance into account
+  fn distance(&self, offset: IVec3) -> i32 {
+    let length_squared = match self.shape {
+      ChunkLoadingShape::Sphere => offset.length_squared(),
+      ChunkLoadingShape::Column => offset.x * offset.x + offset.z * offset.z,
+    };
+    (length_squared as f32).sqrt() as i32
+  }
+
+  fn in_vertical_range(&self, offset: IVec3) -> bool {
+    match self.shape {
+      ChunkLoadingShape::Sphere => true,
+      ChunkLoadingShape::Column => offset.y.abs() <= self.vertical_distance,
+    }
+  }
+
+  fn is_full_detail(&self, offset: IVec3) -> bool {
+    self.in_vertical_range(offset) && self.distance(offset) <= self.render_distance
+  }
+
+  /// Smallest and largest chunk positions that may be inside of the region (inclusive)
+  pub fn bounds(&self) -> (IVec3, IVec3) {
+    let vertical = match self.shape {
+      ChunkLoadingShape::Sphere => self.far_distance,
+      ChunkLoadingShape::Column => self.vertical_distance + 1,
+    };
+    let extent = IVec3::new(self.far_distance, vertical, self.far_distance);
+    let (mut min, mut max) = (self.center - extent, self.center + extent);
+    min.y = min.y.max(self.vertical_limits.0);
+    max.y = max.y.min(self.vertical_limits.1);
+    (min, max)
+  }
+
+  /// Desired state and level of detail of the chunk at `position`\
+  /// Returns `None` if the chunk is outside of the region
+  pub fn chunk_state(&self, position: IVec3) -> Option<(DesiredChunkState, u8)> {
+    if !self.in_world(position) {
+      return None
+    }
+    let offset = position - self.center;
+    let distance = self.distance(offset);
+    if self.in_vertical_range(offset) && distance <= self.far_distance {
+      return Some((DesiredChunkState::Rendered, lod_at_distance(distance, self.render_distance)))
+    }
+    //full detail chunks need all of their neighbors loaded to be meshed
+    let is_border = DIRECTIONS.iter().any(|&direction| {
+      self.in_world(position + direction) && self.is_full_detail(offset + direction)
+    });
+    is_border.then_some((DesiredChunkState::Loaded, 0))
+  }
+}
+
+/// Level of detail of chunks `distance` chunks away from the player\
+/// Chunks within the render distance are at full detail, and each level after that covers twice the distance
+fn lod_at_distance(distance: i32, render_distance: i32) -> u8 {
+  let mut lod = 0;
+  let mut limit = render_distance.max(1);
+  while distance > limit && lod < MAX_LOD {
+    lod += 1;
+    limit *= 2;
+  }
+  lod
+}
+
+#[derive(Unique, Default)]
+pub struct ChunkLoadingState {
+  /// Region the desired chunk states are currently based on
+  pub region: Option<LoadingRegion>,
+  /// Some chunk tasks couldn't be started in the last frame because of the operation limit
+  pub backlog: bool,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn full_detail_within_render_distance() {
+    for distance in 0..=8 {
+      assert_eq!(lod_at_distance(distance, 8), 0);
+    }
+  }
+
+  #[test]
+  fn lod_doubles_distance_per_level() {
+    assert_eq!(lod_at_distance(9, 8), 1);
+    assert_eq!(lod_at_distance(16, 8), 1);
+    assert_eq!(lod_at_distance(17, 8), 2);
+    assert_eq!(lod_at_distance(32, 8), 2);
+    assert_eq!(lod_at_distance(33, 8), 3);
+  }
+
+  #[test]
+  fn lod_is_capped() {
+    assert_eq!(lod_at_distance(1000, 8), MAX_LOD);
+    assert_eq!(lod_at_distance(i32::MAX, 1), MAX_LOD);
+  }
+
+  #[test]
+  fn chunks_outside_of_the_world_height_are_not_loaded() {
+    let region = LoadingRegion {
+      center: IVec3::new(0, 1, 0),
+      shape: ChunkLoadingShape::Sphere,
+      render_distance: 2,
+      vertical_distance: 2,
+      far_distance: 2,
+      vertical_limits: (0, 1),
+    };
+    let (min, max) = region.bounds();
+    assert_eq!((min.y, max.y), (0, 1));
+    assert!(region.chunk_state(IVec3::new(0, 2, 0)).is_none());
+    assert!(region.chunk_state(IVec3::new(0, -1, 0)).is_none());
+    assert_eq!(region.chunk_state(IVec3::new(0, 0, 0)), Some((DesiredChunkState::Rendered, 0)));
+    //horizontal neighbors of full detail chunks are still loaded
+    assert_eq!(region.chunk_state(IVec3::new(0, 1, 3)), Some((DesiredChunkState::Loaded, 0)));
+  }
+
+  #[test]
+  fn zero_render_distance() {
+    assert_eq!(lod_at_distance(0, 0), 0);
+    assert_eq!(lod_at_distance(1, 0), 0);
+    assert_eq!(lod_at_distance(2, 0), 1);
+  }
+}
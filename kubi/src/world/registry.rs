@@ -0,0 +1,77 @@
+use std::{io::Read, path::Path, sync::Arc};
+use anyhow::Result;
+use shipyard::{AllStorages, AllStoragesView, Unique, UniqueView, UniqueViewMut};
+use kubi_shared::{
+  block::{BlockDefinitions, BlockRegistry, SharedBlockRegistry},
+  data::{io_thread::IOThreadManager, open_local_save_file},
+  height::WorldHeight,
+  worldgen::{preset::WorldGenPreset, structures::StructureTemplates},
+};
+use crate::filesystem::AssetManager;
+
+/// Block definitions loaded from the local assets, used in singleplayer
+#[derive(Unique)]
+pub struct LocalBlockDefinitions(pub BlockDefinitions);
+
+/// World generator preset and structure templates of the local world, used in singleplayer
+#[derive(Unique)]
+pub struct LocalWorldGenerator {
+  pub preset: Arc<WorldGenPreset>,
+  pub templates: Arc<StructureTemplates>,
+}
+
+impl LocalWorldGenerator {
+  /// Generator of a new world using the `preset`\
+  /// (the client doesn't have custom structure templates, so the built-in ones are always used)
+  pub fn new(preset: WorldGenPreset) -> Self {
+    Self {
+      preset: Arc::new(preset),
+      templates: Arc::new(StructureTemplates::builtin()),
+    }
+  }
+}
+
+fn read_block_definitions(assman: &AssetManager) -> Result<BlockDefinitions> {
+  //block definitions are game data, resource packs can't change them
+  let mut data = String::new();
+  assman.open_base_asset(Path::new("blocks.toml"))?.read_to_string(&mut data)?;
+  BlockDefinitions::parse(&data)
+}
+
+pub fn init_block_registry(
+  storages: AllStoragesView,
+  assman: UniqueView<AssetManager>,
+) {
+  log::info!("Loading block definitions...");
+  let (definitions, registry) = read_block_definitions(&assman)
+    .and_then(|definitions| {
+      let registry = BlockRegistry::new(&definitions, &[])?;
+      Ok((definitions, registry))
+    })
+    .unwrap_or_else(|error| {
+      log::error!("failed to load block definitions, using built-in ones: {error:?}");
+      (BlockDefinitions::builtin(), BlockRegistry::builtin())
+    });
+  storages.add_unique(SharedBlockRegistry::new(registry));
+  storages.add_unique(LocalBlockDefinitions(definitions));
+}
+
+/// Open a local save file and set up the block registry, world generator and build height limits for it
+///
+/// `preset` and `height` are only used if the world is new
+pub fn open_local_world(storages: &AllStorages, path: &Path, preset: WorldGenPreset, height: WorldHeight) -> Result<()> {
+  let mut save_file = open_local_save_file(path)?;
+  let registry = {
+    let definitions = storages.borrow::<UniqueView<LocalBlockDefinitions>>().unwrap();
+    save_file.create_block_registry(&definitions.0)?
+  };
+  *storages.borrow::<UniqueViewMut<SharedBlockRegistry>>().unwrap() = SharedBlockRegistry::new(registry);
+  let generator = LocalWorldGenerator::new(save_file.world_generator(preset)?);
+  if !save_file.check_structure_templates(&generator.templates)? {
+    log::warn!("structure templates changed since the world was created, structures in new chunks won't match the existing ones");
+  }
+  storages.add_unique(generator);
+  storages.add_unique(save_file.world_height(height)?);
+  storages.add_unique(IOThreadManager::new(save_file));
+  Ok(())
+}
@@ -2,12 +2,17 @@ use std::sync::Arc;
 use atomic::Atomic;
 use flume::{Receiver, Sender, TryIter};
 use glam::IVec3;
-use kubi_shared::{queue::QueuedBlock, worldgen::AbortState};
+use kubi_shared::{block::BlockRegistry, height::WorldHeight, queue::QueuedBlock, worldgen::{AbortState, biome::BiomeMap, preset::WorldGenPreset, structures::StructureTemplates}};
 use shipyard::Unique;
 use rayon::{ThreadPool, ThreadPoolBuilder};
 use super::{
   chunk::BlockData,
-  mesh::{generate_mesh, data::MeshGenData},
+  mesh::{
+    generate_mesh,
+    data::{MeshGenData, LodMeshGenData},
+    lod::generate_lod_mesh,
+    visibility::{compute_visibility, ChunkVisibility},
+  },
   worldgen::generate_world,
 };
 use crate::rendering::world::ChunkVertex;
@@ -15,20 +20,33 @@ use crate::rendering::world::ChunkVertex;
 pub enum ChunkTask {
   ChunkWorldgen {
     seed: u64,
+    generator: Arc<WorldGenPreset>,
+    templates: Arc<StructureTemplates>,
+    registry: Arc<BlockRegistry>,
+    height: WorldHeight,
     position: IVec3,
     abortion: Option<Arc<Atomic<AbortState>>>,
   },
   GenerateMesh {
     position: IVec3,
-    data: MeshGenData
-  }
+    data: MeshGenData,
+    registry: Arc<BlockRegistry>,
+    greedy: bool,
+  },
+  GenerateLodMesh {
+    position: IVec3,
+    data: LodMeshGenData,
+    registry: Arc<BlockRegistry>,
+    lod: u8,
+  },
 }
 
 pub enum ChunkTaskResponse {
   ChunkWorldgenDone {
     position: IVec3,
     chunk_data: BlockData,
-    queued: Vec<QueuedBlock>
+    queued: Vec<QueuedBlock>,
+    biomes: BiomeMap,
   },
   GenerateMeshDone {
     position: IVec3,
@@ -36,6 +54,7 @@ pub enum ChunkTaskResponse {
     indices: Vec<u32>,
     trans_vertices: Vec<ChunkVertex>,
     trans_indices: Vec<u32>,
+    visibility: ChunkVisibility,
   },
 }
 
@@ -67,23 +86,38 @@ impl ChunkTaskManager {
     let sender = self.channel.0.clone();
     self.pool.spawn(move || {
       let _ = sender.send(match task {
-        ChunkTask::GenerateMesh { position, data } => {
+        ChunkTask::GenerateMesh { position, data, registry, greedy } => {
+          let visibility = compute_visibility(&data.block_data, &registry);
+          let (
+            (vertices, indices),
+            (trans_vertices, trans_indices),
+          ) = generate_mesh(data, &registry, greedy);
+          ChunkTaskResponse::GenerateMeshDone {
+            position,
+            vertices, indices,
+            trans_vertices, trans_indices,
+            visibility,
+          }
+        },
+        ChunkTask::GenerateLodMesh { position, data, registry, lod } => {
+          let visibility = compute_visibility(&data.block_data, &registry);
           let (
             (vertices, indices),
             (trans_vertices, trans_indices),
-          ) = generate_mesh(position, data);
+          ) = generate_lod_mesh(data, &registry, lod);
           ChunkTaskResponse::GenerateMeshDone {
             position,
             vertices, indices,
             trans_vertices, trans_indices,
+            visibility,
           }
         },
-        ChunkTask::ChunkWorldgen { position, seed, abortion } => {
-          let Some((chunk_data, queued)) = generate_world(position, seed, abortion) else {
+        ChunkTask::ChunkWorldgen { position, seed, generator, templates, registry, height, abortion } => {
+          let Some((chunk_data, queued, biomes)) = generate_world(position, seed, &generator, &templates, height, &registry, abortion) else {
             log::warn!("aborted operation");
             return
           };
-          ChunkTaskResponse::ChunkWorldgenDone { position, chunk_data, queued }
+          ChunkTaskResponse::ChunkWorldgenDone { position, chunk_data, queued, biomes }
         }
       });
     });
@@ -0,0 +1,40 @@
+use shipyard::{EntitiesViewMut, IntoWorkload, UniqueView, UniqueViewMut, ViewMut, Workload};
+use kubi_shared::{
+  block::{BlockState, SharedBlockRegistry},
+  falling_block::{should_start_falling, FallingBlock, FallingBlockIds},
+  queue::QueuedBlock,
+  tick::{scheduled_tick, RandomTicks, ScheduledTicks, BLOCK_TICK_RATE_MILLIS},
+};
+use crate::fixed_timestamp::FixedTimestamp;
+use super::{queue::BlockUpdateQueue, ChunkStorage};
+
+//In multiplayer, block ticks are handled by the server
+//Ticks get scheduled by `apply_queued_blocks`, once block changes are applied
+
+fn process_block_ticks(
+  world: UniqueView<ChunkStorage>,
+  registry: UniqueView<SharedBlockRegistry>,
+  mut ticks: UniqueViewMut<ScheduledTicks>,
+  mut random_ticks: UniqueViewMut<RandomTicks>,
+  mut queue: UniqueViewMut<BlockUpdateQueue>,
+  mut falling_block_ids: UniqueViewMut<FallingBlockIds>,
+  mut entities: EntitiesViewMut,
+  mut falling_blocks: ViewMut<FallingBlock>,
+) {
+  for position in ticks.advance() {
+    if let Some(state) = should_start_falling(&*world, &registry, position) {
+      entities.add_entity(&mut falling_blocks, FallingBlock::new(falling_block_ids.next_id(), position, state));
+      queue.0.push(QueuedBlock::new(position, BlockState::AIR));
+      continue
+    }
+    queue.0.extend(scheduled_tick(&*world, &registry, position));
+  }
+  let loaded_chunks = world.chunks.iter()
+    .filter(|(_, chunk)| chunk.block_data.is_some())
+    .map(|(&position, _)| position);
+  queue.0.extend(random_ticks.run(&*world, &registry, loaded_chunks));
+}
+
+pub fn update_block_ticks() -> Workload {
+  process_block_ticks.into_workload().make_fixed(BLOCK_TICK_RATE_MILLIS, 0)
+}